@@ -13,11 +13,39 @@
 # This file may be distributed under the terms of the GNU GPLv3 license.
 #
 
-import logging, os, re, fileinput
+import logging, os, re, fileinput, math, time
 
 class MmuServer:
     TOOL_DISCOVERY_REGEX = r"((^MMU_CHANGE_TOOL(_STANDALONE)? .*?TOOL=)|(^T))(?P<tool>\d{1,2})"
     METADATA_REPLACEMENT_STRING = "!referenced_tools!"
+    METADATA_TOOLCHANGES_STRING = "!total_toolchanges!"
+    METADATA_TIP_PARAMETERS_STRING = "!slicer_tip_parameters!"
+    METADATA_FILAMENT_GRAMS_STRING = "!filament_grams!"
+    METADATA_MATERIALS_STRING = "!filament_materials!"
+    METADATA_COLORS_STRING = "!filament_colors!"
+    SLICER_CONFIG_REGEX = r"^;\s*(?P<key>[a-z_]+)\s*=\s*(?P<value>.*)$"
+    SLICER_FILAMENT_GRAMS_REGEX = r"^;\s*filament used \[g\]\s*=\s*(?P<value>.*)$"
+    PREHEAT_COMMAND = "MMU_PREHEAT"
+    PURGE_INFILL_COMMAND = "MMU_PURGE_INFILL"
+    DRYER_DONE_COMMAND = "MMU_DRYER DONE="
+    SLICER_FEATURE_REGEX = r"^;\s*(TYPE:|FEATURE:)\s*(?P<feature>.*)$" # PrusaSlicer/SuperSlicer ';TYPE:', OrcaSlicer '; FEATURE:'
+    SLICER_SPARSE_INFILL_FEATURES = ['internal infill', 'sparse infill']
+
+    # Slicer (PrusaSlicer/SuperSlicer/OrcaSlicer) per-filament settings and the _MMU_FORM_TIP_STANDALONE parameter they map to
+    SLICER_FILAMENT_TIP_SETTINGS = {
+        'filament_unloading_speed': 'UNLOADING_SPEED',
+        'filament_unloading_speed_start': 'UNLOADING_SPEED_START',
+        'filament_cooling_moves': 'COOLING_MOVES',
+        'filament_cooling_initial_speed': 'INITIAL_COOLING_SPEED',
+        'filament_cooling_final_speed': 'FINAL_COOLING_SPEED',
+        'filament_toolchange_temp': 'TOOLCHANGE_TEMP',
+    }
+    # Printer wide settings
+    SLICER_PRINTER_TIP_SETTINGS = {
+        'cooling_tube_length': 'COOLING_TUBE_LENGTH',
+        'cooling_tube_retraction': 'COOLING_TUBE_POSITION',
+    }
+    SLICER_RAMMING_INTERVAL = 0.25 # Ramming speeds are specified for successive 0.25s intervals
 
     def __init__(self, config):
         self.config = config
@@ -26,10 +54,35 @@ class MmuServer:
         # Gcode prerocessing functionality
         self.file_manager = self.server.lookup_component("file_manager")
         self.enable_file_preprocessor = config.getboolean("enable_file_preprocessor", True)
+        self.preheat_lookahead = config.getfloat("preheat_lookahead", 0.) # Seconds before toolchange to insert preheat hint
+        self.purge_infill_markers = config.getboolean("purge_infill_markers", False) # Bracket sparse infill for purge router
+        self.dryer_done_markers = config.getboolean("dryer_done_markers", False) # Mark last use of each tool for dryer
         self.server.register_event_handler("file_manager:filelist_changed", self._filelist_changed)
 
+        # Job queue integration: prepare MMU for next queued job as soon as current job completes
+        if config.getboolean("prepare_next_job", False):
+            self.server.register_event_handler("job_state:complete", self._job_complete)
+
         # Spoolman filament info retrieval functionality and update reporting
         self.server.register_remote_method("spoolman_get_filaments", self.get_filaments)
+        self.server.register_remote_method("spoolman_push_filament", self.push_filament)
+        self.server.register_remote_method("spoolman_set_location", self.set_location)
+        self.server.register_remote_method("spoolman_pull_locations", self.pull_locations)
+        self.server.register_remote_method("spoolman_audit_locations", self.audit_locations)
+        self.server.register_remote_method("spoolman_check_sufficiency", self.check_sufficiency)
+        self.server.register_remote_method("spoolman_create_spool", self.create_spool)
+
+        # MQTT publishing of MMU events (requires moonraker [mqtt] component for broker config)
+        self.mqtt_topic_prefix = config.get("mqtt_topic_prefix", "happy_hare/mmu")
+        self.server.register_remote_method("mmu_publish_event", self.publish_event)
+
+        # Metrics export of MMU performance to InfluxDB (pushed in line protocol) and/or Prometheus (scraped)
+        self.metrics_influxdb_url = config.get("metrics_influxdb_url", None)
+        self.metrics_influxdb_token = config.get("metrics_influxdb_token", None)
+        self.metrics = {} # Latest value for each (measurement, field, labels) for Prometheus
+        self.server.register_remote_method("mmu_record_metric", self.record_metric)
+        if config.getboolean("metrics_prometheus", False):
+            self.server.register_endpoint("/server/mmu/metrics", ["GET"], self._handle_metrics_request, wrap_result=False, content_type="text/plain; version=0.0.4")
 
     def _filelist_changed(self, response):
         if not self.enable_file_preprocessor:
@@ -40,17 +93,26 @@ class MmuServer:
 
             if filepath.endswith(".gcode"):
                 self._write_mmu_metadata(filepath)
+                if self.preheat_lookahead:
+                    self._insert_preheat_hints(filepath)
+                if self.purge_infill_markers:
+                    self._insert_purge_infill_markers(filepath)
+                if self.dryer_done_markers:
+                    self._insert_dryer_done_markers(filepath)
 
     def _write_mmu_metadata(self, file_path):
         self._log("Checking for MMU metadata placeholder in file: " + file_path)
-        has_placeholder, tools_used = self._enumerate_used_tools(file_path)
+        has_placeholder, tools_used, num_toolchanges = self._enumerate_used_tools(file_path)
 
         # An edit-in-place is seen by Moonraker as a file change (rightly so),
         # BUT it's seen this way even if no changes are made. We use `has_placeholder`
         # to determine whether there are any changes to make to prevent an infinite loop.
         if has_placeholder:
             self._log("Writing MMU metadata to file: " + file_path)
-            return self._inject_tool_usage(file_path, tools_used)
+            tip_parameters = self._parse_slicer_tip_parameters(file_path)
+            filament_grams = self._parse_slicer_filament_grams(file_path)
+            filaments = self._parse_slicer_filaments(file_path)
+            return self._inject_tool_usage(file_path, tools_used, num_toolchanges, tip_parameters, filament_grams, filaments)
         else:
             self._log("No MMU metadata placeholder found in file: " + file_path)
             return False
@@ -62,30 +124,291 @@ class MmuServer:
         regex = re.compile(self.TOOL_DISCOVERY_REGEX, re.IGNORECASE)
         tools_used = set()
         has_placeholder = False
+        num_toolchanges = 0
+        last_tool = None
 
         with open(file_path, "r") as f:
             for line in f:
-                if not has_placeholder and not line.startswith(";") and self.METADATA_REPLACEMENT_STRING in line:
+                if not has_placeholder and not line.startswith(";") and self._has_placeholder(line):
                     has_placeholder = True
 
                 match = regex.match(line)
                 if match:
-                    tool = match.group("tool")
-                    tools_used.add(int(tool))
+                    tool = int(match.group("tool"))
+                    tools_used.add(tool)
+                    # Initial tool load is not counted as a toolchange
+                    if last_tool is not None and tool != last_tool:
+                        num_toolchanges += 1
+                    last_tool = tool
+
+        return (has_placeholder, sorted(tools_used), num_toolchanges)
+
+    def _has_placeholder(self, line):
+        return any(p in line for p in [self.METADATA_REPLACEMENT_STRING, self.METADATA_TOOLCHANGES_STRING, self.METADATA_TIP_PARAMETERS_STRING, self.METADATA_FILAMENT_GRAMS_STRING,
+                                       self.METADATA_MATERIALS_STRING, self.METADATA_COLORS_STRING])
+
+    # Extract the per-filament ramming and tip forming settings that the slicer embeds as comments in the gcode so
+    # they can be passed to the standalone tip forming macro. Returns {tool: {PARAM: value}}
+    def _parse_slicer_tip_parameters(self, file_path):
+        settings = self._read_slicer_settings(file_path)
+        tip_parameters = {}
+        for key, param in self.SLICER_FILAMENT_TIP_SETTINGS.items():
+            if key in settings:
+                for tool, value in enumerate(settings[key].split(',')):
+                    tip_parameters.setdefault(tool, {})[param] = value.strip().strip('"')
+
+        diameters = [self._to_float(d, 1.75) for d in settings.get('filament_diameter', '').split(',')]
+        if 'filament_ramming_parameters' in settings:
+            for tool, ramming in enumerate(settings['filament_ramming_parameters'].split(';')):
+                values = ramming.strip().strip('"').split('|')[0].split()
+                # First two values are line width and step multipliers, remaining are volumetric speeds (mm^3/s)
+                speeds = [self._to_float(v, 0.) for v in values[2:]]
+                if not any(speeds):
+                    continue
+                diameter = diameters[tool] if tool < len(diameters) and diameters[tool] > 0 else 1.75
+                area = math.pi * (diameter / 2.) ** 2
+                tip_parameters.setdefault(tool, {})['RAMMING_SPEEDS'] = ",".join("%.2f" % (s / area) for s in speeds)
+
+        printer_parameters = {param: settings[key].split(',')[0].strip() for key, param in self.SLICER_PRINTER_TIP_SETTINGS.items() if key in settings}
+        for tool in tip_parameters:
+            tip_parameters[tool].update(printer_parameters)
+        return tip_parameters
+
+    def _read_slicer_settings(self, file_path):
+        regex = re.compile(self.SLICER_CONFIG_REGEX)
+        settings = {}
+        with open(file_path, "r") as f:
+            for line in f:
+                if line.startswith(";"):
+                    match = regex.match(line.strip())
+                    if match:
+                        settings[match.group("key")] = match.group("value").strip()
+        return settings
+
+    # Per-tool filament material and color from slicer settings. Returns ([material], [color])
+    def _parse_slicer_filaments(self, file_path):
+        settings = self._read_slicer_settings(file_path)
+        materials = [m.strip().strip('"') for m in settings.get('filament_type', '').split(';')]
+        colors = [c.strip().strip('"').lstrip('#')[:6] for c in settings.get('filament_colour', '').split(';')]
+        return materials, colors
+
+    # Estimated weight of filament used by each tool from slicer summary. Returns [grams] indexed by tool
+    def _parse_slicer_filament_grams(self, file_path):
+        regex = re.compile(self.SLICER_FILAMENT_GRAMS_REGEX)
+        with open(file_path, "r") as f:
+            for line in f:
+                if line.startswith(";"):
+                    match = regex.match(line.strip())
+                    if match:
+                        return [self._to_float(g.strip(), 0.) for g in match.group("value").split(',')]
+        return []
+
+    # Per-tool nozzle temperature from slicer settings (PrusaSlicer 'temperature', OrcaSlicer 'nozzle_temperature')
+    def _parse_slicer_temperatures(self, file_path):
+        settings = self._read_slicer_settings(file_path)
+        temps = settings.get('temperature', settings.get('nozzle_temperature', ''))
+        return [self._to_float(t.strip().strip('"'), 0.) for t in re.split(r"[,;]", temps) if t.strip()]
 
-        return (has_placeholder, sorted(tools_used))
+    # Rough print time at the start of each line based on move distance and feedrate. Acceleration is ignored so
+    # this is an underestimate but is good enough to position preheat hints. Yields (line, time)
+    def _gcode_line_times(self, lines):
+        pos = {'X': 0., 'Y': 0., 'Z': 0.}
+        feedrate = 1500. # mm/min
+        relative = False
+        elapsed = 0.
+        for line in lines:
+            yield line, elapsed
+            words = line.split(';', 1)[0].split()
+            if not words:
+                continue
+            code = words[0].upper()
+            params = {w[0].upper(): self._to_float(w[1:], 0.) for w in words[1:]}
+            if code in ['G0', 'G1']:
+                feedrate = params.get('F', feedrate) or feedrate
+                dist = 0.
+                for axis in pos:
+                    if axis in params:
+                        new_pos = pos[axis] + params[axis] if relative else params[axis]
+                        dist += (new_pos - pos[axis]) ** 2
+                        pos[axis] = new_pos
+                dist = math.sqrt(dist) or abs(params.get('E', 0.))
+                elapsed += dist * 60. / feedrate
+            elif code == 'G4':
+                elapsed += params.get('P', 0.) / 1000. + params.get('S', 0.)
+            elif code == 'G90':
+                relative = False
+            elif code == 'G91':
+                relative = True
+
+    # Insert 'MMU_PREHEAT TOOL=n TEMP=t' approximately 'preheat_lookahead' seconds of print time before each toolchange
+    # (but never before the previous toolchange) so the extruder can start heating for the next tool early
+    def _insert_preheat_hints(self, file_path):
+        regex = re.compile(self.TOOL_DISCOVERY_REGEX, re.IGNORECASE)
+        hints = [] # (toolchange line, tool, hint time, previous toolchange line)
+        last_tool, last_line, last_time = None, -1, 0.
+        try:
+            with open(file_path, "r") as f:
+                for i, (line, elapsed) in enumerate(self._gcode_line_times(f)):
+                    if line.startswith(self.PREHEAT_COMMAND):
+                        return False # Already processed
+                    match = regex.match(line)
+                    if match:
+                        tool = int(match.group("tool"))
+                        if last_tool is not None and tool != last_tool:
+                            hints.append((i, tool, max(elapsed - self.preheat_lookahead, last_time), last_line))
+                        last_tool, last_line, last_time = tool, i, elapsed
+        except OSError as e:
+            self._log("Unable to read file for preheat hints: %s" % str(e))
+            return False
+        if not hints:
+            return False
 
-    def _inject_tool_usage(self, file_path, tools_used):
+        self._log("Inserting %d preheat hints into file: %s" % (len(hints), file_path))
+        temps = self._parse_slicer_temperatures(file_path)
+        with fileinput.FileInput(file_path, inplace=1) as file:
+            for i, (line, elapsed) in enumerate(self._gcode_line_times(file)):
+                if hints and i > hints[0][3] and (elapsed >= hints[0][2] or i == hints[0][0]):
+                    tool = hints.pop(0)[1]
+                    temp = " TEMP=%.0f" % temps[tool] if tool < len(temps) and temps[tool] > 0 else ""
+                    print("%s TOOL=%d%s" % (self.PREHEAT_COMMAND, tool, temp))
+                print(line, end="")
+        return True
+
+    # Bracket each sparse infill section (from slicer feature comments) with 'MMU_PURGE_INFILL START=1' and
+    # 'MMU_PURGE_INFILL END=1' so the MMU can route part of the toolchange purge into it. Sections end at the next
+    # feature comment or toolchange (the MMU also ends any open section at the end of the print). Toolchanges whose
+    # first extrusion (ignoring the wipe tower) is sparse infill are preceded by 'MMU_PURGE_INFILL NEXT=1' because
+    # only then can the purge be reduced without contaminating other features
+    def _insert_purge_infill_markers(self, file_path):
+        feature_regex = re.compile(self.SLICER_FEATURE_REGEX, re.IGNORECASE)
+        tool_regex = re.compile(self.TOOL_DISCOVERY_REGEX, re.IGNORECASE)
+        try:
+            with open(file_path, "r") as f:
+                found = False
+                next_infill_tools = set() # Line numbers of toolchanges followed by sparse infill
+                tool_line = None
+                for i, line in enumerate(f):
+                    if line.startswith(self.PURGE_INFILL_COMMAND):
+                        return False # Already processed
+                    if tool_regex.match(line):
+                        tool_line = i
+                        continue
+                    match = feature_regex.match(line)
+                    if not match:
+                        continue
+                    feature = match.group("feature").strip().lower()
+                    if feature in self.SLICER_SPARSE_INFILL_FEATURES:
+                        found = True
+                        if tool_line is not None:
+                            next_infill_tools.add(tool_line)
+                    if "tower" not in feature:
+                        tool_line = None
+        except OSError as e:
+            self._log("Unable to read file for purge infill markers: %s" % str(e))
+            return False
+        if not found:
+            return False
+
+        self._log("Inserting purge infill markers into file: %s" % file_path)
+        in_infill = False
+        with fileinput.FileInput(file_path, inplace=1) as file:
+            for i, line in enumerate(file):
+                match = feature_regex.match(line)
+                if in_infill and (match or tool_regex.match(line)):
+                    print("%s END=1" % self.PURGE_INFILL_COMMAND)
+                    in_infill = False
+                if i in next_infill_tools:
+                    print("%s NEXT=1" % self.PURGE_INFILL_COMMAND)
+                print(line, end="")
+                if match and match.group("feature").strip().lower() in self.SLICER_SPARSE_INFILL_FEATURES:
+                    print("%s START=1" % self.PURGE_INFILL_COMMAND)
+                    in_infill = True
+        return True
+
+    # Insert 'MMU_DRYER DONE=n' after the toolchange that ends the last use of tool n so the MMU can turn off the
+    # dryer zone feeding it once none of its materials are needed. The final tool is left to the end of print
+    def _insert_dryer_done_markers(self, file_path):
+        regex = re.compile(self.TOOL_DISCOVERY_REGEX, re.IGNORECASE)
+        toolchanges = [] # (line, tool)
+        try:
+            with open(file_path, "r") as f:
+                for i, line in enumerate(f):
+                    if line.startswith(self.DRYER_DONE_COMMAND):
+                        return False # Already processed
+                    match = regex.match(line)
+                    if match:
+                        toolchanges.append((i, int(match.group("tool"))))
+        except OSError as e:
+            self._log("Unable to read file for dryer markers: %s" % str(e))
+            return False
+        last_use = {tool: n for n, (i, tool) in enumerate(toolchanges)}
+        done = {} # Toolchange line -> tools no longer needed after it
+        for tool, n in sorted(last_use.items()):
+            if n + 1 < len(toolchanges):
+                done.setdefault(toolchanges[n + 1][0], []).append(tool)
+        if not done:
+            return False
+
+        self._log("Inserting dryer markers into file: %s" % file_path)
+        with fileinput.FileInput(file_path, inplace=1) as file:
+            for i, line in enumerate(file):
+                print(line, end="")
+                if i in done:
+                    print("%s%s" % (self.DRYER_DONE_COMMAND, ",".join(map(str, done[i]))))
+        return True
+
+    def _to_float(self, value, default):
+        try:
+            return float(value)
+        except ValueError:
+            return default
+
+    # Compact dict literal without spaces so it can be passed as a single gcode parameter
+    def _format_tip_parameters(self, tip_parameters):
+        return "{%s}" % ",".join("%d:{%s}" % (tool, ",".join("'%s':'%s'" % (k, v) for k, v in sorted(params.items())))
+                                   for tool, params in sorted(tip_parameters.items()))
+
+    def _inject_tool_usage(self, file_path, tools_used, num_toolchanges=0, tip_parameters=None, filament_grams=None, filaments=None):
+        materials, colors = filaments or ([], [])
         with fileinput.FileInput(file_path, inplace=1) as file:
             for line in file:
-                if not line.startswith(";") and self.METADATA_REPLACEMENT_STRING in line:
+                if not line.startswith(";") and self._has_placeholder(line):
                     # Ignore comment lines to preserve slicer metadata comments
-                    print(line.replace(self.METADATA_REPLACEMENT_STRING, ",".join(map(str, tools_used))), end="")
+                    line = line.replace(self.METADATA_REPLACEMENT_STRING, ",".join(map(str, tools_used)))
+                    line = line.replace(self.METADATA_TIP_PARAMETERS_STRING, self._format_tip_parameters(tip_parameters or {}))
+                    line = line.replace(self.METADATA_FILAMENT_GRAMS_STRING, ",".join("%.2f" % g for g in filament_grams or []))
+                    line = line.replace(self.METADATA_MATERIALS_STRING, ",".join(m.replace(' ', '') for m in materials))
+                    line = line.replace(self.METADATA_COLORS_STRING, ",".join(colors))
+                    print(line.replace(self.METADATA_TOOLCHANGES_STRING, str(num_toolchanges)), end="")
                 else:
                     print(line, end="")
 
         return True
 
+    # Logic to prepare MMU between jobs in moonraker's job queue
+    async def _job_complete(self, prev_stats, new_stats):
+        job_queue = self.server.lookup_component("job_queue", None)
+        if job_queue is None or not job_queue.queued_jobs:
+            return
+        await self.prepare_job(next(iter(job_queue.queued_jobs.values())).filename)
+
+    async def prepare_job(self, filename):
+        file_path = os.path.join(self.file_manager.get_directory(), filename)
+        try:
+            _, tools_used, _ = self._enumerate_used_tools(file_path)
+            materials, colors = self._parse_slicer_filaments(file_path)
+        except OSError as e:
+            self._log("Unable to read next queued job '%s': %s" % (filename, str(e)))
+            return False
+        self._log("Preparing MMU for next queued job: " + filename)
+        kapis = self.server.lookup_component("klippy_apis")
+        try:
+            await kapis.run_gcode(f"MMU_PREPARE_JOB JOB='{os.path.basename(filename)}' TOOLS={','.join(map(str, tools_used))} MATERIALS='{','.join(materials)}' COLORS='{','.join(colors)}'")
+        except self.server.error as e:
+            logging.info(f"mmu_server: Exception running MMU gcode: %s" % str(e))
+            return False
+        return True
+
     # Logic to provide spoolman integration.
     # Leverage configuration from Spoolman component
     async def get_filaments(self, gate_ids):
@@ -113,7 +436,201 @@ class MmuServer:
             logging.info(f"mmu_server: Exception running MMU gcode: %s" % str(e))
 
         return response.json()
+
+    # Update spoolman with filament attributes learned by the MMU (e.g. from RFID tag)
+    async def push_filament(self, spool_id, material=None, color=None):
+        spoolman = self.server.lookup_component("spoolman")
+        body = {}
+        if material:
+            body['material'] = material
+        if color:
+            body['color_hex'] = color
+        if not body:
+            return False
+
+        full_url = f"{spoolman.spoolman_url}/v1/filament/{spool_id}"
+        try:
+            response = await spoolman.http_client.request(method="PATCH", url=full_url, body=body)
+            response.raise_for_status()
+        except Exception as e:
+            self._log("Exception pushing filament %s to spoolman: %s" % (spool_id, str(e)))
+            return False
+        return True
+
+    # Record physical location of spool in spoolman. Empty location clears it
+    async def set_location(self, spool_id, location=""):
+        spoolman = self.server.lookup_component("spoolman")
+        full_url = f"{spoolman.spoolman_url}/v1/spool/{spool_id}"
+        try:
+            response = await spoolman.http_client.request(method="PATCH", url=full_url, body={'location': location})
+            response.raise_for_status()
+        except Exception as e:
+            self._log("Exception setting location of spool %s in spoolman: %s" % (spool_id, str(e)))
+            return False
+        return True
+
+    # Derive gate assignments from spool locations of the form "<printer>:gate N"
+    # Spools whose spoolman location is a gate on this printer
+    async def _get_located_spools(self, printer):
+        spoolman = self.server.lookup_component("spoolman")
+        full_url = f"{spoolman.spoolman_url}/v1/spool"
+        response = await spoolman.http_client.request(method="GET", url=full_url, body=None)
+        response.raise_for_status()
+
+        regex = re.compile(r"^%s:gate (?P<gate>\d+)$" % re.escape(printer), re.IGNORECASE)
+        located = []
+        for spool in response.json():
+            match = regex.match(spool.get('location') or '')
+            if match:
+                located.append((int(match.group("gate")), spool))
+        return located
+
+    async def pull_locations(self, printer):
+        kapis = self.server.lookup_component("klippy_apis")
+        assignments = {}
+        for gate, spool in await self._get_located_spools(printer):
+            filament = spool.get('filament', {})
+            material = filament.get('material', '')[:6] # Keep material spec short for Klipperscreen
+            color_hex = filament.get('color_hex', '')[:6] # Strip alpha channel if it exists
+            assignments[gate] = spool.get('id')
+            try:
+                await kapis.run_gcode(f"MMU_GATE_MAP GATE={gate} SPOOLID={spool.get('id')} MATERIAL='{material}' COLOR='{color_hex}' QUIET=1")
+            except self.server.error as e:
+                logging.info(f"mmu_server: Exception running MMU gcode: %s" % str(e))
+        return assignments
+
+    # Return spoolman gate assignments to MMU_AUDIT for reconciliation with the gate map
+    async def audit_locations(self, printer, fix=0):
+        locations = {}
+        try:
+            for gate, spool in await self._get_located_spools(printer):
+                locations.setdefault(gate, []).append(spool.get('id'))
+        except Exception as e:
+            self._log("Exception retrieving spool locations for audit: %s" % str(e))
+            return False
+        kapis = self.server.lookup_component("klippy_apis")
+        try:
+            await kapis.run_gcode(f"MMU_AUDIT FIX={fix} LOCATIONS=\"{locations}\"")
+        except self.server.error as e:
+            logging.info(f"mmu_server: Exception running MMU gcode: %s" % str(e))
+            return False
+        return True
+
+    # Return remaining weight of spools assigned to gates to MMU_CHECK_FILAMENT_SUFFICIENCY
+    async def check_sufficiency(self, spools, grams, pause=0):
+        spoolman = self.server.lookup_component("spoolman")
+        try:
+            response = await spoolman.http_client.request(method="GET", url=f"{spoolman.spoolman_url}/v1/spool", body=None)
+            response.raise_for_status()
+        except Exception as e:
+            self._log("Exception retrieving spool weights for filament check: %s" % str(e))
+            return False
+        weights = {spool.get('id'): spool.get('remaining_weight') for spool in response.json()}
+        remaining = {int(gate): round(weights[spool_id], 1) for gate, spool_id in spools.items() if weights.get(spool_id) is not None}
+        kapis = self.server.lookup_component("klippy_apis")
+        try:
+            await kapis.run_gcode(f"MMU_CHECK_FILAMENT_SUFFICIENCY GRAMS={grams} PAUSE={pause} REMAINING=\"{remaining}\"")
+        except self.server.error as e:
+            logging.info(f"mmu_server: Exception running MMU gcode: %s" % str(e))
+            return False
+        return True
+
+    # Create a new spool of the same filament as spool_id (a fresh spool replacing an empty one) and assign it to gate
+    async def create_spool(self, gate, spool_id):
+        spoolman = self.server.lookup_component("spoolman")
+        try:
+            response = await spoolman.http_client.request(method="GET", url=f"{spoolman.spoolman_url}/v1/spool/{spool_id}", body=None)
+            response.raise_for_status()
+            filament_id = response.json()['filament']['id']
+            response = await spoolman.http_client.request(method="POST", url=f"{spoolman.spoolman_url}/v1/spool", body={'filament_id': filament_id})
+            response.raise_for_status()
+            new_spool_id = response.json()['id']
+        except Exception as e:
+            self._log("Exception creating new spool like %s in spoolman: %s" % (spool_id, str(e)))
+            return False
+        kapis = self.server.lookup_component("klippy_apis")
+        try:
+            await kapis.run_gcode(f"MMU_SPOOL_CHANGE GATE={gate} SPOOLID={new_spool_id}")
+        except self.server.error as e:
+            logging.info(f"mmu_server: Exception running MMU gcode: %s" % str(e))
+            return False
+        return new_spool_id
+
+    # Logic to publish MMU events to MQTT broker
+    # Leverage configuration from MQTT component
+    async def publish_event(self, event, data=None):
+        mqtt = self.server.lookup_component("mqtt", None)
+        if mqtt is None:
+            self._log("MQTT component not configured. Event '%s' not published" % event)
+            return False
+
+        topic = f"{self.mqtt_topic_prefix}/{event}"
+        payload = {'event': event, 'data': data or {}}
+        try:
+            await mqtt.publish_topic(topic, payload)
+        except Exception as e:
+            self._log("Exception publishing to MQTT topic '%s': %s" % (topic, str(e)))
+            return False
+        return True
    
+    # Logic to export MMU metrics (swap and phase timings, gate health)
+    async def record_metric(self, measurement, fields, tags=None, timestamp=None):
+        tags = {k: v for k, v in (tags or {}).items() if v is not None and v != ''}
+        # 'job' is reserved for the scrape target in Prometheus and would be unbounded so is only sent to InfluxDB
+        labels = tuple(sorted((k, str(v)) for k, v in tags.items() if k != 'job'))
+        for field, value in fields.items():
+            self.metrics[(measurement, field, labels)] = value
+
+        if not self.metrics_influxdb_url:
+            return True
+        line = self._to_line_protocol(measurement, fields, tags, timestamp or time.time())
+        headers = {'Content-Type': 'text/plain; charset=utf-8'}
+        if self.metrics_influxdb_token:
+            headers['Authorization'] = f"Token {self.metrics_influxdb_token}"
+        try:
+            http_client = self.server.lookup_component("http_client")
+            response = await http_client.request(method="POST", url=self.metrics_influxdb_url, body=line, headers=headers)
+            response.raise_for_status()
+        except Exception as e:
+            self._log("Exception pushing metric '%s' to InfluxDB: %s" % (measurement, str(e)))
+            return False
+        return True
+
+    def _to_line_protocol(self, measurement, fields, tags, timestamp):
+        escape = lambda s: re.sub(r"([,= ])", r"\\\1", str(s))
+        def field_value(value):
+            if isinstance(value, bool):
+                return "true" if value else "false"
+            if isinstance(value, int):
+                return f"{value}i"
+            if isinstance(value, float):
+                return repr(value)
+            return '"%s"' % str(value).replace('"', '\\"')
+        line = escape(measurement)
+        for key in sorted(tags):
+            line += f",{escape(key)}={escape(tags[key])}"
+        line += " " + ",".join(f"{escape(k)}={field_value(v)}" for k, v in sorted(fields.items()))
+        return f"{line} {int(timestamp * 1e9)}"
+
+    def _to_prometheus(self):
+        lines = []
+        last_name = None
+        for (measurement, field, labels), value in sorted(self.metrics.items()):
+            if isinstance(value, bool):
+                value = int(value)
+            elif not isinstance(value, (int, float)):
+                continue
+            name = re.sub(r"[^a-zA-Z0-9_]", "_", f"{measurement}_{field}")
+            if name != last_name:
+                lines.append(f"# TYPE {name} gauge")
+                last_name = name
+            label_str = ",".join('%s="%s"' % (k, v.replace('"', '\\"')) for k, v in labels)
+            lines.append(f"{name}{{{label_str}}} {value}" if label_str else f"{name} {value}")
+        return "\n".join(lines) + "\n"
+
+    async def _handle_metrics_request(self, web_request):
+        return self._to_prometheus()
+
 def load_component(config):
     return MmuServer(config)
 
@@ -16,8 +16,19 @@ import logging, logging.handlers, threading, queue, time, contextlib, math, os.p
 from random import randint
 from extras.mmu_toolhead import MmuToolHead, MmuHoming
 from extras.homing import Homing, HomingMove
+from extras.heaters import ControlPID
 from extras.mmu_led_effect import MmuLedEffect
-import chelper, ast
+from extras.mmu_vendor import get_vendor_driver
+from extras.mmu_plugin import MmuPluginManager
+from extras.mmu_api import MmuApi
+from extras.mmu_color import normalize_color, color_to_rgb, delta_e, colors_match, COLOR_FORMATS
+from extras.mmu_telemetry import MmuTelemetry
+from extras.mmu_journal import MmuJournal
+from extras.mmu_purge_assist import MmuPurgeAssist
+from extras.mmu_purge_router import MmuPurgeRouter
+from extras.mmu_virtual_encoder import MmuVirtualEncoder
+from extras.mmu_tip_profiles import get_tip_profile, get_tip_profile_names, get_tip_profile_description, get_cooling_profile, get_hotend_types, get_hotend_description
+import chelper, ast, configparser
 
 # Forward all messages through a queue (polled by background thread)
 class QueueHandler(logging.Handler):
@@ -77,9 +88,29 @@ class SimpleButtonHandler:
     def is_filament_present(sensor_name):
         return SimpleButtonHandler.handlers[sensor_name].filament_present
 
+# Gcode command parameters that remember which were looked up (to know which were consumed by a handler)
+class TrackedParams(dict):
+    def __init__(self, *args, **kwargs):
+        dict.__init__(self, *args, **kwargs)
+        self.accessed = set()
+
+    def get(self, key, default=None):
+        self.accessed.add(key)
+        return dict.get(self, key, default)
+
 # Mmu exception error class
 class MmuError(Exception):
-    pass
+    def __init__(self, message, code="MMU_ERR_GENERAL"):
+        super(MmuError, self).__init__(message)
+        self.code = code
+
+# Minimal config view so Klipper's PID controller can be built from 'dryer_zone_pid' values
+class MmuPidConfig:
+    def __init__(self, kp, ki, kd):
+        self.values = {'pid_Kp': kp, 'pid_Ki': ki, 'pid_Kd': kd}
+
+    def getfloat(self, option, default=None, **kwargs):
+        return self.values.get(option, default)
 
 # Main klipper module
 class Mmu:
@@ -87,6 +118,37 @@ class Mmu:
 
     BOOT_DELAY = 2.0            # Delay before running bootup tasks
 
+    # Machine readable error codes (reported with pause and in printer.mmu.last_error)
+    ERR_GENERAL                 = "MMU_ERR_GENERAL"
+    ERR_MANUAL_PAUSE            = "MMU_ERR_MANUAL_PAUSE"
+    ERR_MACRO                   = "MMU_ERR_MACRO"
+    ERR_CONFIG                  = "MMU_ERR_CONFIG"
+    ERR_STATE                   = "MMU_ERR_STATE"
+    ERR_CALIBRATION             = "MMU_ERR_CALIBRATION"
+    ERR_SELECTOR                = "MMU_ERR_SELECTOR"
+    ERR_GATE_LOAD_FAIL          = "MMU_ERR_GATE_LOAD_FAIL"
+    ERR_GATE_UNLOAD_FAIL        = "MMU_ERR_GATE_UNLOAD_FAIL"
+    ERR_GATE_EMPTY              = "MMU_ERR_GATE_EMPTY"
+    ERR_GATE_LOCKED             = "MMU_ERR_GATE_LOCKED"
+    ERR_BOWDEN_LOAD_FAIL        = "MMU_ERR_BOWDEN_LOAD_FAIL"
+    ERR_EXTRUDER_HOME_TIMEOUT   = "MMU_ERR_EXTRUDER_HOME_TIMEOUT"
+    ERR_TOOLHEAD_HOME_TIMEOUT   = "MMU_ERR_TOOLHEAD_HOME_TIMEOUT"
+    ERR_TOOLHEAD_SENSOR         = "MMU_ERR_TOOLHEAD_SENSOR"
+    ERR_NOZZLE_LOAD_FAIL        = "MMU_ERR_NOZZLE_LOAD_FAIL"
+    ERR_EXTRUDER_STUCK          = "MMU_ERR_EXTRUDER_STUCK"
+    ERR_FILAMENT_STUCK          = "MMU_ERR_FILAMENT_STUCK"
+    ERR_RUNOUT                  = "MMU_ERR_RUNOUT"
+    ERR_CLOG_SUSPECTED          = "MMU_ERR_CLOG_SUSPECTED"
+    ERR_GEAR_STALL              = "MMU_ERR_GEAR_STALL"
+    ERR_TIP_INSPECTION          = "MMU_ERR_TIP_INSPECTION"
+    ERR_GEAR_NOT_SYNCED         = "MMU_ERR_GEAR_NOT_SYNCED"
+    ERR_FILAMENT_MISMATCH       = "MMU_ERR_FILAMENT_MISMATCH"
+    ERR_FILAMENT_INSUFFICIENT   = "MMU_ERR_FILAMENT_INSUFFICIENT"
+    ERR_ENDLESS_SPOOL_EXHAUSTED = "MMU_ERR_ENDLESS_SPOOL_EXHAUSTED"
+    ERR_GEAR_SLIP               = "MMU_ERR_GEAR_SLIP"
+    ERR_SAFETY_WATCHDOG         = "MMU_ERR_SAFETY_WATCHDOG"
+    ERR_BYPASS_ACTIVE           = "MMU_ERR_BYPASS_ACTIVE"
+
     # Calibration steps
     CALIBRATED_GEAR     = 0b00001
     CALIBRATED_ENCODER  = 0b00010
@@ -133,82 +195,157 @@ class Mmu:
     ACTION_CHECKING = 7
     ACTION_HOMING = 8
     ACTION_SELECTING = 9
+    ACTION_WAITING = 10
 
     # Standard sensor and endstop or pseudo endstop names
     ENDSTOP_EXTRUDER_COLLISION = "collision" # Fake endstop
     ENDSTOP_ENCODER            = "encoder"   # Fake endstop
     ENDSTOP_EXTRUDER_TOUCH     = "mmu_ext_touch"
     ENDSTOP_GEAR_TOUCH         = "mmu_gear_touch"
+    ENDSTOP_GATE_TOUCH         = "mmu_gear_touch_gate" # Pseudo endstop using "mmu_gear_touch" at gate
     ENDSTOP_GATE               = "mmu_gate"
     ENDSTOP_EXTRUDER           = "extruder"
     ENDSTOP_TOOLHEAD           = "toolhead"
     ENDSTOP_SELECTOR_TOUCH     = "mmu_sel_touch"
     ENDSTOP_SELECTOR_HOME      = "mmu_sel_home"
+    SENSOR_SYNC_TENSION        = "sync_feedback_tension"
+    SENSOR_SYNC_COMPRESSION    = "sync_feedback_compression"
+    SENSOR_BYPASS              = "mmu_bypass"
 
     EXTRUDER_ENDSTOPS = [ENDSTOP_EXTRUDER_COLLISION, ENDSTOP_GEAR_TOUCH, ENDSTOP_EXTRUDER]
-    GATE_ENDSTOPS     = [ENDSTOP_GATE, ENDSTOP_ENCODER]
+    GATE_ENDSTOPS     = [ENDSTOP_GATE, ENDSTOP_ENCODER, ENDSTOP_GATE_TOUCH]
+
+    # Console output profiles
+    LOG_PROFILE_STANDARD       = "standard"
+    LOG_PROFILE_TERSE          = "terse"   # Single line key=value output for serial consoles and automation
+    LOG_PROFILES = [LOG_PROFILE_STANDARD, LOG_PROFILE_TERSE]
+
+    # Components monitored by safety watchdog
+    SAFETY_COMPONENTS = ['selector', 'servo']
+
+    # Notification routing. Event classes and where their messages can be sent
+    NOTIFY_CLASSES = ['toolchange', 'error', 'runout', 'drying']
+    NOTIFY_TARGETS = ['m117', 'console', 'moonraker', 'macro']
+    NOTIFY_EVENTS = {'error': 'error', 'runout': 'runout', 'drying_complete': 'drying'} # Published event -> class
+    NOTIFY_TEMPLATES = {
+        'toolchange': "{message}",
+        'error': "MMU error: {reason}",
+        'runout': "MMU runout on T{tool} (gate {gate})",
+        'drying': "MMU dryer zone {zone} finished at {humidity}% humidity",
+    }
+
+    # Action when dryer humidity exceeds material limit at print start
+    DRYNESS_POLICY_WARN        = "warn"    # Warn and continue
+    DRYNESS_POLICY_DRY         = "dry"     # Start drying and delay print until humidity is within limit
+    DRYNESS_POLICY_WAIVE       = "waive"   # Continue, logging the waiver
+    DRYNESS_POLICIES = [DRYNESS_POLICY_WAIVE, DRYNESS_POLICY_WARN, DRYNESS_POLICY_DRY] # Increasing strictness
+
+    # Where filament is cut (if a cutter is fitted)
+    CUTTER_TOOLHEAD            = "toolhead" # Cut by 'form_tip_macro' before unloading extruder
+    CUTTER_HUB                 = "hub"      # Cut at merge point of gates after filament is parked (e.g. BoxTurtle)
+    CUTTER_GATE                = "gate"     # Cut at each gate after filament is parked
+    CUTTER_LOCATIONS = [CUTTER_TOOLHEAD, CUTTER_HUB, CUTTER_GATE]
+
+    PRIME_NONE                 = "none"     # No priming after load
+    PRIME_FIXED                = "fixed"    # Always prime 'prime_length'
+    PRIME_AUTO                 = "auto"     # Residual filament plus ooze estimated from time since last extrusion
+    PRIME_MODES = [PRIME_NONE, PRIME_FIXED, PRIME_AUTO]
+
+    ES_MATCH_EXACT             = "exact"    # EndlessSpool candidate has same material and color as empty gate
+    ES_MATCH_COLOR             = "color"    # Same color, any material
+    ES_MATCH_MATERIAL          = "material" # Same material, any color
+    ES_MATCH_ANY               = "any"      # Any gate in group
+    ES_MATCH_RULES = [ES_MATCH_EXACT, ES_MATCH_COLOR, ES_MATCH_MATERIAL, ES_MATCH_ANY]
+
+    CONFIG_SCOPE_SESSION       = "session"  # MMU_TEST_CONFIG override lasts until restart
+    CONFIG_SCOPE_JOB           = "job"      # Override reverted when current print ends
+    CONFIG_SCOPE_GATE          = "gate"     # Override only applies while specific gate is selected
+    CONFIG_SCOPE_PERSIST       = "persist"  # Override saved and reapplied after restart
+    CONFIG_SCOPES = [CONFIG_SCOPE_SESSION, CONFIG_SCOPE_JOB, CONFIG_SCOPE_GATE, CONFIG_SCOPE_PERSIST]
+
+    PHASE_TOOLCHANGE           = "toolchange" # Whole toolchange in print
+    PHASE_UNLOAD               = "unload"
+    PHASE_FORM_TIP             = "form_tip"
+    PHASE_LOAD                 = "load"
+    PHASE_POST_LOAD            = "post_load"  # _MMU_POST_LOAD macro (purge/wipe)
+    DEVICE_PHASES = [PHASE_TOOLCHANGE, PHASE_UNLOAD, PHASE_FORM_TIP, PHASE_LOAD, PHASE_POST_LOAD]
+
+    DOC_URL = "https://github.com/moggieuk/Happy-Hare/blob/main/doc"
+
+    SELF_CHECK_WINDOW = 3 * 3600 # Seconds after 'self_check_time' that a busy printer can delay the scheduled self-check
+    GEAR_SLIP_CHECK_INTERVAL = 0.5 # Seconds between checks of entry sensor for gear slip whilst synced
+    GEAR_THERMAL_CHECK_INTERVAL = 30. # Seconds between checks of gear motor temperature for sync current reduction
+    ESPOOLER_RAMP_INTERVAL = 0.1 # Seconds between PWM steps of rewind assist ramps
+    RFID_PENDING_TIMEOUT = 120. # Seconds to insert filament after scanning tag with single inlet reader
+
+    # Toolhead specific settings included in calibration backup sets (restored values override mmu_parameters.cfg)
+    CALIB_TOOLHEAD_SETTINGS = ['toolhead_extruder_to_nozzle', 'toolhead_sensor_to_nozzle', 'form_tip_macro']
+
+    MAX_STATE_TRANSITIONS = 50 # Size of state transition history for MMU_TRACE
+    MAX_MAP_HISTORY = 20 # Number of gate map / TTG map changes that can be viewed and undone
+    RATIO_DRIFT_SAMPLES = 10   # Number of observed gate ratios retained for autotune and drift monitoring
+    GATE_HEALTH_SAMPLES = 10   # Number of friction profile results retained per gate
+    SUMMARY_MIN_LOADS = 10     # Load attempts on a gate before its success rate is judged by MMU_STATUS SUMMARY=1
+    SUMMARY_LOAD_SUCCESS = 95. # Load success rate (%) below which a gate needs attention
 
     # Stepper config sections
     SELECTOR_STEPPER_CONFIG    = "stepper_mmu_selector"
     GEAR_STEPPER_CONFIG        = "stepper_mmu_gear"
 
     # Vendor MMU's supported
-    VENDOR_ERCF     = "ERCF"
-    VENDOR_TRADRACK = "Tradrack"
-    VENDOR_PRUSA    = "Prusa" # In progress
-    VENDOR_OTHER    = "Other"
+    VENDOR_ERCF      = "ERCF"
+    VENDOR_TRADRACK  = "Tradrack"
+    VENDOR_PRUSA     = "Prusa" # In progress
+    VENDOR_3MS       = "3MS"
+    VENDOR_PICO      = "PicoMMU"
+    VENDOR_NIGHTOWL  = "NightOwl"
+    VENDOR_CHAMELEON = "3DChameleon"
+    VENDOR_OTHER     = "Other"
 
     # mmu_vars.cfg variables
     VARS_MMU_CALIB_CLOG_LENGTH      = "mmu_calibration_clog_length"
     VARS_MMU_ENABLE_ENDLESS_SPOOL   = "mmu_state_enable_endless_spool"
     VARS_MMU_ENDLESS_SPOOL_GROUPS   = "mmu_state_endless_spool_groups"
+    VARS_MMU_ENDLESS_SPOOL_PRIORITIES = "mmu_state_endless_spool_priorities"
     VARS_MMU_TOOL_TO_GATE_MAP       = "mmu_state_tool_to_gate_map"
     VARS_MMU_GATE_STATUS            = "mmu_state_gate_status"
     VARS_MMU_GATE_MATERIAL          = "mmu_state_gate_material"
     VARS_MMU_GATE_COLOR             = "mmu_state_gate_color"
     VARS_MMU_GATE_SPOOL_ID          = "mmu_state_gate_spool_id"
+    VARS_MMU_GATE_PRESSURE_ADVANCE  = "mmu_state_gate_pressure_advance"
+    VARS_MMU_GATE_SMOOTH_TIME       = "mmu_state_gate_smooth_time"
+    VARS_MMU_GATE_TEMPERATURE       = "mmu_state_gate_temperature"
+    VARS_MMU_GATE_UNIT              = "mmu_state_gate_unit"
+    VARS_MMU_GATE_MAINTENANCE       = "mmu_state_gate_maintenance"
+    VARS_MMU_GATE_LOCK              = "mmu_state_gate_lock"
+    VARS_MMU_GATE_STUB_LENGTH       = "mmu_state_gate_stub_length"
     VARS_MMU_GATE_SELECTED          = "mmu_state_gate_selected"
     VARS_MMU_TOOL_SELECTED          = "mmu_state_tool_selected"
+    VARS_MMU_BYPASS_SELECTED        = "mmu_state_bypass_selected"
     VARS_MMU_FILAMENT_POS           = "mmu_state_filament_pos"
     VARS_MMU_CALIB_BOWDEN_LENGTH    = "mmu_calibration_bowden_length"
     VARS_MMU_CALIB_PREFIX           = "mmu_calibration_"
+    VARS_MMU_CALIB_SYNC_PREFIX      = "mmu_calibration_sync_"
+    VARS_MMU_CALIB_STALL_PREFIX     = "mmu_calibration_stall_"
+    VARS_MMU_CALIB_SYNC_FEEDBACK    = "mmu_calibration_sync_feedback"
+    VARS_MMU_CALIB_RATIO_DRIFT      = "mmu_calibration_ratio_drift"
+    VARS_MMU_CALIB_TOOLHEAD         = "mmu_calibration_toolhead"
+    VARS_MMU_CALIB_COLLISION_CURRENT = "mmu_calibration_collision_current"
+    VARS_MMU_CALIB_CUTTER_TRAVEL    = "mmu_calibration_cutter_travel"
+    VARS_MMU_CALIB_BACKUP_PREFIX    = "mmu_backup_calibration_"
     VARS_MMU_GATE_STATISTICS_PREFIX = "mmu_statistics_gate_"
     VARS_MMU_SWAP_STATISTICS        = "mmu_statistics_swaps"
+    VARS_MMU_SAFETY_TRIPS           = "mmu_statistics_safety_trips"
+    VARS_MMU_CUTTER_BLADE           = "mmu_statistics_cutter_blade"
+    VARS_MMU_GATE_HEALTH            = "mmu_statistics_gate_health"
     VARS_MMU_SELECTOR_OFFSETS       = "mmu_selector_offsets"
     VARS_MMU_SELECTOR_BYPASS        = "mmu_selector_bypass"
     VARS_MMU_ENCODER_RESOLUTION     = "mmu_encoder_resolution"
     VARS_MMU_GEAR_ROTATION_DISTANCE = "mmu_gear_rotation_distance"
+    VARS_MMU_PLR_STATE              = "mmu_state_plr"
+    VARS_MMU_CONFIG_OVERRIDES       = "mmu_state_config_overrides"
 
-    EMPTY_GATE_STATS_ENTRY = {'pauses': 0, 'loads': 0, 'load_distance': 0.0, 'load_delta': 0.0, 'unloads': 0, 'unload_distance': 0.0, 'unload_delta': 0.0, 'servo_retries': 0, 'load_failures': 0, 'unload_failures': 0, 'quality': -1.}
-
-    W3C_COLORS = [('aliceblue','#F0F8FF'), ('antiquewhite','#FAEBD7'), ('aqua','#00FFFF'), ('aquamarine','#7FFFD4'), ('azure','#F0FFFF'), ('beige','#F5F5DC'),
-                  ('bisque','#FFE4C4'), ('black','#000000'), ('blanchedalmond','#FFEBCD'), ('blue','#0000FF'), ('blueviolet','#8A2BE2'), ('brown','#A52A2A'),
-                  ('burlywood','#DEB887'), ('cadetblue','#5F9EA0'), ('chartreuse','#7FFF00'), ('chocolate','#D2691E'), ('coral','#FF7F50'),
-                  ('cornflowerblue','#6495ED'), ('cornsilk','#FFF8DC'), ('crimson','#DC143C'), ('cyan','#00FFFF'), ('darkblue','#00008B'), ('darkcyan','#008B8B'),
-                  ('darkgoldenrod','#B8860B'), ('darkgray','#A9A9A9'), ('darkgreen','#006400'), ('darkgrey','#A9A9A9'), ('darkkhaki','#BDB76B'),
-                  ('darkmagenta','#8B008B'), ('darkolivegreen','#556B2F'), ('darkorange','#FF8C00'), ('darkorchid','#9932CC'), ('darkred','#8B0000'),
-                  ('darksalmon','#E9967A'), ('darkseagreen','#8FBC8F'), ('darkslateblue','#483D8B'), ('darkslategray','#2F4F4F'), ('darkslategrey','#2F4F4F'),
-                  ('darkturquoise','#00CED1'), ('darkviolet','#9400D3'), ('deeppink','#FF1493'), ('deepskyblue','#00BFFF'), ('dimgray','#696969'),
-                  ('dimgrey','#696969'), ('dodgerblue','#1E90FF'), ('firebrick','#B22222'), ('floralwhite','#FFFAF0'), ('forestgreen','#228B22'),
-                  ('fuchsia','#FF00FF'), ('gainsboro','#DCDCDC'), ('ghostwhite','#F8F8FF'), ('gold','#FFD700'), ('goldenrod','#DAA520'), ('gray','#808080'),
-                  ('green','#008000'), ('greenyellow','#ADFF2F'), ('grey','#808080'), ('honeydew','#F0FFF0'), ('hotpink','#FF69B4'), ('indianred','#CD5C5C'),
-                  ('indigo','#4B0082'), ('ivory','#FFFFF0'), ('khaki','#F0E68C'), ('lavender','#E6E6FA'), ('lavenderblush','#FFF0F5'), ('lawngreen','#7CFC00'),
-                  ('lemonchiffon','#FFFACD'), ('lightblue','#ADD8E6'), ('lightcoral','#F08080'), ('lightcyan','#E0FFFF'), ('lightgoldenrodyellow','#FAFAD2'),
-                  ('lightgray','#D3D3D3'), ('lightgreen','#90EE90'), ('lightgrey','#D3D3D3'), ('lightpink','#FFB6C1'), ('lightsalmon','#FFA07A'),
-                  ('lightseagreen','#20B2AA'), ('lightskyblue','#87CEFA'), ('lightslategray','#778899'), ('lightslategrey','#778899'),
-                  ('lightsteelblue','#B0C4DE'), ('lightyellow','#FFFFE0'), ('lime','#00FF00'), ('limegreen','#32CD32'), ('linen','#FAF0E6'),
-                  ('magenta','#FF00FF'), ('maroon','#800000'), ('mediumaquamarine','#66CDAA'), ('mediumblue','#0000CD'), ('mediumorchid','#BA55D3'),
-                  ('mediumpurple','#9370DB'), ('mediumseagreen','#3CB371'), ('mediumslateblue','#7B68EE'), ('mediumspringgreen','#00FA9A'),
-                  ('mediumturquoise','#48D1CC'), ('mediumvioletred','#C71585'), ('midnightblue','#191970'), ('mintcream','#F5FFFA'), ('mistyrose','#FFE4E1'),
-                  ('moccasin','#FFE4B5'), ('navajowhite','#FFDEAD'), ('navy','#000080'), ('oldlace','#FDF5E6'), ('olive','#808000'),
-                  ('olivedrab','#6B8E23'), ('orange','#FFA500'), ('orangered','#FF4500'), ('orchid','#DA70D6'), ('palegoldenrod','#EEE8AA'),
-                  ('palegreen','#98FB98'), ('paleturquoise','#AFEEEE'), ('palevioletred','#DB7093'), ('papayawhip','#FFEFD5'), ('peachpuff','#FFDAB9'),
-                  ('peru','#CD853F'), ('pink','#FFC0CB'), ('plum','#DDA0DD'), ('powderblue','#B0E0E6'), ('purple','#800080'), ('red','#FF0000'),
-                  ('rosybrown','#BC8F8F'), ('royalblue','#4169E1'), ('saddlebrown','#8B4513'), ('salmon','#FA8072'), ('sandybrown','#F4A460'),
-                  ('seagreen','#2E8B57'), ('seashell','#FFF5EE'), ('sienna','#A0522D'), ('silver','#C0C0C0'), ('skyblue','#87CEEB'), ('slateblue','#6A5ACD'),
-                  ('slategray','#708090'), ('slategrey','#708090'), ('snow','#FFFAFA'), ('springgreen','#00FF7F'), ('steelblue','#4682B4'),
-                  ('tan','#D2B48C'), ('teal','#008080'), ('thistle','#D8BFD8'), ('tomato','#FF6347'), ('turquoise','#40E0D0'), ('violet','#EE82EE'),
-                  ('wheat','#F5DEB3'), ('white','#FFFFFF'), ('whitesmoke','#F5F5F5'), ('yellow','#FFFF00'), ('yellowgreen','#9ACD32')]
+    EMPTY_GATE_STATS_ENTRY = {'pauses': 0, 'loads': 0, 'full_loads': 0, 'load_distance': 0.0, 'load_delta': 0.0, 'unloads': 0, 'unload_distance': 0.0, 'unload_delta': 0.0, 'servo_retries': 0, 'load_failures': 0, 'unload_failures': 0, 'quality': -1.}
 
     UPGRADE_REMINDER = "Happy Hare minor version has changed which requires you to re-run\n'./install.sh' to update configuration files and klipper modules.\nMore details: https://github.com/moggieuk/Happy-Hare/blob/main/doc/upgrade.md"
 
@@ -222,7 +359,12 @@ class Mmu:
         self.calibrated_bowden_length = -1
         self.ref_gear_rotation_distance = 1.
         self.encoder_force_validation = False
-        self.w3c_colors = dict(self.W3C_COLORS)
+        self.state_transitions = [] # Recent state machine transitions, kept across resets for diagnostics
+        self.map_history = [] # Recent gate map and TTG map changes for MMU_GATE_MAP HISTORY/UNDO
+        self.map_snapshot = None # Gate map and TTG map as of last recorded change
+        self.config_overrides = [] # Stack of scoped MMU_TEST_CONFIG overrides, oldest first
+        self.config_override_base = {} # Value of each overridden setting before any override
+        self.config_reloaded = {} # Raw option values applied by MMU_RELOAD_CONFIG, keyed by (section, option)
 
         self.printer.register_event_handler('klippy:connect', self.handle_connect)
         self.printer.register_event_handler("klippy:disconnect", self.handle_disconnect)
@@ -252,6 +394,8 @@ class Mmu:
         #  cad_bypass_block_delta - distance from previous gate to bypass (ERCF v1.1)
         #  cad_bypass_offset      - distance from end of travel to the bypass
         #  cad_last_gate_offset   - distance from end of travel to last gate
+        #  cad_gate_positions     - optional explicit position of each gate from gate #0 (non-uniform spacing)
+        #  cad_bypass_positions   - optional explicit position of each bypass from gate #0
         self.cad_gate0_pos = 4.2
         self.cad_gate_width = 21.
         self.cad_bypass_offset = 0.
@@ -266,66 +410,10 @@ class Mmu:
         self.encoder_default_resolution = bmg_circ / (2 * 17) # TRCT5000 based sensor
 
         # Specific vendor build parameters / tuning. Mostly CAD related but a few exceptions like gate_park_distance
-        if self.mmu_vendor.lower() == self.VENDOR_ERCF.lower():
-            if self.mmu_version >= 2.0: # V2 community edition
-                self.cad_gate0_pos = 4.0
-                self.cad_gate_width = 23.
-                self.cad_bypass_offset = 0.72
-                self.cad_last_gate_offset = 14.4
-
-                # Non CAD default parameters
-                self.gate_parking_distance = 13.
-                self.encoder_default_resolution = bmg_circ / (2 * 12) # Binky 12 tooth disc with BMG gear
-
-                # Modifications:
-                #  h = ThumperBlocks filament blocks
-                if "h" in self.mmu_version_string:
-                    self.cad_gate_width = 21.
-                    self.gate_parking_distance = 11.
-
-            else: # V1.1 original
-                # Modifications:
-                #  t = TripleDecky filament blocks
-                #  s = Springy sprung servo selector
-                #  b = Binky encoder upgrade
-                if "t" in self.mmu_version_string:
-                    self.cad_gate_width = 23. # Triple Decky is wider filament block
-                    self.cad_block_width = 0. # Bearing blocks are not used
-                    self.gate_parking_distance = 13. # Filament trap in block
-
-                if "s" in self.mmu_version_string:
-                    self.cad_last_gate_offset = 1.2 # Springy has additional bump stops
-
-                if "b" in self.mmu_version_string:
-                    self.encoder_default_resolution = bmg_circ / (2 * 12) # Binky 12 tooth disc with BMG gear
-
-        elif self.mmu_vendor.lower() == self.VENDOR_TRADRACK.lower():
-            self.cad_gate0_pos = 0.5
-            self.cad_gate_width = 17.
-            self.cad_bypass_offset = 0 # Doesn't have bypass
-            self.cad_last_gate_offset = 1. # TODO this is a guess
-
-            self.gate_parking_distance = 17. # Using Gate switch (had user reports from 15 - 17.5)
-            self.encoder_default_resolution = bmg_circ / (2 * 12) # If fitted, assumed to by Binky
-
-            # Modifications:
-            #  e = has encoder modification
-            #      Note: if have encoder but want to use gate sensor to part, then `gate_endstop_to_encoder`
-            #            would need to be set and `gate_parking_distance` set back to original
-            if "e" in self.mmu_version_string:
-                self.gate_parking_distance = 39. # Assume using encoder if we have it
-                self.gate_endstop_to_encoder = 15. # TODO this is a guess
-
-        elif self.mmu_vendor.lower() == self.VENDOR_PRUSA.lower():
+        if self.mmu_vendor.lower() == self.VENDOR_PRUSA.lower():
             raise self.config.error("Support for Prusa systems is comming soon! You can try with vendor=Other and configure `cad` dimensions (see doc)")
-
-        else:
-            # Some arbitary starting values for "Other" designs
-            self.cad_gate0_pos = 1.
-            self.cad_gate_width = 20.
-            self.cad_bypass_offset = 1.
-            self.cad_last_gate_offset = 10.
-            self.gate_parking_distance = 20.
+        self.vendor_driver = get_vendor_driver(self.mmu_vendor, self.mmu_version_string, self.mmu_version)
+        self.vendor_driver.set_defaults(self)
 
         # Allow CAD parameters to be customized
         self.cad_gate0_pos = config.getfloat('cad_gate0_pos', self.cad_gate0_pos, minval=0.)
@@ -339,11 +427,20 @@ class Mmu:
 
         self.cad_selector_tolerance = config.getfloat('cad_selector_tolerance', 10., above=0.) # Extra movement allowed by selector
 
+        # Explicit position table replaces the linear 'cad_gate_width' model for selectors with non-uniform gate spacing
+        self.cad_gate_positions = list(config.getfloatlist('cad_gate_positions', []))
+        self.cad_bypass_positions = list(config.getfloatlist('cad_bypass_positions', []))
+        if self.cad_gate_positions and (self.cad_gate_positions[0] != 0. or any(b <= a for a, b in zip(self.cad_gate_positions, self.cad_gate_positions[1:]))):
+            raise self.config.error("cad_gate_positions must start at 0 (gate #0) and be in increasing order")
+
         # Printer interaction config
         self.extruder_name = config.get('extruder', 'extruder')
         self.timeout_pause = config.getint('timeout_pause', 72000, minval=120)
         self.default_idle_timeout = config.getint('default_idle_timeout', -1, minval=120)
         self.disable_heater = config.getint('disable_heater', 600, minval=60)
+        self.hotend_standby_time = config.getint('hotend_standby_time', 0, minval=0)
+        self.hotend_standby_temp = config.getfloat('hotend_standby_temp', 150., minval=0.)
+        self.tool_preheat = config.getint('tool_preheat', 1, minval=0, maxval=1)
         self.default_extruder_temp = config.getfloat('default_extruder_temp', 200.)
         self.gcode_load_sequence = config.getint('gcode_load_sequence', 0)
         self.gcode_unload_sequence = config.getint('gcode_unload_sequence', 0)
@@ -352,25 +449,103 @@ class Mmu:
         self.z_hop_speed = config.getfloat('z_hop_speed', 15., minval=1.)
         self.slicer_tip_park_pos = config.getfloat('slicer_tip_park_pos', 0., minval=0.)
         self.force_form_tip_standalone = config.getint('force_form_tip_standalone', 0, minval=0, maxval=1)
+        self.slicer_tip_passthrough = config.getint('slicer_tip_passthrough', 1, minval=0, maxval=1)
+        self.form_tip_profile = config.get('form_tip_profile', '').lower()
+        if self.form_tip_profile and get_tip_profile(self.form_tip_profile) is None:
+            raise self.config.error("form_tip_profile '%s' is invalid. Options are: %s" % (self.form_tip_profile, get_tip_profile_names()))
+        self.form_tip_profile_materials = {}
+        for spec in config.getlist('form_tip_profile_materials', []):
+            try:
+                material, profile = spec.split(':')
+            except ValueError:
+                raise self.config.error("form_tip_profile_materials entry '%s' must be in form 'MATERIAL:PROFILE'" % spec)
+            if get_tip_profile(profile.strip()) is None:
+                raise self.config.error("form_tip_profile_materials entry '%s' is invalid. Profile options are: %s" % (spec, get_tip_profile_names()))
+            self.form_tip_profile_materials[material.strip().upper()] = profile.strip().lower()
+        form_tip_profile_gates = config.get('form_tip_profile_gates', '')
+        self.form_tip_profile_gates = [p.strip().lower() for p in form_tip_profile_gates.split(',')] if form_tip_profile_gates else []
+        self.hotend_type = config.get('hotend_type', '').lower()
+        if self.hotend_type and get_cooling_profile(self.hotend_type) is None:
+            raise self.config.error("hotend_type '%s' is invalid. Options are: %s" % (self.hotend_type, get_hotend_types()))
+        self.tip_inspection = config.getint('tip_inspection', 0, minval=0, maxval=1)
+        self.tip_inspection_length = config.getfloat('tip_inspection_length', 10., minval=4., maxval=50.)
+        self.tip_inspection_retries = config.getint('tip_inspection_retries', 1, minval=0, maxval=3)
+        self.cut_recovery_retries = config.getint('cut_recovery_retries', 1, minval=0, maxval=3)
+        self.cut_recovery_purge_length = config.getfloat('cut_recovery_purge_length', 10., minval=0.)
         self.persistence_level = config.getint('persistence_level', 0, minval=0, maxval=4)
         self.auto_calibrate_gates = config.getint('auto_calibrate_gates', 0, minval=0, maxval=1)
+        self.autotune_rotation_distance = config.getint('autotune_rotation_distance', 0, minval=0, maxval=1)
+        self.rotation_distance_drift_threshold = config.getfloat('rotation_distance_drift_threshold', 2., minval=0.)
+        self.gate_health_threshold = config.getfloat('gate_health_threshold', 10., minval=0.)
         self.strict_filament_recovery = config.getint('strict_filament_recovery', 0, minval=0, maxval=1)
         self.retry_tool_change_on_error = config.getint('retry_tool_change_on_error', 0, minval=0, maxval=1)
+        self.selector_stall_recovery = config.getint('selector_stall_recovery', 1, minval=0, maxval=1)
+        self.safety_watchdog = config.getint('safety_watchdog', 0, minval=0, maxval=1)
+        self.safety_watchdog_timeout = config.getfloat('safety_watchdog_timeout', 30., minval=1.)
+        self.safety_watchdog_failures = config.getint('safety_watchdog_failures', 2, minval=1)
+        self.toolchange_grace_mode = config.getint('toolchange_grace_mode', 0, minval=0, maxval=1)
+        self.toolchange_grace_retries = config.getint('toolchange_grace_retries', 2, minval=0, maxval=5)
+        self.toolchange_grace_window = config.getint('toolchange_grace_window', 120, minval=0)
         self.print_start_detection = config.getint('print_start_detection', 1, minval=0, maxval=1)
+        self.cancel_print_unload = config.getint('cancel_print_unload', 0, minval=0, maxval=2)
+        self.resume_verify_time = config.getint('resume_verify_time', 3600, minval=0)
+        self.resume_verify_temp_tolerance = config.getfloat('resume_verify_temp_tolerance', 15., minval=0.)
+        self.print_start_check_gates = config.getint('print_start_check_gates', 0, minval=0, maxval=1)
+        self.toolchange_budget_margin = config.getint('toolchange_budget_margin', 10, minval=0)
+        self.print_start_check_filament = config.getint('print_start_check_filament', 1, minval=0, maxval=2)
+        self.print_start_check_sufficiency = config.getint('print_start_check_sufficiency', 0, minval=0, maxval=2)
+        self.print_start_ttg_manifest = config.getint('print_start_ttg_manifest', 1, minval=0, maxval=2)
+        self.filament_sufficiency_margin = config.getfloat('filament_sufficiency_margin', 5., minval=0.)
+        self.prepare_job_remap = config.getint('prepare_job_remap', 1, minval=0, maxval=1)
+        self.prepare_job_dry = config.getint('prepare_job_dry', 1, minval=0, maxval=1)
+        self.prepare_job_preload = config.getint('prepare_job_preload', 0, minval=0, maxval=1)
+        self.prepare_job_color_tolerance = config.getfloat('prepare_job_color_tolerance', 10., minval=0.)
+        self.idle_retract_time = config.getint('idle_retract_time', 0, minval=0)
+        self.idle_retract_materials = [m.strip().upper() for m in config.getlist('idle_retract_materials', [])]
+        self.idle_retract_length = config.getfloat('idle_retract_length', 0., minval=0.)
+        self.idle_retract_reload = config.getint('idle_retract_reload', 1, minval=0, maxval=1)
+        self.idle_care_interval = config.getfloat('idle_care_interval', 0., minval=0.) # Hours
+        self.idle_care_length = config.getfloat('idle_care_length', 5., minval=1., maxval=50.)
+        self.idle_care_materials = [m.strip().upper() for m in config.getlist('idle_care_materials', [])]
+        self.self_check_time = config.get('self_check_time', '').strip() # Local time 'HH:MM' for daily self-check
+        if self.self_check_time:
+            try:
+                self.self_check_hhmm = tuple(int(x) for x in self.self_check_time.split(':'))
+                if len(self.self_check_hhmm) != 2 or not 0 <= self.self_check_hhmm[0] < 24 or not 0 <= self.self_check_hhmm[1] < 60:
+                    raise ValueError()
+            except ValueError:
+                raise config.error("self_check_time must be a 24 hour local time in form 'HH:MM'")
+        self.self_check_gates = config.getint('self_check_gates', 1, minval=0, maxval=1)
 
         # Internal macro overrides
         self.pause_macro = config.get('pause_macro', 'PAUSE')
         self.form_tip_macro = config.get('form_tip_macro', '_MMU_FORM_TIP_STANDALONE')
+        self.cutter_location = config.get('cutter_location', self.CUTTER_TOOLHEAD).lower()
+        if self.cutter_location not in self.CUTTER_LOCATIONS:
+            raise self.config.error("cutter_location is invalid. Options are: %s" % self.CUTTER_LOCATIONS)
+        self.cutter_macro = config.get('cutter_macro', '_MMU_CUT_FILAMENT')
+        self.cutter_distance = config.getfloat('cutter_distance', 0.) # Gate endstop to blade (positive towards toolhead)
+        self.cutter_tip_length = config.getfloat('cutter_tip_length', 10., minval=0.) # Length of deformed tip to cut off
+        self.cutter_stub_length = config.getfloat('cutter_stub_length', 0., minval=0.) # Cut off piece left in filament path
+        self.cutter_blade_life = config.getint('cutter_blade_life', 0, minval=0) # Cuts before blade replacement warning
 
         # User MMU setup
         self.mmu_num_gates = config.getint('mmu_num_gates')
+        if self.cad_gate_positions and len(self.cad_gate_positions) != self.mmu_num_gates:
+            raise self.config.error("cad_gate_positions has a different number of values than the number of gates")
         self.selector_offsets = list(config.getfloatlist('selector_offsets', []))
-        self.bypass_offset = config.getfloat('selector_bypass', 0)
+        self.bypass_offsets = [o for o in config.getfloatlist('selector_bypass', []) if o > 0.]
+        self.bypass_selected = 0
+        self.bypass_offset = self.bypass_offsets[0] if self.bypass_offsets else 0.
         self.default_tool_to_gate_map = list(config.getintlist('tool_to_gate_map', []))
         self.default_gate_status = list(config.getintlist('gate_status', []))
         self.default_gate_material = list(config.getlist('gate_material', []))
         self.default_gate_color = list(config.getlist('gate_color', []))
         self.default_gate_spool_id = list(config.getintlist('gate_spool_id', []))
+        self.default_gate_pressure_advance = list(config.getfloatlist('gate_pressure_advance', []))
+        self.default_gate_smooth_time = list(config.getfloatlist('gate_smooth_time', []))
+        self.default_gate_temperature = list(config.getintlist('gate_temperature', []))
+        self.default_gate_unit = list(config.getlist('gate_unit', []))
 
         # Configuration for gate loading and unloading
         self.gate_homing_endstop = config.get('gate_homing_endstop', self.ENDSTOP_ENCODER) # "encoder" or "mmu_gate"
@@ -381,6 +556,13 @@ class Mmu:
         self.gate_homing_max = config.getfloat('gate_homing_max', 2 * self.gate_unload_buffer, minval=self.gate_unload_buffer)
         self.gate_parking_distance = config.getfloat('gate_parking_distance', self.gate_parking_distance) # Can be +ve or -ve
         self.gate_load_retries = config.getint('gate_load_retries', 2, minval=1, maxval=5)
+        self.gate_park_verify = config.getint('gate_park_verify', 0, minval=0, maxval=2)
+        self.gate_park_verify_length = config.getfloat('gate_park_verify_length', 3., above=0.)
+        self.gate_park_verify_retries = config.getint('gate_park_verify_retries', 2, minval=0, maxval=5)
+        self.gate_ready_park = list(config.getfloatlist('gate_ready_park', []))
+        self.gate_ready_park_revert = config.getfloat('gate_ready_park_revert', 0., minval=0.)
+        self.path_sensor_tolerance = config.getfloat('path_sensor_tolerance', 20., minval=0.)
+        self.toolchange_overlap_length = config.getfloat('toolchange_overlap_length', 0., minval=0.) # Type-B only
         self.encoder_move_step_size = config.getfloat('encoder_move_step_size', 15., minval=5., maxval=25.) # Not exposed
         self.encoder_dwell = config.getfloat('encoder_dwell', 0.1, minval=0., maxval=2.) # Not exposed
         self.encoder_default_resolution = config.getfloat('encoder_default_resolution', self.encoder_default_resolution)
@@ -388,10 +570,19 @@ class Mmu:
         # Configuration for (fast) bowden move
         self.bowden_apply_correction = config.getint('bowden_apply_correction', 0, minval=0, maxval=1)
         self.bowden_allowable_load_delta = config.getfloat('bowden_allowable_load_delta', 10., minval=1.)
+        self.bowden_live_adjust = config.getint('bowden_live_adjust', 0, minval=0, maxval=1)
+        self.bowden_live_adjust_samples = config.getint('bowden_live_adjust_samples', 5, minval=2, maxval=50)
+        self.bowden_live_adjust_max = config.getfloat('bowden_live_adjust_max', 10., minval=0., maxval=50.)
         self.bowden_allowable_unload_delta = config.getfloat('bowden_allowable_unload_delta', self.bowden_allowable_load_delta, minval=1.)
         self.bowden_move_error_tolerance = config.getfloat('bowden_move_error_tolerance', 60, minval=0, maxval=100) # Percentage of delta of move that results in error
         self.bowden_pre_unload_test = config.getint('bowden_pre_unload_test', 0, minval=0, maxval=1) # Check for bowden movement before full pull
         self.bowden_pre_unload_error_tolerance = config.getfloat('bowden_pre_unload_error_tolerance', 100, minval=0, maxval=100) # Allowable delta movement % before error
+        self.bowden_stall_detection = config.getint('bowden_stall_detection', 0, minval=0, maxval=1) # Sample gear StallGuard during bowden moves
+        self.bowden_stall_threshold = config.getfloat('bowden_stall_threshold', 30, minval=1, maxval=99) # % of calibrated SG_RESULT baseline indicating jam
+        self.bowden_stall_samples = config.getint('bowden_stall_samples', 3, minval=1) # Consecutive low samples required to declare jam
+        self.bowden_segment_checks = config.getint('bowden_segment_checks', 0, minval=0, maxval=1) # Verify encoder/sensors between bowden move segments
+        self.bowden_segment_length = config.getfloat('bowden_segment_length', 200., minval=20.) # Nominal (minimum) segment length
+        self.bowden_segment_max_overhead = config.getfloat('bowden_segment_max_overhead', 3., minval=0.5, maxval=50.) # % extra time allowed for stops
 
         # Configuration for extruder and toolhead homing
         self.extruder_force_homing = config.getint('extruder_force_homing', 0, minval=0, maxval=1)
@@ -401,6 +592,18 @@ class Mmu:
         self.extruder_homing_max = config.getfloat('extruder_homing_max', 50., above=10.)
         self.extruder_collision_homing_step = config.getint('extruder_collision_homing_step', 3,  minval=2, maxval=5)
         self.toolhead_homing_max = config.getfloat('toolhead_homing_max', 20., minval=0.)
+        self.toolhead_homing_retries = config.getint('toolhead_homing_retries', 2, minval=0, maxval=5)
+        self.toolhead_homing_retract = config.getfloat('toolhead_homing_retract', 5., minval=0.)
+        self.toolhead_homing_jog = config.getfloat('toolhead_homing_jog', 2., minval=0.)
+
+        # Soft filament (e.g. TPU) load assist
+        self.soft_load_materials = [m.strip().upper() for m in config.getlist('soft_load_materials', []) if m.strip()]
+        self.soft_load_gates = list(config.getintlist('soft_load_gates', []))
+        self.soft_load_nibble_length = config.getfloat('soft_load_nibble_length', 5., above=0.)
+        self.soft_load_speed = config.getfloat('soft_load_speed', 5., above=0.)
+        self.soft_load_relief = config.getfloat('soft_load_relief', 1., minval=0.)
+        self.soft_load_retries = config.getint('soft_load_retries', 3, minval=0)
+        self.soft_load_current = config.getint('soft_load_current', 60, minval=10, maxval=100)
         self.toolhead_extruder_to_nozzle = config.getfloat('toolhead_extruder_to_nozzle', 0., minval=5.) # For "sensorless"
         self.toolhead_sensor_to_nozzle = config.getfloat('toolhead_sensor_to_nozzle', 0., minval=5.) # For toolhead sensor
         self.toolhead_sync_unload = config.getint('toolhead_sync_unload', 0, minval=0, maxval=1)
@@ -410,6 +613,8 @@ class Mmu:
         # Extra Gear/Extruder synchronization controls
         self.sync_to_extruder = config.getint('sync_to_extruder', 0, minval=0, maxval=1)
         self.sync_form_tip = config.getint('sync_form_tip', 0, minval=0, maxval=1)
+        self.sync_feedback_buffer_range = config.getfloat('sync_feedback_buffer_range', 10., minval=0.)
+        self.sync_feedback_buffer_maxrange = config.getfloat('sync_feedback_buffer_maxrange', 10., minval=0.)
 
         # Servo control
         self.servo_down_angle = config.getfloat('servo_down_angle')
@@ -421,8 +626,19 @@ class Mmu:
 
         # TMC current control
         self.extruder_homing_current = config.getint('extruder_homing_current', 50, minval=10, maxval=100)
+        self.extruder_homing_current_revalidate = config.getint('extruder_homing_current_revalidate', 0, minval=0, maxval=1)
+        self.gate_homing_current = config.getint('gate_homing_current', self.extruder_homing_current, minval=10, maxval=100)
         self.extruder_form_tip_current = config.getint('extruder_form_tip_current', 100, minval=100, maxval=150)
         self.sync_gear_current = config.getint('sync_gear_current', 50, minval=10, maxval=100)
+        self.purge_assist_length = config.getfloat('purge_assist_length', 0., minval=0.)
+        self.purge_assist_hold = config.getfloat('purge_assist_hold', 2., minval=0.)
+        self.gear_slip_detection = config.getint('gear_slip_detection', 0, minval=0, maxval=2)
+        self.gear_slip_length = config.getfloat('gear_slip_length', 15., minval=1.)
+        self.gear_temp_sensor = config.get('gear_temp_sensor', '') # Temperature object or 'tmc' for driver over-temperature flags
+        self.gear_temp_max = config.getfloat('gear_temp_max', 70., minval=30.)
+        self.gear_temp_hysteresis = config.getfloat('gear_temp_hysteresis', 5., minval=1.)
+        self.sync_gear_current_min = config.getint('sync_gear_current_min', 30, minval=10, maxval=100)
+        self.sync_gear_current_step = config.getint('sync_gear_current_step', 10, minval=1, maxval=50)
 
         # Filament move speeds and accelaration
         self.gear_from_buffer_speed = config.getfloat('gear_from_buffer_speed', 150., minval=10.)
@@ -433,6 +649,29 @@ class Mmu:
         self.gear_short_move_accel = config.getfloat('gear_short_move_accel', 400, minval=10.)
         self.gear_short_move_threshold = config.getfloat('gear_short_move_threshold', self.gate_homing_max, minval=1.)
         self.gear_homing_speed = config.getfloat('gear_homing_speed', 150, minval=1.)
+        self.jog_max_distance = config.getfloat('jog_max_distance', 100., minval=1.)
+        self.homing_creep_speed = config.getfloat('homing_creep_speed', 10., minval=1.)
+        self.homing_creep_accel = config.getfloat('homing_creep_accel', 100., minval=10.)
+        self.homing_creep_backoff = config.getfloat('homing_creep_backoff', 5., minval=0.5)
+        self.homing_creep_count = config.getint('homing_creep_count', 0, minval=0, maxval=5)
+        self.gear_material_max_speed = {} # material -> speed cap for gear moves of gate with that material
+        for spec in config.getlist('gear_material_max_speed', []):
+            try:
+                material, speed = spec.split(':')
+                self.gear_material_max_speed[material.strip().upper()] = float(speed)
+            except ValueError:
+                raise self.config.error("gear_material_max_speed entry '%s' must be in form 'MATERIAL:SPEED'" % spec)
+            if self.gear_material_max_speed[material.strip().upper()] < 1.:
+                raise self.config.error("gear_material_max_speed entry '%s' is invalid. Speed must be at least 1mm/s" % spec)
+        self.gear_material_max_accel = {} # material -> accel cap for gear moves of gate with that material
+        for spec in config.getlist('gear_material_max_accel', []):
+            try:
+                material, accel = spec.split(':')
+                self.gear_material_max_accel[material.strip().upper()] = float(accel)
+            except ValueError:
+                raise self.config.error("gear_material_max_accel entry '%s' must be in form 'MATERIAL:ACCEL'" % spec)
+            if self.gear_material_max_accel[material.strip().upper()] < 10.:
+                raise self.config.error("gear_material_max_accel entry '%s' is invalid. Accel must be at least 10mm/s^2" % spec)
 
         self.extruder_load_speed = config.getfloat('extruder_load_speed', 15, minval=1.)
         self.extruder_unload_speed = config.getfloat('extruder_unload_speed', 15, minval=1.)
@@ -452,11 +691,161 @@ class Mmu:
         self.encoder_move_validation = config.getint('encoder_move_validation', 1, minval=0, maxval=1) # Use encoder to check load/unload movement
         self.selector_touch_enable = config.getint('selector_touch_enable', 1, minval=0, maxval=1)
         self.enable_clog_detection = config.getint('enable_clog_detection', 2, minval=0, maxval=2)
+        self.enable_virtual_encoder = config.getint('enable_virtual_encoder', 0, minval=0, maxval=1)
+        self.virtual_encoder_samples = config.getint('virtual_encoder_samples', 3, minval=1)
+        self.virtual_clog_length = config.getfloat('virtual_clog_length', 15., minval=1.)
         self.enable_spoolman = config.getint('enable_spoolman', 0, minval=0, maxval=1)
+        self.spoolman_push = config.getint('spoolman_push', 0, minval=0, maxval=1)
+        self.spoolman_location = config.getint('spoolman_location', 0, minval=0, maxval=1)
+        self.spoolman_printer_name = config.get('spoolman_printer_name', 'printer')
+        self.spool_change_prompt = config.getint('spool_change_prompt', 1, minval=0, maxval=2)
+        self.enable_plr = config.getint('enable_plr', 0, minval=0, maxval=1)
+        self.enable_mqtt = config.getint('enable_mqtt', 0, minval=0, maxval=1)
+        self.enable_metrics = config.getint('enable_metrics', 0, minval=0, maxval=1)
+        self.enable_telemetry = config.getint('enable_telemetry', 0, minval=0, maxval=1)
+        self.telemetry_max_jobs = config.getint('telemetry_max_jobs', 10, minval=1)
+        self.enable_journal = config.getint('enable_journal', 0, minval=0, maxval=1)
+        self.journal_max_files = config.getint('journal_max_files', 10, minval=1)
+        self.filament_change_m600 = config.getint('filament_change_m600', 1, minval=0, maxval=1)
+        self.print_protection = config.getint('print_protection', 1, minval=0, maxval=2)
+        self.print_locked_commands = [c.strip().upper() for c in config.getlist('print_locked_commands', []) if c.strip()]
+        self.dryer_heaters = list(config.getlist('dryer_heater', [])) # One heater per zone
+        self.dryer_gate_zones = list(config.getintlist('dryer_gate_zones', [])) # Zone feeding each gate
+        self.dryer_zone_max_temps = list(config.getfloatlist('dryer_zone_max_temps', []))
+        self.dryer_units = list(config.getlist('dryer_units', [])) # Gate map unit heated by each zone
+        self.dryer_sensors = list(config.getlist('dryer_sensors', [])) # Optional ambient sensor object for each zone
+        self.dryer_zone_pid = []
+        for spec in config.getlist('dryer_zone_pid', []):
+            try:
+                self.dryer_zone_pid.append(None if spec.strip() == '-' else tuple(float(v) for v in spec.split(':')))
+            except ValueError:
+                raise self.config.error("dryer_zone_pid entry '%s' must be in form 'KP:KI:KD' or '-'" % spec)
+            if self.dryer_zone_pid[-1] is not None and len(self.dryer_zone_pid[-1]) != 3:
+                raise self.config.error("dryer_zone_pid entry '%s' must be in form 'KP:KI:KD' or '-'" % spec)
+        self.dryer_preheat_time = config.getint('dryer_preheat_time', 0, minval=0)
+        self.dryer_material_temps = {}
+        for spec in config.getlist('dryer_material_temps', []):
+            try:
+                material, temp = spec.split(':')
+                self.dryer_material_temps[material.strip().upper()] = float(temp)
+            except ValueError:
+                raise self.config.error("dryer_material_temps entry '%s' must be in form 'MATERIAL:TEMP'" % spec)
+        self.dryer_material_humidity = {}
+        for spec in config.getlist('dryer_material_humidity', []):
+            try:
+                material, humidity = spec.split(':')
+                self.dryer_material_humidity[material.strip().upper()] = float(humidity)
+            except ValueError:
+                raise self.config.error("dryer_material_humidity entry '%s' must be in form 'MATERIAL:HUMIDITY'" % spec)
+        self.dryer_policy = config.get('dryer_policy', self.DRYNESS_POLICY_WARN).lower()
+        if self.dryer_policy not in self.DRYNESS_POLICIES:
+            raise self.config.error("dryer_policy is invalid. Options are: %s" % self.DRYNESS_POLICIES)
+        self.dryer_material_policy = {}
+        for spec in config.getlist('dryer_material_policy', []):
+            try:
+                material, policy = spec.split(':')
+            except ValueError:
+                raise self.config.error("dryer_material_policy entry '%s' must be in form 'MATERIAL:POLICY'" % spec)
+            if policy.strip().lower() not in self.DRYNESS_POLICIES:
+                raise self.config.error("dryer_material_policy entry '%s' is invalid. Policy options are: %s" % (spec, self.DRYNESS_POLICIES))
+            self.dryer_material_policy[material.strip().upper()] = policy.strip().lower()
+        self.dryer_max_wait = config.getint('dryer_max_wait', 3600, minval=0)
         self.default_enable_endless_spool = config.getint('enable_endless_spool', 0, minval=0, maxval=1)
         self.endless_spool_final_eject = config.getfloat('endless_spool_final_eject', 50, minval=0.)
+        self.gate_final_eject = list(config.getintlist('gate_final_eject', []))
+        self.gate_load_sequence = {} # gate -> custom load sequence macro (takes precedence over gcode_load_sequence)
+        self.gate_unload_sequence = {} # gate -> custom unload sequence macro (takes precedence over gcode_unload_sequence)
+        for option, sequences in [('gate_load_sequence', self.gate_load_sequence), ('gate_unload_sequence', self.gate_unload_sequence)]:
+            for spec in config.getlist(option, []):
+                try:
+                    gate, macro = [p.strip() for p in spec.split(':')]
+                    gate = int(gate)
+                except ValueError:
+                    raise self.config.error("%s entry '%s' must be in form 'GATE:MACRO'" % (option, spec))
+                if not 0 <= gate < self.mmu_num_gates or not macro:
+                    raise self.config.error("%s entry '%s' is invalid. Gate must be in range 0..%d" % (option, spec, self.mmu_num_gates - 1))
+                sequences[gate] = macro
+        self.gate_final_eject_margin = config.getfloat('gate_final_eject_margin', 20., minval=0.)
+        self.gate_final_eject_max = config.getfloat('gate_final_eject_max', 300., above=0.)
+        self.espooler_rewind_pwm = config.getfloat('espooler_rewind_pwm', 1., minval=0., maxval=1.)
+        self.espooler_ramp_time = config.getfloat('espooler_ramp_time', 0., minval=0., maxval=10.)
+        self.espooler_max_dpwm = config.getfloat('espooler_max_dpwm', 0., minval=0.)
+        self.espooler_spool_inertia = list(config.getfloatlist('espooler_spool_inertia', []))
         self.endless_spool_on_load = config.getint('endless_spool_on_load', 0, minval=0, maxval=1)
+        self.bypass_runout_gate = config.getint('bypass_runout_gate', -1, minval=-1, maxval=self.mmu_num_gates - 1)
+        self.bypass_guard = config.getint('bypass_guard', 1, minval=0, maxval=1)
+        self.gate_load_balancing = config.getint('gate_load_balancing', 0, minval=0, maxval=2)
+        self.purge_volume = config.getfloat('purge_volume', 0., minval=0.)
+        self.purge_similar_color_threshold = config.getfloat('purge_similar_color_threshold', 0., minval=0., maxval=100.)
+        self.purge_similar_volume_percent = config.getfloat('purge_similar_volume_percent', 0., minval=0., maxval=100.)
+        self.purge_to_infill_percent = config.getfloat('purge_to_infill_percent', 0., minval=0., maxval=100.)
+        self.purge_to_infill_flow = config.getfloat('purge_to_infill_flow', 10., minval=1., maxval=50.)
+        self.purge_to_infill_caps = {} # material -> max % of purge routed into infill (caps purge_to_infill_percent)
+        for spec in config.getlist('purge_to_infill_caps', []):
+            try:
+                material, percent = [p.strip() for p in spec.split(':')]
+                percent = float(percent)
+            except ValueError:
+                raise self.config.error("purge_to_infill_caps entry '%s' must be in form 'MATERIAL:PERCENT'" % spec)
+            if not material or not 0. <= percent <= 100.:
+                raise self.config.error("purge_to_infill_caps entry '%s' is invalid. Percent must be in range 0..100" % spec)
+            self.purge_to_infill_caps[material.upper()] = percent
+        self.purge_idle_curve = [] # (minutes, percent) points of purge scaling by idle time since print last extruded
+        for spec in config.getlist('purge_idle_curve', []):
+            try:
+                minutes, percent = [float(p.strip()) for p in spec.split(':')]
+            except ValueError:
+                raise self.config.error("purge_idle_curve entry '%s' must be in form 'MINUTES:PERCENT'" % spec)
+            if minutes <= 0. or percent < 100. or (self.purge_idle_curve and minutes <= self.purge_idle_curve[-1][0]):
+                raise self.config.error("purge_idle_curve entry '%s' is invalid. Minutes must be increasing and percent at least 100" % spec)
+            self.purge_idle_curve.append((minutes, percent))
+        self.purge_idle_materials = {} # material -> sensitivity factor applied to idle purge increase
+        for spec in config.getlist('purge_idle_materials', []):
+            try:
+                material, factor = [p.strip() for p in spec.split(':')]
+                factor = float(factor)
+            except ValueError:
+                raise self.config.error("purge_idle_materials entry '%s' must be in form 'MATERIAL:FACTOR'" % spec)
+            if not material or factor < 0.:
+                raise self.config.error("purge_idle_materials entry '%s' is invalid. Factor must not be negative" % spec)
+            self.purge_idle_materials[material.upper()] = factor
+        self.prime_mode = config.get('prime_mode', self.PRIME_NONE).lower()
+        if self.prime_mode not in self.PRIME_MODES:
+            raise self.config.error("prime_mode is invalid. Options are: %s" % self.PRIME_MODES)
+        self.prime_length = config.getfloat('prime_length', 1., minval=0.)
+        self.prime_max_length = config.getfloat('prime_max_length', 5., minval=0.)
+        self.prime_ooze_rate = config.getfloat('prime_ooze_rate', 0.5, minval=0.) # mm per minute since last extrusion
+        self.prime_speed = config.getfloat('prime_speed', 5., above=0.)
+        self.toolhead_residual_filament = config.getfloat('toolhead_residual_filament', 0., minval=0.)
+        self.phase_devices = {} # phase -> list of (device, value, pulse duration)
+        for spec in config.getlist('phase_device_control', []):
+            parts = [p.strip() for p in spec.split(':')]
+            try:
+                phase, device, value = parts[0].lower(), parts[1], float(parts[2])
+                duration = float(parts[3]) if len(parts) > 3 else 0.
+                if len(parts) > 4 or not 0. <= value <= 1. or duration < 0.:
+                    raise ValueError()
+            except (ValueError, IndexError):
+                raise self.config.error("phase_device_control entry '%s' must be in form 'PHASE:DEVICE:VALUE[:PULSE_SECONDS]' with VALUE 0..1" % spec)
+            if phase not in self.DEVICE_PHASES:
+                raise self.config.error("phase_device_control entry '%s' is invalid. Phase options are: %s" % (spec, self.DEVICE_PHASES))
+            if device != "fan" and device.split()[0] not in ["fan_generic", "output_pin"]:
+                raise self.config.error("phase_device_control entry '%s' is invalid. Device must be 'fan', 'fan_generic <name>' or 'output_pin <name>'" % spec)
+            self.phase_devices.setdefault(phase, []).append((device, value, duration))
+        self.phase_device_restore = [] # Stack of (phase, device, value, pulse expiry) to restore on phase exit
         self.default_endless_spool_groups = list(config.getintlist('endless_spool_groups', []))
+        self.default_endless_spool_priorities = list(config.getintlist('endless_spool_priorities', []))
+        self.endless_spool_fallback = {} # group -> group to try when all gates in group are exhausted
+        for spec in config.getlist('endless_spool_fallback', []):
+            try:
+                group, fallback = [int(p.strip()) for p in spec.split(':')]
+            except ValueError:
+                raise self.config.error("endless_spool_fallback entry '%s' must be in form 'GROUP:FALLBACK_GROUP'" % spec)
+            self.endless_spool_fallback[group] = fallback
+        self.endless_spool_match = [m.strip().lower() for m in config.getlist('endless_spool_match', [self.ES_MATCH_ANY])]
+        for rule in self.endless_spool_match:
+            if rule not in self.ES_MATCH_RULES:
+                raise self.config.error("endless_spool_match rule '%s' is invalid. Options are: %s" % (rule, self.ES_MATCH_RULES))
         self.tool_extrusion_multipliers = []
         self.tool_speed_multipliers = []
 
@@ -466,6 +855,23 @@ class Mmu:
         self.log_statistics = config.getint('log_statistics', 0, minval=0, maxval=1)
         self.log_visual = config.getint('log_visual', 1, minval=0, maxval=2)
         self.log_startup_status = config.getint('log_startup_status', 1, minval=0, maxval=2)
+        self.log_profile = config.get('log_profile', self.LOG_PROFILE_STANDARD).lower()
+        if self.log_profile not in self.LOG_PROFILES:
+            raise self.config.error("log_profile is invalid. Options are: %s" % self.LOG_PROFILES)
+
+        # Notifications
+        self.notify_routes = {c: [] for c in self.NOTIFY_CLASSES}
+        for spec in config.getlist('notify_routes', ['toolchange:m117']):
+            parts = [p.strip().lower() for p in spec.split(':')]
+            if parts[0] not in self.NOTIFY_CLASSES:
+                raise self.config.error("notify_routes entry '%s' is invalid. Event class options are: %s" % (spec, self.NOTIFY_CLASSES))
+            targets = [p for p in parts[1:] if p]
+            if any(t not in self.NOTIFY_TARGETS for t in targets):
+                raise self.config.error("notify_routes entry '%s' is invalid. Target options are: %s" % (spec, self.NOTIFY_TARGETS))
+            self.notify_routes[parts[0]] = targets
+        self.notify_templates = {c: config.get('notify_template_%s' % c, self.NOTIFY_TEMPLATES[c]) for c in self.NOTIFY_CLASSES}
+        self.notify_macro = config.get('notify_macro', '_MMU_NOTIFY')
+        self.notify_moonraker_name = config.get('notify_moonraker_name', 'mmu')
 
         # Currently hidden and testing options
         self.homing_extruder = config.getint('homing_extruder', 1, minval=0, maxval=1) # Special MMU homing extruder or klipper default
@@ -482,6 +888,21 @@ class Mmu:
             for i in range(self.mmu_num_gates):
                 self.default_endless_spool_groups.append(i)
         self.endless_spool_groups = list(self.default_endless_spool_groups)
+        if len(self.default_endless_spool_priorities) > 0:
+            if len(self.default_endless_spool_priorities) != self.mmu_num_gates:
+                raise self.config.error("endless_spool_priorities has a different number of values than the number of gates")
+        else:
+            self.default_endless_spool_priorities = [0] * self.mmu_num_gates
+        self.endless_spool_priorities = list(self.default_endless_spool_priorities)
+        self.gate_maintenance = [] # Gates taken out of service with MMU_MAINTENANCE
+        self.gate_lock = {} # Gates reserved with MMU_GATE_MAP LOCK=1. Gate -> owner label
+        self.resume_verify_failed = False # Verification after long pause isn't repeated on next RESUME
+        self.safety_tripped = {} # Component -> reason for components disabled by safety watchdog
+        self.safety_failures = {c: 0 for c in self.SAFETY_COMPONENTS} # Consecutive unconfirmed commands
+        self.safety_active = set() # Components with watchdog wrapper in progress (so nested operations count once)
+        self.safety_timed_out = set() # Components whose driver was disabled by watchdog timer during current operation
+        self.gate_stub_length = [0.] * self.mmu_num_gates # Cutter stub left in filament path ahead of each gate
+        self.loaded_stub = (self.TOOL_GATE_UNKNOWN, 0.) # Gate and length of stub pushed through by last load
 
         # Status (availability of filament) at each gate
         if len(self.default_gate_status) > 0:
@@ -505,6 +926,10 @@ class Mmu:
         if len(self.default_gate_color) > 0:
             if not len(self.default_gate_color) == self.mmu_num_gates:
                 raise self.config.error("gate_color has different number of entries than the number of gates")
+            for i, color in enumerate(self.default_gate_color):
+                self.default_gate_color[i] = normalize_color(color)
+                if self.default_gate_color[i] is None:
+                    raise self.config.error("gate_color '%s' not recognized. Must be %s" % (color, COLOR_FORMATS))
         else:
             for i in range(self.mmu_num_gates):
                 self.default_gate_color.append("")
@@ -519,6 +944,77 @@ class Mmu:
                 self.default_gate_spool_id.append(-1)
         self.gate_spool_id = list(self.default_gate_spool_id)
 
+        # Pressure advance and smooth time for each gate (-1 means no override)
+        if len(self.default_gate_pressure_advance) > 0:
+            if not len(self.default_gate_pressure_advance) == self.mmu_num_gates:
+                raise self.config.error("gate_pressure_advance has different number of entries than the number of gates")
+        else:
+            for i in range(self.mmu_num_gates):
+                self.default_gate_pressure_advance.append(-1)
+        self.gate_pressure_advance = list(self.default_gate_pressure_advance)
+        if len(self.default_gate_smooth_time) > 0:
+            if not len(self.default_gate_smooth_time) == self.mmu_num_gates:
+                raise self.config.error("gate_smooth_time has different number of entries than the number of gates")
+        else:
+            for i in range(self.mmu_num_gates):
+                self.default_gate_smooth_time.append(-1)
+        self.gate_smooth_time = list(self.default_gate_smooth_time)
+
+        # Print temperature of filament in each gate (-1 means unknown)
+        if len(self.default_gate_temperature) > 0:
+            if not len(self.default_gate_temperature) == self.mmu_num_gates:
+                raise self.config.error("gate_temperature has different number of entries than the number of gates")
+        else:
+            for i in range(self.mmu_num_gates):
+                self.default_gate_temperature.append(-1)
+        self.gate_temperature = list(self.default_gate_temperature)
+
+        # Physical unit or container (e.g. drybox) feeding each gate ('' means none)
+        if len(self.default_gate_unit) > 0:
+            if not len(self.default_gate_unit) == self.mmu_num_gates:
+                raise self.config.error("gate_unit has different number of entries than the number of gates")
+        else:
+            for i in range(self.mmu_num_gates):
+                self.default_gate_unit.append("")
+        self.gate_unit = list(self.default_gate_unit)
+
+        # Automatic final eject policy for each gate (0 = off, 1 = on MMU_UNLOAD/MMU_EJECT, 2 = also on gate swap)
+        if len(self.gate_final_eject) > 0:
+            if not len(self.gate_final_eject) == self.mmu_num_gates:
+                raise self.config.error("gate_final_eject has different number of entries than the number of gates")
+            if any(p not in [0, 1, 2] for p in self.gate_final_eject):
+                raise self.config.error("gate_final_eject entries must be 0 (off), 1 (on unload) or 2 (on unload and gate swap)")
+        else:
+            self.gate_final_eject = [0] * self.mmu_num_gates
+
+        # Rewind assist (espooler) ramp scaling for each gate. Heavier spools need gentler ramps
+        if len(self.espooler_spool_inertia) > 0:
+            if not len(self.espooler_spool_inertia) == self.mmu_num_gates:
+                raise self.config.error("espooler_spool_inertia has different number of entries than the number of gates")
+            if any(i <= 0. for i in self.espooler_spool_inertia):
+                raise self.config.error("espooler_spool_inertia entries must be greater than 0")
+        else:
+            self.espooler_spool_inertia = [1.] * self.mmu_num_gates
+
+        # Optional shallower "ready" parking distance for each gate used during a print (0 = normal deep park)
+        if len(self.gate_ready_park) > 0:
+            if not len(self.gate_ready_park) == self.mmu_num_gates:
+                raise self.config.error("gate_ready_park has different number of entries than the number of gates")
+            if any(p < 0 or p >= self.gate_parking_distance for p in self.gate_ready_park if p != 0):
+                raise self.config.error("gate_ready_park entries must be 0 (disabled) or positive and less than gate_parking_distance")
+        else:
+            self.gate_ready_park = [0.] * self.mmu_num_gates
+
+        # Optional tip forming profile for each gate ('' = use material or default profile)
+        if len(self.form_tip_profile_gates) > 0:
+            if not len(self.form_tip_profile_gates) == self.mmu_num_gates:
+                raise self.config.error("form_tip_profile_gates has different number of entries than the number of gates")
+            for profile in self.form_tip_profile_gates:
+                if profile and get_tip_profile(profile) is None:
+                    raise self.config.error("form_tip_profile_gates entry '%s' is invalid. Options are: %s" % (profile, get_tip_profile_names()))
+        else:
+            self.form_tip_profile_gates = [''] * self.mmu_num_gates
+
         # Tool to gate mapping
         if len(self.default_tool_to_gate_map) > 0:
             if not len(self.default_tool_to_gate_map) == self.mmu_num_gates:
@@ -540,6 +1036,10 @@ class Mmu:
         # Logging
         self.queue_listener = None
         self.mmu_logger = None
+        self.telemetry = None
+        self.journal = None
+        self.purge_assist = None
+        self.purge_router = None
 
         # Register GCODE commands
         self.gcode = self.printer.lookup_object('gcode')
@@ -549,13 +1049,23 @@ class Mmu:
         self.gcode.register_command('MMU_RESET', self.cmd_MMU_RESET, desc = self.cmd_MMU_RESET_help)
         self.gcode.register_command('MMU_STATS', self.cmd_MMU_STATS, desc = self.cmd_MMU_STATS_help)
         self.gcode.register_command('MMU_STATUS', self.cmd_MMU_STATUS, desc = self.cmd_MMU_STATUS_help)
+        self.gcode.register_command('MMU_CHECK_CONFIG', self.cmd_MMU_CHECK_CONFIG, desc = self.cmd_MMU_CHECK_CONFIG_help)
+        self.gcode.register_command('MMU_LOG', self.cmd_MMU_LOG, desc = self.cmd_MMU_LOG_help)
+        self.gcode.register_command('MMU_TRACE', self.cmd_MMU_TRACE, desc = self.cmd_MMU_TRACE_help)
+        self.gcode.register_command('MMU_TELEMETRY', self.cmd_MMU_TELEMETRY, desc = self.cmd_MMU_TELEMETRY_help)
+        self.gcode.register_command('MMU_JOURNAL', self.cmd_MMU_JOURNAL, desc = self.cmd_MMU_JOURNAL_help)
 
         # Calibration
         self.gcode.register_command('MMU_CALIBRATE_GEAR', self.cmd_MMU_CALIBRATE_GEAR, desc=self.cmd_MMU_CALIBRATE_GEAR_help)
         self.gcode.register_command('MMU_CALIBRATE_ENCODER', self.cmd_MMU_CALIBRATE_ENCODER, desc=self.cmd_MMU_CALIBRATE_ENCODER_help)
         self.gcode.register_command('MMU_CALIBRATE_SELECTOR', self.cmd_MMU_CALIBRATE_SELECTOR, desc = self.cmd_MMU_CALIBRATE_SELECTOR_help)
         self.gcode.register_command('MMU_CALIBRATE_BOWDEN', self.cmd_MMU_CALIBRATE_BOWDEN, desc = self.cmd_MMU_CALIBRATE_BOWDEN_help)
+        self.gcode.register_command('MMU_CALIBRATE_CUTTER', self.cmd_MMU_CALIBRATE_CUTTER, desc = self.cmd_MMU_CALIBRATE_CUTTER_help)
+        self.gcode.register_command('MMU_CALIBRATE_COLLISION', self.cmd_MMU_CALIBRATE_COLLISION, desc = self.cmd_MMU_CALIBRATE_COLLISION_help)
         self.gcode.register_command('MMU_CALIBRATE_GATES', self.cmd_MMU_CALIBRATE_GATES, desc = self.cmd_MMU_CALIBRATE_GATES_help)
+        self.gcode.register_command('MMU_CALIBRATE_SYNC', self.cmd_MMU_CALIBRATE_SYNC, desc = self.cmd_MMU_CALIBRATE_SYNC_help)
+        self.gcode.register_command('MMU_CALIBRATE_SYNC_FEEDBACK', self.cmd_MMU_CALIBRATE_SYNC_FEEDBACK, desc = self.cmd_MMU_CALIBRATE_SYNC_FEEDBACK_help)
+        self.gcode.register_command('MMU_CALIBRATION', self.cmd_MMU_CALIBRATION, desc = self.cmd_MMU_CALIBRATION_help)
 
         # Servo and motor control
         self.gcode.register_command('MMU_SERVO', self.cmd_MMU_SERVO, desc = self.cmd_MMU_SERVO_help)
@@ -568,6 +1078,16 @@ class Mmu:
         # Endstops for print start / stop. Automatically called if printing from virtual SD-card
         self.gcode.register_command('_MMU_PRINT_START', self.cmd_MMU_PRINT_START, desc = self.cmd_MMU_PRINT_START_help)
         self.gcode.register_command('_MMU_PRINT_END', self.cmd_MMU_PRINT_END, desc = self.cmd_MMU_PRINT_END_help)
+        self.gcode.register_command('MMU_SLICER_TIP_PARAMETERS', self.cmd_MMU_SLICER_TIP_PARAMETERS, desc = self.cmd_MMU_SLICER_TIP_PARAMETERS_help)
+        self.gcode.register_command('MMU_PREPARE_JOB', self.cmd_MMU_PREPARE_JOB, desc = self.cmd_MMU_PREPARE_JOB_help)
+        self.gcode.register_command('MMU_CHECK_FILAMENT_SUFFICIENCY', self.cmd_MMU_CHECK_FILAMENT_SUFFICIENCY, desc = self.cmd_MMU_CHECK_FILAMENT_SUFFICIENCY_help)
+        self.gcode.register_command('MMU_PREHEAT', self.cmd_MMU_PREHEAT, desc = self.cmd_MMU_PREHEAT_help)
+        self.gcode.register_command('MMU_PURGE_INFILL', self.cmd_MMU_PURGE_INFILL, desc = self.cmd_MMU_PURGE_INFILL_help)
+        self.gcode.register_command('_MMU_IDLE_RETRACT', self.cmd_MMU_IDLE_RETRACT, desc = self.cmd_MMU_IDLE_RETRACT_help)
+        self.gcode.register_command('_MMU_IDLE_CARE', self.cmd_MMU_IDLE_CARE, desc = self.cmd_MMU_IDLE_CARE_help)
+        self.gcode.register_command('_MMU_DEEP_PARK', self.cmd_MMU_DEEP_PARK, desc = self.cmd_MMU_DEEP_PARK_help)
+        self.gcode.register_command('MMU_SELF_CHECK', self.cmd_MMU_SELF_CHECK, desc = self.cmd_MMU_SELF_CHECK_help)
+        self.gcode.register_command('MMU_SAFETY', self.cmd_MMU_SAFETY, desc = self.cmd_MMU_SAFETY_help)
 
         self.gcode.register_command('MMU_HELP', self.cmd_MMU_HELP, desc = self.cmd_MMU_HELP_help)
         self.gcode.register_command('MMU_ENCODER', self.cmd_MMU_ENCODER, desc = self.cmd_MMU_ENCODER_help)
@@ -583,20 +1103,29 @@ class Mmu:
         self.gcode.register_command('MMU_LOAD', self.cmd_MMU_LOAD, desc=self.cmd_MMU_LOAD_help)
         self.gcode.register_command('MMU_EJECT', self.cmd_MMU_EJECT, desc = self.cmd_MMU_EJECT_help)
         self.gcode.register_command('MMU_UNLOAD', self.cmd_MMU_EJECT, desc = self.cmd_MMU_EJECT_help) # Alias for MMU_EJECT
+        self.gcode.register_command('MMU_FILAMENT_CHANGE', self.cmd_MMU_FILAMENT_CHANGE, desc = self.cmd_MMU_FILAMENT_CHANGE_help)
         self.gcode.register_command('MMU_PAUSE', self.cmd_MMU_PAUSE, desc = self.cmd_MMU_PAUSE_help)
         self.gcode.register_command('MMU_UNLOCK', self.cmd_MMU_UNLOCK, desc = self.cmd_MMU_UNLOCK_help)
         self.gcode.register_command('MMU_RECOVER', self.cmd_MMU_RECOVER, desc = self.cmd_MMU_RECOVER_help)
+        self.gcode.register_command('MMU_PLR_SAVE', self.cmd_MMU_PLR_SAVE, desc = self.cmd_MMU_PLR_SAVE_help)
+        self.gcode.register_command('MMU_PLR_RESUME', self.cmd_MMU_PLR_RESUME, desc = self.cmd_MMU_PLR_RESUME_help)
+        self.gcode.register_command('MMU_DRYER', self.cmd_MMU_DRYER, desc = self.cmd_MMU_DRYER_help)
+        self.gcode.register_command('MMU_HEATER', self.cmd_MMU_DRYER, desc = self.cmd_MMU_DRYER_help) # Alias
+        self.gcode.register_command('MMU_SPOOL_CHANGE', self.cmd_MMU_SPOOL_CHANGE, desc = self.cmd_MMU_SPOOL_CHANGE_help)
 
         # User Setup and Testing
         self.gcode.register_command('MMU_TEST_BUZZ_MOTOR', self.cmd_MMU_TEST_BUZZ_MOTOR, desc=self.cmd_MMU_TEST_BUZZ_MOTOR_help)
         self.gcode.register_command('MMU_TEST_GRIP', self.cmd_MMU_TEST_GRIP, desc = self.cmd_MMU_TEST_GRIP_help)
         self.gcode.register_command('MMU_TEST_LOAD', self.cmd_MMU_TEST_LOAD, desc=self.cmd_MMU_TEST_LOAD_help)
         self.gcode.register_command('MMU_TEST_MOVE', self.cmd_MMU_TEST_MOVE, desc = self.cmd_MMU_TEST_MOVE_help)
+        self.gcode.register_command('MMU_JOG', self.cmd_MMU_JOG, desc = self.cmd_MMU_JOG_help)
         self.gcode.register_command('MMU_TEST_HOMING_MOVE', self.cmd_MMU_TEST_HOMING_MOVE, desc = self.cmd_MMU_TEST_HOMING_MOVE_help)
         self.gcode.register_command('MMU_TEST_TRACKING', self.cmd_MMU_TEST_TRACKING, desc=self.cmd_MMU_TEST_TRACKING_help)
         self.gcode.register_command('MMU_TEST_CONFIG', self.cmd_MMU_TEST_CONFIG, desc = self.cmd_MMU_TEST_CONFIG_help)
+        self.gcode.register_command('MMU_RELOAD_CONFIG', self.cmd_MMU_RELOAD_CONFIG, desc = self.cmd_MMU_RELOAD_CONFIG_help)
         self.gcode.register_command('MMU_TEST_RUNOUT', self.cmd_MMU_TEST_RUNOUT, desc = self.cmd_MMU_TEST_RUNOUT_help)
         self.gcode.register_command('MMU_FORM_TIP', self.cmd_MMU_FORM_TIP, desc = self.cmd_MMU_FORM_TIP_help)
+        self.gcode.register_command('MMU_TIP_PROFILES', self.cmd_MMU_TIP_PROFILES, desc = self.cmd_MMU_TIP_PROFILES_help)
 
         # Soak Testing
         self.gcode.register_command('MMU_SOAKTEST_SELECTOR', self.cmd_MMU_SOAKTEST_SELECTOR, desc = self.cmd_MMU_SOAKTEST_SELECTOR_help)
@@ -604,9 +1133,14 @@ class Mmu:
 
         # TTG and Endless spool
         self.gcode.register_command('MMU_REMAP_TTG', self.cmd_MMU_REMAP_TTG, desc = self.cmd_MMU_REMAP_TTG_help)
+        self.gcode.register_command('MMU_OPTIMIZE_TTG', self.cmd_MMU_OPTIMIZE_TTG, desc = self.cmd_MMU_OPTIMIZE_TTG_help)
         self.gcode.register_command('MMU_GATE_MAP', self.cmd_MMU_GATE_MAP, desc = self.cmd_MMU_GATE_MAP_help)
+        self.gcode.register_command('MMU_AUDIT', self.cmd_MMU_AUDIT, desc = self.cmd_MMU_AUDIT_help)
+        self.gcode.register_command('MMU_RFID_TAG', self.cmd_MMU_RFID_TAG, desc = self.cmd_MMU_RFID_TAG_help)
         self.gcode.register_command('MMU_ENDLESS_SPOOL', self.cmd_MMU_ENDLESS_SPOOL, desc = self.cmd_MMU_ENDLESS_SPOOL_help)
         self.gcode.register_command('MMU_CHECK_GATE', self.cmd_MMU_CHECK_GATE, desc = self.cmd_MMU_CHECK_GATE_help)
+        self.gcode.register_command('MMU_PROFILE_GATES', self.cmd_MMU_PROFILE_GATES, desc = self.cmd_MMU_PROFILE_GATES_help)
+        self.gcode.register_command('MMU_MAINTENANCE', self.cmd_MMU_MAINTENANCE, desc = self.cmd_MMU_MAINTENANCE_help)
         self.gcode.register_command('MMU_TOOL_OVERRIDES', self.cmd_MMU_TOOL_OVERRIDES, desc = self.cmd_MMU_TOOL_OVERRIDES_help)
 
         # For use in user controlled load and unload macros
@@ -628,11 +1162,28 @@ class Mmu:
         self.gcode.register_command('__MMU_GATE_INSERT', self.cmd_MMU_GATE_INSERT, desc = self.cmd_MMU_GATE_INSERT_help)
         self.gcode.register_command('__MMU_PRE_GATE_RUNOUT', self.cmd_MMU_PRE_GATE_RUNOUT, desc = self.cmd_MMU_PRE_GATE_RUNOUT_help)
         self.gcode.register_command('__MMU_PRE_GATE_INSERT', self.cmd_MMU_PRE_GATE_INSERT, desc = self.cmd_MMU_PRE_GATE_INSERT_help)
+        self.gcode.register_command('__MMU_BYPASS_RUNOUT', self.cmd_MMU_BYPASS_RUNOUT, desc = self.cmd_MMU_BYPASS_RUNOUT_help)
+        self.gcode.register_command('__MMU_BYPASS_INSERT', self.cmd_MMU_BYPASS_INSERT, desc = self.cmd_MMU_BYPASS_INSERT_help)
+        self.gcode.register_command('__MMU_GEAR_SLIP', self.cmd_MMU_GEAR_SLIP, desc = self.cmd_MMU_GEAR_SLIP_help)
+        self.gcode.register_command('__MMU_GEAR_THERMAL', self.cmd_MMU_GEAR_THERMAL, desc = self.cmd_MMU_GEAR_THERMAL_help)
         self.gcode.register_command('__MMU_M400', self.cmd_MMU_M400, desc = self.cmd_MMU_M400_help) # Wait on both movequeues
 
         # Initializer tasks
         self.gcode.register_command('__MMU_BOOTUP_TASKS', self.cmd_MMU_BOOTUP_TASKS, desc = self.cmd_MMU_BOOTUP_TASKS_help) # Bootup tasks
 
+        # Directory of named gate map templates saved and applied with MMU_GATE_MAP
+        self.gate_map_template_dir = os.path.expanduser(config.get('gate_map_template_dir', '~/printer_data/config/mmu/gate_maps'))
+
+        # Third-party plugins (loaded last so they can register commands and read their own [mmu] options)
+
+        # Stable facade for other Klipper extras: printer.lookup_object('mmu').api. Created before plugins because
+        # plugins receive events as API listeners
+        self.api = MmuApi(self)
+
+        plugin_dir = config.get('plugin_dir', os.path.join(os.path.dirname(os.path.realpath(__file__)), '..', 'mmu_plugins'))
+        plugins = [p.strip() for p in config.get('plugins', '').split(',') if p.strip()]
+        self.plugin_manager = MmuPluginManager(self, os.path.expanduser(plugin_dir), plugins, MmuError)
+
         # We setup MMU hardware during configuration since some hardware like endstop requires
         # configuration during the MCU config phase, which happens before klipper connection
         # This assumes that the hardware configuartion appears before the `[mmu]` section
@@ -657,12 +1208,15 @@ class Mmu:
         self.gear_rail = rails[1]
         self.gear_stepper = self.gear_rail.steppers[0]
         self.mmu_extruder_stepper = self.mmu_toolhead.mmu_extruder_stepper # Available now if `self.homing_extruder` is True
+        gear_mux = self.mmu_toolhead.gear_mux
+        if gear_mux and self.mmu_num_gates > gear_mux.get_num_gates():
+            raise config.error("mmu_gear_mux can only route %d gates (%d drivers x %d channels) but mmu_num_gates is %d" % (gear_mux.get_num_gates(), len(gear_mux.get_driver_names()), gear_mux.channels_per_driver, self.mmu_num_gates))
 
         # Detect if selector touch is possible
         self.selector_touch = self.ENDSTOP_SELECTOR_TOUCH in self.selector_rail.get_extra_endstop_names() and self.selector_touch_enable
 
         # Setup filament homing sensors ------
-        for name in [self.ENDSTOP_TOOLHEAD, self.ENDSTOP_GATE, self.ENDSTOP_EXTRUDER]:
+        for name in [self.ENDSTOP_TOOLHEAD, self.ENDSTOP_GATE, self.ENDSTOP_EXTRUDER, self.SENSOR_SYNC_TENSION, self.SENSOR_SYNC_COMPRESSION]:
             sensor = self.printer.lookup_object("filament_switch_sensor %s_sensor" % name, None)
             if sensor is not None:
                 self.sensors[name] = sensor
@@ -684,14 +1238,28 @@ class Mmu:
                 if self.homing_extruder:
                     mcu_endstop.add_stepper(self.mmu_extruder_stepper.stepper)
 
+        # Setup custom path sensors (checkpoints ordered by position from gate) ------
+        self.path_sensors = []
+        mmu_sensors = self.printer.lookup_object('mmu_sensors', None)
+        if mmu_sensors is not None:
+            for name, position in mmu_sensors.get_path_sensors():
+                sensor = self.printer.lookup_object("filament_switch_sensor %s_sensor" % name, None)
+                if sensor is not None:
+                    self.sensors[name] = sensor
+                    self.path_sensors.append((position, name))
+            self.path_sensors.sort()
+
         # Get servo and (optional) encoder setup -----
         self.servo = self.printer.lookup_object('mmu_servo mmu_servo', None)
-        if not self.servo:
+        if not self.servo and self.vendor_driver.HAS_SERVO:
             raise self.config.error("No [mmu_servo] definition found in mmu_hardware.cfg")
         self.encoder_sensor = self.printer.lookup_object('mmu_encoder mmu_encoder', None)
         if not self.encoder_sensor:
             # MMU logging not set up so use main klippy logger
             logging.warn("No [mmu_encoder] definition found in mmu_hardware.cfg. Assuming encoder is not available")
+        self.virtual_encoder = None
+        if not self.encoder_sensor and self.enable_virtual_encoder:
+            self.virtual_encoder = MmuVirtualEncoder(self.virtual_encoder_samples, self.virtual_clog_length)
 
     def _setup_logging(self):
         # Setup background file based logging before logging any messages
@@ -710,15 +1278,61 @@ class Mmu:
             self.mmu_logger.setLevel(logging.INFO)
             self.mmu_logger.addHandler(queue_handler)
 
+        # Optional per-job telemetry recording (JSONL file per print)
+        if self.enable_telemetry:
+            dirname = os.path.dirname(self.printer.start_args['log_file'] or '') or '/tmp'
+            self.telemetry = MmuTelemetry(os.path.join(dirname, 'mmu_telemetry'), self.telemetry_max_jobs)
+
+        # Optional journal of each toolchange saved on failure for offline replay
+        if self.enable_journal:
+            dirname = os.path.dirname(self.printer.start_args['log_file'] or '') or '/tmp'
+            self.journal = MmuJournal(os.path.join(dirname, 'mmu_journal'), self.journal_max_files)
+            self.journal.instrument(self)
+
     def handle_connect(self):
         self._setup_logging()
+        self.plugin_manager.on_connect()
         self.toolhead = self.printer.lookup_object('toolhead')
+        for name in self.print_locked_commands:
+            self._lock_command_in_print(name)
 
         # Sanity check extruder name
         extruder = self.printer.lookup_object(self.extruder_name, None)
         if not extruder:
             raise self.config.error("Extruder named `%s` not found on printer" % self.extruder_name)
 
+        # Sanity check dryer heater names and zones
+        for heater in self.dryer_heaters:
+            if heater not in self.printer.lookup_object('heaters').get_all_heaters():
+                raise self.config.error("Dryer heater named `%s` not found on printer" % heater)
+        if self.dryer_gate_zones:
+            if len(self.dryer_gate_zones) != self.mmu_num_gates:
+                raise self.config.error("dryer_gate_zones has different number of entries than the number of gates")
+            if any(z < 0 or z >= len(self.dryer_heaters) for z in self.dryer_gate_zones):
+                raise self.config.error("dryer_gate_zones entries must be in the range 0..%d (one zone per dryer_heater)" % (len(self.dryer_heaters) - 1))
+        else:
+            self.dryer_gate_zones = [0] * self.mmu_num_gates
+        if self.dryer_zone_max_temps and len(self.dryer_zone_max_temps) != len(self.dryer_heaters):
+            raise self.config.error("dryer_zone_max_temps must have one entry per dryer_heater")
+        if self.dryer_units and len(self.dryer_units) != len(self.dryer_heaters):
+            raise self.config.error("dryer_units must have one entry per dryer_heater")
+        if self.dryer_sensors:
+            if len(self.dryer_sensors) != len(self.dryer_heaters):
+                raise self.config.error("dryer_sensors must have one entry per dryer_heater")
+            for sensor in self.dryer_sensors:
+                if self.printer.lookup_object(sensor, None) is None:
+                    raise self.config.error("Dryer sensor named `%s` not found on printer" % sensor)
+        if self.dryer_zone_pid:
+            if len(self.dryer_zone_pid) != len(self.dryer_heaters):
+                raise self.config.error("dryer_zone_pid must have one entry per dryer_heater")
+            for z, pid in enumerate(self.dryer_zone_pid):
+                if pid is None: continue
+                heater = self.printer.lookup_object('heaters').lookup_heater(self.dryer_heaters[z])
+                heater.set_control(ControlPID(heater, MmuPidConfig(*pid)))
+        self.dryer_zone_start = [None] * len(self.dryer_heaters)
+        self.dryer_soaked_zones = set() # Zones that have already had their first load soak in this print
+        self.dryer_job_tools = None # Tools the dryer zones were set for by MMU_DRYER (reduced by DONE=)
+
         # See if we have a TMC controller capable of current control for filament collision detection and syncing
         # on gear_stepper and tip forming on extruder
         self.selector_tmc = self.gear_tmc = self.extruder_tmc = None
@@ -743,11 +1357,55 @@ class Mmu:
             self._log_debug("TMC driver not found for gear_stepper, cannot use current reduction for collision detection or while synchronized printing")
         if self.extruder_tmc is None:
             self._log_debug("TMC driver not found for extruder, cannot use current increase for tip forming move")
+        if self.gear_temp_sensor and self.gear_tmc is None:
+            raise self.config.error("gear_temp_sensor requires a TMC driver on gear_stepper for current control")
+        if self.gear_temp_sensor not in ['', 'tmc'] and self.printer.lookup_object(self.gear_temp_sensor, None) is None:
+            raise self.config.error("gear_temp_sensor '%s' not found on printer" % self.gear_temp_sensor)
+
+        # StallGuard sampling of gear stepper for jam detection during bowden moves
+        self.gear_stall_samples = []
+        self.gear_stall_baseline = None
+        self.gear_stall_tripped = False
+        self.gear_stall_timer = self.reactor.register_timer(self._sample_gear_stallguard, self.reactor.NEVER)
+        if self.bowden_stall_detection and (self.gear_tmc is None or self.gear_tmc.fields.lookup_register("sg_result", None) is None):
+            self._log_debug("TMC driver for gear_stepper does not report SG_RESULT, bowden stall detection disabled")
+            self.bowden_stall_detection = 0
 
         # Establish gear_stepper initial gear_stepper and extruder currents
         self.gear_default_run_current = self.gear_tmc.get_status(0)['run_current'] if self.gear_tmc else None
+        self.gear_default_rotation_distance = self.gear_stepper.get_rotation_distance()[0]
+        self.default_extruder_homing_current = self.extruder_homing_current
         self.extruder_default_run_current = self.extruder_tmc.get_status(0)['run_current'] if self.extruder_tmc else None
         self.gear_percentage_run_current = self.gear_restore_percent_run_current = self.extruder_percentage_run_current = 100.
+        self.gear_thermal_reduction = 0 # Percentage points sync_gear_current is reduced because gear motor is hot
+
+        # Optional per-gate gear driver overrides for gates driven by different motors. Record each gear stepper's own
+        # configuration (before calibration is applied) so it can be restored when a gate without overrides is selected
+        self.gate_drivers = {}
+        for _, driver in self.printer.lookup_objects('mmu_gate_driver'):
+            if driver.gate >= self.mmu_num_gates:
+                raise self.config.error("[mmu_gate_driver %d] refers to gate that doesn't exist" % driver.gate)
+            self.gate_drivers[driver.gate] = driver
+        self.gear_driver_defaults = {}
+        self.gear_driver_applied = {}
+        if self.gate_drivers:
+            for stepper in self.mmu_toolhead.all_gear_rail_steppers:
+                name = stepper.get_name()
+                section = self.config.getsection(name)
+                tmc = None
+                for chip in tmc_chips:
+                    tmc = tmc or self.printer.lookup_object('%s %s' % (chip, name), None)
+                status = tmc.get_status(0) if tmc else {}
+                self.gear_driver_defaults[name] = {
+                    'stepper': stepper,
+                    'tmc': tmc,
+                    'run_current': status.get('run_current', None),
+                    'hold_current': status.get('hold_current', None),
+                    'rotation_distance': stepper.get_rotation_distance()[0],
+                    'microsteps': section.getint('microsteps'),
+                    'full_steps_per_rotation': section.getint('full_steps_per_rotation', 200),
+                }
+            self._log_debug("Per-gate gear driver overrides for gates: %s" % ", ".join(map(str, sorted(self.gate_drivers))))
 
         # Sanity check that required klipper options are enabled
         self.print_stats = self.printer.lookup_object("print_stats", None)
@@ -766,6 +1424,10 @@ class Mmu:
         if self.default_idle_timeout < 0:
             self.default_idle_timeout = self.printer.lookup_object("idle_timeout").idle_timeout
 
+        self._load_calibration()
+
+    # Apply persisted calibration. Also called after restoring a calibration set with MMU_CALIBRATION
+    def _load_calibration(self):
         # Configure gear stepper calibration (set with MMU_CALIBRATE_GEAR)
         rotation_distance = self.variables.get(self.VARS_MMU_GEAR_ROTATION_DISTANCE, None)
         if rotation_distance:
@@ -807,15 +1469,22 @@ class Mmu:
             else:
                 self._log_error("Incorrect number of gates specified in %s" % self.VARS_MMU_SELECTOR_OFFSETS)
                 self.selector_offsets = [0.] * self.mmu_num_gates
+        elif not self.vendor_driver.HAS_SELECTOR:
+            self.selector_offsets = [0.] * self.mmu_num_gates
+            self.calibration_status |= self.CALIBRATED_SELECTOR # Nothing to calibrate
+        elif len(self.selector_offsets) == self.mmu_num_gates:
+            self._log_debug("Using configured selector offsets: %s" % self.selector_offsets)
+            self.calibration_status |= self.CALIBRATED_SELECTOR
         else:
             self._log_always("Warning: Selector offsets not found in mmu_vars.cfg. Probably not calibrated")
             self.selector_offsets = [0.] * self.mmu_num_gates
         bypass_offset = self.variables.get(self.VARS_MMU_SELECTOR_BYPASS, None)
         if bypass_offset:
-            self.bypass_offset = bypass_offset
+            self.bypass_offsets = [o for o in bypass_offset if o > 0.] if isinstance(bypass_offset, list) else [bypass_offset]
             self._log_debug("Loaded saved bypass offset: %s" % bypass_offset)
-        else:
-            self.bypass_offset = 0
+        elif bypass_offset is not None:
+            self.bypass_offsets = []
+        self._set_bypass_selected(self.variables.get(self.VARS_MMU_BYPASS_SELECTED, 0))
 
         # Set bowden length from calibration
         bowden_length = self.variables.get(self.VARS_MMU_CALIB_BOWDEN_LENGTH, None)
@@ -826,10 +1495,65 @@ class Mmu:
         else:
             self._log_always("Warning: Reference bowden length not found in mmu_vars.cfg. Probably not calibrated")
 
+        # Gate ratios relative to gate #0 (set with MMU_CALIBRATE_GATES)
+        if any(self.variables.get("%s%d" % (self.VARS_MMU_CALIB_PREFIX, gate), None) for gate in range(self.mmu_num_gates)):
+            self.calibration_status |= self.CALIBRATED_GATES
+
+        # Sync feedback buffer travel (set with MMU_CALIBRATE_SYNC_FEEDBACK)
+        sync_feedback = self.variables.get(self.VARS_MMU_CALIB_SYNC_FEEDBACK, None)
+        if sync_feedback:
+            self.sync_feedback_buffer_range = sync_feedback.get('range', self.sync_feedback_buffer_range)
+            self.sync_feedback_buffer_maxrange = sync_feedback.get('maxrange', self.sync_feedback_buffer_maxrange)
+            self._log_debug("Loaded saved sync feedback buffer range: %.1f, maxrange: %.1f" % (self.sync_feedback_buffer_range, self.sync_feedback_buffer_maxrange))
+
+        # Observed gate ratio history for autotune and drift monitoring
+        ratio_drift = self.variables.get(self.VARS_MMU_CALIB_RATIO_DRIFT, {})
+        baselines = list(ratio_drift.get('baseline', []))
+        history = list(ratio_drift.get('history', []))
+        self.gate_ratio_baseline = (baselines + [-1.] * self.mmu_num_gates)[:self.mmu_num_gates]
+        self.gate_ratio_history = ([list(h) for h in history] + [[] for _ in range(self.mmu_num_gates)])[:self.mmu_num_gates]
+        self.gate_ratio_drift_alerted = [False] * self.mmu_num_gates
+
+        # Friction profile health score history
+        gate_health = self.variables.get(self.VARS_MMU_GATE_HEALTH, {})
+        baselines = list(gate_health.get('baseline', []))
+        history = list(gate_health.get('history', []))
+        self.gate_health_baseline = (baselines + [-1.] * self.mmu_num_gates)[:self.mmu_num_gates]
+        self.gate_health_history = ([list(h) for h in history] + [[] for _ in range(self.mmu_num_gates)])[:self.mmu_num_gates]
+        self.gate_health_alerted = [False] * self.mmu_num_gates
+
+        # Safety watchdog trip counters
+        trips = self.variables.get(self.VARS_MMU_SAFETY_TRIPS, {})
+        self.safety_trips = {c: trips.get(c, 0) for c in self.SAFETY_COMPONENTS}
+
+        # Cutter blade usage and calibrated pin travel
+        blade = self.variables.get(self.VARS_MMU_CUTTER_BLADE, {})
+        self.cutter_blade = {k: blade.get(k, 0) for k in ['cuts', 'total_cuts', 'blades']}
+        self.cutter_travel = self.variables.get(self.VARS_MMU_CALIB_CUTTER_TRAVEL, None)
+        self.cutter_travel_trial = None # Pin travel being tried by MMU_CALIBRATE_CUTTER
+
+        # Collision homing current tuned by MMU_CALIBRATE_COLLISION overrides config
+        collision_current = self.variables.get(self.VARS_MMU_CALIB_COLLISION_CURRENT, None)
+        if collision_current:
+            self.extruder_homing_current = collision_current
+            self._log_debug("Loaded tuned extruder collision homing current: %d%%" % collision_current)
+
+        # Persisted MMU_TEST_CONFIG overrides are reapplied on top of config
+        overrides = self.variables.get(self.VARS_MMU_CONFIG_OVERRIDES, {})
+        for attr, value in overrides.items():
+            if hasattr(self, attr):
+                self._add_config_override(attr, value, self.CONFIG_SCOPE_PERSIST, None, "saved")
+        if overrides:
+            self._apply_config_overrides()
+            self._log_debug("Loaded persisted config overrides: %s" % overrides)
+
     def handle_disconnect(self):
         self._log_debug('Klipper disconnected! MMU Shutdown')
+        self.plugin_manager.on_disconnect()
         if self.queue_listener is not None:
             self.queue_listener.stop()
+        if self.telemetry is not None:
+            self.telemetry.stop()
 
     def handle_ready(self):
         # Reference correct extruder stepper which will definitely be available now
@@ -837,10 +1561,35 @@ class Mmu:
         if not self.homing_extruder:
             self._log_debug("Warning: Using original klipper extruder stepper")
 
+        # Extruder pressure advance active before a per-gate override was applied, restored when leaving that gate
+        self.pressure_advance_before_override = None
+
+        # Record all MMU filament sensor edges (including pre-gate sensors) to telemetry
+        if self.telemetry:
+            for name, sensor in self.printer.lookup_objects('filament_switch_sensor'):
+                name = name.split()[-1]
+                if name.endswith("_sensor"):
+                    name = name[:-len("_sensor")]
+                if name in self.sensors or name.startswith("mmu_pre_gate_") or name == "mmu_bypass":
+                    self._wrap_sensor_telemetry(name, sensor.runout_helper)
+
+        # Sync gear for long slicer purge extrusions when not normally synced
+        if self.purge_assist_length > 0:
+            self.purge_assist = MmuPurgeAssist(self, self.purge_assist_length, self.purge_assist_hold)
+            self.purge_assist.install(self.gcode_move)
+
+        # Route part of toolchange purge into following sparse infill
+        if self.purge_to_infill_percent > 0:
+            self.purge_router = MmuPurgeRouter(self, self.purge_to_infill_flow)
+            self.purge_router.install(self.gcode_move)
+
         # Restore state if fully calibrated
         if not self._check_is_calibrated(silent=True):
             self._load_persisted_state()
 
+        # Sanity check interdependent settings now that calibration is known
+        self._report_config_check(startup=True)
+
         # Setup events for managing internal print state machine
         self.printer.register_event_handler("idle_timeout:printing", self._handle_idle_timeout_printing)
         self.printer.register_event_handler("idle_timeout:ready", self._handle_idle_timeout_ready)
@@ -880,6 +1629,13 @@ class Mmu:
         except Exception as e:
             self._log_error('Error trying to wrap PAUSE/RESUME/CLEAR_PAUSE/CANCEL_PRINT macros: %s' % str(e))
 
+        # Handle M600 manual filament change unless the user has their own macro
+        if self.filament_change_m600:
+            if self.printer.lookup_object('gcode_macro M600', None) is None:
+                self.gcode.register_command('M600', self.cmd_MMU_FILAMENT_CHANGE, desc = self.cmd_MMU_FILAMENT_CHANGE_help)
+            else:
+                self._log_debug("Existing M600 macro found. Call MMU_FILAMENT_CHANGE from it for MMU aware filament change")
+
         # Ensure that the control macro knows the index of the first LED in the strip
         first = MmuLedEffect.first_led_index or 1
         try:
@@ -888,21 +1644,39 @@ class Mmu:
             pass # Probably just means the macro is missing
 
         self.estimated_print_time = self.printer.lookup_object('mcu').estimated_print_time
+        self.plugin_manager.on_ready()
         self.last_selector_move_time = self.estimated_print_time(self.reactor.monotonic())
+        self.map_snapshot = self._get_map_snapshot() # Baseline for change history
         self._schedule_mmu_bootup_tasks(self.BOOT_DELAY)
 
     def _initialize_state(self):
         self.is_enabled = True
         self.paused_extruder_temp = None
-        self.is_homed = False
+        self.hotend_standby_saved_temp = None
+        self.is_homed = self.virtual_selector # Nothing to home without a selector
         self.last_print_stats = None
         self.tool_selected = self._next_tool = self._last_tool = self.TOOL_GATE_UNKNOWN
         self._last_toolchange = "Unknown"
+        self.toolchange_purge_volume = 0.
+        self.last_extrusion_time = 0. # When print last extruded before toolchange (for auto priming)
+        self.last_error = None
+        self.self_check_last = {} # Summary of last MMU_SELF_CHECK
+        self.last_tip_inspection = {}
+        self.rfid_pending_tag = None
         self.gate_selected = self.TOOL_GATE_UNKNOWN # We keep record of gate selected in case user messes with mapping in print
         self.servo_state = self.servo_angle = self.SERVO_UNKNOWN_STATE
         self.filament_pos = self.FILAMENT_POS_UNKNOWN
         self.filament_direction = self.DIRECTION_UNKNOWN
         self.filament_remaining = 0. # Tracker of filament left in extruder by cutter
+        self.tip_was_cut = False # Whether last tip was formed by cutter (macro reported park_pos)
+        self.filament_change_tool = self.filament_change_gate = self.TOOL_GATE_UNKNOWN # Pending manual filament change (M600)
+        self.overlap_next_gate = self.TOOL_GATE_UNKNOWN # Gate to start feeding during bowden retract of toolchange unload
+        self.final_eject_gates = set() # Gates deliberately ejected past pre-gate sensor (runout is expected)
+        self.ready_parked_gates = set() # Gates parked at 'gate_ready_park' rather than 'gate_parking_distance'
+        self.load_balanced_gates = {} # Gate each tool was last loaded from by 'gate_load_balancing' (TTG map is unchanged)
+        self.slicer_tip_params = {} # Per-tool tip forming parameters from slicer profile (MMU_SLICER_TIP_PARAMETERS)
+        self.job_saved_ttg_map = None # Tool-to-gate map to restore after job scoped remap from slicer manifest
+        self._reset_bowden_live_adjustment()
         self.action = self.ACTION_IDLE
         self.calibrating = False
         self._clear_saved_toolhead_position()
@@ -923,33 +1697,11 @@ class Mmu:
 
     # This retuns a convenient RGB spec for controlling LEDs in form (0.32, 0.56, 1.00)
     def _color_to_rgb(self, color):
-        if color in self.w3c_colors:
-            color = self.w3c_colors.get(color)
-        elif color == '':
-            color = "#000000"
-        hex_rgb = color.lstrip('#')
-        length = len(hex_rgb)
-        if length % 3 == 0:
-            return tuple(round(float(int(hex_rgb[i:i + length // 3], 16)) / 255, 2) for i in range(0, length, length // 3))
-        return (0.,0.,0.)
-
-    # Helper to return validated color string or None if invalid
-    def _validate_color(self, color):
-        color = color.lower()
-        if color == "":
-            return ""
+        return color_to_rgb(color)
 
-        # Try w3c named color
-        if color in self.w3c_colors:
-            return color
-
-        # Try RGB color
-        color = color.lstrip('#')
-        x = re.search("^([a-f\d]{6})$", color)
-        if x is not None and x.group() == color:
-            return color
-
-        return None # Not valid
+    # Helper to return normalized color string (w3c name or 'rrggbb') from any supported format or None if invalid
+    def _validate_color(self, color):
+        return normalize_color(color)
 
     # Helper to keep parallel RGB color map updated when color changes
     def _update_gate_color(self, new_color_map):
@@ -970,6 +1722,20 @@ class Mmu:
                 self.endless_spool_groups = endless_spool_groups
             else:
                 errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_ENDLESS_SPOOL_GROUPS)
+            endless_spool_priorities = self.variables.get(self.VARS_MMU_ENDLESS_SPOOL_PRIORITIES, self.endless_spool_priorities)
+            if len(endless_spool_priorities) == self.mmu_num_gates:
+                self.endless_spool_priorities = endless_spool_priorities
+            else:
+                errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_ENDLESS_SPOOL_PRIORITIES)
+            gate_maintenance = self.variables.get(self.VARS_MMU_GATE_MAINTENANCE, self.gate_maintenance)
+            self.gate_maintenance = [g for g in gate_maintenance if 0 <= g < self.mmu_num_gates]
+            gate_lock = self.variables.get(self.VARS_MMU_GATE_LOCK, self.gate_lock)
+            self.gate_lock = {int(g): o for g, o in gate_lock.items() if 0 <= int(g) < self.mmu_num_gates}
+            gate_stub_length = self.variables.get(self.VARS_MMU_GATE_STUB_LENGTH, self.gate_stub_length)
+            if len(gate_stub_length) == self.mmu_num_gates:
+                self.gate_stub_length = gate_stub_length
+            else:
+                errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_GATE_STUB_LENGTH)
 
         if self.persistence_level >= 2:
             # Load tool to gate map
@@ -1008,6 +1774,32 @@ class Mmu:
             else:
                 errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_GATE_SPOOL_ID)
 
+            # Load pressure advance and smooth time at each gate
+            gate_pressure_advance = self.variables.get(self.VARS_MMU_GATE_PRESSURE_ADVANCE, self.gate_pressure_advance)
+            if len(gate_pressure_advance) == self.mmu_num_gates:
+                self.gate_pressure_advance = gate_pressure_advance
+            else:
+                errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_GATE_PRESSURE_ADVANCE)
+            gate_smooth_time = self.variables.get(self.VARS_MMU_GATE_SMOOTH_TIME, self.gate_smooth_time)
+            if len(gate_smooth_time) == self.mmu_num_gates:
+                self.gate_smooth_time = gate_smooth_time
+            else:
+                errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_GATE_SMOOTH_TIME)
+
+            # Load filament temperature at each gate
+            gate_temperature = self.variables.get(self.VARS_MMU_GATE_TEMPERATURE, self.gate_temperature)
+            if len(gate_temperature) == self.mmu_num_gates:
+                self.gate_temperature = gate_temperature
+            else:
+                errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_GATE_TEMPERATURE)
+
+            # Load physical unit of each gate
+            gate_unit = self.variables.get(self.VARS_MMU_GATE_UNIT, self.gate_unit)
+            if len(gate_unit) == self.mmu_num_gates:
+                self.gate_unit = gate_unit
+            else:
+                errors.append("Incorrect number of gates specified in %s" % self.VARS_MMU_GATE_UNIT)
+
         if self.persistence_level >= 4:
             # Load selected tool and gate
             tool_selected = self.variables.get(self.VARS_MMU_TOOL_SELECTED, self.tool_selected)
@@ -1029,7 +1821,7 @@ class Mmu:
                 else:
                     self.tool_selected = self.TOOL_GATE_UNKNOWN
                     self.gate_selected = self.TOOL_GATE_UNKNOWN
-                    self.is_homed = False
+                    self.is_homed = self.virtual_selector
             else:
                 errors.append("Incorrect number of gates specified in %s or %s" % (self.VARS_MMU_TOOL_SELECTED, self.VARS_MMU_GATE_SELECTED))
             if gate_selected != self.TOOL_GATE_UNKNOWN and tool_selected != self.TOOL_GATE_UNKNOWN:
@@ -1057,7 +1849,10 @@ class Mmu:
     cmd_MMU_BOOTUP_TASKS_help = "Internal commands to complete bootup of MMU"
     def cmd_MMU_BOOTUP_TASKS(self, gcmd):
         try:
-            self._log_always('(\_/)\n( *,*)\n(")_(") Happy Hare Ready')
+            if self.log_profile == self.LOG_PROFILE_TERSE:
+                self._log_always("mmu event=ready version=%.1f" % self.VERSION)
+            else:
+                self._log_always('(\_/)\n( *,*)\n(")_(") Happy Hare Ready')
             if self.log_startup_status > 0:
                 self._log_always(self._tool_to_gate_map_to_human_string(summary=self.log_startup_status == 1))
                 self._display_visual_state(silent=self.persistence_level < 4)
@@ -1067,7 +1862,10 @@ class Mmu:
                 self._disable_encoder_sensor() # Initially disable clog/runout detection
             self._servo_move()
             self.gate_status = self._validate_gate_status(self.gate_status) # Delay to allow for correct initial state
-            self._update_filaments_from_spoolman()
+            if self.spoolman_location and not self.spoolman_push:
+                self._pull_spoolman_locations()
+            else:
+                self._update_filaments_from_spoolman()
         except Exception as e:
             self._log_always('Warning: Error booting up MMU: %s' % str(e))
 
@@ -1082,7 +1880,7 @@ class Mmu:
         except Exception as e:
             if exception is not None:
                 if exception:
-                    raise MmuError("Error running %s: %s" % (macro, str(e)))
+                    raise MmuError("Error running %s: %s" % (macro, str(e)), code=self.ERR_MACRO)
                 else:
                     self._log_debug("Error running %s: %s" % (macro, str(e)))
 
@@ -1130,6 +1928,7 @@ class Mmu:
                 "Checking" if action == self.ACTION_CHECKING else
                 "Homing" if action == self.ACTION_HOMING else
                 "Selecting" if action == self.ACTION_SELECTING else
+                "Waiting" if action == self.ACTION_WAITING else
                 "Unknown") # Error case - should not happen
 
     def get_status(self, eventtime):
@@ -1143,6 +1942,15 @@ class Mmu:
                 'next_tool': self._next_tool,
                 'last_tool': self._last_tool,
                 'last_toolchange': self._last_toolchange,
+                'toolchange_purge_volume': self.toolchange_purge_volume,
+                'last_error': dict(self.last_error) if self.last_error else {},
+                'self_check': dict(self.self_check_last),
+                'safety_tripped': list(self.safety_tripped),
+                'cutter_blade_cuts': self.cutter_blade['cuts'],
+                'sync_gear_current': self._get_sync_gear_current(),
+                'plugins': self.plugin_manager.get_status(eventtime),
+                'num_toolchanges': self.job_statistics['total_swaps'],
+                'expected_toolchanges': self.job_expected_swaps,
                 'filament': "Loaded" if self.filament_pos == self.FILAMENT_POS_LOADED else
                             "Unloaded" if self.filament_pos == self.FILAMENT_POS_UNLOADED else
                             "Unknown",
@@ -1159,18 +1967,66 @@ class Mmu:
                 'gate_color': list(self.gate_color),
                 'gate_color_rgb': self.gate_color_rgb,
                 'gate_spool_id': list(self.gate_spool_id),
+                'gate_pressure_advance': list(self.gate_pressure_advance),
+                'gate_smooth_time': list(self.gate_smooth_time),
+                'gate_temperature': list(self.gate_temperature),
+                'gate_unit': list(self.gate_unit),
                 'endless_spool_groups': list(self.endless_spool_groups),
+                'endless_spool_priorities': list(self.endless_spool_priorities),
+                'gate_maintenance': list(self.gate_maintenance),
+                'gate_health': [h[-1][1] if h else -1 for h in self.gate_health_history],
+                'gate_locked': [int(g in self.gate_lock) for g in range(self.mmu_num_gates)],
+                'gate_lock_owner': [self.gate_lock.get(g, "") for g in range(self.mmu_num_gates)],
+                'gate_stub_length': list(self.gate_stub_length),
                 'tool_extrusion_multipliers': list(self.tool_extrusion_multipliers),
                 'tool_speed_multipliers': list(self.tool_speed_multipliers),
                 'action': self._get_action_string(),
                 'has_bypass': self.bypass_offset > 0.,
+                'num_bypass': len(self.bypass_offsets),
                 'sync_drive': self.mmu_toolhead.is_synced(),
+                'tip_inspection': dict(self.last_tip_inspection),
                 'print_state': self.print_state,
+                'log_profile': self.log_profile,
+                'dryer_zones': self._get_dryer_zone_status(eventtime),
                 'clog_detection': self.enable_clog_detection,
+                'virtual_encoder': self.virtual_encoder.get_status() if self.virtual_encoder else None,
                 'endless_spool': self.enable_endless_spool,
                 'print_start_detection': self.print_start_detection,
+                'ui_actions': self._get_ui_actions(),
         }
 
+    # Context sensitive list of currently valid operations for UI's to render as buttons
+    def _get_ui_actions(self):
+        action = lambda label, command : {'label': label, 'command': command}
+        if not self.is_enabled:
+            return [action("Enable", "MMU ENABLE=1")]
+        actions = []
+        if self._is_mmu_pause_locked():
+            actions.append(action("Unlock", "MMU_UNLOCK"))
+        if self._is_mmu_paused():
+            actions.append(action("Recover", "MMU_RECOVER"))
+            if self.filament_pos not in [self.FILAMENT_POS_UNLOADED, self.FILAMENT_POS_LOADED]:
+                actions.append(action("Unload", "MMU_UNLOAD"))
+            elif self.filament_pos == self.FILAMENT_POS_UNLOADED and self.gate_selected >= 0:
+                actions.append(action("Retry load gate %d" % self.gate_selected, "MMU_LOAD"))
+                if self.tool_selected >= 0:
+                    actions.append(action("Retry T%d" % self.tool_selected, "MMU_CHANGE_TOOL TOOL=%d" % self.tool_selected))
+            actions.append(action("Resume", "RESUME"))
+            return actions
+        if not self.is_homed:
+            actions.append(action("Home", "MMU_HOME"))
+        if self.filament_pos == self.FILAMENT_POS_LOADED:
+            actions.append(action("Unload", "MMU_UNLOAD"))
+        elif self.filament_pos == self.FILAMENT_POS_UNLOADED:
+            if self.tool_selected >= 0:
+                actions.append(action("Load T%d" % self.tool_selected, "MMU_LOAD"))
+        else:
+            actions.append(action("Recover", "MMU_RECOVER"))
+            actions.append(action("Unload", "MMU_UNLOAD"))
+        if self.is_homed and not self._is_printing() and self.filament_pos == self.FILAMENT_POS_UNLOADED:
+            actions.append(action("Check gates", "MMU_CHECK_GATE"))
+        return actions
+
     def _reset_statistics(self):
         self.statistics = dict.fromkeys(['total_swaps', 'time_spent_loading', 'time_spent_unloading', 'total_pauses', 'time_spent_paused'], 0)
         self.gate_statistics = []
@@ -1180,12 +2036,26 @@ class Mmu:
 
     def _reset_job_statistics(self):
         self.job_statistics = dict.fromkeys(['total_swaps', 'time_spent_loading', 'time_spent_unloading', 'total_pauses', 'time_spent_paused'], 0)
+        self.job_expected_swaps = 0 # Unknown
+        self.toolchange_budget_warned = False
         self.tracked_start_time = 0
         self.pause_start_time = 0
 
     def _track_swap_completed(self):
         self.statistics['total_swaps'] += 1
         self.job_statistics['total_swaps'] += 1
+        self._check_toolchange_budget()
+
+    # Warn (once per print) if more swaps than expected occur. Usually a sign of EndlessSpool churn or
+    # a mismatch between the sliced file and the tool map
+    def _check_toolchange_budget(self):
+        if self.job_expected_swaps <= 0 or self.toolchange_budget_warned or not self._is_in_print():
+            return
+        allowed = self.job_expected_swaps + max(1, self.job_expected_swaps * self.toolchange_budget_margin // 100)
+        if self.job_statistics['total_swaps'] > allowed:
+            self.toolchange_budget_warned = True
+            self._log_always("Warning: %d toolchanges have occurred but only %d were expected for this print. Check for EndlessSpool activity or a slicer/tool map mismatch" % (self.job_statistics['total_swaps'], self.job_expected_swaps))
+            self._publish_event("toolchange_budget", swaps=self.job_statistics['total_swaps'], expected=self.job_expected_swaps)
 
     def _track_load_start(self):
         self.tracked_start_time = time.time()
@@ -1194,6 +2064,8 @@ class Mmu:
         elapsed = time.time() - self.tracked_start_time
         self.statistics['time_spent_loading'] += elapsed
         self.job_statistics['time_spent_loading'] += elapsed
+        self._publish_metric("mmu_phase", {'duration': round(elapsed, 2)}, phase="load", gate=self.gate_selected)
+        self._publish_gate_metrics(self.gate_selected)
 
     def _track_unload_start(self):
         self.tracked_start_time = time.time()
@@ -1202,6 +2074,8 @@ class Mmu:
         elapsed = time.time() - self.tracked_start_time
         self.statistics['time_spent_unloading'] += elapsed
         self.job_statistics['time_spent_unloading'] += elapsed
+        self._publish_metric("mmu_phase", {'duration': round(elapsed, 2)}, phase="unload", gate=self.gate_selected)
+        self._publish_gate_metrics(self.gate_selected)
 
     def _track_pause_start(self):
         self.statistics['total_pauses'] += 1
@@ -1241,11 +2115,18 @@ class Mmu:
     def _swap_statistics_to_human_string(self, total=True):
         (msg, stats) = ("MMU Total Statistics:", self.statistics) if total == True else ("MMU Last Print Statistics:", self.job_statistics)
         msg += "\n%d swaps completed" % stats['total_swaps']
+        if not total and self.job_expected_swaps > 0:
+            msg += " (%d expected)" % self.job_expected_swaps
         msg += "\n%s spent loading (average: %s)" % (self._seconds_to_human_string(stats['time_spent_loading']),
                                                      self._seconds_to_human_string(stats['time_spent_loading'] / stats['total_swaps']) if stats['total_swaps'] > 0 else "0")
         msg += "\n%s spent unloading (average: %s)" % (self._seconds_to_human_string(stats['time_spent_unloading']),
                                                        self._seconds_to_human_string(stats['time_spent_unloading'] / stats['total_swaps']) if stats['total_swaps'] > 0 else "0")
         msg += "\n%s spent paused (total pauses: %d)" % (self._seconds_to_human_string(stats['time_spent_paused']), stats['total_pauses'])
+        if total and self.cutter_blade['total_cuts'] > 0:
+            msg += "\n%d cuts on current cutter blade" % self.cutter_blade['cuts']
+            if self.cutter_blade_life > 0:
+                msg += " (life: %d)" % self.cutter_blade_life
+            msg += ", %d cuts in total over %d blade change(s)" % (self.cutter_blade['total_cuts'], self.cutter_blade['blades'])
         return msg
 
     def _dump_statistics(self, force_log=False, total=False, job=False, gate=False, detail=False):
@@ -1263,6 +2144,12 @@ class Mmu:
                 if detail:
                     msg += "\n" if msg != "" else ""
                     msg += d
+                drift = self._gate_ratio_drift_to_human_string(detail=detail)
+                if drift:
+                    msg += "\n\n%s" % drift
+                health = self._gate_health_to_human_string(detail=detail)
+                if health:
+                    msg += "\n\n%s" % health
             self._log_always(msg)
 
         # This is good place to update the persisted stats...
@@ -1316,58 +2203,143 @@ class Mmu:
         self.statistics['time_spent_unloading'] = round(self.statistics['time_spent_unloading'], 2)
         self.statistics['time_spent_paused'] = round(self.statistics['time_spent_paused'], 2)
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SWAP_STATISTICS, self.statistics))
+        if self.cutter_blade['total_cuts'] > 0:
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_CUTTER_BLADE, self.cutter_blade))
 
-    def _persist_gate_map(self):
+    def _persist_gate_map(self, source=None):
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_STATUS, self.gate_status))
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_MATERIAL, list(map(lambda x: ("\'%s\'" %x), self.gate_material))))
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_COLOR, list(map(lambda x: ("\'%s\'" %x), self.gate_color))))
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_SPOOL_ID, self.gate_spool_id))
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_PRESSURE_ADVANCE, self.gate_pressure_advance))
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_SMOOTH_TIME, self.gate_smooth_time))
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_TEMPERATURE, self.gate_temperature))
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_UNIT, list(map(lambda x: ("\'%s\'" %x), self.gate_unit))))
         gcode = self.printer.lookup_object('gcode_macro _MMU_GATE_MAP_CHANGED', None)
         if gcode is not None:
             self._wrap_gcode_command("_MMU_GATE_MAP_CHANGED GATE=-1")
+        self._publish_event("gate_map", gate=-1, gate_status=self.gate_status, gate_material=self.gate_material, gate_color=self.gate_color, gate_spool_id=self.gate_spool_id)
+        self._record_map_change(source)
+
+    MAP_HISTORY_FIELDS = ['gate_status', 'gate_material', 'gate_color', 'gate_spool_id', 'gate_pressure_advance', 'gate_smooth_time', 'gate_temperature', 'gate_unit', 'tool_to_gate_map']
+
+    def _get_map_snapshot(self):
+        return {f: list(getattr(self, f)) for f in self.MAP_HISTORY_FIELDS}
+
+    # Record what changed in gate map or TTG map since last change and what made it ('source', e.g. runout, autoload,
+    # spoolman sync or the command). Called whenever either is persisted
+    def _record_map_change(self, source=None):
+        snapshot = self._get_map_snapshot()
+        if self.map_snapshot is None or snapshot == self.map_snapshot:
+            self.map_snapshot = snapshot
+            return
+        changes = []
+        for field in self.MAP_HISTORY_FIELDS:
+            old, new = self.map_snapshot[field], snapshot[field]
+            for i in range(min(len(old), len(new))):
+                if old[i] != new[i]:
+                    if field == 'tool_to_gate_map':
+                        changes.append("T%d: gate #%s -> #%s" % (i, old[i], new[i]))
+                    else:
+                        changes.append("Gate #%d %s: %s -> %s" % (i, field[5:], old[i], new[i]))
+        self.map_history.append({'time': time.time(), 'source': source or "internal", 'changes': changes, 'previous': self.map_snapshot})
+        del self.map_history[:-self.MAX_MAP_HISTORY]
+        self.map_snapshot = snapshot
+
+    # Source recorded in change history for gate map or TTG map changes made by a command. Macros can override with SOURCE=
+    def _get_command_source(self, gcmd):
+        return gcmd.get('SOURCE', "command: %s" % gcmd.get_commandline().strip())
+
+    def _restore_map_snapshot(self, snapshot):
+        for gate in range(self.mmu_num_gates):
+            if snapshot['gate_spool_id'][gate] != self.gate_spool_id[gate]:
+                self._set_spoolman_location(self.gate_spool_id[gate])
+                if snapshot['gate_status'][gate] != self.GATE_EMPTY:
+                    self._set_spoolman_location(snapshot['gate_spool_id'][gate], gate)
+        for field in self.MAP_HISTORY_FIELDS:
+            setattr(self, field, list(snapshot[field]))
+        self.map_snapshot = self._get_map_snapshot() # Undo isn't itself recorded
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+        self._update_gate_color(self.gate_color)
+        self._persist_gate_map()
+
+    # Snapshot of MMU state sufficient for power-loss recovery to continue a multi-material print
+    def _persist_plr_state(self, layer=None):
+        if not self.enable_plr or not self._is_in_print(): return
+        if layer is None:
+            layer = self.variables.get(self.VARS_MMU_PLR_STATE, {}).get('layer', -1)
+        plr_state = {
+            'tool': self.tool_selected,
+            'gate': self.gate_selected,
+            'filament_pos': self.filament_pos,
+            'ttg_map': list(self.tool_to_gate_map),
+            'layer': layer
+        }
+        self.variables[self.VARS_MMU_PLR_STATE] = plr_state
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_PLR_STATE, plr_state))
+
+    def _clear_plr_state(self):
+        if self.variables.get(self.VARS_MMU_PLR_STATE, None) is None: return
+        self.variables[self.VARS_MMU_PLR_STATE] = None
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=None" % self.VARS_MMU_PLR_STATE)
 
     def _log_error(self, message):
         if self.mmu_logger:
             self.mmu_logger.info(message)
-        self.gcode.respond_raw("!! %s" % message)
+        self.gcode.respond_raw("!! %s" % self._format_console(message))
 
     def _log_always(self, message):
         if self.mmu_logger:
             self.mmu_logger.info(message)
-        self.gcode.respond_info(message)
+        self.gcode.respond_info(self._format_console(message))
 
     def _log_info(self, message):
         if self.mmu_logger and self.log_file_level > 0:
             self.mmu_logger.info(message)
         if self.log_level > 0:
-            self.gcode.respond_info(message)
+            self.gcode.respond_info(self._format_console(message))
 
     def _log_debug(self, message):
         message = "- DEBUG: %s" % message
         if self.mmu_logger and self.log_file_level > 1:
             self.mmu_logger.info(message)
         if self.log_level > 1:
-            self.gcode.respond_info(message)
+            self.gcode.respond_info(self._format_console(message))
 
     def _log_trace(self, message):
         message = "- - TRACE: %s" % message
         if self.mmu_logger and self.log_file_level > 2:
             self.mmu_logger.info(message)
         if self.log_level > 2:
-            self.gcode.respond_info(message)
+            self.gcode.respond_info(self._format_console(message))
 
     def _log_stepper(self, message):
         message = "- - - STEPPER: %s" % message
         if self.mmu_logger and self.log_file_level > 3:
             self.mmu_logger.info(message)
         if self.log_level > 3:
-            self.gcode.respond_info(message)
+            self.gcode.respond_info(self._format_console(message))
+
+    # Terse profile collapses multi-line output (tables, maps, etc) into a single line so that
+    # console parsers always see one message per line. The logfile is unaffected
+    def _format_console(self, message):
+        if self.log_profile != self.LOG_PROFILE_TERSE:
+            return message
+        return " | ".join([line.strip() for line in message.splitlines() if line.strip()])
+
+    # Single line key=value event for terse profile
+    def _log_terse_event(self, event, **kwargs):
+        if self.log_profile != self.LOG_PROFILE_TERSE: return
+        fields = ["event=%s" % event]
+        for key in sorted(kwargs):
+            fields.append("%s=%s" % (key, "".join(str(kwargs[key]).split())))
+        self.gcode.respond_info("mmu %s" % " ".join(fields))
 
     # Fun visual display of MMU state
     def _display_visual_state(self, direction=None, silent=False):
         if direction is not None:
             self.filament_direction = direction
-        if not silent and self.log_visual > 0 and not self.calibrating:
+        if not silent and self.log_visual > 0 and not self.calibrating and self.log_profile != self.LOG_PROFILE_TERSE:
             visual_str = self._state_to_human_string()
             self._log_always(visual_str)
 
@@ -1438,6 +2410,7 @@ class Mmu:
     def cmd_MMU_STATS(self, gcmd):
         if self._check_is_disabled(): return
         reset = gcmd.get_int('RESET', 0, minval=0, maxval=1)
+        if reset and self._check_print_protection(gcmd): return
         total = gcmd.get_int('TOTAL', 0, minval=0, maxval=1)
         detail = gcmd.get_int('DETAIL', 0, minval=0, maxval=1)
         if reset:
@@ -1452,6 +2425,11 @@ class Mmu:
     def cmd_MMU_STATUS(self, gcmd):
         config = gcmd.get_int('SHOWCONFIG', 0, minval=0, maxval=1)
         detail = gcmd.get_int('DETAIL', 0, minval=0, maxval=1)
+        summary = gcmd.get_int('SUMMARY', 0, minval=0, maxval=1)
+
+        if summary:
+            self._log_always(self._health_summary_to_human_string())
+            return
 
         msg = "Happy Hare v%.1f running %s v%s" % (self.config_version, self.mmu_vendor, self.mmu_version_string)
         msg += " with %d gates" % (self.mmu_num_gates)
@@ -1471,7 +2449,7 @@ class Mmu:
             msg += "\n\nConfiguration:"
 
             msg += "\nLoad Sequence"
-            msg += "\n- Filament loads into gate by homing a maximum of %.1fmm to %s" % (self.gate_homing_max, "ENCODER" if self.gate_homing_endstop == self.ENDSTOP_ENCODER else "GATE SENSOR")
+            msg += "\n- Filament loads into gate by homing a maximum of %.1fmm to %s" % (self.gate_homing_max, "ENCODER" if self.gate_homing_endstop == self.ENDSTOP_ENCODER else "GATE TOUCH" if self.gate_homing_endstop == self.ENDSTOP_GATE_TOUCH else "GATE SENSOR")
             msg += "\n- Bowden is loaded with a fast %.1fmm move" % self.calibrated_bowden_length
             if self._must_home_to_extruder():
                 if self.extruder_homing_endstop == self.ENDSTOP_EXTRUDER_COLLISION:
@@ -1493,7 +2471,7 @@ class Mmu:
                 msg += "\n- Bowden is unloaded with a short %.1fmm validation move before %.1fmm (%.1f calibration - %.1f buffer - %.1f validation) fast move" % (self.encoder_move_step_size, self.calibrated_bowden_length - self.gate_unload_buffer - self.encoder_move_step_size, self.calibrated_bowden_length, self.gate_unload_buffer, self.encoder_move_step_size)
             else:
                 msg += "\n- Bowden is unloaded with a fast %.1fmm (%.1f calibration - %.1f buffer) move" % (self.calibrated_bowden_length - self.gate_unload_buffer, self.calibrated_bowden_length, self.gate_unload_buffer)
-            msg += "\n- Filament is stored by homing a maximum of %.1fmm to %s and parking %.1fmm in the gate" % (self.gate_homing_max, "ENCODER" if self.gate_homing_endstop == self.ENDSTOP_ENCODER else "GATE SENSOR", self.gate_parking_distance)
+            msg += "\n- Filament is stored by homing a maximum of %.1fmm to %s and parking %.1fmm in the gate" % (self.gate_homing_max, "ENCODER" if self.gate_homing_endstop == self.ENDSTOP_ENCODER else "GATE TOUCH" if self.gate_homing_endstop == self.ENDSTOP_GATE_TOUCH else "GATE SENSOR", self.gate_parking_distance)
 
             if self.toolhead_sync_unload or self.sync_form_tip or self.sync_to_extruder:
                 msg += "\nGear and Extruder steppers are synchronized during: "
@@ -1522,6 +2500,7 @@ class Mmu:
             msg += ", Logfile %d(%s)" % (self.log_file_level, self._log_level_to_human_string(self.log_file_level))
             msg += ", Visual %d(%s)" % (self.log_visual, self._visual_log_level_to_human_string(self.log_visual))
             msg += ", Statistics %d(%s)" % (self.log_statistics, "ON" if self.log_statistics else "OFF")
+            msg += ", Profile %s" % self.log_profile.upper()
 
         if not detail:
             msg += "\nFor details on TTG and endless spool groups use 'MMU_STATUS DETAIL=1'"
@@ -1537,6 +2516,253 @@ class Mmu:
 
         self._log_always(msg)
 
+    # Short health report for MMU_STATUS SUMMARY=1. List of (ok, finding, command to run next or None)
+    def _get_health_summary(self):
+        results = []
+
+        steps = self._get_calibration_steps(self.CALIBRATED_ALL)
+        if steps:
+            results.append((False, "Calibration incomplete", ", ".join(steps)))
+        else:
+            results.append((True, "Calibration complete", None))
+
+        sensors = self._check_all_sensors()
+        disabled = [name for name, state in sensors.items() if state is None]
+        if not sensors:
+            results.append((True, "No filament sensors configured", None))
+        else:
+            results.append((not disabled, "%d of %d configured sensors enabled (%s)" % (len(sensors) - len(disabled), len(sensors), ", ".join(sensors)), None))
+            for name in disabled:
+                results.append((False, "Sensor '%s' is disabled" % name, "SET_FILAMENT_SENSOR SENSOR=%s_sensor ENABLE=1" % name))
+
+        checks = self._check_config()
+        errors = len([c for c in checks if c[0] == "ERROR"])
+        if checks:
+            results.append((False, "Configuration check found %d errors and %d warnings" % (errors, len(checks) - errors), "MMU_CHECK_CONFIG"))
+
+        if self.last_error:
+            error = "Last error: %s [%s]" % (self.last_error['reason'], self.last_error['code'])
+            if self._is_mmu_paused():
+                results.append((False, error, "Fix the issue then RESUME (MMU_UNLOCK restores temperature first)"))
+            else:
+                results.append((False, error, "MMU_RECOVER"))
+        else:
+            results.append((True, "No errors", None))
+
+        for gate, stats in enumerate(self.gate_statistics):
+            attempts = stats['full_loads'] + stats['load_failures']
+            if attempts >= self.SUMMARY_MIN_LOADS:
+                rate = stats['full_loads'] * 100. / attempts
+                if rate < self.SUMMARY_LOAD_SUCCESS:
+                    results.append((False, "Gate #%d load success rate is %.0f%% (%d of %d)" % (gate, rate, stats['full_loads'], attempts),
+                                    ("MMU_PROFILE_GATES GATES=%d" % gate) if self._has_encoder() else ("MMU_MAINTENANCE GATE=%d" % gate)))
+
+        for gate in sorted(self.gate_maintenance):
+            results.append((False, "Gate #%d is in maintenance" % gate, "MMU_MAINTENANCE GATE=%d ENABLE=1" % gate))
+        for gate in range(self.mmu_num_gates):
+            if self.gate_ratio_drift_alerted[gate]:
+                results.append((False, "Gate #%d rotation distance has drifted" % gate, "MMU_CALIBRATE_GATES GATE=%d" % gate))
+            if self.gate_health_alerted[gate]:
+                results.append((False, "Gate #%d friction health has degraded. Clean filament path and re-profile" % gate, "MMU_PROFILE_GATES GATES=%d" % gate))
+        if self.cutter_blade_life > 0 and self.cutter_blade['cuts'] >= self.cutter_blade_life:
+            results.append((False, "Cutter blade is due for replacement (%d cuts)" % self.cutter_blade['cuts'], "MMU_CALIBRATE_CUTTER NEW_BLADE=1"))
+        return results
+
+    def _health_summary_to_human_string(self):
+        results = self._get_health_summary()
+        actions = len([r for r in results if not r[0]])
+        msg = "MMU health summary: %s" % (("%d item%s need%s attention" % (actions, "s" if actions > 1 else "", "" if actions > 1 else "s")) if actions else "all good")
+        for ok, finding, command in results:
+            msg += "\n%s %s" % ("OK  " if ok else "TODO", finding)
+            if command:
+                msg += "\n     -> %s" % command
+        return msg
+
+    cmd_MMU_CHECK_CONFIG_help = "Cross-check interdependent configuration parameters and report suspicious values"
+    def cmd_MMU_CHECK_CONFIG(self, gcmd):
+        self._report_config_check()
+
+    # At startup only errors are detailed to avoid noise, otherwise full report
+    def _report_config_check(self, startup=False):
+        results = self._check_config()
+        errors = [r for r in results if r[0] == "ERROR"]
+        if startup:
+            if errors:
+                msg = "Happy Hare configuration check found %d error(s) that are likely to cause failures:" % len(errors)
+                msg += "".join("\n- %s" % r[1] for r in errors)
+                if len(results) > len(errors):
+                    msg += "\nRun MMU_CHECK_CONFIG for %d more warning(s)" % (len(results) - len(errors))
+                self._log_error(msg)
+            elif results:
+                self._log_info("Happy Hare configuration check found %d warning(s). Run MMU_CHECK_CONFIG for details" % len(results))
+            return
+        if not results:
+            self._log_always("Configuration check passed. No problems found")
+            return
+        msg = "Configuration check found %d error(s) and %d warning(s):" % (len(errors), len(results) - len(errors))
+        for level, message, doc in results:
+            msg += "\n%s: %s\n    See: %s/%s" % (level, message, self.DOC_URL, doc)
+        self._log_always(msg)
+
+    # Return list of (level, message, doc) for interdependent parameters that disagree or values with suspicious magnitudes.
+    # These checks are deliberately conservative - anything here is very likely to cause an obscure failure later
+    def _check_config(self):
+        results = []
+        def error(message, doc="configuration.md"):
+            results.append(("ERROR", message, doc))
+        def warning(message, doc="configuration.md"):
+            results.append(("WARNING", message, doc))
+
+        # Toolhead dimensions
+        if self._has_sensor(self.ENDSTOP_TOOLHEAD) and self.toolhead_sensor_to_nozzle > 0 and self.toolhead_extruder_to_nozzle > 0:
+            if self.toolhead_sensor_to_nozzle >= self.toolhead_extruder_to_nozzle:
+                error("toolhead_sensor_to_nozzle (%.1fmm) must be less than toolhead_extruder_to_nozzle (%.1fmm) because the toolhead sensor is after the extruder gears"
+                    % (self.toolhead_sensor_to_nozzle, self.toolhead_extruder_to_nozzle), "calibration.md")
+            elif self.toolhead_homing_max < self.toolhead_extruder_to_nozzle - self.toolhead_sensor_to_nozzle:
+                warning("toolhead_homing_max (%.1fmm) is shorter than the %.1fmm distance from extruder gears to toolhead sensor"
+                    % (self.toolhead_homing_max, self.toolhead_extruder_to_nozzle - self.toolhead_sensor_to_nozzle))
+        if self.toolhead_extruder_to_nozzle > 150:
+            warning("toolhead_extruder_to_nozzle of %.1fmm is unusually long. Typical values are 40-100mm" % self.toolhead_extruder_to_nozzle, "calibration.md")
+        if self.toolhead_sensor_to_nozzle > 150:
+            warning("toolhead_sensor_to_nozzle of %.1fmm is unusually long. Typical values are 20-80mm" % self.toolhead_sensor_to_nozzle, "calibration.md")
+        if self.toolhead_extruder_to_nozzle > 0 and self.toolhead_unload_safety_margin > self.toolhead_extruder_to_nozzle:
+            warning("toolhead_unload_safety_margin (%.1fmm) is longer than toolhead_extruder_to_nozzle (%.1fmm)" % (self.toolhead_unload_safety_margin, self.toolhead_extruder_to_nozzle))
+
+        # Bowden and gate dimensions
+        bowden = self.calibrated_bowden_length
+        if bowden > 0:
+            if self.gate_unload_buffer >= bowden:
+                error("gate_unload_buffer (%.1fmm) must be less than calibrated bowden length (%.1fmm)" % (self.gate_unload_buffer, bowden))
+            elif self.gate_unload_buffer + self.toolhead_unload_safety_margin >= bowden:
+                warning("gate_unload_buffer plus toolhead_unload_safety_margin (%.1fmm) leaves no fast bowden unload move (bowden is %.1fmm)"
+                    % (self.gate_unload_buffer + self.toolhead_unload_safety_margin, bowden))
+            if self.gate_homing_max > bowden:
+                warning("gate_homing_max (%.1fmm) is longer than calibrated bowden length (%.1fmm)" % (self.gate_homing_max, bowden))
+            if self.extruder_homing_max > bowden:
+                warning("extruder_homing_max (%.1fmm) is longer than calibrated bowden length (%.1fmm). A missed extruder would push a lot of filament"
+                    % (self.extruder_homing_max, bowden))
+            if self.bowden_allowable_load_delta >= bowden / 2.:
+                warning("bowden_allowable_load_delta (%.1fmm) is so large that encoder slippage checks are ineffective" % self.bowden_allowable_load_delta)
+            if bowden > 3000:
+                warning("Calibrated bowden length of %.1fmm is unusually long. Consider recalibrating with MMU_CALIBRATE_BOWDEN" % bowden, "calibration.md")
+        if abs(self.gate_parking_distance) >= self.gate_homing_max:
+            warning("gate_parking_distance (%.1fmm) is not less than gate_homing_max (%.1fmm) so filament may not be found when next loaded"
+                % (self.gate_parking_distance, self.gate_homing_max))
+        if self.extruder_homing_endstop != self.ENDSTOP_EXTRUDER_COLLISION and self.extruder_homing_max < 2 * self.bowden_allowable_load_delta:
+            warning("extruder_homing_max (%.1fmm) is small compared to bowden_allowable_load_delta (%.1fmm)" % (self.extruder_homing_max, self.bowden_allowable_load_delta))
+
+        # Speeds
+        gear_max_velocity, gear_max_accel = self.mmu_toolhead.get_gear_limits()
+        for name in ['gear_from_buffer_speed', 'gear_from_spool_speed', 'gear_short_move_speed', 'gear_homing_speed']:
+            if getattr(self, name) > gear_max_velocity:
+                warning("%s (%.1fmm/s) exceeds gear_max_velocity (%.1fmm/s) and will be limited" % (name, getattr(self, name), gear_max_velocity))
+        for name in ['gear_from_buffer_accel', 'gear_from_spool_accel', 'gear_short_move_accel']:
+            if getattr(self, name) > gear_max_accel:
+                warning("%s (%.1fmm/s^2) exceeds gear_max_accel (%.1fmm/s^2) and will be limited" % (name, getattr(self, name), gear_max_accel))
+        for name in ['extruder_load_speed', 'extruder_unload_speed']:
+            if getattr(self, name) > 50:
+                warning("%s of %.1fmm/s is unusually fast for moves in the extruder" % (name, getattr(self, name)))
+
+        # Macros
+        for name, macro in [('form_tip_macro', self.form_tip_macro), ('pause_macro', self.pause_macro)] + (
+                [('cutter_macro', self.cutter_macro)] if self.cutter_location != self.CUTTER_TOOLHEAD else []) + (
+                [("gate_load_sequence (gate %d)" % g, m) for g, m in sorted(self.gate_load_sequence.items())]) + (
+                [("gate_unload_sequence (gate %d)" % g, m) for g, m in sorted(self.gate_unload_sequence.items())]) + (
+                [("%s gate selection" % self.mmu_vendor, self.vendor_driver.SELECT_MACRO)] if self.vendor_driver.SELECT_MACRO else []):
+            if self.printer.lookup_object("gcode_macro %s" % macro, None) is None and macro not in self.gcode.ready_gcode_handlers:
+                error("%s '%s' is not defined" % (name, macro), "gcode_customization.md")
+
+        return results
+
+    cmd_MMU_LOG_help = "Change console output profile and log levels at runtime"
+    def cmd_MMU_LOG(self, gcmd):
+        profile = gcmd.get('PROFILE', self.log_profile).lower()
+        if profile not in self.LOG_PROFILES:
+            raise gcmd.error("PROFILE is invalid. Options are: %s" % self.LOG_PROFILES)
+        self.log_profile = profile
+        self.log_level = gcmd.get_int('LEVEL', self.log_level, minval=0, maxval=4)
+        self.log_visual = gcmd.get_int('VISUAL', self.log_visual, minval=0, maxval=2)
+        self._log_always("Console profile: %s, Log level: %d(%s), Visual: %d(%s)" % (self.log_profile, self.log_level, self._log_level_to_human_string(self.log_level), self.log_visual, self._visual_log_level_to_human_string(self.log_visual)))
+
+
+    cmd_MMU_TRACE_help = "Dump internal state machine and recent state transitions"
+    def cmd_MMU_TRACE(self, gcmd):
+        state = gcmd.get_int('STATE', 1, minval=0, maxval=1)
+        reset = gcmd.get_int('RESET', 0, minval=0, maxval=1)
+        if reset:
+            self.state_transitions = []
+            self._log_always("State transition history cleared")
+            return
+        if not state: return
+        msg = "MMU State: print_state=%s, action=%s, filament_pos=%s (%d)" % (self.print_state, self._get_action_string(), self._filament_pos_to_string(self.filament_pos), self.filament_pos)
+        msg += "\nTool: %s, Gate: %s, Homed: %s, Synced: %s, Resume to: %s" % (self._selected_tool_string(), self._selected_gate_string(), self.is_homed, self.mmu_toolhead.is_gear_synced_to_extruder(), self.resume_to_state)
+        if self.last_error:
+            msg += "\nLast error: %s [%s] (in print_state %s)" % (self.last_error['reason'], self.last_error['code'], self.last_error['print_state'])
+        msg += "\nLast %d transitions:" % len(self.state_transitions)
+        for t in self.state_transitions:
+            msg += "\n%s.%03d %s: %s -> %s (action: %s%s)" % (time.strftime("%H:%M:%S", time.localtime(t['time'])), int((t['time'] % 1) * 1000), t['kind'], t['old'], t['new'], t['action'], (", cause: %s" % t['cause']) if t['cause'] else "")
+        self._log_always(msg)
+
+    cmd_MMU_TELEMETRY_help = "List recorded telemetry jobs or summarize one"
+    def cmd_MMU_TELEMETRY(self, gcmd):
+        if not self.telemetry:
+            self._log_always("Telemetry is not enabled (see 'enable_telemetry')")
+            return
+        jobs = self.telemetry.list_jobs()
+        job = gcmd.get_int('JOB', None, minval=1, maxval=max(len(jobs), 1))
+        if job is None:
+            msg = "Telemetry%s in %s:" % (" (recording)" if self.telemetry.is_recording() else "", self.telemetry.dirname)
+            for i, f in enumerate(reversed(jobs)):
+                msg += "\n%d: %s" % (i + 1, f)
+            if not jobs:
+                msg += "\nNo jobs recorded yet"
+            self._log_always(msg)
+            return
+        if not jobs:
+            raise gcmd.error("No telemetry jobs recorded yet")
+
+        filename = jobs[-job]
+        try:
+            summary = self.telemetry.summarize(filename)
+        except (IOError, OSError) as e:
+            raise gcmd.error("Unable to read telemetry '%s': %s" % (filename, str(e)))
+        duration = (summary['end'] - summary['start']) if summary['start'] is not None else 0
+        msg = "Telemetry summary for %s:" % filename
+        msg += "\n%d records over %s, ended: %s" % (summary['records'], self._seconds_to_human_string(duration), summary['state'] or "still recording/unknown")
+        msg += "\nRecords: %s" % ", ".join("%s=%d" % (k, v) for k, v in sorted(summary['kinds'].items()))
+        if summary['sensor_edges']:
+            msg += "\nSensor edges: %s" % ", ".join("%s=%d" % (k, v) for k, v in sorted(summary['sensor_edges'].items()))
+        msg += "\nHoming failures: %d" % len(summary['homing_failures'])
+        for rec in summary['homing_failures'][-5:]:
+            msg += "\n  t=%.2f gate %d endstop '%s' after %.1fmm (of %.1fmm)" % (rec['t'], rec.get('gate', -1), rec.get('endstop'), rec.get('actual', 0), rec.get('dist', 0))
+        msg += "\nErrors: %d" % len(summary['errors'])
+        for rec in summary['errors'][-5:]:
+            msg += "\n  t=%.2f %s: %s" % (rec['t'], rec.get('new'), rec.get('cause'))
+        if summary['max_delta']:
+            rec = summary['max_delta']
+            msg += "\nLargest move discrepancy: %.1fmm on gate %d (moved %.1fmm, measured %.1fmm) at t=%.2f" % (rec.get('delta', 0), rec.get('gate', -1), rec.get('dist', 0), rec.get('measured', 0), rec['t'])
+        self._log_always(msg)
+
+    cmd_MMU_JOURNAL_help = "List saved toolchange journals or save the journal of the last toolchange"
+    def cmd_MMU_JOURNAL(self, gcmd):
+        if not self.journal:
+            self._log_always("Journaling is not enabled (see 'enable_journal')")
+            return
+        if gcmd.get_int('SAVE', 0, minval=0, maxval=1):
+            filename = self.journal.save()
+            if filename is None:
+                raise gcmd.error("No unsaved toolchange journal")
+            self._log_always("Journal of last toolchange saved to %s" % filename)
+            return
+        files = self.journal.list_files()
+        msg = "Toolchange journals in %s:" % self.journal.dirname
+        for f in reversed(files):
+            msg += "\n%s" % f
+        if not files:
+            msg += "\nNo journals saved yet"
+        msg += "\nReplay offline with: python3 -m test.extras.replay_journal <file>"
+        self._log_always(msg)
+
 
 #############################
 # SERVO AND MOTOR FUNCTIONS #
@@ -1547,27 +2773,40 @@ class Mmu:
         self.servo_angle = self.SERVO_UNKNOWN_STATE
 
     def _servo_set_angle(self, angle):
+        if not self.servo: return
         self.servo.set_value(angle=angle, duration=self.servo_duration)
         self.servo_angle = angle
         self.servo_state = self.SERVO_UNKNOWN_STATE
 
     def _servo_down(self, buzz_gear=True):
         if self.gate_selected == self.TOOL_GATE_BYPASS: return
+        if self.vendor_driver.grip(self): return
         if self.servo_state == self.SERVO_DOWN_STATE: return
+        self._check_safety_watchdog('servo')
         self._log_debug("Setting servo to down (filament drive) position at angle: %d" % self.servo_down_angle)
         self._movequeues_wait_moves()
         self.servo.set_value(angle=self.servo_down_angle, duration=None if self.servo_active_down else self.servo_duration)
         if self.servo_angle != self.servo_down_angle and buzz_gear:
+            # Grip can only be confirmed with encoder when filament is free to move in the bowden
+            confirm = self.safety_watchdog and self._can_use_encoder() and self.FILAMENT_POS_START_BOWDEN <= self.filament_pos <= self.FILAMENT_POS_END_BOWDEN
+            initial_encoder_position = self._get_encoder_distance(dwell=None)
             oscillations = 3
             self.gear_buzz_accel = 1000
             for i in range(oscillations):
                 self._trace_filament_move(None, 0.8, speed=25, accel=self.gear_buzz_accel, encoder_dwell=None)
                 self._trace_filament_move(None, -0.8, speed=25, accel=self.gear_buzz_accel, encoder_dwell=None)
             self._movequeues_dwell(max(self.servo_dwell - self.servo_duration, 0))
+            if confirm:
+                moved = self._get_encoder_distance(dwell=True) - initial_encoder_position
+                if moved < 1.:
+                    self._safety_watchdog_failure('servo', "encoder measured %.1fmm when gripping filament" % moved)
+                else:
+                    self.safety_failures['servo'] = 0
         self.servo_angle = self.servo_down_angle
         self.servo_state = self.SERVO_DOWN_STATE
 
     def _servo_move(self): # Position servo for selector movement
+        if self.vendor_driver.release(self): return
         if self.servo_state == self.SERVO_MOVE_STATE: return
         self._log_debug("Setting servo to move (filament hold) position at angle: %d" % self.servo_move_angle)
         if self.servo_angle != self.servo_move_angle:
@@ -1578,6 +2817,7 @@ class Mmu:
             self.servo_state = self.SERVO_MOVE_STATE
 
     def _servo_up(self, measure=False):
+        if self.vendor_driver.release(self): return 0.
         if self.servo_state == self.SERVO_UP_STATE: return 0.
         self._log_debug("Setting servo to up (filament released) position at angle: %d" % self.servo_up_angle)
         delta = 0.
@@ -1603,6 +2843,71 @@ class Mmu:
         else:
             self._servo_up()
 
+    # Safety watchdog. A selector command not confirmed by its endstop (or touch) or a servo grip not confirmed by
+    # encoder movement is a failure. A reactor timer switches the driver off if the command is still running after
+    # 'safety_watchdog_timeout'. After 'safety_watchdog_failures' consecutive failures the driver is switched off and
+    # the component refused until 'MMU_SAFETY RESET=1' rather than retried indefinitely
+    @contextlib.contextmanager
+    def _wrap_safety_watchdog(self, component):
+        self._check_safety_watchdog(component)
+        if not self.safety_watchdog or component in self.safety_active:
+            yield self
+            return
+        self.safety_active.add(component)
+        self.safety_timed_out.discard(component)
+        timer = self.reactor.register_timer(lambda eventtime: self._handle_safety_watchdog_timeout(component, eventtime), self.reactor.monotonic() + self.safety_watchdog_timeout)
+        try:
+            yield self
+        except MmuError as ee:
+            if ee.code == self.ERR_SELECTOR and component not in self.safety_timed_out:
+                self._safety_watchdog_failure(component, str(ee))
+            if component not in self.safety_timed_out:
+                raise
+        finally:
+            self.reactor.unregister_timer(timer)
+            self.safety_active.discard(component)
+        if component in self.safety_timed_out:
+            reason = "not confirmed within %.1fs" % self.safety_watchdog_timeout
+            if component == 'selector':
+                self.is_homed = False
+                self._set_gate_selected(self.TOOL_GATE_UNKNOWN)
+                self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
+            self._safety_watchdog_failure(component, reason)
+            raise MmuError("MMU %s %s. Driver was disabled" % (component, reason), code=self.ERR_SAFETY_WATCHDOG)
+        self.safety_failures[component] = 0
+
+    # Timer fires whilst the watched command is still in progress. Only the driver is switched off here, the failure is
+    # counted when the command unwinds back in command context. The driver is disabled now rather than after the queued move
+    def _handle_safety_watchdog_timeout(self, component, eventtime):
+        self.safety_timed_out.add(component)
+        self._log_error("Safety watchdog: %s not confirmed within %.1fs. Disabling driver" % (component, self.safety_watchdog_timeout))
+        if component == 'selector':
+            se = self.printer.lookup_object('stepper_enable').lookup_enable(self.selector_stepper.get_name())
+            se.motor_disable(self.estimated_print_time(eventtime))
+        elif self.servo:
+            self.servo.set_value(width=0.)
+        return self.reactor.NEVER
+
+    def _check_safety_watchdog(self, component):
+        if component in self.safety_tripped:
+            raise MmuError("MMU %s is disabled by safety watchdog (%s). Check hardware and then run MMU_SAFETY RESET=1" % (component, self.safety_tripped[component]), code=self.ERR_SAFETY_WATCHDOG)
+
+    def _safety_watchdog_failure(self, component, reason):
+        self.safety_failures[component] += 1
+        self._log_debug("Safety watchdog: %s failure %d of %d: %s" % (component, self.safety_failures[component], self.safety_watchdog_failures, reason))
+        if self.safety_failures[component] < self.safety_watchdog_failures: return
+        self.safety_failures[component] = 0
+        self.safety_tripped[component] = reason
+        self.safety_trips[component] += 1
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SAFETY_TRIPS, self.safety_trips))
+        if component == 'selector':
+            self._motors_off(motor="selector")
+        elif self.servo:
+            self.servo.set_value(width=0.) # Stop servo signal
+            self._servo_reset_state()
+        self._publish_event("safety_watchdog", component=component, reason=reason, trips=self.safety_trips[component])
+        raise MmuError("Safety watchdog tripped on %s (%s). Driver has been disabled" % (component, reason), code=self.ERR_SAFETY_WATCHDOG)
+
     def _motors_off(self, motor="all"):
         stepper_enable = self.printer.lookup_object('stepper_enable')
         if motor in ["all", "gear"]:
@@ -1629,7 +2934,10 @@ class Mmu:
             self._servo_move()
         elif pos == "down":
             if self._check_in_bypass(): return
-            self._servo_down()
+            try:
+                self._servo_down()
+            except MmuError as ee:
+                self._log_error(str(ee))
         elif pos == "":
             if self._check_in_bypass(): return
             angle = gcmd.get_float('ANGLE', None)
@@ -1660,7 +2968,7 @@ class Mmu:
             pos = self.mmu_toolhead.get_position()[0]
             self._trace_selector_move(None, pos + 5, wait=False)
             self._trace_selector_move(None, pos - 5, wait=False)
-        elif motor == "servo":
+        elif motor == "servo" and self.servo:
             self._movequeues_wait_moves()
             old_state = self.servo_state
             small=min(self.servo_down_angle, self.servo_up_angle)
@@ -1700,6 +3008,8 @@ class Mmu:
         self.variables[self.VARS_MMU_CALIB_BOWDEN_LENGTH] = reference
         self.calibrated_bowden_length = reference
         self.calibration_status |= self.CALIBRATED_BOWDEN
+        if self.virtual_encoder:
+            self.virtual_encoder.reset()
 
     def _calibrate_encoder(self, length, repeats, speed, min_speed, max_speed, accel, save=True):
         try:
@@ -1762,7 +3072,7 @@ class Mmu:
 
         except MmuError as ee:
             # Add some more context to the error and re-raise
-            raise MmuError("Calibration of encoder failed. Aborting, because:\n%s" % str(ee))
+            raise MmuError("Calibration of encoder failed. Aborting, because:\n%s" % str(ee), code=self.ERR_CALIBRATION)
         finally:
             if mean == 0:
                 self._set_filament_pos_state(self.FILAMENT_POS_UNKNOWN)
@@ -1816,6 +3126,7 @@ class Mmu:
                     self._set_calibrated_bowden_length(average_reference)
                     self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%.1f" % (self.VARS_MMU_CALIB_BOWDEN_LENGTH, average_reference))
                     self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s%d VALUE=1.0" % (self.VARS_MMU_CALIB_PREFIX, 0))
+                    self._reset_gate_ratio_drift(0, 1.)
                     self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%.1f" % (self.VARS_MMU_CALIB_CLOG_LENGTH, detection_length))
                     self.encoder_sensor.set_clog_detection_length(detection_length)
                     self._log_always("Bowden calibration and clog detection length have been saved")
@@ -1823,7 +3134,7 @@ class Mmu:
                 self._log_always("All %d attempts at homing failed. MMU needs some adjustments!" % repeats)
         except MmuError as ee:
             # Add some more context to the error and re-raise
-            raise MmuError("Calibration of bowden length (on Gate #0) failed. Aborting, because:\n%s" % str(ee))
+            raise MmuError("Calibration of bowden length (on Gate #0) failed. Aborting, because:\n%s" % str(ee), code=self.ERR_CALIBRATION)
         finally:
             self._servo_auto()
 
@@ -1835,9 +3146,15 @@ class Mmu:
             self._load_gate(allow_retry=False)
             self._log_always("%s gate %d over %.1fmm..." % ("Calibrating" if (gate > 0 and save) else "Validating calibration of", gate, length))
 
+            sg_values = []
             for x in range(repeats):
                 self._initialize_filament_position(dwell=True)    # Encoder 0000
-                _,_,measured,delta = self._trace_filament_move("Calibration load movement", length, encoder_dwell=True)
+                if self.bowden_stall_detection:
+                    with self._wrap_gear_stall_sampling():
+                        _,_,measured,delta = self._trace_filament_move("Calibration load movement", length, encoder_dwell=True, wait=True)
+                    sg_values.extend(self.gear_stall_samples[2:])
+                else:
+                    _,_,measured,delta = self._trace_filament_move("Calibration load movement", length, encoder_dwell=True)
                 pos_values.append(measured)
                 self._log_always("+ measured =  %.1fmm (counts = %d)" % ((length - delta), self._get_encoder_counts(dwell=None)))
                 self._initialize_filament_position(dwell=True)    # Encoder 0000
@@ -1860,38 +3177,107 @@ class Mmu:
                     if save:
                         self.variables["%s%d" % (self.VARS_MMU_CALIB_PREFIX, gate)] = ratio
                         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s%d VALUE=%.6f" % (self.VARS_MMU_CALIB_PREFIX, gate, ratio))
+                        self._reset_gate_ratio_drift(gate, ratio)
                         self._log_always("Calibration for gate #%d has been saved" % gate)
                         self.calibration_status |= self.CALIBRATED_GATES
                 else:
                     self._log_always("Calibration ratio ignored because it is not considered valid (0.8 < ratio < 1.2)")
+            if sg_values and save:
+                baseline = self._sample_stats(sg_values)['mean']
+                self.variables["%s%d" % (self.VARS_MMU_CALIB_STALL_PREFIX, gate)] = baseline
+                self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s%d VALUE=%.1f" % (self.VARS_MMU_CALIB_STALL_PREFIX, gate, baseline))
+                self._log_always("StallGuard baseline for gate #%d (SG_RESULT mean of %d samples): %.1f" % (gate, len(sg_values), baseline))
             self._unload_gate()
             self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
         except MmuError as ee:
             # Add some more context to the error and re-raise
-            raise MmuError("Calibration for gate #%d failed. Aborting, because: %s" % (gate, str(ee)))
+            raise MmuError("Calibration for gate #%d failed. Aborting, because: %s" % (gate, str(ee)), code=self.ERR_CALIBRATION)
         finally:
             self._servo_auto()
 
-    def _get_max_selector_movement(self, gate=-1):
-        n = gate if gate >= 0 else self.mmu_num_gates - 1
+    # Measure the effective gear rotation distance when synced to extruder from the distance between the
+    # extruder entry sensor and toolhead sensor. The extruder (which is assumed to be well calibrated)
+    # establishes the true sensor gap and then the gear alone homes over the same gap with the extruder
+    # stepper free-wheeling. The resulting ratio is relative to gate #0 reference
+    def _calibrate_sync_ratio(self, gate, repeats, speed, save=True):
+        try:
+            ext_transits, gear_transits = [], []
+            self._select_tool(gate)
+            self._set_gate_ratio(self._get_gate_ratio(gate))
+            self._log_always("Calibrating synced gear ratio for gate %d at %.1fmm/s..." % (gate, speed))
+            self._load_gate(allow_retry=False)
+            self._load_bowden(self.calibrated_bowden_length)
+            self._set_filament_direction(self.DIRECTION_LOAD)
+            self._ensure_safe_extruder_temperature(wait=True)
+
+            self._servo_down()
+            actual,homed,_,_ = self._trace_filament_move("Homing to extruder sensor", self.extruder_homing_max, motor="gear", homing_move=1, endstop_name=self.ENDSTOP_EXTRUDER)
+            if not homed:
+                raise MmuError("Failed to reach extruder sensor after moving %.1fmm" % self.extruder_homing_max, code=self.ERR_CALIBRATION)
+            self._set_filament_pos_state(self.FILAMENT_POS_HOMED_EXTRUDER)
 
-        if self.mmu_vendor.lower() == self.VENDOR_ERCF.lower():
-            # ERCF Designs
-            if self.mmu_version >= 2.0 or "t" in self.mmu_version_string:
-                max_movement = self.cad_gate0_pos + (n * self.cad_gate_width)
+            stepper_enable = self.printer.lookup_object('stepper_enable')
+            ext_enable = stepper_enable.lookup_enable(self.mmu_extruder_stepper.stepper.get_name())
+            for i in range(repeats):
+                # Reference pass: extruder alone establishes the true sensor gap
+                self._servo_up()
+                ext_dist,homed,_,_ = self._trace_filament_move("Extruder transit to toolhead sensor", self.toolhead_homing_max, speed=speed, motor="extruder", homing_move=1, endstop_name=self.ENDSTOP_TOOLHEAD)
+                if not homed:
+                    raise MmuError("Failed to reach toolhead sensor after moving %.1fmm" % self.toolhead_homing_max, code=self.ERR_CALIBRATION)
+                self._trace_filament_move("Retracting to extruder sensor", -self.toolhead_homing_max, speed=speed, motor="extruder", homing_move=-1, endstop_name=self.ENDSTOP_EXTRUDER)
+
+                # Measured pass: gear alone with extruder stepper free-wheeling
+                self._servo_down()
+                self._movequeues_wait_moves()
+                ext_enable.motor_disable(self.toolhead.get_last_move_time())
+                gear_dist,homed,_,_ = self._trace_filament_move("Gear transit to toolhead sensor", self.toolhead_homing_max, speed=speed, motor="gear", homing_move=1, endstop_name=self.ENDSTOP_TOOLHEAD)
+                if not homed:
+                    raise MmuError("Gear failed to push filament to toolhead sensor after moving %.1fmm" % self.toolhead_homing_max, code=self.ERR_CALIBRATION)
+                self._trace_filament_move("Retracting to extruder sensor", -self.toolhead_homing_max, speed=speed, motor="gear+extruder", homing_move=-1, endstop_name=self.ENDSTOP_EXTRUDER)
+
+                ext_transits.append(ext_dist)
+                gear_transits.append(gear_dist)
+                self._log_always("Pass #%d: extruder transit %.1fmm, gear transit %.1fmm" % (i+1, ext_dist, gear_dist))
+
+            mean_ext = self._sample_stats(ext_transits)['mean']
+            mean_gear = self._sample_stats(gear_transits)['mean']
+            if mean_gear <= 0.:
+                raise MmuError("Invalid gear transit measurement", code=self.ERR_CALIBRATION)
+            ratio = self._get_gate_ratio(gate) * mean_ext / mean_gear
+
+            self._log_always("Sensor gap (extruder): mean=%(mean).1f stdev=%(stdev).2f min=%(min).1f max=%(max).1f range=%(range).1f" % self._sample_stats(ext_transits))
+            self._log_always("Sensor gap (gear): mean=%(mean).1f stdev=%(stdev).2f min=%(min).1f max=%(max).1f range=%(range).1f" % self._sample_stats(gear_transits))
+            self._log_always("Synced ratio for gate %d is %.6f (rotation_distance: %.6f vs Gate #0: %.6f)" % (gate, ratio, ratio * self.ref_gear_rotation_distance, self.ref_gear_rotation_distance))
+            if ratio > 0.8 and ratio < 1.2:
+                if save:
+                    self.variables["%s%d" % (self.VARS_MMU_CALIB_SYNC_PREFIX, gate)] = ratio
+                    self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s%d VALUE=%.6f" % (self.VARS_MMU_CALIB_SYNC_PREFIX, gate, ratio))
+                    self._log_always("Synced calibration for gate %d has been saved" % gate)
             else:
-                max_movement = self.cad_gate0_pos + (n * self.cad_gate_width) + (n//3) * self.cad_block_width
+                self._log_always("Calibration ratio ignored because it is not considered valid (0.8 < ratio < 1.2)")
 
-        else:
-            # Everything else
-            max_movement = self.cad_gate0_pos + (n * self.cad_gate_width)
+            self._set_filament_pos_state(self.FILAMENT_POS_END_BOWDEN)
+            self._unload_bowden(self.calibrated_bowden_length)
+            self._unload_gate()
+            self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
+        except MmuError as ee:
+            # Add some more context to the error and re-raise
+            raise MmuError("Synced calibration for gate #%d failed. Aborting, because: %s" % (gate, str(ee)), code=self.ERR_CALIBRATION)
+        finally:
+            self._servo_auto()
 
+    def _get_max_selector_movement(self, gate=-1):
+        n = gate if gate >= 0 else self.mmu_num_gates - 1
+        if self.cad_gate_positions:
+            max_movement = self.cad_gate0_pos + (self.cad_gate_positions[gate] if gate >= 0 else max(self.cad_gate_positions + self.cad_bypass_positions))
+        else:
+            max_movement = self.vendor_driver.get_max_selector_movement(self, n)
         max_movement += self.cad_last_gate_offset if gate in [self.TOOL_GATE_UNKNOWN] else 0.
         max_movement += self.cad_selector_tolerance
         return max_movement
 
-    def _calibrate_selector(self, gate, save=True):
-        gate_str = lambda gate : ("gate #%d" % gate) if gate >= 0 else "bypass"
+    def _calibrate_selector(self, gate, save=True, bypass=0):
+        gate_str = lambda gate : ("gate #%d" % gate) if gate >= 0 else ("bypass %d" % (bypass + 1)) if bypass else "bypass"
         try:
             self._initialize_state()
             self.calibrating = True
@@ -1918,15 +3304,124 @@ class Mmu:
                     self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SELECTOR_OFFSETS, self.selector_offsets))
                     self.calibration_status |= self.CALIBRATED_SELECTOR
                 else:
-                    self.bypass_offset = round(traveled, 1)
-                    self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SELECTOR_BYPASS, self.bypass_offset))
+                    if bypass < len(self.bypass_offsets):
+                        self.bypass_offsets[bypass] = round(traveled, 1)
+                    else:
+                        self.bypass_offsets.append(round(traveled, 1))
+                    self._save_bypass_offsets()
                 self._log_always("Selector offset (%.1fmm) for %s has been saved" % (traveled, gate_str(gate)))
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
         finally:
             self.calibrating = False
             self._motors_off()
 
+    # Find end of selector travel and then measure the full length back to home. Returns None on failure
+    def _measure_selector_travel(self):
+        max_movement = self._get_max_selector_movement()
+        self._log_always("Searching for end of selector... (up to %.1fmm)" % max_movement)
+        self._trace_selector_move("Moving off endstop", self.cad_gate0_pos)
+        if self.selector_touch:
+            halt_pos, found_home = self._trace_selector_move("Detecting end of selector movement",
+                    max_movement, speed=self.selector_touch_speed, homing_move=1, endstop_name=self.ENDSTOP_SELECTOR_TOUCH)
+        else:
+            # This might not sound good!
+            self._trace_selector_move("Forceably detecting end of selector movement", max_movement, speed=self.selector_homing_speed)
+            found_home = True
+        if not found_home:
+            self._log_always("Didn't detect the end of the selector")
+            return None
+
+        self._log_always("Measuring the full selector length...")
+        traveled, found_home = self._measure_to_home()
+        if not found_home:
+            self._log_always("Selector didn't find home position after full length move")
+            return None
+        self._log_always("Maximum selector movement is %.1fmm" % traveled)
+        return traveled
+
+    # Discover actual gate positions by sweeping the selector and probing for filament engagement with a small gear
+    # move at each candidate position rather than interpolating from CAD dimensions. Requires filament to be parked
+    # in every gate and an encoder or gate sensor to detect that the filament moved
+    def _calibrate_selector_discover(self, step, probe_length, save=True):
+        if not self._has_encoder() and not self._has_sensor(self.ENDSTOP_GATE):
+            self._log_error("Gate discovery requires an encoder or gate sensor to detect filament engagement")
+            return
+        try:
+            self._log_always("Discovering gate positions. Filament must be parked in every gate...")
+            self._initialize_state()
+            self.calibrating = True
+            self._servo_move()
+
+            # Step 1 - establish full selector travel
+            _, found_home = self._measure_to_home()
+            if not found_home:
+                self._log_always("Selector didn't find home position")
+                return
+            traveled = self._measure_selector_travel()
+            if traveled is None:
+                return
+
+            # Step 2 - sweep selector probing for filament at each candidate position
+            engaged = []
+            pos = step
+            while pos < traveled - step:
+                self._trace_selector_move("Moving to probe position", pos)
+                if self._probe_gate_engagement(probe_length):
+                    engaged.append(pos)
+                pos += step
+            self._servo_move()
+
+            # Step 3 - each group of adjacent engaged positions is a gate. Gate position is the center of the group
+            groups = []
+            for p in engaged:
+                if groups and p - groups[-1][-1] <= step * 1.5:
+                    groups[-1].append(p)
+                else:
+                    groups.append([p])
+            selector_offsets = [round(sum(g) / len(g), 1) for g in groups]
+            self._log_debug("Filament engaged at: %s" % engaged)
+
+            if len(selector_offsets) != self.mmu_num_gates:
+                self._log_error("You configued your MMU for %d gates but I discovered %d at %s! Check filament is parked in every gate or reduce STEP" % (self.mmu_num_gates, len(selector_offsets), selector_offsets))
+                return
+
+            # Bypass positions can't be probed so are placed from CAD table (scaled to discovered gates) or end of travel
+            if self.cad_bypass_positions:
+                scale = (selector_offsets[-1] - selector_offsets[0]) / self.cad_gate_positions[-1] if len(self.cad_gate_positions) > 1 else 1.
+                bypass_offsets = [round(selector_offsets[0] + p * scale, 1) for p in self.cad_bypass_positions]
+            else:
+                bypass_offsets = [round(traveled - self.cad_bypass_offset, 1)] if self.cad_bypass_offset > 0 else []
+
+            self._log_always("Offsets %s and bypass %s" % (selector_offsets, bypass_offsets or "none"))
+            if save:
+                self.selector_offsets = selector_offsets
+                self.bypass_offsets = bypass_offsets
+                self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SELECTOR_OFFSETS, self.selector_offsets))
+                self._save_bypass_offsets()
+                self._log_always("Selector calibration has been saved")
+                self.calibration_status |= self.CALIBRATED_SELECTOR
+
+            self._home(0, force_unload=0)
+        except MmuError as ee:
+            self._mmu_pause(str(ee), code=ee.code)
+            self._motors_off()
+        finally:
+            self.calibrating = False
+
+    # Small gear move at current selector position to see if filament is gripped. Filament is returned if it moved
+    def _probe_gate_engagement(self, length):
+        self._servo_down(buzz_gear=False)
+        if self._has_encoder():
+            actual,_,measured,_ = self._trace_filament_move("Probing for filament", length, encoder_dwell=True)
+            engaged = measured > self.encoder_min
+        else:
+            actual,engaged,_,_ = self._trace_filament_move("Probing for filament", length, homing_move=1, endstop_name=self.ENDSTOP_GATE)
+        if engaged:
+            self._trace_filament_move("Returning probed filament", -actual)
+        self._servo_move()
+        return engaged
+
     def _calibrate_selector_auto(self, v1_bypass_block=-1, save=True):
         # Strategy is to find the two end gates, infer and set number of gates and distribute selector positions
         # Assumption: the user has manually positioned the selector aligned with gate #0 before calling
@@ -1944,28 +3439,10 @@ class Mmu:
                 return
             gate0_pos = traveled
 
-            # Step 2 - end of selector
-            max_movement = self._get_max_selector_movement()
-            self._log_always("Searching for end of selector... (up to %.1fmm)" % max_movement)
-            self._trace_selector_move("Moving off endstop", self.cad_gate0_pos)
-            if self.selector_touch:
-                halt_pos, found_home = self._trace_selector_move("Detecting end of selector movement",
-			max_movement, speed=self.selector_touch_speed, homing_move=1, endstop_name=self.ENDSTOP_SELECTOR_TOUCH)
-            else:
-                # This might not sound good!
-                self._trace_selector_move("Forceably detecting end of selector movement", max_movement, speed=self.selector_homing_speed)
-                found_home = True
-            if not found_home:
-                self._log_always("Didn't detect the end of the selector")
-                return
-
-            # Step 3 - bypass (v2) and last gate position
-            self._log_always("Measuring the full selector length...")
-            traveled, found_home = self._measure_to_home()
-            if not found_home:
-                self._log_always("Selector didn't find home position after full length move")
+            # Step 2 & 3 - end of selector and full selector length
+            traveled = self._measure_selector_travel()
+            if traveled is None:
                 return
-            self._log_always("Maximum selector movement is %.1fmm" % traveled)
             bypass_pos = traveled - self.cad_bypass_offset
             last_gate_pos = traveled - self.cad_last_gate_offset
 
@@ -1973,7 +3450,15 @@ class Mmu:
             length = last_gate_pos - gate0_pos
             self._log_debug("Results: gate0_pos=%.1f, last_gate_pos=%.1f, length=%.1f" % (gate0_pos, last_gate_pos, length))
             selector_offsets = []
-            if self.mmu_version >= 2.0:
+            if self.cad_gate_positions:
+                # Explicit position table scaled to the measured distance between first and last gate
+                num_gates = len(self.cad_gate_positions)
+                scale = length / self.cad_gate_positions[-1] if num_gates > 1 else 1.
+                self._log_debug("CAD position table scale: %.4f" % scale)
+                selector_offsets = [round(gate0_pos + p * scale, 1) for p in self.cad_gate_positions]
+                bypass_offset = [round(gate0_pos + p * scale, 1) for p in self.cad_bypass_positions]
+
+            elif self.mmu_version >= 2.0:
                 num_gates = int(round(length / self.cad_gate_width)) + 1
                 adj_gate_width = length / (num_gates - 1)
                 self._log_debug("Adjusted gate width: %.1f" % adj_gate_width)
@@ -2001,22 +3486,33 @@ class Mmu:
                 self._log_error("You configued your MMU for %d gates but I counted %d! Please update `mmu_num_gates`" % (self.mmu_num_gates, num_gates))
                 return
 
-            self._log_always("Offsets %s and bypass %.1f" % (selector_offsets, bypass_offset))
+            bypass_offsets = bypass_offset if isinstance(bypass_offset, list) else [round(bypass_offset, 1)] if bypass_offset > 0 else []
+            self._log_always("Offsets %s and bypass %s" % (selector_offsets, bypass_offsets or "none"))
             if save:
                 self.selector_offsets = selector_offsets
-                self.bypass_offset = bypass_offset
+                self.bypass_offsets = bypass_offsets
                 self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SELECTOR_OFFSETS, self.selector_offsets))
-                self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SELECTOR_BYPASS, self.bypass_offset))
+                self._save_bypass_offsets()
                 self._log_always("Selector calibration has been saved")
                 self.calibration_status |= self.CALIBRATED_SELECTOR
 
             self._home(0, force_unload=0)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
             self._motors_off()
         finally:
             self.calibrating = False
 
+    # Bypass positions are persisted as single value (one bypass) or list. 'bypass_offset' is the selected bypass
+    def _save_bypass_offsets(self):
+        value = self.bypass_offsets[0] if len(self.bypass_offsets) == 1 else self.bypass_offsets or 0.
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_SELECTOR_BYPASS, value))
+        self._set_bypass_selected(self.bypass_selected)
+
+    def _set_bypass_selected(self, index):
+        self.bypass_selected = index if 0 <= index < len(self.bypass_offsets) else 0
+        self.bypass_offset = self.bypass_offsets[self.bypass_selected] if self.bypass_offsets else 0.
+
     def _sample_stats(self, values):
         mean = stdev = vmin = vmax = 0.
         if values:
@@ -2033,6 +3529,7 @@ class Mmu:
     cmd_MMU_CALIBRATE_GEAR_help = "Calibration routine for gear stepper rotational distance"
     def cmd_MMU_CALIBRATE_GEAR(self, gcmd):
         if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
         if self._check_has_encoder(): return
         length = gcmd.get_float('LENGTH', 100., above=50.)
         measured = gcmd.get_float('MEASURED', above=0.)
@@ -2045,6 +3542,7 @@ class Mmu:
             self.gear_stepper.set_rotation_distance(new_rotation_distance)
             self.ref_gear_rotation_distance = new_rotation_distance
             self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%.6f" % (self.VARS_MMU_GEAR_ROTATION_DISTANCE, new_rotation_distance))
+            self._reset_gate_ratio_drift(0, 1.)
             self._log_always("Gear calibration has been saved")
             self.calibration_status |= self.CALIBRATED_GEAR
 
@@ -2053,6 +3551,7 @@ class Mmu:
     cmd_MMU_CALIBRATE_ENCODER_help = "Calibration routine for the MMU encoder"
     def cmd_MMU_CALIBRATE_ENCODER(self, gcmd):
         if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
         if self._check_has_encoder(): return
         if self._check_in_bypass(): return
         if self._check_is_calibrated(self.CALIBRATED_GEAR): return
@@ -2070,22 +3569,27 @@ class Mmu:
             with self._require_encoder():
                 self._calibrate_encoder(length, repeats, speed, min_speed, max_speed, accel, save)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
         finally:
             self.calibrating = False
 
     cmd_MMU_CALIBRATE_SELECTOR_help = "Calibration of the selector positions or postion of specified gate"
     def cmd_MMU_CALIBRATE_SELECTOR(self, gcmd):
         if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
 
         save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
         gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
-        if gate == -1:
-            if gcmd.get_int('BYPASS', -1, minval=0, maxval=1) == 1:
-                gate = self.TOOL_GATE_BYPASS
+        bypass = gcmd.get_int('BYPASS', -1, minval=0, maxval=len(self.bypass_offsets) + 1) # Next one adds bypass position
+        if gate == -1 and bypass >= 1:
+            gate = self.TOOL_GATE_BYPASS
 
         if gate != -1:
-            self._calibrate_selector(gate, save=save)
+            self._calibrate_selector(gate, save=save, bypass=max(bypass, 1) - 1)
+        elif gcmd.get_int('DISCOVER', 0, minval=0, maxval=1):
+            step = gcmd.get_float('STEP', self.cad_gate_width / 4., above=0.5)
+            probe_length = gcmd.get_float('PROBE_LENGTH', self.gate_parking_distance + 10., above=0.)
+            self._calibrate_selector_discover(step, probe_length, save=save)
         else:
             v1_bypass_block = gcmd.get_int('BYPASS_BLOCK', -1, minval=1, maxval=3)
             self._calibrate_selector_auto(v1_bypass_block, save=save)
@@ -2095,6 +3599,7 @@ class Mmu:
     cmd_MMU_CALIBRATE_BOWDEN_help = "Calibration of reference bowden length for gate #0"
     def cmd_MMU_CALIBRATE_BOWDEN(self, gcmd):
         if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
         if self._check_has_encoder(): return
         if self._check_not_homed(): return
         if self._check_in_bypass(): return
@@ -2105,47 +3610,433 @@ class Mmu:
         extruder_homing_max = gcmd.get_float('HOMING_MAX', 100, above=0.)
         save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
         try:
-            self._reset_ttg_mapping() # To force tool = gate
+            self._reset_ttg_mapping(self._get_command_source(gcmd)) # To force tool = gate
             self._unload_tool()
             self.calibrating = True
             with self._require_encoder():
                 self._calibrate_bowden_length(approx_bowden_length, extruder_homing_max, repeats, save)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
         finally:
             self.calibrating = False
 
-    # Start: Will home selector, select gate #0 or required gate
-    # End: Filament will unload
-    cmd_MMU_CALIBRATE_GATES_help = "Optional calibration of individual MMU gate"
-    def cmd_MMU_CALIBRATE_GATES(self, gcmd):
+    # Start: Filament must be loaded to nozzle
+    # End: Filament loaded to nozzle (filament same as start)
+    cmd_MMU_CALIBRATE_CUTTER_help = "Find minimal toolhead cutter pin travel that reliably cuts or reset blade usage after replacement"
+    def cmd_MMU_CALIBRATE_CUTTER(self, gcmd):
         if self._check_is_disabled(): return
-        if self._check_not_homed(): return
+        if self._check_print_protection(gcmd): return
+        if gcmd.get_int('NEW_BLADE', 0, minval=0, maxval=1):
+            self.cutter_blade['blades'] += 1
+            self.cutter_blade['cuts'] = 0
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_CUTTER_BLADE, self.cutter_blade))
+            self._log_always("Cutter blade usage counter reset (%d cuts in total)" % self.cutter_blade['total_cuts'])
+            return
         if self._check_in_bypass(): return
-        if self._check_is_calibrated(self.CALIBRATED_GEAR|self.CALIBRATED_ENCODER|self.CALIBRATED_SELECTOR|self.CALIBRATED_BOWDEN): return
-        length = gcmd.get_float('LENGTH', 400., above=0.)
-        repeats = gcmd.get_int('REPEATS', 3, minval=1, maxval=10)
-        auto = gcmd.get_int('ALL', 0, minval=0, maxval=1)
-        gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
+        if self.cutter_location != self.CUTTER_TOOLHEAD:
+            raise gcmd.error("Cutter calibration is only possible with a toolhead cutter ('cutter_location: %s')" % self.CUTTER_TOOLHEAD)
+        if not self._has_sensor(self.ENDSTOP_TOOLHEAD):
+            raise gcmd.error("Cutter calibration requires a toolhead sensor to verify cuts")
+        if self.filament_pos != self.FILAMENT_POS_LOADED:
+            raise gcmd.error("Filament must be loaded to the nozzle to calibrate cutter")
+        gcode_macro = self.printer.lookup_object("gcode_macro %s" % self.form_tip_macro, None)
+        if gcode_macro is None or 'cut_travel' not in gcode_macro.variables:
+            raise gcmd.error("Macro '%s' does not declare 'variable_cut_travel' so cannot be calibrated" % self.form_tip_macro)
+
+        min_travel = gcmd.get_float('MIN', 0., minval=0.)
+        max_travel = gcmd.get_float('MAX', 5., above=min_travel)
+        step = gcmd.get_float('STEP', 0.5, above=0.)
+        repeats = gcmd.get_int('REPEATS', 2, minval=1, maxval=10)
+        margin = gcmd.get_float('MARGIN', step, minval=0.)
         save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
-        if gate == -1 and not auto:
-            raise gcmd.error("Must specify 'GATE=' or 'ALL=1' for all gates")
         try:
-            self._reset_ttg_mapping() # To force tool = gate
-            self._unload_tool()
             self.calibrating = True
-            with self._require_encoder():
-                if gate == -1:
-                    self._log_always("Start the complete calibration of ancillary gates...")
-                    for gate in range(self.mmu_num_gates - 1):
-                        self._calibrate_gate(gate + 1, length, repeats, save=save)
-                    self._log_always("Phew! End of auto gate calibration")
-                else:
+            self._calibrate_cutter_travel(min_travel, max_travel, step, repeats, margin, save)
+        except MmuError as ee:
+            self._mmu_pause(str(ee), code=ee.code)
+        finally:
+            self.calibrating = False
+            self.cutter_travel_trial = None
+
+    # Cut at increasing pin travel (how far the cutter lever is pressed past contact with the pin). A cut is good if the
+    # filament end is at the blade, i.e. the toolhead sensor clears when unloading from there. Otherwise filament (or a
+    # fragment left by an incomplete cut) is pushed out of the nozzle before the next attempt. The lowest travel where
+    # every repeat cut cleanly plus 'margin' is chosen so the least force is put through the blade and pin
+    def _calibrate_cutter_travel(self, min_travel, max_travel, step, repeats, margin, save=True):
+        travel = min_travel
+        try:
+            while travel <= max_travel + 0.001:
+                good = 0
+                for i in range(repeats):
+                    self.cutter_travel_trial = travel
+                    _, park_pos = self._form_tip_standalone()
+                    self.cutter_travel_trial = None
+                    try:
+                        self._unload_extruder(park_pos=park_pos)
+                    except MmuError as ee:
+                        if ee.code != self.ERR_TOOLHEAD_HOME_TIMEOUT:
+                            raise
+                    cut = not self._check_sensor(self.ENDSTOP_TOOLHEAD)
+                    self._log_info("Pin travel %.2fmm trial #%d: %s" % (travel, i + 1, "clean cut" if cut else "incomplete cut"))
+                    if cut:
+                        self._set_filament_pos_state(self.FILAMENT_POS_END_BOWDEN, silent=True)
+                        self._load_extruder()
+                        good += 1
+                    else:
+                        self._push_fragment_from_nozzle()
+                        break
+                if good == repeats:
+                    break
+                travel += step
+            else:
+                raise MmuError("No pin travel up to %.2fmm gave a reliable cut. Check blade, pin position and 'pin_loc_x_compressed' limit" % max_travel, code=self.ERR_CALIBRATION)
+        finally:
+            self._servo_auto()
+
+        recommended = round(travel + margin, 2)
+        msg = "Cutter cuts reliably with %.2fmm of pin travel. Recommended cut_travel: %.2fmm" % (travel, recommended)
+        if save:
+            self.cutter_travel = recommended
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%.2f" % (self.VARS_MMU_CALIB_CUTTER_TRAVEL, recommended))
+            msg += " (saved)"
+        self._log_always(msg)
+
+    # Start: Will home selector, select gate #0
+    # End: Filament will unload
+    cmd_MMU_CALIBRATE_COLLISION_help = "Tune gear current used for extruder collision homing by scoring test collisions"
+    def cmd_MMU_CALIBRATE_COLLISION(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
+        if self._check_has_encoder(): return
+        if self._check_not_homed(): return
+        if self._check_in_bypass(): return
+        if self._check_is_calibrated(self.CALIBRATED_GEAR|self.CALIBRATED_ENCODER|self.CALIBRATED_SELECTOR|self.CALIBRATED_BOWDEN): return
+
+        min_current = gcmd.get_int('MIN', 20, minval=10, maxval=100)
+        max_current = gcmd.get_int('MAX', 80, minval=min_current, maxval=100)
+        step = gcmd.get_int('STEP', 10, minval=5, maxval=50)
+        trials = gcmd.get_int('TRIALS', 3, minval=2, maxval=10)
+        homing_max = gcmd.get_float('HOMING_MAX', self.extruder_homing_max, above=0.)
+        save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
+        if self.extruder_homing_endstop != self.ENDSTOP_EXTRUDER_COLLISION:
+            self._log_always("Warning: 'extruder_homing_endstop' is not '%s' so tuned current will only be used if you change it" % self.ENDSTOP_EXTRUDER_COLLISION)
+        try:
+            self._reset_ttg_mapping(self._get_command_source(gcmd)) # To force tool = gate
+            self._unload_tool()
+            self.calibrating = True
+            with self._require_encoder():
+                self._calibrate_collision_current(list(range(min_current, max_current + 1, step)), trials, homing_max, save)
+        except MmuError as ee:
+            self._mmu_pause(str(ee), code=ee.code)
+        finally:
+            self.calibrating = False
+
+    # Perform repeated test collisions at each current level. A level is reliable if every collision was detected with
+    # filament spring back (a real collision) and the detected positions are consistent. Too little current slips early
+    # so detects short of the extruder. The lowest good level whose next step up also passed is chosen for margin
+    def _calibrate_collision_current(self, currents, trials, homing_max, save=True):
+        saved_current = self.extruder_homing_current
+        backoff = homing_max / 2.
+        results = []
+        try:
+            self._log_always("Tuning collision homing current on Gate #0 at %s%% with %d trials each" % (", ".join(str(c) for c in currents), trials))
+            self._select_tool(0)
+            self._initialize_filament_position(dwell=True)    # Encoder 0000
+            self._load_gate(allow_retry=False)
+            self._load_bowden(max(self.calibrated_bowden_length - backoff, 0.))
+            for current in currents:
+                self.extruder_homing_current = current
+                positions = []
+                for i in range(trials):
+                    self._servo_down()
+                    actual, homed, _, _ = self._home_to_extruder_collision_detection(homing_max)
+                    spring = self._servo_up(measure=True)
+                    if homed and spring > 0.:
+                        positions.append(actual)
+                    self._log_info("Current %d%% trial #%d: %s after %.1fmm (spring %.1fmm)" % (current, i + 1, "collision detected" if homed else "not detected", actual, spring))
+                    self._servo_down()
+                    self._trace_filament_move("Returning to trial start position", -actual)
+                reliable = len(positions) == trials
+                spread = (max(positions) - min(positions)) if positions else 0.
+                mean = (sum(positions) / len(positions)) if positions else 0.
+                results.append((current, reliable, mean, spread))
+
+            self._servo_down()
+            self._unload_bowden(max(self.calibrated_bowden_length - backoff, 0.))
+            self._unload_gate()
+            self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
+        except MmuError as ee:
+            raise MmuError("Tuning of collision homing current failed. Aborting, because:\n%s" % str(ee), code=self.ERR_CALIBRATION)
+        finally:
+            self.extruder_homing_current = saved_current
+            self._servo_auto()
+
+        collision_step = self.extruder_collision_homing_step * math.ceil(self.encoder_resolution * 10) / 10
+        reliable_means = [m for _, r, m, _ in results if r]
+        best_mean = max(reliable_means) if reliable_means else 0.
+        good = [c for c, r, m, s in results if r and s <= collision_step and m >= best_mean - collision_step]
+        msg = "Collision homing results (current: detected/consistent, mean position, spread):"
+        for current, reliable, mean, spread in results:
+            msg += "\n%d%%: %s, %.1fmm, %.1fmm%s" % (current, "reliable" if reliable else "unreliable", mean, spread, " (good)" if current in good else "")
+        if not good:
+            msg += "\nNo current level gave reliable collision detection. Check gear grip on filament and extruder entrance"
+            self._log_always(msg)
+            return
+        levels = sorted(c for c, _, _, _ in results)
+        recommended = next((c for c, n in zip(levels, levels[1:]) if c in good and n in good), None)
+        if recommended is None:
+            recommended = min(good)
+            msg += "\nNo two consecutive current levels were good so there is no margin. Consider a smaller STEP"
+        msg += "\nRecommended extruder_homing_current: %d%%" % recommended
+        if save:
+            self.extruder_homing_current = recommended
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_CALIB_COLLISION_CURRENT, recommended))
+            msg += " (saved)"
+        self._log_always(msg)
+
+    # Mid-job recovery when collision homing finds nothing. Too much current means the gear forces filament into the
+    # extruder without slipping, so retry from the start at successively lower current and use the first that works. It
+    # isn't persisted mid-job because a single success isn't a calibration, MMU_CALIBRATE_COLLISION should be re-run
+    def _revalidate_collision_current(self, max_length, moved):
+        self._trace_filament_move("Backing off failed collision homing", -moved)
+        for current in [self.extruder_homing_current - 10, self.extruder_homing_current - 20]:
+            if current < 10:
+                break
+            saved_current = self.extruder_homing_current
+            self.extruder_homing_current = current
+            self._log_info("Revalidating collision homing at %d%% gear current..." % current)
+            actual, homed, measured, delta = self._home_to_extruder_collision_detection(max_length)
+            if homed:
+                self._log_always("Collision homing succeeded at reduced %d%% gear current. Using it until restart, run MMU_CALIBRATE_COLLISION to retune extruder_homing_current" % current)
+                return actual, homed, measured, delta
+            self.extruder_homing_current = saved_current
+            self._trace_filament_move("Backing off failed collision homing", -actual)
+        return 0., False, 0., 0.
+
+    # Start: Will home selector, select gate #0 or required gate
+    # End: Filament will unload
+    cmd_MMU_CALIBRATE_GATES_help = "Optional calibration of individual MMU gate"
+    def cmd_MMU_CALIBRATE_GATES(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
+        if self._check_not_homed(): return
+        if self._check_in_bypass(): return
+        if self._check_is_calibrated(self.CALIBRATED_GEAR|self.CALIBRATED_ENCODER|self.CALIBRATED_SELECTOR|self.CALIBRATED_BOWDEN): return
+        length = gcmd.get_float('LENGTH', 400., above=0.)
+        repeats = gcmd.get_int('REPEATS', 3, minval=1, maxval=10)
+        auto = gcmd.get_int('ALL', 0, minval=0, maxval=1)
+        gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
+        save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
+        if gate == -1 and not auto:
+            raise gcmd.error("Must specify 'GATE=' or 'ALL=1' for all gates")
+        try:
+            self._reset_ttg_mapping(self._get_command_source(gcmd)) # To force tool = gate
+            self._unload_tool()
+            self.calibrating = True
+            with self._require_encoder():
+                if gate == -1:
+                    self._log_always("Start the complete calibration of ancillary gates...")
+                    for gate in range(self.mmu_num_gates - 1):
+                        self._calibrate_gate(gate + 1, length, repeats, save=save)
+                    self._log_always("Phew! End of auto gate calibration")
+                else:
                     self._calibrate_gate(gate, length, repeats, save=(save and gate != 0))
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
+        finally:
+            self.calibrating = False
+
+    # Start: Will home selector, select gate #0 or required gate
+    # End: Filament will unload
+    cmd_MMU_CALIBRATE_SYNC_help = "Calibration of gear rotation distance when synced to extruder from the distance between extruder and toolhead sensors"
+    def cmd_MMU_CALIBRATE_SYNC(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
+        if self._check_not_homed(): return
+        if self._check_in_bypass(): return
+        if self._check_is_calibrated(self.CALIBRATED_GEAR|self.CALIBRATED_SELECTOR|self.CALIBRATED_BOWDEN): return
+        if not (self._has_sensor(self.ENDSTOP_EXTRUDER) and self._has_sensor(self.ENDSTOP_TOOLHEAD)):
+            raise gcmd.error("Synced calibration requires both 'extruder' and 'toolhead' sensors")
+        repeats = gcmd.get_int('REPEATS', 3, minval=1, maxval=10)
+        speed = gcmd.get_float('SPEED', self.extruder_homing_speed, above=0.)
+        auto = gcmd.get_int('ALL', 0, minval=0, maxval=1)
+        gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
+        save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
+        if gate == -1 and not auto:
+            raise gcmd.error("Must specify 'GATE=' or 'ALL=1' for all gates")
+        try:
+            self._reset_ttg_mapping(self._get_command_source(gcmd)) # To force tool = gate
+            self._unload_tool()
+            self.calibrating = True
+            if gate == -1:
+                self._log_always("Start the synced calibration of all gates...")
+                for gate in range(self.mmu_num_gates):
+                    self._calibrate_sync_ratio(gate, repeats, speed, save=save)
+                self._log_always("End of synced gate calibration")
+            else:
+                self._calibrate_sync_ratio(gate, repeats, speed, save=save)
+        except MmuError as ee:
+            self._mmu_pause(str(ee), code=ee.code)
+        finally:
+            self.calibrating = False
+
+    # Measure travel of sync feedback buffer by driving gear with extruder stationary (filament gripped in extruder).
+    # The trigger points are found by homing to each sensor and physical end stops by gear touch (stallguard) if
+    # available, otherwise when the encoder stops seeing movement
+    def _calibrate_sync_feedback(self, speed, max_travel, save=True):
+        compression_pos = tension_pos = None
+        self._servo_down()
+        self._log_always("Finding compression trigger point...")
+        actual,homed,_,_ = self._trace_filament_move("Homing to compression sensor", max_travel, speed=speed, motor="gear", homing_move=1, endstop_name=self.SENSOR_SYNC_COMPRESSION)
+        if not homed:
+            raise MmuError("Compression sensor did not trigger after moving %.1fmm" % max_travel, code=self.ERR_CALIBRATION)
+        end_compression = self._find_sync_feedback_end(1, speed, max_travel)
+
+        self._log_always("Finding tension trigger point...")
+        self._trace_filament_move("Return to compression trigger", -(end_compression or 0.), speed=speed, motor="gear")
+        actual,homed,_,_ = self._trace_filament_move("Homing to tension sensor", -2 * max_travel, speed=speed, motor="gear", homing_move=1, endstop_name=self.SENSOR_SYNC_TENSION)
+        if not homed:
+            raise MmuError("Tension sensor did not trigger after moving %.1fmm" % (2 * max_travel), code=self.ERR_CALIBRATION)
+        buffer_range = abs(actual)
+        end_tension = self._find_sync_feedback_end(-1, speed, max_travel)
+
+        # Return buffer to neutral position between trigger points
+        self._trace_filament_move("Return to neutral", (end_tension or 0.) + buffer_range / 2., speed=speed, motor="gear")
+
+        msg = "Sync feedback buffer range (between trigger points): %.1fmm" % buffer_range
+        if end_compression is not None and end_tension is not None:
+            buffer_maxrange = buffer_range + end_compression + end_tension
+            msg += ", maxrange (end to end): %.1fmm" % buffer_maxrange
+        else:
+            buffer_maxrange = max(buffer_range, self.sync_feedback_buffer_maxrange)
+            msg += ". Unable to detect end stops without encoder or 'mmu_gear_touch' endstop, maxrange unchanged"
+        self._log_always(msg)
+        if save:
+            self.sync_feedback_buffer_range = round(buffer_range, 1)
+            self.sync_feedback_buffer_maxrange = round(buffer_maxrange, 1)
+            sync_feedback = {'range': self.sync_feedback_buffer_range, 'maxrange': self.sync_feedback_buffer_maxrange}
+            self.variables[self.VARS_MMU_CALIB_SYNC_FEEDBACK] = sync_feedback
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_CALIB_SYNC_FEEDBACK, sync_feedback))
+            self._log_always("Sync feedback buffer calibration has been saved")
+
+    # Continue past trigger point until buffer bottoms out. Returns extra travel or None if it cannot be detected
+    def _find_sync_feedback_end(self, direction, speed, max_travel):
+        if self.gear_rail.get_extra_endstop(self.ENDSTOP_GEAR_TOUCH) is not None:
+            with self._wrap_gear_current(self.gate_homing_current, "for buffer end detection"):
+                actual,homed,_,_ = self._trace_filament_move("Touch homing to buffer end", direction * max_travel, speed=speed, motor="gear", homing_move=1, endstop_name=self.ENDSTOP_GEAR_TOUCH)
+            return abs(actual) if homed else None
+        if self._has_encoder():
+            travel, step = 0., 1.
+            with self._require_encoder():
+                while travel < max_travel:
+                    _,_,measured,_ = self._trace_filament_move("Probing for buffer end", direction * step, speed=speed, motor="gear", encoder_dwell=True)
+                    if measured < step * 0.5:
+                        return travel
+                    travel += step
+        return None
+
+    cmd_MMU_CALIBRATE_SYNC_FEEDBACK_help = "Measure and save travel of sync feedback buffer between trigger points and end stops"
+    def cmd_MMU_CALIBRATE_SYNC_FEEDBACK(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
+        if self._check_in_bypass(): return
+        if not (self._has_sensor(self.SENSOR_SYNC_TENSION) and self._has_sensor(self.SENSOR_SYNC_COMPRESSION)):
+            raise gcmd.error("Sync feedback calibration requires both 'sync_feedback_tension' and 'sync_feedback_compression' sensors")
+        if self.filament_pos != self.FILAMENT_POS_LOADED:
+            raise gcmd.error("Filament must be loaded so the extruder holds it stationary during calibration")
+        speed = gcmd.get_float('SPEED', 5., above=0.)
+        max_travel = gcmd.get_float('MAX_TRAVEL', 50., above=0.)
+        save = gcmd.get_int('SAVE', 1, minval=0, maxval=1)
+        try:
+            self.calibrating = True
+            with self._wrap_disable_encoder():
+                self._calibrate_sync_feedback(speed, max_travel, save=save)
+        except MmuError as ee:
+            self._mmu_pause(str(ee), code=ee.code)
         finally:
             self.calibrating = False
+            self._servo_auto()
+
+    # All persisted calibration variables
+    def _get_calibration_vars(self):
+        calib_vars = [self.VARS_MMU_SELECTOR_OFFSETS, self.VARS_MMU_SELECTOR_BYPASS, self.VARS_MMU_ENCODER_RESOLUTION, self.VARS_MMU_GEAR_ROTATION_DISTANCE]
+        return {k: v for k, v in self.variables.items() if (k.startswith(self.VARS_MMU_CALIB_PREFIX) or k in calib_vars) and k != self.VARS_MMU_CALIB_TOOLHEAD and v is not None}
+
+    # Snapshot of all persisted calibration plus toolhead specific settings
+    def _get_calibration_set(self):
+        calibration = self._get_calibration_vars()
+        calibration[self.VARS_MMU_CALIB_TOOLHEAD] = {s: getattr(self, s) for s in self.CALIB_TOOLHEAD_SETTINGS}
+        return calibration
+
+    cmd_MMU_CALIBRATION_help = "Backup, restore or list named sets of calibration (e.g. for different toolheads)"
+    def cmd_MMU_CALIBRATION(self, gcmd):
+        if self._check_is_disabled(): return
+        backup = gcmd.get('BACKUP', None)
+        restore = gcmd.get('RESTORE', None)
+        delete = gcmd.get('DELETE', None)
+        for name in [backup, restore, delete]:
+            if name is not None and not name.replace('_', '').isalnum():
+                raise gcmd.error("Calibration set name '%s' must only contain letters, numbers and '_'" % name)
+
+        if backup:
+            calibration = self._get_calibration_set()
+            var = "%s%s" % (self.VARS_MMU_CALIB_BACKUP_PREFIX, backup.lower())
+            self.variables[var] = calibration
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (var, calibration))
+            self._log_always("Calibration (%d values) saved as set '%s'" % (len(calibration), backup.lower()))
+
+        elif restore:
+            if self._is_in_print():
+                raise gcmd.error("Calibration cannot be restored during a print")
+            calibration = self.variables.get("%s%s" % (self.VARS_MMU_CALIB_BACKUP_PREFIX, restore.lower()), None)
+            if not calibration:
+                raise gcmd.error("Calibration set '%s' not found" % restore.lower())
+            calibration = dict(calibration)
+            toolhead = calibration.pop(self.VARS_MMU_CALIB_TOOLHEAD, {})
+
+            # Calibration added since the backup doesn't belong to the restored set
+            for var in self._get_calibration_vars():
+                if var not in calibration:
+                    self.variables.pop(var)
+                    self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=None" % var)
+            for var, value in calibration.items():
+                self.variables[var] = value
+                if isinstance(value, (dict, list)):
+                    self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (var, value))
+                else:
+                    self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%s" % (var, value))
+
+            # Toolhead settings are owned by config so are only applied as session overrides (lost on restart)
+            attrs = [s for s, v in toolhead.items() if s in self.CALIB_TOOLHEAD_SETTINGS and getattr(self, s) != v]
+            for attr in attrs:
+                self._add_config_override(attr, toolhead[attr], self.CONFIG_SCOPE_SESSION, None, "calibration set '%s'" % restore.lower())
+            self._apply_config_overrides(attrs)
+            if attrs:
+                self._log_always("Toolhead settings from set applied until restart: %s. Update mmu_parameters.cfg to keep them" % ", ".join("%s=%s" % (a, toolhead[a]) for a in attrs))
+            # Start from config so calibration absent from the set doesn't leak through from the current one
+            self.calibration_status = 0b0
+            self.gear_stepper.set_rotation_distance(self.gear_default_rotation_distance)
+            self.extruder_homing_current = self.default_extruder_homing_current
+            self._load_calibration()
+            if self.gate_selected >= 0:
+                self._set_gate_ratio(self._get_gate_ratio(self.gate_selected))
+            self._log_always("Calibration set '%s' restored" % restore.lower())
+            self._check_is_calibrated()
+
+        elif delete:
+            var = "%s%s" % (self.VARS_MMU_CALIB_BACKUP_PREFIX, delete.lower())
+            if var not in self.variables:
+                raise gcmd.error("Calibration set '%s' not found" % delete.lower())
+            self.variables.pop(var)
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=None" % var)
+            self._log_always("Calibration set '%s' deleted" % delete.lower())
+
+        else:
+            sets = sorted([k[len(self.VARS_MMU_CALIB_BACKUP_PREFIX):] for k, v in self.variables.items() if k.startswith(self.VARS_MMU_CALIB_BACKUP_PREFIX) and v])
+            if sets:
+                self._log_always("Saved calibration sets: %s" % ", ".join(sets))
+            else:
+                self._log_always("No saved calibration sets. Use 'MMU_CALIBRATION BACKUP=name' to save current calibration")
 
 
 #######################
@@ -2154,12 +4045,175 @@ class Mmu:
 
     def _setup_heater_off_reactor(self):
         self.heater_off_handler = self.reactor.register_timer(self._handle_pause_timeout, self.reactor.NEVER)
+        self.hotend_standby_handler = self.reactor.register_timer(self._handle_hotend_standby_timeout, self.reactor.NEVER)
+        self.hotend_standby_armed = False
+        self.hotend_standby_due = False
+        self.hotend_standby_idle_time = 0.
+        self.idle_retract_handler = self.reactor.register_timer(self._handle_idle_retract_timeout, self.reactor.NEVER)
+        self.idle_retracted_tool = self.TOOL_GATE_UNKNOWN
+        self._arm_idle_retract()
+        self.idle_care_handler = self.reactor.register_timer(self._handle_idle_care_timeout, self.reactor.NEVER)
+        self._arm_idle_care()
+        self.deep_park_handler = self.reactor.register_timer(self._handle_deep_park_timeout, self.reactor.NEVER)
+        self.self_check_handler = self.reactor.register_timer(self._handle_self_check_timeout, self.reactor.NEVER)
+        if self.self_check_time:
+            self.reactor.update_timer(self.self_check_handler, self._next_self_check_waketime())
+        self.gear_slip_start = None # Extruder position when entry sensor first indicated slip
+        self.gear_slip_handler = self.reactor.register_timer(self._handle_gear_slip_check, self.reactor.NEVER)
+        if self.gear_slip_detection or (self.virtual_encoder and self.enable_clog_detection):
+            self.reactor.update_timer(self.gear_slip_handler, self.reactor.NOW)
+        self.gear_thermal_handler = self.reactor.register_timer(self._handle_gear_thermal_check, self.reactor.NEVER)
+        if self.gear_temp_sensor:
+            self.reactor.update_timer(self.gear_thermal_handler, self.reactor.NOW)
 
     def _handle_pause_timeout(self, eventtime):
         self._log_info("Disable extruder heater")
         self.gcode.run_script_from_command("M104 S0")
         return self.reactor.NEVER
 
+    # Drop hotend to 'hotend_standby_temp' after 'hotend_standby_time' of MMU activity that doesn't engage the extruder
+    # (e.g. long recovery or calibration) and restore with wait before the next phase that does. The timer only flags
+    # that standby is due; the heater change is made at the next action boundary because that runs in command context.
+    # Whilst paused the 'disable_heater' logic applies instead
+    def _update_hotend_standby(self, old_action, action):
+        if self.hotend_standby_time <= 0: return
+        if action in [self.ACTION_LOADING_EXTRUDER, self.ACTION_UNLOADING_EXTRUDER, self.ACTION_FORMING_TIP]:
+            self.reactor.update_timer(self.hotend_standby_handler, self.reactor.NEVER)
+            self.hotend_standby_armed = self.hotend_standby_due = False
+            self._restore_hotend_from_standby()
+        elif action == self.ACTION_IDLE:
+            self.hotend_standby_idle_time = self.reactor.monotonic()
+        elif action not in [self.ACTION_HEATING, self.ACTION_WAITING]:
+            # Consecutive actions of one operation pass through idle momentarily so only restart timer after a real gap
+            now = self.reactor.monotonic()
+            new_operation = old_action == self.ACTION_IDLE and now - self.hotend_standby_idle_time > 1.
+            if new_operation:
+                self.hotend_standby_due = False
+            elif self.hotend_standby_due:
+                self._enter_hotend_standby()
+            if not self.hotend_standby_armed or new_operation:
+                self.hotend_standby_armed = True
+                self.reactor.update_timer(self.hotend_standby_handler, now + self.hotend_standby_time)
+
+    def _handle_hotend_standby_timeout(self, eventtime):
+        self.hotend_standby_armed = False
+        if self.action != self.ACTION_IDLE and not self._is_mmu_paused():
+            self.hotend_standby_due = True
+        return self.reactor.NEVER
+
+    def _enter_hotend_standby(self):
+        self.hotend_standby_due = False
+        if self._is_mmu_paused() or self.hotend_standby_saved_temp is not None: return
+        target = self.printer.lookup_object(self.extruder_name).heater.target_temp
+        if target > self.hotend_standby_temp:
+            self._log_info("MMU has not used extruder for %s. Dropping hotend to standby temperature (%.1f)" % (self._seconds_to_human_string(self.hotend_standby_time), self.hotend_standby_temp))
+            self.hotend_standby_saved_temp = target
+            self.gcode.run_script_from_command("SET_HEATER_TEMPERATURE HEATER=%s TARGET=%.1f" % (self.extruder_name, self.hotend_standby_temp))
+
+    def _restore_hotend_from_standby(self):
+        temp, self.hotend_standby_saved_temp = self.hotend_standby_saved_temp, None
+        if temp is None: return
+        self._log_info("Restoring hotend from standby to %.1f" % temp)
+        self.gcode.run_script_from_command("SET_HEATER_TEMPERATURE HEATER=%s TARGET=%.1f" % (self.extruder_name, temp))
+        with self._wrap_action(self.ACTION_HEATING):
+            self.gcode.run_script_from_command("TEMPERATURE_WAIT SENSOR=%s MINIMUM=%.1f MAXIMUM=%.1f" % (self.extruder_name, temp - 1, temp + 1))
+
+    # Extruder target temperature ignoring any temporary drop to standby temperature
+    def _get_extruder_target_temp(self):
+        if self.hotend_standby_saved_temp is not None:
+            return self.hotend_standby_saved_temp
+        return self.printer.lookup_object(self.extruder_name).heater.target_temp
+
+    # Arm (or re-arm) timer to retract filament back to gate after period of inactivity outside of a print
+    def _arm_idle_retract(self):
+        if self.idle_retract_time > 0 and not self._is_in_print():
+            self.reactor.update_timer(self.idle_retract_handler, self.reactor.monotonic() + self.idle_retract_time)
+
+    def _handle_idle_retract_timeout(self, eventtime):
+        if not self.is_enabled or self._is_in_print(): return self.reactor.NEVER
+        if self.filament_pos == self.FILAMENT_POS_UNLOADED or self.gate_selected < 0: return self.reactor.NEVER
+        if self.idle_retract_materials and self.gate_material[self.gate_selected].upper() not in self.idle_retract_materials:
+            return self.reactor.NEVER
+        if self._is_printer_printing():
+            return eventtime + 60. # Printer busy with other commands, try again later
+        self._exec_gcode("_MMU_IDLE_RETRACT")
+        return self.reactor.NEVER
+
+    # Arm (or re-arm) timer to periodically flex filament at each gate whilst the MMU sits unused
+    def _arm_idle_care(self):
+        if self.idle_care_interval > 0 and not self._is_in_print():
+            self.reactor.update_timer(self.idle_care_handler, self.reactor.monotonic() + self.idle_care_interval * 3600)
+
+    def _handle_idle_care_timeout(self, eventtime):
+        if self._is_in_print(): return self.reactor.NEVER
+        if self._is_printer_printing():
+            return eventtime + 60. # Printer busy with other commands, try again later
+        if self.is_enabled and self.is_homed and self.filament_pos == self.FILAMENT_POS_UNLOADED:
+            self._exec_gcode("_MMU_IDLE_CARE")
+        return eventtime + self.idle_care_interval * 3600
+
+    def _handle_deep_park_timeout(self, eventtime):
+        if self._is_in_print() or not self.ready_parked_gates: return self.reactor.NEVER
+        if self._is_printer_printing():
+            return eventtime + 60. # Printer busy with other commands, try again later
+        self._exec_gcode("_MMU_DEEP_PARK")
+        return self.reactor.NEVER
+
+    # Reactor time of next daily self-check at 'self_check_time' (local wall clock)
+    def _next_self_check_waketime(self):
+        now = time.time()
+        lt = time.localtime(now)
+        target = time.mktime((lt.tm_year, lt.tm_mon, lt.tm_mday, self.self_check_hhmm[0], self.self_check_hhmm[1], 0, 0, 0, -1))
+        if target <= now + 60:
+            target += 86400
+        self.self_check_due = target
+        return self.reactor.monotonic() + target - now
+
+    def _handle_self_check_timeout(self, eventtime):
+        if self._is_in_print() or self._is_printer_printing():
+            if time.time() - self.self_check_due < self.SELF_CHECK_WINDOW:
+                return eventtime + 600. # Printer busy, try again later
+            self._log_info("Scheduled MMU self-check skipped because printer has been busy")
+        else:
+            self._exec_gcode("MMU_SELF_CHECK SCHEDULED=1")
+        return self._next_self_check_waketime()
+
+    # A slipping gear stepper whilst synced shows as the entry sensor (sync tension switch if fitted, else extruder
+    # entry sensor) reporting the extruder pulling filament the gear isn't delivering whilst the extruder keeps
+    # extruding. A clog stops extrusion so isn't confused with this. Runout is excluded by the gate sensor.
+    # The virtual encoder uses the same check for the opposite case, the buffer staying compressed, as a clog
+    def _handle_gear_slip_check(self, eventtime):
+        synced_printing = (self._is_printing() and self.action == self.ACTION_IDLE and self.filament_pos == self.FILAMENT_POS_LOADED
+                and self.gate_selected >= 0 and self.mmu_toolhead.is_gear_synced_to_extruder()
+                and self._check_sensor(self.ENDSTOP_GATE) is not False)
+        if self.virtual_encoder and self.enable_clog_detection and self._has_sensor(self.SENSOR_SYNC_COMPRESSION):
+            compressed = synced_printing and self.sensors[self.SENSOR_SYNC_COMPRESSION].runout_helper.filament_present
+            if self.virtual_encoder.check_clog(compressed, self.mmu_extruder_stepper.stepper.get_commanded_position()):
+                self._log_info("Virtual encoder: sync feedback buffer stayed compressed for %.1fmm of extrusion" % self.virtual_clog_length)
+                self._exec_gcode("__MMU_ENCODER_RUNOUT")
+                return eventtime + 10. # Allow time for runout/clog handling
+        if not self.gear_slip_detection:
+            return eventtime + self.GEAR_SLIP_CHECK_INTERVAL
+
+        slipping = False
+        if synced_printing:
+            if self._has_sensor(self.SENSOR_SYNC_TENSION):
+                slipping = self.sensors[self.SENSOR_SYNC_TENSION].runout_helper.filament_present
+            elif self._has_sensor(self.ENDSTOP_EXTRUDER):
+                slipping = not self.sensors[self.ENDSTOP_EXTRUDER].runout_helper.filament_present
+        if not slipping:
+            self.gear_slip_start = None
+            return eventtime + self.GEAR_SLIP_CHECK_INTERVAL
+
+        pos = self.mmu_extruder_stepper.stepper.get_commanded_position()
+        if self.gear_slip_start is None:
+            self.gear_slip_start = pos
+        elif pos - self.gear_slip_start >= self.gear_slip_length:
+            self.gear_slip_start = None
+            self._exec_gcode("__MMU_GEAR_SLIP LENGTH=%.1f" % self.gear_slip_length)
+            return eventtime + 10. # Allow time for remediation before warning again
+        return eventtime + self.GEAR_SLIP_CHECK_INTERVAL
+
     def _handle_idle_timeout_printing(self, eventtime):
         self._handle_idle_timeout_event(eventtime, "printing")
 
@@ -2274,6 +4328,8 @@ class Mmu:
                 gcode = self.printer.lookup_object('gcode_macro _MMU_PRINT_STATE_CHANGED', None)
                 if gcode is not None:
                     self._wrap_gcode_command("_MMU_PRINT_STATE_CHANGED STATE='%s' OLD_STATE='%s'" % (print_state, self.print_state))
+            self._publish_event("print_state", state=print_state, old_state=self.print_state)
+            self._record_transition("print_state", self.print_state, print_state)
             self.print_state = print_state
 
     # If this is called automatically when printing starts. The pre_start_only operations are performed on an idle_timeout
@@ -2285,9 +4341,18 @@ class Mmu:
             self._clear_saved_toolhead_position()
             self.paused_extruder_temp = None
             self._reset_job_statistics() # Reset job stats but leave persisted totals alone
+            self._reset_bowden_live_adjustment() # Live correction only applies for duration of a job
             self.reactor.update_timer(self.heater_off_handler, self.reactor.NEVER) # Don't automatically turn off extruder heaters
+            self.reactor.update_timer(self.idle_retract_handler, self.reactor.NEVER) # Don't retract filament whilst printing
+            self.reactor.update_timer(self.idle_care_handler, self.reactor.NEVER) # Don't flex filament whilst printing
+            self.reactor.update_timer(self.deep_park_handler, self.reactor.NEVER) # Keep gates at ready park position for next job
+            self.dryer_soaked_zones.clear()
+            if self.purge_router:
+                self.purge_router.reset()
             self._enable_encoder_sensor(True) # Enable runout/clog detection
             self._initialize_filament_position(dwell=None) # Encoder 0000
+            if self.telemetry:
+                self.telemetry.start_job(self.reactor.monotonic(), self.print_stats.get_status(self.reactor.monotonic()).get('filename', '') if self.print_stats else '')
             self._set_print_state("started", call_macro=False)
 
         if not pre_start_only and self.print_state not in ["printing"]:
@@ -2301,17 +4366,27 @@ class Mmu:
             self._log_info(msg)
             self._set_print_state("printing")
 
-    def _mmu_pause(self, reason, force_in_print=False):
+    def _mmu_pause(self, reason, force_in_print=False, code=None):
         run_pause_macro = False
+        code = code or self.ERR_GENERAL
+        self.last_error = {'code': code, 'reason': reason, 'tool': self.tool_selected, 'gate': self.gate_selected, 'print_state': self.print_state}
+        self._record_transition("error", self.print_state, code, cause=reason)
+        self._record_journal("error", code=code, reason=reason)
+        if self.journal and self.journal.is_recording():
+            filename = self.journal.save()
+            if filename:
+                self._log_info("Journal of failed toolchange saved to %s (see MMU_JOURNAL)" % filename)
+        self._publish_event("error", code=code, reason=reason, tool=self.tool_selected, gate=self.gate_selected, print_state=self.print_state)
         if not self.paused_extruder_temp: # Only save the initial pause temp
-            self.paused_extruder_temp = self.printer.lookup_object(self.extruder_name).heater.target_temp
+            self.paused_extruder_temp = self._get_extruder_target_temp()
         self.resume_to_state = "printing" if self._is_in_print() else "ready"
 
         if self._is_printing(force_in_print) and not self._is_mmu_paused():
-            self._log_error("An issue with the MMU has been detected. Print paused\nReason: %s" % reason)
+            self._log_error("An issue with the MMU has been detected. Print paused\nReason: %s [%s]" % (reason, code))
             self._log_always("After fixing the issue, call \'RESUME\' to continue printing (MMU_UNLOCK can restore temperature)")
 
             self._track_pause_start()
+            self.resume_verify_failed = False
             self._log_trace("Extruder heater will be disabled in %s" % self._seconds_to_human_string(self.disable_heater))
             self.reactor.update_timer(self.heater_off_handler, self.reactor.monotonic() + self.disable_heater) # Set extruder off timer
             self.gcode.run_script_from_command("SET_IDLE_TIMEOUT TIMEOUT=%d" % self.timeout_pause) # Set alternative pause idle_timeout
@@ -2322,10 +4397,10 @@ class Mmu:
             self.printer.send_event("mmu:mmu_paused", self) # Notify MMU paused event
 
         elif self._is_mmu_paused():
-            self._log_error("An issue with the MMU has been detected whilst printer is paused\nReason: %s" % reason)
+            self._log_error("An issue with the MMU has been detected whilst printer is paused\nReason: %s [%s]" % (reason, code))
 
         else:
-            self._log_error("An issue with the MMU has been detected whilst out of a print\nReason: %s" % reason)
+            self._log_error("An issue with the MMU has been detected whilst out of a print\nReason: %s [%s]" % (reason, code))
 
         if self._is_printing(force_in_print):
             self._recover_filament_pos(strict=False, message=True)
@@ -2333,7 +4408,7 @@ class Mmu:
         self._sync_gear_to_extruder(False, servo=True) # Should we just leave state where it ends up?
 
         if run_pause_macro:
-            self._wrap_gcode_command(self.pause_macro)
+            self._wrap_gcode_command("%s MMU_ERROR=%s" % (self.pause_macro, code))
 
     def _mmu_unlock(self):
         if self._is_mmu_paused():
@@ -2364,13 +4439,34 @@ class Mmu:
             self._clear_saved_toolhead_position()
             self.resume_to_state = "ready"
             self.paused_extruder_temp = None
+            self.hotend_standby_saved_temp = None
+            self.hotend_standby_due = False
+            self.filament_change_tool = self.filament_change_gate = self.TOOL_GATE_UNKNOWN
+            self.slicer_tip_params = {}
+            self._restore_job_ttg_map()
+            if self.purge_assist:
+                self.purge_assist.release()
+            if self.purge_router:
+                self.purge_router.reset()
+            if self._clear_config_overrides(self.CONFIG_SCOPE_JOB):
+                self._log_info("Job scoped MMU_TEST_CONFIG overrides have been reverted")
             self.reactor.update_timer(self.heater_off_handler, self.reactor.NEVER) # Don't automatically turn off extruder heaters
             self._disable_encoder_sensor() # Disable runout/clog detection after print
 
             if self.printer.lookup_object("idle_timeout").idle_timeout != self.default_idle_timeout:
                 self.gcode.run_script_from_command("SET_IDLE_TIMEOUT TIMEOUT=%d" % self.default_idle_timeout) # Restore original idle_timeout
             self._sync_gear_to_extruder(False, servo=True)
+            if state in ["complete", "cancelled"]:
+                self._clear_plr_state()
+            self._set_dryer_temp(0)
+            self.dryer_job_tools = None
             self._set_print_state(state)
+            if self.telemetry:
+                self.telemetry.end_job(self.reactor.monotonic(), state)
+            self._arm_idle_retract()
+            self._arm_idle_care()
+            if self.ready_parked_gates:
+                self.reactor.update_timer(self.deep_park_handler, self.reactor.monotonic() + max(self.gate_ready_park_revert, 1.))
         if state == "standby" and not self._is_in_standby():
             self._set_print_state(state)
 
@@ -2427,6 +4523,53 @@ class Mmu:
                 self._log_debug("Enabled encoder sensor, force_in_print=%s. Status: %s" % (force_in_print, self.encoder_sensor.get_status(0)))
                 self.encoder_sensor.enable()
 
+    # Gear stepper StallGuard sampling. SG_RESULT falls towards zero as load increases so a hard jam is seen as a
+    # run of samples well below the free running baseline recorded for the gate during calibration
+    def _get_gear_sg_result(self):
+        fields = self.gear_tmc.fields
+        reg_name = fields.lookup_register("sg_result", None)
+        return fields.get_field("sg_result", self.gear_tmc.mcu_tmc.get_register(reg_name), reg_name)
+
+    # If a baseline is supplied the gear driver is disabled as soon as a jam is seen so the remainder of the move
+    # doesn't grind the filament. The error is raised by the caller once the move has unwound
+    def _sample_gear_stallguard(self, eventtime):
+        try:
+            self.gear_stall_samples.append(self._get_gear_sg_result())
+        except Exception as e:
+            self._log_debug("Unable to read gear StallGuard: %s" % str(e))
+            return self.reactor.NEVER
+        if self.gear_stall_baseline and self._gear_stall_detected(self.gear_stall_baseline):
+            self.gear_stall_tripped = True
+            self._log_error("Gear stall detected (StallGuard). Disabling gear driver")
+            ge = self.printer.lookup_object('stepper_enable').lookup_enable(self.gear_stepper.get_name())
+            ge.motor_disable(self.estimated_print_time(eventtime))
+            return self.reactor.NEVER
+        return eventtime + 0.05
+
+    @contextlib.contextmanager
+    def _wrap_gear_stall_sampling(self, baseline=None):
+        self.gear_stall_samples = []
+        self.gear_stall_baseline = baseline
+        self.gear_stall_tripped = False
+        self.reactor.update_timer(self.gear_stall_timer, self.reactor.NOW)
+        try:
+            yield self
+        finally:
+            self.reactor.update_timer(self.gear_stall_timer, self.reactor.NEVER)
+            self.gear_stall_baseline = None
+
+    def _get_gear_stall_baseline(self, gate):
+        return self.variables.get("%s%d" % (self.VARS_MMU_CALIB_STALL_PREFIX, gate), None)
+
+    def _gear_stall_detected(self, baseline):
+        threshold = baseline * self.bowden_stall_threshold / 100.
+        low = 0
+        for sg in self.gear_stall_samples[2:]: # Skip samples taken while accelerating
+            low = low + 1 if sg < threshold else 0
+            if low >= self.bowden_stall_samples:
+                return True
+        return False
+
     @contextlib.contextmanager
     def _wrap_disable_encoder(self):
         old_enable = self._disable_encoder_sensor()
@@ -2473,7 +4616,7 @@ class Mmu:
     @contextlib.contextmanager
     def _require_encoder(self):
         if not self._has_encoder():
-            raise MmuError("Assertion failure: Encoder required for chosen operation but not present on MMU")
+            raise MmuError("Assertion failure: Encoder required for chosen operation but not present on MMU", code=self.ERR_CONFIG)
         self.encoder_force_validation = True
         try:
             yield self
@@ -2514,6 +4657,8 @@ class Mmu:
         return position
 
     def _set_filament_pos_state(self, state, silent=False):
+        if state != self.filament_pos:
+            self._record_transition("filament_pos", self._filament_pos_to_string(self.filament_pos), self._filament_pos_to_string(state))
         self.filament_pos = state
         if self.gate_selected != self.TOOL_GATE_BYPASS or state == self.FILAMENT_POS_UNLOADED or state == self.FILAMENT_POS_LOADED:
             self._display_visual_state(silent=silent)
@@ -2547,9 +4692,68 @@ class Mmu:
             result[name] = sensor.runout_helper.filament_present if sensor.runout_helper.sensor_enabled else None
         return result
 
+    # Describe filament location from custom path sensors, e.g. "between 'bowden_mid' and extruder"
+    def _locate_filament_on_path(self):
+        states = [(name, self._check_sensor(name)) for _, name in self.path_sensors]
+        states = [(name, detected) for name, detected in states if detected is not None]
+        last = max([i for i, (_, detected) in enumerate(states) if detected] or [-1])
+        after = "'%s'" % states[last + 1][0] if last + 1 < len(states) else "extruder"
+        before = "'%s'" % states[last][0] if last >= 0 else "gate"
+        return "between %s and %s" % (before, after)
+
+    # Verify custom path sensors agree with expected position of filament tip (distance from gate)
+    def _verify_path_sensors(self, position, code):
+        for sensor_pos, name in self.path_sensors:
+            detected = self._check_sensor(name)
+            if detected is False and sensor_pos < position - self.path_sensor_tolerance:
+                raise MmuError("Filament lost %s. Expected tip at %.1fmm but '%s' sensor at %.1fmm is empty" % (self._locate_filament_on_path(), position, name, sensor_pos), code=code)
+            if detected is True and sensor_pos > position + self.path_sensor_tolerance:
+                raise MmuError("Filament stuck %s. Expected tip at %.1fmm but '%s' sensor at %.1fmm still detects filament" % (self._locate_filament_on_path(), position, name, sensor_pos), code=code)
+
+    # Custom gcode load/unload sequence macro for the selected gate (per-gate override, else global option) or None for internal logic
+    def _get_sequence_macro(self, gate_sequences, enabled, default_macro):
+        if self.gate_selected in gate_sequences:
+            return gate_sequences[self.gate_selected]
+        return default_macro if enabled else None
+
     def _must_home_to_extruder(self):
         return self.extruder_force_homing or not self._has_sensor(self.ENDSTOP_TOOLHEAD)
 
+    # Destructive commands issued while printing or paused require FORCE=1. With 'print_protection: 2' a
+    # confirmation dialog is also offered in clients that support it (Mainsail/Fluidd)
+    def _check_print_protection(self, gcmd):
+        if not self.print_protection or not self._is_in_print():
+            return False
+        if gcmd.get_int('FORCE', 0, minval=0, maxval=1):
+            if self.print_protection == 2:
+                self.gcode.respond_raw("// action:prompt_end")
+            return False
+        command = gcmd.get_command()
+        commandline = gcmd.get_commandline().strip()
+        if self.print_protection == 2 and '"' not in commandline:
+            for line in ["prompt_begin Happy Hare",
+                         "prompt_text %s is destructive and a print is in progress. Are you sure?" % command,
+                         "prompt_footer_button Cancel|RESPOND TYPE=command MSG=action:prompt_end|secondary",
+                         "prompt_footer_button Confirm|%s FORCE=1|error" % commandline,
+                         "prompt_show"]:
+                self.gcode.respond_raw("// action:%s" % line)
+        self._log_error("%s is destructive and a print is in progress. Re-run with FORCE=1 if you are sure" % command)
+        return True
+
+    # Wrap command so it is rejected while printing or paused ('print_locked_commands')
+    def _lock_command_in_print(self, name):
+        desc = self.gcode.gcode_help.get(name)
+        prev = self.gcode.register_command(name, None)
+        if prev is None:
+            self._log_error("Command '%s' in print_locked_commands doesn't exist" % name)
+            return
+        def locked(gcmd):
+            if self._is_in_print():
+                self._log_error("%s is locked out during a print (see 'print_locked_commands')" % name)
+                return
+            prev(gcmd)
+        self.gcode.register_command(name, locked, desc=desc)
+
     def _check_is_disabled(self):
         if not self.is_enabled:
             self._log_error("MMU is disabled. Please use MMU ENABLE=1 to use")
@@ -2563,6 +4767,29 @@ class Mmu:
             return True
         return False
 
+    # Filament inserted through bypass without selecting it would be ground by the next toolchange. Detected with the
+    # bypass sensor or, when all gates are parked, with the extruder sensor. Blocks the command (pausing if printing)
+    def _check_bypass_guard(self):
+        if not self.bypass_guard or self.tool_selected == self.TOOL_GATE_BYPASS: return False
+        reason = self._detect_bypass_filament()
+        if reason is None: return False
+        msg = "Filament appears to be loaded through bypass (%s). Command blocked to avoid grinding filament.\n" % reason
+        msg += "To print from bypass run MMU_SELECT_BYPASS. To use the MMU run MMU_SELECT_BYPASS then MMU_UNLOAD to eject it first"
+        if self._is_in_print():
+            self._mmu_pause(msg, code=self.ERR_BYPASS_ACTIVE)
+        else:
+            self._log_error(msg)
+        return True
+
+    # Return reason filament is believed to be in bypass path or None
+    def _detect_bypass_filament(self):
+        sensor = self.printer.lookup_object("filament_switch_sensor %s_sensor" % self.SENSOR_BYPASS, None)
+        if sensor is not None and sensor.runout_helper.sensor_enabled and sensor.runout_helper.filament_present:
+            return "bypass sensor detects filament"
+        if self.filament_pos == self.FILAMENT_POS_UNLOADED and self._check_sensor(self.ENDSTOP_EXTRUDER):
+            return "extruder sensor detects filament but no gate is loaded"
+        return None
+
     def _check_not_bypass(self):
         if self.tool_selected != self.TOOL_GATE_BYPASS:
             self._log_error("Bypass not selected. Please use MMU_SELECT_BYPASS first")
@@ -2585,24 +4812,30 @@ class Mmu:
         if not required:
             required = self.CALIBRATED_ALL
         if not (self.calibration_status & required == required):
-            steps = []
-            if self.CALIBRATED_GEAR & required and not self.calibration_status & self.CALIBRATED_GEAR:
-                    steps.append("MMU_CALIBRATE_GEAR")
-            if self.CALIBRATED_ENCODER & required and not self.calibration_status & self.CALIBRATED_ENCODER:
-                steps.append("MMU_CALIBRATE_ENCODER")
-            if self.CALIBRATED_SELECTOR & required and not self.calibration_status & self.CALIBRATED_SELECTOR:
-                steps.append("MMU_CALIBRATE_SELECTOR")
-            if self.CALIBRATED_BOWDEN & required and not self.calibration_status & self.CALIBRATED_BOWDEN:
-                steps.append("MMU_CALIBRATE_BOWDEN")
-            if self.CALIBRATED_GATES & required and not self.calibration_status & self.CALIBRATED_GATES:
-                steps.append("MMU_CALIBRATE_GATES")
+            steps = self._get_calibration_steps(required)
             msg = "Prerequsite calibration steps are not complete. Please run:"
             if not silent:
                 self._log_error("%s %s" % (msg, ", ".join(steps)))
             return True
         return False
 
+    # Calibration commands still to be run for the required calibration
+    def _get_calibration_steps(self, required):
+        steps = []
+        if self.CALIBRATED_GEAR & required and not self.calibration_status & self.CALIBRATED_GEAR:
+            steps.append("MMU_CALIBRATE_GEAR")
+        if self.CALIBRATED_ENCODER & required and not self.calibration_status & self.CALIBRATED_ENCODER:
+            steps.append("MMU_CALIBRATE_ENCODER")
+        if self.CALIBRATED_SELECTOR & required and not self.calibration_status & self.CALIBRATED_SELECTOR:
+            steps.append("MMU_CALIBRATE_SELECTOR")
+        if self.CALIBRATED_BOWDEN & required and not self.calibration_status & self.CALIBRATED_BOWDEN:
+            steps.append("MMU_CALIBRATE_BOWDEN")
+        if self.CALIBRATED_GATES & required and not self.calibration_status & self.CALIBRATED_GATES:
+            steps.append("MMU_CALIBRATE_GATES")
+        return steps
+
     def _ensure_safe_extruder_temperature(self, source="auto", wait=False):
+        self._restore_hotend_from_standby()
         extruder = self.printer.lookup_object(self.extruder_name)
         current_temp = extruder.get_status(0)['temperature']
         current_target_temp = extruder.heater.target_temp
@@ -2683,15 +4916,121 @@ class Mmu:
         else:
             return self.toolhead_extruder_to_nozzle
 
+    # Record state transition together with the current action and optional cause (e.g. error reason). This
+    # history is always kept so it is available after an unexpected pause
+    def _record_transition(self, kind, old, new, cause=None):
+        self.state_transitions.append({'time': time.time(), 'kind': kind, 'old': old, 'new': new, 'action': self._get_action_string(), 'cause': cause})
+        del self.state_transitions[:-self.MAX_STATE_TRANSITIONS]
+        self._record_telemetry("transition", type=kind, old=old, new=new, cause=cause)
+        self._record_journal("transition", type=kind, old=old, new=new, cause=cause)
+
+    def _record_telemetry(self, kind, **fields):
+        if self.telemetry:
+            self.telemetry.record(self.reactor.monotonic(), kind, **fields)
+
+    def _record_journal(self, kind, **fields):
+        if self.journal:
+            self.journal.record(self.reactor.monotonic(), kind, **fields)
+
+    def _start_journal(self, tool, in_print, skip_tip):
+        if not self.journal: return
+        self.journal.start(self.reactor.monotonic(), tool, in_print=in_print, skip_tip=skip_tip, gate_selected=self.gate_selected,
+                           tool_selected=self.tool_selected, filament_pos=self.filament_pos, is_homed=self.is_homed,
+                           gate_status=list(self.gate_status), gate_material=list(self.gate_material), tool_to_gate_map=list(self.tool_to_gate_map))
+
+    def _end_journal(self):
+        if self.journal:
+            self.journal.end()
+
+    # Intercept sensor state changes. Signature of note_filament_present() differs between klipper versions
+    # and our pre-gate helper but the state is always the last argument
+    def _wrap_sensor_telemetry(self, name, runout_helper):
+        note_filament_present = runout_helper.note_filament_present
+        def wrapped(*args):
+            if bool(args[-1]) != bool(runout_helper.filament_present):
+                self._record_telemetry("sensor", name=name, present=bool(args[-1]), gate=self.gate_selected)
+            note_filament_present(*args)
+        runout_helper.note_filament_present = wrapped
+
+    def _filament_pos_to_string(self, pos):
+        names = {
+            self.FILAMENT_POS_UNKNOWN: "unknown", self.FILAMENT_POS_UNLOADED: "unloaded",
+            self.FILAMENT_POS_START_BOWDEN: "start_bowden", self.FILAMENT_POS_IN_BOWDEN: "in_bowden",
+            self.FILAMENT_POS_END_BOWDEN: "end_bowden", self.FILAMENT_POS_HOMED_EXTRUDER: "homed_extruder",
+            self.FILAMENT_POS_EXTRUDER_ENTRY: "extruder_entry", self.FILAMENT_POS_HOMED_TS: "homed_ts",
+            self.FILAMENT_POS_IN_EXTRUDER: "in_extruder", self.FILAMENT_POS_LOADED: "loaded",
+        }
+        return names.get(pos, str(pos))
+
     def _set_action(self, action):
         if action == self.action: return
         old_action = self.action
         self.action = action
+        self._record_telemetry("transition", type="action", old=self._get_action_string(old_action), new=self._get_action_string())
+        self._exit_device_phase(self._action_to_phase(old_action))
+        self._enter_device_phase(self._action_to_phase(action))
         gcode = self.printer.lookup_object('gcode_macro _MMU_ACTION_CHANGED', None)
         if gcode is not None:
             self._wrap_gcode_command("_MMU_ACTION_CHANGED ACTION='%s' OLD_ACTION='%s'" % (self._get_action_string(), self._get_action_string(old_action)))
+        self._update_hotend_standby(old_action, action)
         return old_action
 
+    def _action_to_phase(self, action):
+        return {
+            self.ACTION_UNLOADING: self.PHASE_UNLOAD, self.ACTION_UNLOADING_EXTRUDER: self.PHASE_UNLOAD,
+            self.ACTION_FORMING_TIP: self.PHASE_FORM_TIP,
+            self.ACTION_LOADING: self.PHASE_LOAD, self.ACTION_LOADING_EXTRUDER: self.PHASE_LOAD,
+        }.get(action, None)
+
+    # Apply 'phase_device_control' settings for phase remembering current values for restore on exit.
+    # Pulsed devices are restored at the first action change after their duration (or phase exit if sooner)
+    # so the restore is always made in command context
+    def _enter_device_phase(self, phase):
+        for device, value, duration in self.phase_devices.get(phase, []):
+            current = self._get_device_value(device)
+            if current is None:
+                continue
+            self._set_device_value(device, value)
+            expires = self.reactor.monotonic() + duration if duration > 0 else None
+            self.phase_device_restore.append((phase, device, current, expires))
+
+    def _exit_device_phase(self, phase):
+        now = self.reactor.monotonic()
+        for entry in reversed(list(self.phase_device_restore)):
+            p, device, value, expires = entry
+            if p == phase or (expires is not None and now >= expires):
+                self.phase_device_restore.remove(entry)
+                self._set_device_value(device, value)
+
+    @contextlib.contextmanager
+    def _wrap_device_phase(self, phase):
+        self._enter_device_phase(phase)
+        try:
+            yield self
+        finally:
+            self._exit_device_phase(phase)
+
+    def _get_device_value(self, device):
+        obj = self.printer.lookup_object(device, None)
+        if obj is None:
+            self._log_debug("Device '%s' in phase_device_control not found" % device)
+            return None
+        status = obj.get_status(self.reactor.monotonic())
+        return status.get('speed', status.get('value', 0.))
+
+    def _set_device_value(self, device, value):
+        if device == "fan":
+            cmd = "M106 S%d" % int(round(value * 255.)) if value > 0 else "M107"
+        elif device.startswith("fan_generic"):
+            cmd = "SET_FAN_SPEED FAN=%s SPEED=%.3f" % (device.split()[-1], value)
+        else:
+            cmd = "SET_PIN PIN=%s VALUE=%.3f" % (device.split()[-1], value)
+        self._log_trace("Phase device control: %s" % cmd)
+        try:
+            self.gcode.run_script_from_command(cmd)
+        except Exception as e:
+            self._log_error("Error setting '%s' for phase_device_control: %s" % (device, str(e)))
+
     @contextlib.contextmanager
     def _wrap_action(self, new_action):
         old_action = self._set_action(new_action)
@@ -2814,6 +5153,7 @@ class Mmu:
     cmd_MMU_RESET_help = "Forget persisted state and re-initialize defaults"
     def cmd_MMU_RESET(self, gcmd):
         if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
         confirm = gcmd.get_int('CONFIRM', 0, minval=0, maxval=1)
         if confirm != 1:
             self._log_always("You must re-run and add 'CONFIRM=1' to reset all state back to default")
@@ -2824,13 +5164,24 @@ class Mmu:
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_ENABLE_ENDLESS_SPOOL, self.enable_endless_spool))
         self.endless_spool_groups = list(self.default_endless_spool_groups)
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_ENDLESS_SPOOL_GROUPS, self.endless_spool_groups))
+        self.endless_spool_priorities = list(self.default_endless_spool_priorities)
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_ENDLESS_SPOOL_PRIORITIES, self.endless_spool_priorities))
+        self.gate_maintenance = []
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_MAINTENANCE, self.gate_maintenance))
+        self.gate_lock = {}
+        self._persist_gate_lock()
         self.tool_to_gate_map = list(self.default_tool_to_gate_map)
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+        self._record_map_change(self._get_command_source(gcmd))
         self.gate_status = self._validate_gate_status(list(self.default_gate_status))
         self.gate_material = list(self.default_gate_material)
         self._update_gate_color(list(self.default_gate_color))
         self.gate_spool_id = list(self.default_gate_spool_id)
-        self._persist_gate_map()
+        self.gate_pressure_advance = list(self.default_gate_pressure_advance)
+        self.gate_smooth_time = list(self.default_gate_smooth_time)
+        self.gate_temperature = list(self.default_gate_temperature)
+        self.gate_unit = list(self.default_gate_unit)
+        self._persist_gate_map(self._get_command_source(gcmd))
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_GATE_SELECTED, self.gate_selected))
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_TOOL_SELECTED, self.tool_selected))
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_FILAMENT_POS, self.filament_pos))
@@ -3023,7 +5374,7 @@ class Mmu:
                     _,_,m,_ = self._trace_filament_move(msg, self.gate_homing_max)
                     measured += m
                     if (m) > 6.0:
-                        self._set_gate_status(self.gate_selected, max(self.gate_status[self.gate_selected], self.GATE_AVAILABLE)) # Don't reset if filament is buffered
+                        self._set_gate_status(self.gate_selected, max(self.gate_status[self.gate_selected], self.GATE_AVAILABLE), source="load") # Don't reset if filament is buffered
                         self._set_filament_position(measured)
                         self._set_filament_pos_state(self.FILAMENT_POS_START_BOWDEN)
                         return
@@ -3033,13 +5384,30 @@ class Mmu:
                             self._track_gate_statistics('servo_retries', self.gate_selected)
                             self._servo_up()
                             self._servo_down()
+        elif self.gate_homing_endstop == self.ENDSTOP_GATE_TOUCH:
+            with self._wrap_gear_current(self.gate_homing_current, "for gate touch homing"):
+                for i in range(retries):
+                    msg = "Initial touch homing to gate" if i == 0 else ("Retry touch homing to gate #%d" % i)
+                    actual,homed,measured,_ = self._trace_filament_move(msg, self.gate_homing_max, motor="gear", homing_move=1, endstop_name=self.ENDSTOP_GEAR_TOUCH)
+                    if homed:
+                        self._log_debug("Gate stop detected by touch after %.1fmm (measured %.1fmm)" % (actual, measured))
+                        self._set_gate_status(self.gate_selected, max(self.gate_status[self.gate_selected], self.GATE_AVAILABLE), source="load") # Don't reset if filament is buffered
+                        self._initialize_filament_position()
+                        self._set_filament_pos_state(self.FILAMENT_POS_START_BOWDEN)
+                        return
+                    else:
+                        self._log_debug("Error loading filament - gate touch not detected. %s" % ("Retrying..." if i < retries - 1 else ""))
+                        if i < retries - 1:
+                            self._track_gate_statistics('servo_retries', self.gate_selected)
+                            self._servo_up()
+                            self._servo_down()
         else:
             for i in range(retries):
                 msg = "Initial homing to gate sensor" if i == 0 else ("Retry homing to gate sensor #%d" % i)
                 actual,homed,measured,_ = self._trace_filament_move(msg, self.gate_homing_max, motor="gear", homing_move=1, endstop_name=self.ENDSTOP_GATE)
                 if homed:
                     self._log_debug("Gate endstop reached after %.1fmm (measured %.1fmm)" % (actual, measured))
-                    self._set_gate_status(self.gate_selected, max(self.gate_status[self.gate_selected], self.GATE_AVAILABLE)) # Don't reset if filament is buffered
+                    self._set_gate_status(self.gate_selected, max(self.gate_status[self.gate_selected], self.GATE_AVAILABLE), source="load") # Don't reset if filament is buffered
                     self._initialize_filament_position()
                     self._set_filament_pos_state(self.FILAMENT_POS_START_BOWDEN)
                     return
@@ -3050,14 +5418,16 @@ class Mmu:
                         self._servo_up()
                         self._servo_down()
 
-        self._set_gate_status(self.gate_selected, self.GATE_UNKNOWN)
+        self._set_gate_status(self.gate_selected, self.GATE_UNKNOWN, source="load")
         self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
         if adjust_servo_on_error:
             self._servo_auto()
         if self.gate_homing_endstop == self.ENDSTOP_ENCODER:
-            raise MmuError("Error loading filament at gate - not enough movement detected at encoder")
+            raise MmuError("Error loading filament at gate - not enough movement detected at encoder", code=self.ERR_GATE_LOAD_FAIL)
+        elif self.gate_homing_endstop == self.ENDSTOP_GATE_TOUCH:
+            raise MmuError("Error loading filament at gate - gear touch (stallguard) didn't trigger", code=self.ERR_GATE_LOAD_FAIL)
         else:
-            raise MmuError("Error loading filament at gate - gate endstop didn't trigger")
+            raise MmuError("Error loading filament at gate - gate endstop didn't trigger", code=self.ERR_GATE_LOAD_FAIL)
 
     # Unload filament through gate to final MMU park position.
     # Strategies include use of encoder or homing to gate endstop and then parking
@@ -3080,46 +5450,252 @@ class Mmu:
                     delta += sdelta
                     # Large enough delta here means we are out of the encoder
                     if sdelta >= self.encoder_move_step_size * 0.2: # 20 %
-                        park = self.gate_parking_distance - sdelta # will be between 8 and 20mm (for 23mm gate_parking_distance, 15mm step)
+                        park = self._gate_parking_distance() - sdelta # will be between 8 and 20mm (for 23mm gate_parking_distance, 15mm step)
                         self._set_filament_position(self.mmu_toolhead.get_position()[1] + delta)
                         _,_,measured,_ = self._trace_filament_move("Final parking", -park)
                         self._set_filament_position(self.mmu_toolhead.get_position()[1] + park)
                         # We don't expect any movement of the encoder unless it is free-spinning
                         if measured > self.encoder_min: # We expect 0, but relax the test a little (allow one pulse)
                             self._log_info("Warning: Possible encoder malfunction (free-spinning) during final filament parking")
+                        self._verify_gate_park()
+                        self._record_gate_park()
                         self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
                         return
                 self._log_debug("Filament did not clear encoder even after moving %.1fmm" % (self.encoder_move_step_size * max_steps))
+        elif self.gate_homing_endstop == self.ENDSTOP_GATE_TOUCH:
+            # Stallguard cannot detect filament leaving so retract fully then confirm position by touching gate stop
+            self._trace_filament_move("Slow unload to gate", -homing_max)
+            with self._wrap_gear_current(self.gate_homing_current, "for gate touch homing"):
+                _,homed,_,_ = self._trace_filament_move("Touch homing to gate", self.gate_homing_max, motor="gear", homing_move=1, endstop_name=self.ENDSTOP_GEAR_TOUCH)
+            if homed:
+                self._trace_filament_move("Final parking", -self._gate_parking_distance())
+                self._record_gate_park()
+                self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
+                return
+            else:
+                self._log_debug("Did not detect gate stop by touch")
         else:
             _,homed,_,_ = self._trace_filament_move("Reverse homing to gate sensor", -homing_max, motor="gear", homing_move=-1, endstop_name=self.ENDSTOP_GATE)
             if homed:
                 # Final parking step
-                self._trace_filament_move("Final parking", -self.gate_parking_distance)
+                self._trace_filament_move("Final parking", -self._gate_parking_distance())
+                self._verify_gate_park()
+                self._record_gate_park()
                 self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
                 return
             else:
                 self._log_debug("Did not home to gate sensor")
 
-        raise MmuError("Unloading gate failed")
+        raise MmuError("Unloading gate failed", code=self.ERR_GATE_UNLOAD_FAIL)
+
+    # Optional check that parked filament is free. A toolhead cutter can leave a sharp flag that catches in the gate so
+    # the filament doesn't retract to its parking position. A tiny forward and back move must leave the gate sensor clear
+    # and not be seen by the encoder (parked filament is clear of both). If not, park further back and retry
+    def _verify_gate_park(self):
+        if not self.gate_park_verify or (self.gate_park_verify == 1 and self.cutter_location != self.CUTTER_TOOLHEAD): return
+        if self._gate_parking_distance() <= 0: return
+        use_sensor = self.gate_homing_endstop == self.ENDSTOP_GATE and self._has_sensor(self.ENDSTOP_GATE)
+        use_encoder = self.gate_homing_endstop == self.ENDSTOP_ENCODER and self._can_use_encoder()
+        if not (use_sensor or use_encoder): return
+        length = min(self.gate_park_verify_length, self._gate_parking_distance() / 2.)
+        for attempt in range(self.gate_park_verify_retries + 1):
+            _,_,measured_fwd,_ = self._trace_filament_move("Verifying gate park", length)
+            _,_,measured_back,_ = self._trace_filament_move("Verifying gate park", -length)
+            problem = None
+            if use_sensor and self._check_sensor(self.ENDSTOP_GATE):
+                problem = "gate sensor still detects filament"
+            elif use_encoder and measured_fwd + measured_back > self.encoder_min:
+                problem = "encoder registered %.1fmm of movement" % (measured_fwd + measured_back)
+            if problem is None:
+                if attempt > 0:
+                    self._log_info("Filament free at gate after parking an extra %.1fmm" % (attempt * length))
+                return
+            if attempt < self.gate_park_verify_retries:
+                self._log_info("Gate park verification failed (%s). Filament tip may be caught, parking %.1fmm further back..." % (problem, length))
+                self._trace_filament_move("Adjusting gate park", -length)
+        self._log_always("Warning: Unable to verify filament at gate #%d is parked and free (%s). Check the filament tip" % (self.gate_selected, problem))
+
+    # Hub or gate mounted cutter. With filament parked, push the tip that was deformed in the extruder 'cutter_tip_length'
+    # past the blade, cut it off with 'cutter_macro' and park again. The new filament end is at the blade so the final
+    # retract is shorter by the length that was cut and the filament ends up at the usual parking position. A stub that
+    # stays in the filament path is recorded against the gate so the next load can account for it
+    def _cut_filament_after_park(self):
+        if self.cutter_location == self.CUTTER_TOOLHEAD:
+            return
+        if self.printer.lookup_object("gcode_macro %s" % self.cutter_macro, None) is None:
+            raise MmuError("Cutter macro '%s' not found but 'cutter_location' is '%s'" % (self.cutter_macro, self.cutter_location), code=self.ERR_CONFIG)
+        self._log_info("Cutting filament tip at %s cutter..." % self.cutter_location)
+        park = self._gate_parking_distance()
+        self._trace_filament_move("Positioning tip past %s cutter" % self.cutter_location, park + self.cutter_distance + self.cutter_tip_length)
+        self._wrap_gcode_command("%s GATE=%d LOCATION=%s" % (self.cutter_macro, self.gate_selected, self.cutter_location), exception=True)
+        self._count_cutter_blade_use()
+        self._trace_filament_move("Parking cut filament", -(park + self.cutter_distance))
+        self.filament_remaining = 0.
+        if self.cutter_stub_length > 0:
+            self.gate_stub_length[self.gate_selected] = self.cutter_stub_length
+            self._persist_gate_stub_length()
+
+    def _count_cutter_blade_use(self):
+        self.cutter_blade['cuts'] += 1
+        self.cutter_blade['total_cuts'] += 1
+        if self.cutter_blade_life > 0 and self.cutter_blade['cuts'] == self.cutter_blade_life:
+            self._log_always("Warning: Cutter blade has reached its replacement threshold of %d cuts. After replacing run MMU_CALIBRATE_CUTTER NEW_BLADE=1" % self.cutter_blade_life)
+            self._publish_event("cutter_blade_worn", cuts=self.cutter_blade['cuts'])
+
+    # The stub left by a cutter is pushed ahead of the filament on the next load. A gate cutter leaves it in the path
+    # of that gate only whereas a hub cutter leaves it in the shared path so any gate consumes it
+    def _consume_gate_stub(self):
+        self.loaded_stub = (self.TOOL_GATE_UNKNOWN, 0.)
+        gates = [self.gate_selected] if self.cutter_location == self.CUTTER_GATE else range(self.mmu_num_gates)
+        stub_gate = next((g for g in gates if self.gate_stub_length[g] > 0), self.TOOL_GATE_UNKNOWN)
+        if stub_gate < 0:
+            return 0.
+        self.loaded_stub = (stub_gate, self.gate_stub_length[stub_gate])
+        for g in gates:
+            self.gate_stub_length[g] = 0.
+        self._persist_gate_stub_length()
+        self._log_debug("Cutter stub of %.1fmm (from gate #%d) pushed ahead of filament" % (self.loaded_stub[1], stub_gate))
+        return self.loaded_stub[1]
+
+    # Extra purge needed for a stub of different filament pushed through by the last load
+    def _calc_stub_purge_volume(self, to_gate):
+        stub_gate, stub_length = self.loaded_stub
+        self.loaded_stub = (self.TOOL_GATE_UNKNOWN, 0.)
+        if self.purge_volume <= 0. or stub_gate < 0 or stub_gate == to_gate:
+            return 0.
+        return stub_length * self.printer.lookup_object(self.extruder_name).filament_area
+
+    # On designs without a selector (separate gear motor per gate) the next gate of a toolchange can start its
+    # gate-to-hub feed whilst the previous filament is still retracting from the hub. Returns the next gate and the
+    # length of the bowden retract that can be overlapped. The feed is limited to the next gate's parking distance
+    # so the filaments don't meet in the hub (not possible with a hub or gate cutter because the filament must be
+    # advanced again to cut)
+    def _get_toolchange_overlap(self, length):
+        next_gate, self.overlap_next_gate = self.overlap_next_gate, self.TOOL_GATE_UNKNOWN
+        if (self.toolchange_overlap_length <= 0 or self.vendor_driver.HAS_SELECTOR or next_gate < 0 or next_gate == self.gate_selected
+                or self.gate_status[next_gate] == self.GATE_EMPTY or self.cutter_location != self.CUTTER_TOOLHEAD):
+            return next_gate, 0.
+        next_park = self.gate_ready_park[next_gate] if self.gate_ready_park[next_gate] > 0 and self._is_in_print() else self.gate_parking_distance
+        return next_gate, max(min(self.toolchange_overlap_length, next_park, length), 0.)
+
+    def _overlap_next_gate_feed(self, next_gate, overlap):
+        if self.mmu_toolhead.select_opposed_gear_steppers(next_gate, self.gate_selected):
+            try:
+                self._trace_filament_move("Overlapped bowden unload and gate #%d feed" % next_gate, overlap, motor="gear")
+            finally:
+                self.mmu_toolhead.select_gear_stepper(self.gate_selected)
+        else:
+            self._trace_filament_move("Course unloading move from bowden", -overlap)
+
+    # During a print gates with a 'gate_ready_park' distance are parked just behind the gate sensor/encoder so
+    # the next load of the gate is quicker. They are returned to 'gate_parking_distance' after the print
+    def _gate_parking_distance(self):
+        gate = self.gate_selected
+        if gate >= 0 and self.gate_ready_park[gate] > 0 and self._is_in_print():
+            return self.gate_ready_park[gate]
+        return self.gate_parking_distance
+
+    def _record_gate_park(self):
+        if self._gate_parking_distance() != self.gate_parking_distance:
+            self.ready_parked_gates.add(self.gate_selected)
+        else:
+            self.ready_parked_gates.discard(self.gate_selected)
+
+    # At print start move unloaded gates used by the print (all if unknown) forward to their 'gate_ready_park'
+    # position so even the first load of each gate is quick. Skipped if filament is loaded
+    def _ready_park_gates(self, tools=None):
+        if not self.is_homed or self.filament_pos != self.FILAMENT_POS_UNLOADED: return
+        gates = range(self.mmu_num_gates) if tools is None else sorted(set(self.tool_to_gate_map[t] for t in tools))
+        gates = [g for g in gates if g >= 0 and self.gate_ready_park[g] > 0 and g not in self.ready_parked_gates and self.gate_status[g] != self.GATE_EMPTY]
+        if not gates: return
+        self._log_info("Moving filament at gates %s to ready park position" % ", ".join(map(str, gates)))
+        initial_gate = self.gate_selected
+        with self._wrap_disable_encoder():
+            try:
+                for gate in gates:
+                    self._select_gate(gate)
+                    self._servo_down()
+                    self._trace_filament_move("Ready park", self.gate_parking_distance - self.gate_ready_park[gate], speed=self.gear_short_move_speed)
+                    self._servo_up()
+                    self.ready_parked_gates.add(gate)
+                if initial_gate >= 0 or initial_gate == self.TOOL_GATE_BYPASS:
+                    self._select_gate(initial_gate)
+            except MmuError as ee:
+                self._log_error("Move to ready park failed: %s" % str(ee))
+            finally:
+                self._servo_auto()
 
     # Shared gate functions to deduplicate logic
     def _validate_gate_config(self, direction):
         if self.gate_homing_endstop == self.ENDSTOP_ENCODER:
             if not self._has_encoder():
-                raise MmuError("Attempting to %s encoder but encoder is not configured on MMU!" % direction)
+                raise MmuError("Attempting to %s encoder but encoder is not configured on MMU!" % direction, code=self.ERR_CONFIG)
         elif self.gate_homing_endstop == self.ENDSTOP_GATE:
             if not self._has_sensor(self.ENDSTOP_GATE):
-                raise MmuError("Attempting to %s gate but gate sensor '%s' is not configured on MMU!" % (direction, self.ENDSTOP_GATE))
+                raise MmuError("Attempting to %s gate but gate sensor '%s' is not configured on MMU!" % (direction, self.ENDSTOP_GATE), code=self.ERR_CONFIG)
+        elif self.gate_homing_endstop == self.ENDSTOP_GATE_TOUCH:
+            if self.gear_rail.get_extra_endstop(self.ENDSTOP_GEAR_TOUCH) is None:
+                raise MmuError("Attempting to %s gate by touch but '%s' endstop is not configured on gear stepper!" % (direction, self.ENDSTOP_GEAR_TOUCH), code=self.ERR_CONFIG)
         else:
-            raise MmuError("Unsupported gate endstop")
+            raise MmuError("Unsupported gate endstop", code=self.ERR_CONFIG)
 
     # Fast load of filament in bowden, optionally to the end
     # Handles setting of cailbration ratio if not set
+    # Fast bowden move. If stall detection is enabled and gate has a StallGuard baseline the gear driver is sampled
+    # throughout the move and disabled on a jam to avoid grinding filament for the full length. If segment checks are
+    # enabled the move is also broken into checkpointed segments. Returns encoder delta
+    def _bowden_move(self, trace_str, dist, encoder_dwell=False, code=None):
+        baseline = self._get_gear_stall_baseline(self.gate_selected) if self.bowden_stall_detection and not self.calibrating else None
+        checks = self.bowden_segment_checks and not self.calibrating
+        if not baseline and not checks:
+            _,_,_,delta = self._trace_filament_move(trace_str, dist, track=True, encoder_dwell=encoder_dwell)
+            return delta
+        segment_length = self._get_bowden_segment_length(dist) if checks else abs(dist)
+        moved = delta = 0.
+        while moved < abs(dist) - 0.01:
+            segment = min(segment_length, abs(dist) - moved)
+            with self._wrap_gear_stall_sampling(baseline) if baseline else contextlib.nullcontext():
+                _,_,_,seg_delta = self._trace_filament_move("%s (segment)" % trace_str if checks else trace_str, math.copysign(segment, dist), track=True, encoder_dwell=encoder_dwell, wait=True)
+            if baseline and self.gear_stall_tripped:
+                self._set_filament_pos_state(self.FILAMENT_POS_IN_BOWDEN)
+                raise MmuError("Gear stall detected (StallGuard) during %.1fmm bowden move. Filament appears to be jammed" % abs(dist), code=self.ERR_GEAR_STALL)
+            moved += segment
+            delta += seg_delta
+            if checks and moved < abs(dist) - 0.01:
+                self._check_bowden_segment(moved, segment, seg_delta, dist > 0, code)
+        return delta
+
+    # Length of each checkpointed bowden segment. Every stop costs roughly speed/accel seconds of extra ramping, so the
+    # nominal 'bowden_segment_length' is stretched until that is within 'bowden_segment_max_overhead' % of the move time.
+    # The move is then divided into equal segments so there is no short tail
+    def _get_bowden_segment_length(self, dist):
+        if self.gate_selected >= 0 and self.gate_status[self.gate_selected] != self.GATE_AVAILABLE_FROM_BUFFER and dist > 0:
+            speed, accel = self.gear_from_spool_speed, self.gear_from_spool_accel
+        else:
+            speed, accel = self.gear_from_buffer_speed, self.gear_from_buffer_accel
+        min_length = speed ** 2 / (accel * self.bowden_segment_max_overhead / 100.)
+        segments = max(1, int(abs(dist) // max(self.bowden_segment_length, min_length)))
+        return abs(dist) / segments
+
+    # Mid-move checkpoint: abort the remainder of the bowden move if the encoder didn't follow the last segment
+    # or (when loading) a path sensor disagrees with where the filament should be
+    def _check_bowden_segment(self, moved, segment, seg_delta, loading, code):
+        if self._can_use_encoder() and seg_delta >= segment * (self.bowden_move_error_tolerance / 100.):
+            self._set_filament_pos_state(self.FILAMENT_POS_IN_BOWDEN)
+            raise MmuError("Bowden %s aborted after %.1fmm. Encoder only measured %.1fmm of last %.1fmm segment. Filament appears to be stuck" % (
+                "load" if loading else "unload", moved, segment - seg_delta, segment), code=code)
+        if loading:
+            self._verify_path_sensors(self.mmu_toolhead.get_position()[1], code)
+        self._log_trace("Bowden checkpoint passed after %.1fmm" % moved)
+
     def _load_bowden(self, length):
         if length <= 0: return
         if self.calibrated_bowden_length > 0 and not self.calibrating:
             length = min(length, self.calibrated_bowden_length)
         full = length == self.calibrated_bowden_length
+        if full and not self.calibrating and self.bowden_live_adjustment != 0.:
+            self._log_debug("Applying live bowden adjustment of %.1fmm" % self.bowden_live_adjustment)
+            length += self.bowden_live_adjustment
         length -= self.mmu_toolhead.get_position()[1]
 
         self._log_debug("Loading bowden tube")
@@ -3137,12 +5713,16 @@ class Mmu:
             self._log_info("Warning: Gate #%d not calibrated! Using default 1.0 gear ratio!" % self.gate_selected)
 
         # "Fast" load
-        _,_,_,delta = self._trace_filament_move("Course loading move into bowden", length, track=True, encoder_dwell=reference_load)
+        delta = self._bowden_move("Course loading move into bowden", length, encoder_dwell=reference_load, code=self.ERR_BOWDEN_LOAD_FAIL)
         delta -= self._get_encoder_dead_space()
 
         # Encoder based validation test
         if self._can_use_encoder() and delta >= length * (self.bowden_move_error_tolerance/100.) and not self.calibrating:
-            raise MmuError("Failed to load bowden. Perhaps filament is stuck in gate. Gear moved %.1fmm, Encoder delta %.1fmm" % (length, delta))
+            raise MmuError("Failed to load bowden. Perhaps filament is stuck in gate. Gear moved %.1fmm, Encoder delta %.1fmm" % (length, delta), code=self.ERR_BOWDEN_LOAD_FAIL)
+
+        # Observe actual gate ratio on full bowden moves (measured relative to current ratio)
+        if full and not reference_load and self._can_use_encoder() and current_ratio and not self.calibrating:
+            self._track_gate_ratio(self.gate_selected, current_ratio * (length - delta) / length)
 
         if reference_load:
             ratio = (length - delta) / length
@@ -3151,6 +5731,7 @@ class Mmu:
                 self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s%d VALUE=%.6f" % (self.VARS_MMU_CALIB_PREFIX, self.gate_selected, ratio))
                 self._log_always("Calibration ratio for gate #%d was missing. Value of %.6f has been automatically saved" % (self.gate_selected, ratio))
                 self._set_gate_ratio(ratio)
+                self._reset_gate_ratio_drift(self.gate_selected, ratio)
 
         # Encoder based validation test
         elif self._can_use_encoder() and delta >= tolerance and not self.calibrating and current_ratio:
@@ -3174,6 +5755,8 @@ class Mmu:
 
         self._random_failure()
         self._movequeues_wait_moves()
+        if not self.calibrating:
+            self._verify_path_sensors(self.mmu_toolhead.get_position()[1], self.ERR_BOWDEN_LOAD_FAIL)
         if full:
             self._set_filament_pos_state(self.FILAMENT_POS_END_BOWDEN)
         elif not self.filament_pos == self.FILAMENT_POS_IN_BOWDEN:
@@ -3201,13 +5784,16 @@ class Mmu:
                 _,_,_,delta = self._trace_filament_move("Bowden pre-unload test", -self.encoder_move_step_size)
                 if delta > self.encoder_move_step_size * (self.bowden_pre_unload_error_tolerance/100.):
                     self._set_filament_pos_state(self.FILAMENT_POS_EXTRUDER_ENTRY)
-                    raise MmuError("Bowden pre-unload test failed. Filament seems to be stuck in the extruder")
+                    raise MmuError("Bowden pre-unload test failed. Filament seems to be stuck in the extruder", code=self.ERR_EXTRUDER_STUCK)
                 length -= self.encoder_move_step_size
                 self._set_filament_pos_state(self.FILAMENT_POS_IN_BOWDEN)
 
-        # "Fast" unload
-        _,_,_,delta = self._trace_filament_move("Course unloading move from bowden", -length, track=True)
+        # "Fast" unload with the last part optionally overlapped with the next gate's initial feed
+        next_gate, overlap = self._get_toolchange_overlap(length)
+        delta = self._bowden_move("Course unloading move from bowden", -(length - overlap), code=self.ERR_FILAMENT_STUCK)
         delta -= self._get_encoder_dead_space()
+        if overlap > 0:
+            self._overlap_next_gate_feed(next_gate, overlap)
 
         # Encoder based validation test
         if self._can_use_encoder() and delta >= tolerance and not self.calibrating:
@@ -3216,6 +5802,11 @@ class Mmu:
 
         self._random_failure()
         self._movequeues_wait_moves()
+        if full and not self.calibrating:
+            self._verify_path_sensors(self.gate_unload_buffer, self.ERR_FILAMENT_STUCK)
+            # Filament that has separated from the spool (e.g. runout) can be beyond reach of the gear and left in the bowden
+            if self._check_sensor(self.ENDSTOP_EXTRUDER):
+                raise MmuError("Filament still detected at extruder entrance after unloading bowden. Possibly a runout fragment out of reach of the gear", code=self.ERR_FILAMENT_STUCK)
         if full:
             self._set_filament_pos_state(self.FILAMENT_POS_START_BOWDEN)
         elif not self.filament_pos == self.FILAMENT_POS_IN_BOWDEN:
@@ -3228,21 +5819,155 @@ class Mmu:
 
         if self.extruder_homing_endstop != self.ENDSTOP_EXTRUDER_COLLISION:
             self._log_debug("Homing to extruder '%s' endstop, up to %.1fmm" % (self.extruder_homing_endstop, max_length))
-            actual,homed,measured,_ = self._trace_filament_move("Homing filament to extruder", max_length, motor="gear", homing_move=1, endstop_name=self.extruder_homing_endstop)
+            if self._is_soft_filament(self.gate_selected):
+                actual,homed,measured = self._nibble_move("Soft homing filament to extruder", max_length, motor="gear", endstop_name=self.extruder_homing_endstop)
+            else:
+                actual,homed,measured,_ = self._trace_filament_move("Homing filament to extruder", max_length, motor="gear", homing_move=1, endstop_name=self.extruder_homing_endstop)
             if homed:
                 self._log_debug("Extruder entrance reached after %.1fmm (measured %.1fmm)" % (actual, measured))
         else:
             actual,homed,measured,_ = self._home_to_extruder_collision_detection(max_length)
+            if not homed and self.extruder_homing_current_revalidate and not self.calibrating:
+                actual,homed,measured,_ = self._revalidate_collision_current(max_length, actual)
 
         if not homed:
             self._set_filament_pos_state(self.FILAMENT_POS_END_BOWDEN)
-            raise MmuError("Failed to reach extruder gear after moving %.1fmm" % max_length)
+            raise MmuError("Failed to reach extruder gear after moving %.1fmm" % max_length, code=self.ERR_EXTRUDER_HOME_TIMEOUT)
 
         if measured > (max_length * 0.8):
             self._log_info("Warning: 80%% of 'extruder_homing_max' was used homing. You may want to adjust your calibrated bowden length ('%s') or increase 'extruder_homing_max'" % self.VARS_MMU_CALIB_BOWDEN_LENGTH)
 
+        self._track_extruder_homing_distance(measured if self._can_use_encoder() else actual)
+        if self.extruder_homing_endstop == self.ENDSTOP_EXTRUDER:
+            self._check_virtual_encoder_transition(self.ENDSTOP_EXTRUDER)
         self._set_filament_pos_state(self.FILAMENT_POS_HOMED_EXTRUDER)
 
+    # Encoder-less validation of the load from the distance needed to reach a sensor (see mmu_virtual_encoder.py). Only
+    # warns because homing to the sensor has already positioned the filament correctly
+    def _check_virtual_encoder_transition(self, sensor):
+        if not self.virtual_encoder or self.calibrating or self.gate_selected < 0: return
+        commanded = self.mmu_toolhead.get_position()[1] # Filament position relative to gate
+        measured, delta = self.virtual_encoder.measure(self.gate_selected, sensor, commanded)
+        self._log_debug("Virtual encoder: '%s' sensor reached after %.1fmm (measured %.1fmm, delta %.1fmm)" % (sensor, commanded, measured, delta))
+        if delta > 0 and delta >= self.bowden_allowable_load_delta:
+            self.virtual_encoder.slip_events += 1
+            self._log_info("Warning: Excess slippage was detected loading gate #%d (virtual encoder). Filament needed %.1fmm more movement than usual to reach '%s' sensor" % (self.gate_selected, delta, sensor))
+            self._publish_event("gear_slip", gate=self.gate_selected, length=round(delta, 1))
+
+    def _reset_bowden_live_adjustment(self):
+        self.bowden_live_adjustment = 0.
+        self.bowden_homing_samples = []
+        self.bowden_homing_baseline = None
+
+    # Fast in-job corrector for the bowden endpoint. The first 'bowden_live_adjust_samples' extruder homing distances
+    # establish a baseline and thereafter the rolling average drift from that baseline is applied to the bowden move,
+    # bounded by 'bowden_live_adjust_max'. Nothing is persisted - calibrated bowden length is left for slower calibration
+    def _track_extruder_homing_distance(self, distance):
+        if not self.bowden_live_adjust or self.calibrating or not self._is_in_print():
+            return
+        n = self.bowden_live_adjust_samples
+        # Record distance as if no adjustment had been applied so the trend is not self-referential
+        self.bowden_homing_samples.append(distance + self.bowden_live_adjustment)
+        self.bowden_homing_samples = self.bowden_homing_samples[-n * 2:]
+        if self.bowden_homing_baseline is None:
+            if len(self.bowden_homing_samples) >= n:
+                self.bowden_homing_baseline = sum(self.bowden_homing_samples[:n]) / n
+                self._log_debug("Live bowden adjustment baseline homing distance: %.1fmm" % self.bowden_homing_baseline)
+            return
+        recent = self.bowden_homing_samples[-n:]
+        drift = sum(recent) / len(recent) - self.bowden_homing_baseline
+        adjustment = max(-self.bowden_live_adjust_max, min(self.bowden_live_adjust_max, drift))
+        if abs(adjustment - self.bowden_live_adjustment) >= 0.1:
+            self._log_debug("Live bowden adjustment changed from %.1fmm to %.1fmm (homing drift %.1fmm)" % (self.bowden_live_adjustment, adjustment, drift))
+            self.bowden_live_adjustment = adjustment
+
+    # Continue retracting a parked filament until the pre-gate sensor clears plus 'gate_final_eject_margin' so the
+    # spool is fully rewound and the gate free. Optional '_MMU_REWIND_ASSIST' macro can drive a rewinder/espooler
+    def _auto_final_eject(self, gate, gate_swap=False):
+        if gate < 0 or self.gate_final_eject[gate] == 0 or (gate_swap and self.gate_final_eject[gate] < 2):
+            return
+        if self.filament_pos != self.FILAMENT_POS_UNLOADED:
+            return
+        sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+        if sensor is None:
+            self._log_debug("Final eject of gate #%d skipped because it has no pre-gate sensor" % gate)
+            return
+        if not sensor.runout_helper.filament_present:
+            return
+
+        self._log_info("Final eject of gate #%d until clear of pre-gate sensor..." % gate)
+        self._select_gate(gate)
+        self.final_eject_gates.add(gate)
+        assist = self.printer.lookup_object('gcode_macro _MMU_REWIND_ASSIST', None) is not None
+        if assist:
+            self._ramp_rewind_assist(gate, 0., self.espooler_rewind_pwm, exception=True)
+        self._servo_down()
+        try:
+            ejected, step = 0., 10.
+            while sensor.runout_helper.filament_present and ejected < self.gate_final_eject_max:
+                self._trace_filament_move("Final eject", -step, wait=True)
+                ejected += step
+            if sensor.runout_helper.filament_present:
+                self.final_eject_gates.discard(gate)
+                raise MmuError("Filament still detected by pre-gate sensor after final eject of %.1fmm from gate #%d" % (ejected, gate), code=self.ERR_FILAMENT_STUCK)
+            if self.gate_final_eject_margin > 0.:
+                self._trace_filament_move("Final eject margin", -self.gate_final_eject_margin)
+            self._log_debug("Gate #%d clear after final eject of %.1fmm" % (gate, ejected + self.gate_final_eject_margin))
+        finally:
+            self._servo_up()
+            if assist:
+                self._ramp_rewind_assist(gate, self.espooler_rewind_pwm, 0.)
+
+    # Soft start/stop of the rewind assist (espooler) so light spools aren't yanked. The PWM passed to the
+    # '_MMU_REWIND_ASSIST' macro is stepped over 'espooler_ramp_time' and limited to 'espooler_max_dpwm' change
+    # per second, both scaled by the gate's 'espooler_spool_inertia'. Without either the level is set directly
+    def _ramp_rewind_assist(self, gate, from_pwm, to_pwm, exception=False):
+        inertia = self.espooler_spool_inertia[gate]
+        duration = self.espooler_ramp_time * inertia
+        if self.espooler_max_dpwm > 0.:
+            duration = max(duration, abs(to_pwm - from_pwm) * inertia / self.espooler_max_dpwm)
+        steps = max(int(math.ceil(duration / self.ESPOOLER_RAMP_INTERVAL)), 1)
+        for i in range(1, steps + 1):
+            if i > 1:
+                self._movequeues_dwell(duration / steps)
+            pwm = from_pwm + (to_pwm - from_pwm) * i / steps
+            self._wrap_gcode_command("_MMU_REWIND_ASSIST GATE=%d ENABLE=%d PWM=%.3f" % (gate, 1 if pwm > 0. else 0, pwm), exception=exception)
+
+    def _is_soft_filament(self, gate):
+        if gate < 0:
+            return False
+        return gate in self.soft_load_gates or self.gate_material[gate].upper() in self.soft_load_materials
+
+    # Soft filaments buckle if pushed against a resistance in one long move. Advance in short low speed nibbles at reduced
+    # gear current, verifying progress with encoder (if fitted) and briefly relieving tension between each nibble
+    def _nibble_move(self, trace_str, max_length, motor="gear", endstop_name=None):
+        actual = measured = 0.
+        homed = False
+        stalls = 0
+        with self._wrap_gear_current(self.soft_load_current, "for soft filament loading"):
+            while actual < max_length:
+                step = min(self.soft_load_nibble_length, max_length - actual)
+                if endstop_name:
+                    sactual,homed,smeasured,_ = self._trace_filament_move(trace_str, step, speed=self.soft_load_speed, motor=motor, homing_move=1, endstop_name=endstop_name, encoder_dwell=True)
+                else:
+                    sactual,_,smeasured,_ = self._trace_filament_move(trace_str, step, speed=self.soft_load_speed, motor=motor, encoder_dwell=True)
+                actual += sactual
+                measured += smeasured
+                if homed:
+                    break
+                if self._can_use_encoder() and smeasured < sactual * 0.5:
+                    stalls += 1
+                    if stalls > self.soft_load_retries:
+                        self._log_debug("Soft filament stopped advancing after %.1fmm" % actual)
+                        break
+                    self._log_debug("Soft filament not advancing (measured %.1fmm of %.1fmm). Relieving tension and retrying" % (smeasured, sactual))
+                else:
+                    stalls = 0
+                if self.soft_load_relief > 0.:
+                    self._trace_filament_move("Soft load tension relief", -self.soft_load_relief, speed=self.soft_load_speed, motor=motor)
+                    self._trace_filament_move("Soft load tension restore", self.soft_load_relief, speed=self.soft_load_speed, motor=motor)
+        return actual, homed, measured
+
     # Special extruder homing option for detecting the collision base on lack of encoder movement
     def _home_to_extruder_collision_detection(self, max_length):
         # Lock the extruder stepper
@@ -3296,19 +6021,31 @@ class Mmu:
             homed = False
             if self._has_sensor(self.ENDSTOP_TOOLHEAD):
                 # With toolhead sensor we first home to toolhead sensor past the extruder entrance
-                if self.sensors[self.ENDSTOP_TOOLHEAD].runout_helper.filament_present:
-                    raise MmuError("Possible toolhead sensor malfunction - filament detected before it entered extruder")
+                if self._check_sensor(self.ENDSTOP_TOOLHEAD):
+                    raise MmuError("Possible toolhead sensor malfunction - filament detected before it entered extruder", code=self.ERR_TOOLHEAD_SENSOR)
                 self._log_debug("Homing up to %.1fmm to toolhead sensor%s" % (self.toolhead_homing_max, (" (synced)" if synced else "")))
-                _,homed,_,_ = self._trace_filament_move("Homing to toolhead sensor", self.toolhead_homing_max, motor=motor, homing_move=1, endstop_name=self.ENDSTOP_TOOLHEAD)
+                if synced and self._is_soft_filament(self.gate_selected):
+                    _,homed,_ = self._nibble_move("Soft homing to toolhead sensor", self.toolhead_homing_max, motor=motor, endstop_name=self.ENDSTOP_TOOLHEAD)
+                else:
+                    _,homed,_,_ = self._trace_filament_move("Homing to toolhead sensor", self.toolhead_homing_max, motor=motor, homing_move=1, endstop_name=self.ENDSTOP_TOOLHEAD)
+                    if not homed:
+                        homed = self._retry_home_to_toolhead_sensor(motor)
                 if homed:
+                    if not extruder_only:
+                        self._check_virtual_encoder_transition(self.ENDSTOP_TOOLHEAD)
                     self._set_filament_pos_state(self.FILAMENT_POS_HOMED_TS)
                 else:
                     self._set_filament_pos_state(self.FILAMENT_POS_EXTRUDER_ENTRY) # But could also still be POS_IN_BOWDEN!
-                    raise MmuError("Failed to reach toolhead sensor after moving %.1fmm" % self.toolhead_homing_max)
+                    raise MmuError("Failed to reach toolhead sensor after moving %.1fmm" % self.toolhead_homing_max, code=self.ERR_TOOLHEAD_HOME_TIMEOUT)
 
             # Length may be reduced by previous unload in filament cutting use case. Ensure it is used only one time
             length = max(self._get_home_position_to_nozzle() - self.filament_remaining, 0)
             self.filament_remaining = 0.
+
+            # A cutter stub ahead of the filament triggers the toolhead sensor itself but otherwise makes the move long
+            stub = self._consume_gate_stub() if not extruder_only else 0.
+            if stub > 0 and not homed:
+                length = max(length - stub, 0)
             self._log_debug("Loading last %.1fmm to the nozzle..." % length)
             _,_,measured,delta = self._trace_filament_move("Loading filament to nozzle", length, speed=speed, motor=motor, wait=True)
 
@@ -3318,16 +6055,31 @@ class Mmu:
             # Encoder based validation test
             if self._can_use_encoder() and not homed:
                 if measured < self.encoder_min:
-                    raise MmuError("Move to nozzle failed (encoder didn't sense any movement). Extruder may not have picked up filament or filament did not home correctly")
+                    raise MmuError("Move to nozzle failed (encoder didn't sense any movement). Extruder may not have picked up filament or filament did not home correctly", code=self.ERR_NOZZLE_LOAD_FAIL)
                 elif delta > length * (self.toolhead_move_error_tolerance/100.):
                     self._set_filament_pos_state(self.FILAMENT_POS_IN_EXTRUDER)
-                    raise MmuError("Move to nozzle failed (encoder didn't sense sufficient movement). Extruder may not have picked up filament or filament did not home correctly")
+                    raise MmuError("Move to nozzle failed (encoder didn't sense sufficient movement). Extruder may not have picked up filament or filament did not home correctly", code=self.ERR_NOZZLE_LOAD_FAIL)
 
             self._random_failure()
             self._movequeues_wait_moves()
             self._set_filament_pos_state(self.FILAMENT_POS_LOADED)
             self._log_debug("Filament should loaded to nozzle")
 
+    # Recovery for filament tip catching in the extruder gear path on the way to the toolhead sensor. Retract a little,
+    # jog back and forth to re-orient the tip and home again (allowing for the extra retracted distance)
+    def _retry_home_to_toolhead_sensor(self, motor):
+        for attempt in range(self.toolhead_homing_retries):
+            self._log_info("Failed to reach toolhead sensor. Retracting and retrying (attempt %d of %d)..." % (attempt + 1, self.toolhead_homing_retries))
+            self._trace_filament_move("Retracting to free filament tip", -self.toolhead_homing_retract, motor=motor)
+            if self.toolhead_homing_jog > 0:
+                self._trace_filament_move("Jogging to re-orient filament tip", -self.toolhead_homing_jog, motor=motor)
+                self._trace_filament_move("Jogging to re-orient filament tip", self.toolhead_homing_jog, motor=motor)
+            _,homed,_,_ = self._trace_filament_move("Retry homing to toolhead sensor", self.toolhead_homing_max + self.toolhead_homing_retract, motor=motor, homing_move=1, endstop_name=self.ENDSTOP_TOOLHEAD)
+            if homed:
+                self._log_info("Toolhead sensor reached after %d retry(s)" % (attempt + 1))
+                return True
+        return False
+
     # Extract filament past extruder gear (to end of bowden). Assume that tip has already been formed
     # and we are parked somewhere in the extruder either by slicer or by stand alone tip creation
     def _unload_extruder(self, extruder_only=False, park_pos=0.):
@@ -3352,7 +6104,7 @@ class Mmu:
             ts_actual = 0.
             if self._has_sensor(self.ENDSTOP_TOOLHEAD):
                 # With toolhead sensor we first home to toolhead sensor past the extruder entrance
-                if not self.sensors[self.ENDSTOP_TOOLHEAD].runout_helper.filament_present:
+                if not self._check_sensor(self.ENDSTOP_TOOLHEAD):
                     self._log_info("Warning: Filament was not detected in extruder by toolhead sensor at start of extruder unload")
                     homed = True
                 else:
@@ -3361,7 +6113,7 @@ class Mmu:
                 if homed:
                     self._set_filament_pos_state(self.FILAMENT_POS_HOMED_TS)
                 else:
-                    raise MmuError("Failed to reach toolhead sensor after moving %.1fmm" % length)
+                    raise MmuError("Failed to reach toolhead sensor after moving %.1fmm" % length, code=self.ERR_TOOLHEAD_HOME_TIMEOUT)
 
                 if self.toolhead_sensor_to_nozzle > 0. and self.toolhead_extruder_to_nozzle > 0.:
                     length = self.toolhead_extruder_to_nozzle - self.toolhead_sensor_to_nozzle + self.toolhead_unload_safety_margin
@@ -3380,10 +6132,10 @@ class Mmu:
             # Encoder based validation test
             if self._can_use_encoder() and self._is_in_print() and not homed:
                 if measured < self.encoder_min:
-                    raise MmuError("Encoder not sensing any movement: Concluding filament either stuck in the extruder or tip forming erroneously ejected filament")
+                    raise MmuError("Encoder not sensing any movement: Concluding filament either stuck in the extruder or tip forming erroneously ejected filament", code=self.ERR_EXTRUDER_STUCK)
                 elif synced and delta > length * (self.toolhead_move_error_tolerance/100.):
                     self._set_filament_pos_state(self.FILAMENT_POS_EXTRUDER_ENTRY)
-                    raise MmuError("Encoder not sensing sufficent movement: Concluding filament either stuck in the extruder or tip forming erroneously ejected filament")
+                    raise MmuError("Encoder not sensing sufficent movement: Concluding filament either stuck in the extruder or tip forming erroneously ejected filament", code=self.ERR_EXTRUDER_STUCK)
 
             self._random_failure()
             self._movequeues_wait_moves()
@@ -3391,6 +6143,45 @@ class Mmu:
             self._log_debug("Filament should be out of extruder")
 
 
+    # With a cutter a failed cut can leave a fragment between the toolhead sensor and nozzle that will break the next load.
+    # If the toolhead sensor doesn't clear after unloading cut filament, push the fragment out of the nozzle with the
+    # remaining filament, cut again and retry the unload before declaring an error
+    def _push_fragment_from_nozzle(self):
+        self._ensure_safe_extruder_temperature(wait=True)
+        self._servo_down()
+        length = (self.toolhead_extruder_to_nozzle or self._get_home_position_to_nozzle()) + self.toolhead_unload_safety_margin + self.cut_recovery_purge_length
+        self._trace_filament_move("Pushing fragment out of nozzle", length, speed=self.extruder_sync_load_speed, motor="gear+extruder", wait=True)
+        self._set_filament_pos_state(self.FILAMENT_POS_LOADED)
+
+    def _unload_extruder_with_cut_recovery(self, park_pos):
+        check = self.tip_was_cut and self.cut_recovery_retries > 0 and self._has_sensor(self.ENDSTOP_TOOLHEAD)
+        try:
+            self._unload_extruder(park_pos=park_pos)
+        except MmuError as ee:
+            if not check or ee.code != self.ERR_TOOLHEAD_HOME_TIMEOUT:
+                raise
+            self._log_debug("Toolhead sensor did not clear after cut: %s" % str(ee))
+        if not check or not self._check_sensor(self.ENDSTOP_TOOLHEAD):
+            self._set_filament_pos_state(self.FILAMENT_POS_END_BOWDEN, silent=True)
+            return
+
+        for attempt in range(self.cut_recovery_retries):
+            self._log_info("Filament fragment detected in extruder after cut. Attempting recovery (attempt %d of %d)..." % (attempt + 1, self.cut_recovery_retries))
+            self._push_fragment_from_nozzle()
+            _, park_pos = self._form_tip_standalone()
+            try:
+                self._unload_extruder(park_pos=park_pos)
+            except MmuError as ee:
+                if ee.code != self.ERR_TOOLHEAD_HOME_TIMEOUT:
+                    raise
+            if not self._check_sensor(self.ENDSTOP_TOOLHEAD):
+                self._log_info("Recovered from filament fragment stuck in extruder")
+                self._set_filament_pos_state(self.FILAMENT_POS_END_BOWDEN, silent=True)
+                return
+        self._set_filament_pos_state(self.FILAMENT_POS_UNKNOWN)
+        raise MmuError("Filament fragment appears to be stuck in extruder after cut and automatic recovery failed", code=self.ERR_EXTRUDER_STUCK)
+
+
 ##############################################
 # LOAD / UNLOAD SEQUENCES AND FILAMENT TESTS #
 ##############################################
@@ -3418,22 +6209,26 @@ class Mmu:
             current_action = self._set_action(self.ACTION_LOADING)
 
         try:
+            if not extruder_only:
+                self.plugin_manager.call_hook('pre_load', self.gate_selected)
+
             # Note: Conditionals deliberately coded this way to match macro alternative
             start_filament_pos = self.filament_pos
-            if self.gcode_load_sequence:
-                self._log_debug("Calling external user defined loading sequence macro")
-                self._wrap_gcode_command("_MMU_LOAD_SEQUENCE FILAMENT_POS=%d LENGTH=%.1f FULL=%d HOME_EXTRUDER=%d SKIP_EXTRUDER=%d EXTRUDER_ONLY=%d" % (start_filament_pos, length, int(full), int(home), int(skip_extruder), int(extruder_only)), exception=True)
+            sequence_macro = self._get_sequence_macro(self.gate_load_sequence, self.gcode_load_sequence, "_MMU_LOAD_SEQUENCE")
+            if sequence_macro:
+                self._log_debug("Calling external user defined loading sequence macro '%s'" % sequence_macro)
+                self._wrap_gcode_command("%s FILAMENT_POS=%d LENGTH=%.1f FULL=%d HOME_EXTRUDER=%d SKIP_EXTRUDER=%d EXTRUDER_ONLY=%d" % (sequence_macro, start_filament_pos, length, int(full), int(home), int(skip_extruder), int(extruder_only)), exception=True)
 
             elif extruder_only:
                 if start_filament_pos < self.FILAMENT_POS_EXTRUDER_ENTRY:
                     self._load_extruder(extruder_only=True)
                 else:
                     self._log_debug("Assertion failure: Unexpected state %d in _load_sequence(extruder_only=True)" % start_filament_pos)
-                    raise MmuError("Cannot load extruder because already in extruder. Unload first")
+                    raise MmuError("Cannot load extruder because already in extruder. Unload first", code=self.ERR_STATE)
 
             elif start_filament_pos >= self.FILAMENT_POS_EXTRUDER_ENTRY:
                 self._log_debug("Assertion failure: Unexpected state %d in _load_sequence()" % start_filament_pos)
-                raise MmuError("Cannot load because already in extruder. Unload first")
+                raise MmuError("Cannot load because already in extruder. Unload first", code=self.ERR_STATE)
 
             else:
                 if start_filament_pos <= self.FILAMENT_POS_UNLOADED:
@@ -3453,10 +6248,14 @@ class Mmu:
             if self._can_use_encoder():
                 msg += " (encoder measured %.1fmm)" % self._get_encoder_distance(dwell=None)
             self._log_info(msg)
+            if full:
+                self._track_gate_statistics('full_loads', self.gate_selected)
+            if not extruder_only:
+                self.plugin_manager.call_hook('post_load', self.gate_selected)
         except MmuError as ee:
             if full:
                 self._track_gate_statistics('load_failures', self.gate_selected)
-            raise MmuError("Load sequence failed: %s" % (str(ee)))
+            raise MmuError("Load sequence failed: %s" % (str(ee)), code=ee.code)
         finally:
             if full:
                 self._track_load_end()
@@ -3485,6 +6284,7 @@ class Mmu:
                 current_action = self._set_action(self.ACTION_UNLOADING)
                 self._track_unload_start()
                 self._display_visual_state()
+                self.plugin_manager.call_hook('pre_unload', self.gate_selected)
 
             # Check for cases where we must form tip
             if skip_tip:
@@ -3492,6 +6292,8 @@ class Mmu:
                 self._set_filament_position(-park_pos)
             elif self.filament_pos >= self.FILAMENT_POS_IN_EXTRUDER:
                 detected, park_pos = self._form_tip_standalone(extruder_only=extruder_only)
+                if detected and self.tip_inspection and self._can_use_encoder() and not extruder_only:
+                    park_pos = self._inspect_tip(park_pos)
                 if detected:
                     # Definitely in extruder
                     self._set_filament_pos_state(self.FILAMENT_POS_IN_EXTRUDER)
@@ -3508,49 +6310,57 @@ class Mmu:
             # Note: Conditionals deliberately coded this way to match macro alternative
             start_filament_pos = self.filament_pos
             unload_to_buffer = (start_filament_pos >= self.FILAMENT_POS_END_BOWDEN and not extruder_only)
-            if self.gcode_unload_sequence:
-                self._log_debug("Calling external user defined unloading sequence macro")
-                self._wrap_gcode_command("_MMU_UNLOAD_SEQUENCE FILAMENT_POS=%d LENGTH=%.1f EXTRUDER_ONLY=%d PARK_POS=%.1f" % (start_filament_pos, length, extruder_only, park_pos), exception=True)
+            sequence_macro = self._get_sequence_macro(self.gate_unload_sequence, self.gcode_unload_sequence, "_MMU_UNLOAD_SEQUENCE")
+            if sequence_macro:
+                self._log_debug("Calling external user defined unloading sequence macro '%s'" % sequence_macro)
+                self._wrap_gcode_command("%s FILAMENT_POS=%d LENGTH=%.1f EXTRUDER_ONLY=%d PARK_POS=%.1f" % (sequence_macro, start_filament_pos, length, extruder_only, park_pos), exception=True)
 
             elif extruder_only:
                 if start_filament_pos >= self.FILAMENT_POS_EXTRUDER_ENTRY:
                     self._unload_extruder(extruder_only=True, park_pos=park_pos)
                 else:
                     self._log_debug("Assertion failure: Unexpected state %d in _unload_sequence(extruder_only=True)" % start_filament_pos)
-                    raise MmuError("Cannot unload extruder because filament not in extruder!")
+                    raise MmuError("Cannot unload extruder because filament not in extruder!", code=self.ERR_STATE)
 
             elif start_filament_pos == self.FILAMENT_POS_UNLOADED:
                 self._log_debug("Assertion failure: Unexpected state %d in _unload_sequence()" % start_filament_pos)
-                raise MmuError("Cannot unload because already unloaded!")
+                raise MmuError("Cannot unload because already unloaded!", code=self.ERR_STATE)
 
             else:
                 if start_filament_pos >= self.FILAMENT_POS_EXTRUDER_ENTRY:
                     # Exit extruder, fast unload of bowden, then slow unload encoder
-                    self._unload_extruder(park_pos=park_pos)
+                    self._unload_extruder_with_cut_recovery(park_pos)
 
                 if start_filament_pos >= self.FILAMENT_POS_END_BOWDEN:
                     # Fast unload of bowden, then unload encoder
                     self._unload_bowden(length)
                     self._unload_gate()
+                    if start_filament_pos >= self.FILAMENT_POS_EXTRUDER_ENTRY:
+                        self._cut_filament_after_park()
 
                 elif start_filament_pos >= self.FILAMENT_POS_START_BOWDEN:
                     # Have to do slow unload because we don't know exactly where we are
                     self._unload_gate(homing_max=length) # Full slow unload
 
             if unload_to_buffer and self.gate_status[self.gate_selected] != self.GATE_EMPTY:
-                self._set_gate_status(self.gate_selected, self.GATE_AVAILABLE_FROM_BUFFER)
+                self._set_gate_status(self.gate_selected, self.GATE_AVAILABLE_FROM_BUFFER, source="unload")
 
             # If runout then over unload to prevent accidental reload
             if runout and self.endless_spool_final_eject > 0.:
                 self._log_info("Ejecting filament from MMU...")
                 _,_,measured,_ = self._trace_filament_move("EndlessSpool final eject", -self.endless_spool_final_eject)
 
+            # Any vendor specific movement after filament is parked at gate
+            if not extruder_only:
+                self.vendor_driver.eject(self)
+                self.plugin_manager.call_hook('post_unload', self.gate_selected)
+
             # Encoder based validation test
             if self._can_use_encoder():
                 movement = self._servo_up(measure=True)
                 if movement > self.encoder_min:
                     self._set_filament_pos_state(self.FILAMENT_POS_UNKNOWN)
-                    raise MmuError("It may be time to get the pliers out! Filament appears to stuck somewhere")
+                    raise MmuError("It may be time to get the pliers out! Filament appears to stuck somewhere", code=self.ERR_FILAMENT_STUCK)
             else:
                 self._servo_up()
 
@@ -3562,7 +6372,7 @@ class Mmu:
         except MmuError as ee:
             if not extruder_only:
                 self._track_gate_statistics('unload_failures', self.gate_selected)
-            raise MmuError("Unload sequence failed: %s" % (str(ee)))
+            raise MmuError("Unload sequence failed: %s" % (str(ee)), code=ee.code)
 
         finally:
             if not extruder_only:
@@ -3586,6 +6396,8 @@ class Mmu:
             self._set_filament_pos_state(self.FILAMENT_POS_LOADED)
         else: # Filament not detected in toolhead
             if self._check_filament_in_mmu():
+                if self.path_sensors:
+                    self._log_info("Path sensors locate filament %s" % self._locate_filament_on_path())
                 if self.strict_filament_recovery or strict:
                     if self._check_filament_still_in_extruder():
                         self._set_filament_pos_state(self.FILAMENT_POS_EXTRUDER_ENTRY)
@@ -3610,8 +6422,46 @@ class Mmu:
             self._log_debug("Filament %s in extruder" % ("detected" if detected else "not detected"))
             return detected, measured
 
-    # Form tip and return True if filament was detected and the assumed filament (park) position
-    def _form_tip_standalone(self, extruder_only=False):
+    # Pull filament back a short distance in steps after tip forming and classify the encoder movement signature:
+    #   good     - filament moves consistently
+    #   hesitant - initial step catches then frees (stringing)
+    #   slip     - erratic movement between steps
+    #   stuck    - little movement (blobbed tip or filament caught in extruder)
+    # Bad signatures cause tip forming to be retried before raising an error. Returns updated park_pos
+    def _inspect_tip(self, park_pos):
+        steps = 4
+        step = self.tip_inspection_length / steps
+        for attempt in range(self.tip_inspection_retries + 1):
+            self._servo_down()
+            ratios = []
+            for i in range(steps):
+                _,_,measured,_ = self._trace_filament_move("Tip inspection pull", -step, motor="gear+extruder", wait=True)
+                ratios.append(round(measured / step, 2))
+            park_pos += self.tip_inspection_length
+            total_ratio = sum(ratios) / steps
+            if total_ratio < 0.3:
+                classification = "stuck"
+            elif ratios[0] < 0.5 and min(ratios[1:]) >= 0.7:
+                classification = "hesitant"
+            elif max(ratios) - min(ratios) > 0.5:
+                classification = "slip"
+            else:
+                classification = "good"
+            self.last_tip_inspection = {'gate': self.gate_selected, 'attempt': attempt + 1, 'classification': classification, 'ratios': ratios}
+            self._log_debug("Tip inspection (attempt %d): %s, encoder/movement ratios: %s" % (attempt + 1, classification, ratios))
+            self._publish_event("tip_inspection", **self.last_tip_inspection)
+            if classification == "good":
+                return park_pos
+            if attempt < self.tip_inspection_retries:
+                self._log_info("Tip inspection suggests %s tip. Retrying tip forming..." % classification)
+                self._servo_down()
+                self._trace_filament_move("Tip inspection return", self.tip_inspection_length, motor="gear+extruder", wait=True)
+                park_pos -= self.tip_inspection_length
+                _, park_pos = self._form_tip_standalone()
+        raise MmuError("Tip inspection failed after %d attempts. Encoder signature indicates %s tip" % (self.tip_inspection_retries + 1, classification), code=self.ERR_TIP_INSPECTION)
+
+    # Form tip and return True if filament was detected and the assumed filament (park) position
+    def _form_tip_standalone(self, extruder_only=False):
         self._movequeues_wait_moves()
         # Pre check to validate the presence of filament in the extruder and case where we don't need to form tip
         if self._check_sensor(self.ENDSTOP_EXTRUDER) or self._check_sensor(self.ENDSTOP_TOOLHEAD):
@@ -3629,7 +6479,7 @@ class Mmu:
 
         gcode_macro = self.printer.lookup_object("gcode_macro %s" % self.form_tip_macro, None)
         if gcode_macro is None:
-            raise MmuError("Filament tip forming macro '%s' not found" % self.form_tip_macro)
+            raise MmuError("Filament tip forming macro '%s' not found" % self.form_tip_macro, code=self.ERR_CONFIG)
 
         self._log_debug("Preparing to form tip...")
         with self._wrap_action(self.ACTION_FORMING_TIP):
@@ -3644,7 +6494,7 @@ class Mmu:
                 try:
                     initial_pa = self.printer.lookup_object(self.extruder_name).get_status(0)['pressure_advance'] # Capture PA in case user's tip forming resets it
                     self._log_info("Forming tip...")
-                    self._wrap_gcode_command(self.form_tip_macro, exception=True)
+                    self._wrap_gcode_command(("%s %s" % (self.form_tip_macro, self._get_form_tip_params())).strip(), exception=True)
                 finally:
                     self.gcode.run_script_from_command("SET_PRESSURE_ADVANCE ADVANCE=%.4f" % initial_pa) # Restore PA
                 self._movequeues_wait_moves()
@@ -3671,6 +6521,9 @@ class Mmu:
                         self.filament_remaining = park_pos - measured_park_pos
                         self._log_trace("After tip formation, park_pos reported as: %.1f with %.1f filament remaining in extruder (extruder moved: %.1f, encoder measured %.1f)" % (park_pos, self.filament_remaining, measured_park_pos, measured))
                     filament_check = False
+                self.tip_was_cut = not filament_check
+                if self.tip_was_cut:
+                    self._count_cutter_blade_use()
                 self._set_filament_position(-park_pos)
                 self._set_encoder_distance(initial_encoder_position + park_pos)
 
@@ -3680,7 +6533,7 @@ class Mmu:
                 if filament_initially_present is True:
                     # With encoder we might be able to check for clog now
                     if not measured > self.encoder_min:
-                        raise MmuError("No encoder movement: Concluding filament is stuck in extruder")
+                        raise MmuError("No encoder movement: Concluding filament is stuck in extruder", code=self.ERR_EXTRUDER_STUCK)
                 else:
                     # Couldn't determine if we initially had filament at start (lack of sensors)
                     if not measured > self.encoder_min:
@@ -3721,19 +6574,20 @@ class Mmu:
                 self._select_tool(tool)
 
     def _home_selector(self):
-        self.is_homed = False
-        self.gate_selected = self.TOOL_GATE_UNKNOWN
-        self._servo_move()
-        self._movequeues_wait_moves()
-        homing_state = MmuHoming(self.printer, self.mmu_toolhead)
-        homing_state.set_axes([0])
-        try:
-            self.mmu_kinematics.home(homing_state)
-            self.is_homed = True
-        except Exception as e: # Homing failed
-            self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
-            raise MmuError("Homing selector failed because of blockage or malfunction. Klipper reports: %s" % str(e))
-        self.last_selector_move_time = self.estimated_print_time(self.reactor.monotonic())
+        with self._wrap_safety_watchdog('selector'):
+            self.is_homed = False
+            self.gate_selected = self.TOOL_GATE_UNKNOWN
+            self._servo_move()
+            self._movequeues_wait_moves()
+            homing_state = MmuHoming(self.printer, self.mmu_toolhead)
+            homing_state.set_axes([0])
+            try:
+                self.mmu_kinematics.home(homing_state)
+                self.is_homed = True
+            except Exception as e: # Homing failed
+                self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
+                raise MmuError("Homing selector failed because of blockage or malfunction. Klipper reports: %s" % str(e), code=self.ERR_SELECTOR)
+            self.last_selector_move_time = self.estimated_print_time(self.reactor.monotonic())
 
     def _position_selector(self, target):
         if not self.selector_touch:
@@ -3754,14 +6608,14 @@ class Mmu:
                         # Try to engage filament to the encoder
                         _,_,measured,delta = self._trace_filament_move("Trying to re-enguage encoder", 45.)
                         if measured < self.encoder_min:
-                            raise MmuError("Selector recovery failed. Path is probably internally blocked and unable to move filament to clear")
+                            raise MmuError("Selector recovery failed. Path is probably internally blocked and unable to move filament to clear", code=self.ERR_SELECTOR)
     
                     # Now try a full unload sequence
                     try:
                         self._unload_sequence(check_state=True)
                     except MmuError as ee:
                         # Add some more context to the error and re-raise
-                        raise MmuError("Selector recovery failed because: %s" % (str(ee)))
+                        raise MmuError("Selector recovery failed because: %s" % (str(ee)), code=ee.code)
     
                     # Ok, now check if selector can now reach proper target
                     self._home_selector()
@@ -3770,11 +6624,11 @@ class Mmu:
                         # Selector path is still blocked
                         self.is_homed = False
                         self._unselect_tool()
-                        raise MmuError("Selector recovery failed. Path is probably internally blocked")
+                        raise MmuError("Selector recovery failed. Path is probably internally blocked", code=self.ERR_SELECTOR)
                 else: # Selector path is blocked, probably not internally
                     self.is_homed = False
                     self._unselect_tool()
-                    raise MmuError("Selector path is probably externally blocked")
+                    raise MmuError("Selector path is probably externally blocked", code=self.ERR_SELECTOR)
 
     def _attempt_selector_touch_move(self, target):
         halt_pos,homed = self._trace_selector_move("Attempting selector 'touch' movement", target, homing_move=1, endstop_name=self.ENDSTOP_SELECTOR_TOUCH)
@@ -3872,6 +6726,17 @@ class Mmu:
 # FILAMENT MOVEMENT AND CONTROL #
 #################################
 
+    # Limit gear move speed and accel to the caps for the material in the selected gate (e.g. flexibles)
+    def _clamp_material_gear_speed(self, speed, accel):
+        if self.gate_selected < 0:
+            return speed, accel
+        material = self.gate_material[self.gate_selected].upper()
+        max_speed = self.gear_material_max_speed.get(material, speed)
+        max_accel = self.gear_material_max_accel.get(material, accel)
+        if speed > max_speed or accel > max_accel:
+            self._log_trace("Gear move limited for %s to speed=%.1f (was %.1f), accel=%.1f (was %.1f)" % (material, min(speed, max_speed), speed, min(accel, max_accel), accel))
+        return min(speed, max_speed), min(accel, max_accel)
+
     # Convenience wrapper around all gear and extruder motor movement that tracks measured movement and create trace log entry
     # motor = "gear"           - gear motor(s) only on rail
     #         "gear+extruder"  - gear and extruder included on rail
@@ -3950,6 +6815,9 @@ class Mmu:
             self._log_error("Assertion failure: Invalid motor specification '%'" % motor)
             return null_rtn
 
+        if motor != "extruder":
+            speed, accel = self._clamp_material_gear_speed(speed, accel)
+
         if sync:
             self._movequeues_sync()
 
@@ -3970,6 +6838,8 @@ class Mmu:
                             # Stallguard doesn't do well at slow speed. Try to infer move completion
                             if abs(trig_pos[1] - dist) < 1.0:
                                     homed = False
+                        if homed and self.homing_creep_count > 0:
+                            self._homing_creep(hmove, dist, homing_move)
                     except self.printer.command_error as e:
                         # CANbus mcu's often seen to exhibit "Communication timeout" errors so surface errors to user
                         if abs(trig_pos[1] - dist) > 0. and not "after full movement" in str(e):
@@ -4027,6 +6897,10 @@ class Mmu:
                 trace_str = trace_str % (motor, dist, measured, delta)
                 trace_str += ". Pos: @%.1f, (%.1fmm)" % (self.mmu_toolhead.get_position()[1], encoder_end)
             self._log_trace(trace_str)
+        if homing_move != 0:
+            self._record_telemetry("homing", endstop=endstop_name, motor=motor, dist=round(dist, 2), actual=round(actual, 2), homed=homed, measured=round(measured, 2), gate=self.gate_selected)
+        else:
+            self._record_telemetry("move", motor=motor, dist=round(dist, 2), measured=round(measured, 2), delta=round(delta, 2), gate=self.gate_selected)
 
         if self._can_use_encoder() and motor == "gear" and track:
             if dist > 0:
@@ -4046,6 +6920,19 @@ class Mmu:
 
         return actual, homed, measured, delta
 
+    # Optional fine approach to improve repeatability of homing endstops. After initial homing
+    # the filament is backed off and then re-approaches the endstop at creep speed
+    def _homing_creep(self, hmove, dist, homing_move):
+        direction = 1 if dist > 0 else -1
+        for i in range(self.homing_creep_count):
+            pos = self.mmu_toolhead.get_position()
+            pos[1] -= direction * self.homing_creep_backoff
+            self._log_stepper("CREEP #%d: backoff=%.1f, speed=%.1f, accel=%.1f" % (i + 1, self.homing_creep_backoff, self.homing_creep_speed, self.homing_creep_accel))
+            with self._wrap_accel(self.homing_creep_accel):
+                self.mmu_toolhead.move(pos, self.homing_creep_speed)
+                pos[1] += direction * self.homing_creep_backoff * 2
+                hmove.homing_move(pos, self.homing_creep_speed, probe_pos=True, triggered=homing_move > 0, check_triggered=True)
+
     @contextlib.contextmanager
     def _wrap_accel(self, accel):
         self.mmu_kinematics.set_accel_limit(accel)
@@ -4124,26 +7011,89 @@ class Mmu:
                 self._servo_auto()
         if prev_sync_state != sync:
             self._log_debug("%s gear stepper and extruder" % ("Syncing" if sync else "Unsyncing"))
+            self._set_gate_ratio(self._get_gate_sync_ratio(self.gate_selected) if sync else self._get_gate_ratio(self.gate_selected))
             self.mmu_toolhead.sync_gear_to_extruder(self.extruder_name if sync else None)
             self.printer.send_event("mmu:extruder_synced" if sync else "mmu:extruder_unsynced")
 
         # Option to reduce current during print
         if current and sync:
-            self._adjust_gear_current(self.sync_gear_current, "for extruder syncing")
+            self._adjust_gear_current(self._get_sync_gear_current(), "for extruder syncing")
         else:
             self._restore_gear_current()
         return prev_sync_state
 
+    # Purge assist only helps printing moves on a loaded gate when gear isn't already synced and MMU is not busy
+    def _purge_assist_allowed(self):
+        return (self._is_printing() and not self.sync_to_extruder and self.action == self.ACTION_IDLE and
+                self._next_tool == self.TOOL_GATE_UNKNOWN and self.gate_selected >= 0 and
+                self.filament_pos == self.FILAMENT_POS_LOADED and not self.mmu_toolhead.is_gear_synced_to_extruder())
+
+    def _engage_purge_assist(self, length):
+        self._log_debug("Purge assist: syncing gear to extruder for %.1fmm extrusion" % length)
+        self._sync_gear_to_extruder(True, servo=True, current=True)
+
+    def _release_purge_assist(self):
+        self._log_debug("Purge assist: releasing gear sync")
+        self._sync_gear_to_extruder(False, servo=True)
+
+    # Runtime invariant check that sync really happened. A silent failure (e.g. stepper not registered after a
+    # restart) would otherwise leave the extruder pulling filament through the full bowden on its own
+    def _verify_gear_synced(self):
+        problem = self.mmu_toolhead.check_gear_synced_to_extruder()
+        if problem:
+            raise MmuError("Gear stepper expected to be synced to extruder but %s" % problem, code=self.ERR_GEAR_NOT_SYNCED)
+
+    # Configured sync current less any reduction because gear motor is running hot
+    def _get_sync_gear_current(self):
+        return self.sync_gear_current - self.gear_thermal_reduction
+
+    # Closed loop reduction of sync current when gear motor runs hot. Current is stepped down by 'sync_gear_current_step'
+    # each check whilst above 'gear_temp_max' (or TMC over-temperature flags are set) to no lower than
+    # 'sync_gear_current_min' and stepped back up once cooled by 'gear_temp_hysteresis' (or flags clear)
+    def _handle_gear_thermal_check(self, eventtime):
+        state, reading = self._get_gear_thermal_state(eventtime)
+        floor = min(self.sync_gear_current_min, self.sync_gear_current)
+        reduction = self.gear_thermal_reduction
+        if state > 0:
+            reduction = min(reduction + self.sync_gear_current_step, self.sync_gear_current - floor)
+        elif state < 0:
+            reduction = max(reduction - self.sync_gear_current_step, 0)
+        if reduction != self.gear_thermal_reduction:
+            old_percent = self._get_sync_gear_current()
+            self.gear_thermal_reduction = reduction
+            percent = self._get_sync_gear_current()
+            self._log_info("Gear motor %s (%s), %s synced gear current to %d%%" % ("running hot" if state > 0 else "cooled", reading, "reducing" if state > 0 else "restoring", percent))
+            self._publish_event("gear_thermal", percent=percent, reading=reading)
+            if self.mmu_toolhead.is_gear_synced_to_extruder() and self.gear_percentage_run_current == old_percent:
+                self._exec_gcode("__MMU_GEAR_THERMAL FROM=%d" % old_percent) # Current must be changed in command context
+        return eventtime + self.GEAR_THERMAL_CHECK_INTERVAL
+
+    # Returns (state, reading) where state is 1 = hot, -1 = cool enough to restore current, 0 = hold
+    def _get_gear_thermal_state(self, eventtime):
+        if self.gear_temp_sensor == 'tmc':
+            drv_status = self.gear_tmc.get_status(eventtime).get('drv_status') or {}
+            flags = [f for f in ['ot', 'otpw'] if drv_status.get(f)]
+            return (1 if flags else -1), ("TMC %s" % "/".join(flags) if flags else "TMC no over-temperature flags")
+        temp = self.printer.lookup_object(self.gear_temp_sensor).get_status(eventtime).get('temperature', 0.)
+        reading = "%.1fC" % temp
+        if temp >= self.gear_temp_max:
+            return 1, reading
+        if temp <= self.gear_temp_max - self.gear_temp_hysteresis:
+            return -1, reading
+        return 0, reading
+
     def _adjust_gear_current(self, percent=100, reason=""):
          if self.gear_tmc and percent != self.gear_percentage_run_current and percent > 0 and percent < 200:
              self._log_info("Modifying MMU gear stepper run current to %d%% %s" % (percent, reason))
-             self.gcode.run_script_from_command("SET_TMC_CURRENT STEPPER=stepper_mmu_gear CURRENT=%.2f" % ((self.gear_default_run_current * percent) / 100.))
+             name, run_current = self._get_gear_run_current()
+             self.gcode.run_script_from_command("SET_TMC_CURRENT STEPPER=%s CURRENT=%.2f" % (name, (run_current * percent) / 100.))
              self.gear_percentage_run_current = percent
 
     def _restore_gear_current(self):
         if self.gear_tmc and self.gear_percentage_run_current != self.gear_restore_percent_run_current:
             self._log_info("Restoring MMU gear stepper run current to %d%% configured" % self.gear_restore_percent_run_current)
-            self.gcode.run_script_from_command("SET_TMC_CURRENT STEPPER=stepper_mmu_gear CURRENT=%.2f" % self.gear_default_run_current)
+            name, run_current = self._get_gear_run_current()
+            self.gcode.run_script_from_command("SET_TMC_CURRENT STEPPER=%s CURRENT=%.2f" % (name, run_current))
             self.gear_percentage_run_current = self.gear_restore_percent_run_current
 
     @contextlib.contextmanager
@@ -4267,6 +7217,25 @@ class Mmu:
             if current_speed_factor != speed_factor or current_extrude_factor != extrude_factor:
                 self._log_debug("Restored speed/extrusion multiplier for tool T%d as %d%% and %d%%" % (tool, speed_factor * 100, extrude_factor * 100))
 
+    # Apply per-gate pressure advance and smooth time. Extruder settings are only touched if the gate has an override
+    # or the previous gate had one, in which case the values active before that override are restored
+    def _restore_gate_pressure_advance(self, gate):
+        pa = self.gate_pressure_advance[gate] if gate >= 0 and self.gate_pressure_advance[gate] >= 0 else None
+        smooth_time = self.gate_smooth_time[gate] if gate >= 0 and self.gate_smooth_time[gate] >= 0 else None
+        status = self.printer.lookup_object(self.extruder_name).get_status(0)
+        if pa is None and smooth_time is None:
+            if self.pressure_advance_before_override is None: return
+            pa, smooth_time = self.pressure_advance_before_override
+            self.pressure_advance_before_override = None
+        else:
+            if self.pressure_advance_before_override is None:
+                self.pressure_advance_before_override = (status.get('pressure_advance', 0.), status.get('smooth_time', 0.04))
+            pa = pa if pa is not None else self.pressure_advance_before_override[0]
+            smooth_time = smooth_time if smooth_time is not None else self.pressure_advance_before_override[1]
+        if status.get('pressure_advance') != pa or status.get('smooth_time') != smooth_time:
+            self.gcode.run_script_from_command("SET_PRESSURE_ADVANCE EXTRUDER=%s ADVANCE=%.4f SMOOTH_TIME=%.3f" % (self.extruder_name, pa, smooth_time))
+            self._log_debug("Set pressure advance for %s to %.4f (smooth time %.3f)" % (("gate #%d" % gate) if gate >= 0 else "bypass", pa, smooth_time))
+
     def _set_tool_override(self, tool, speed_percent, extrude_percent):
         if tool == -1:
             for i in range(self.mmu_num_gates):
@@ -4292,18 +7261,27 @@ class Mmu:
     def _select_and_load_tool(self, tool):
         self._log_debug('Loading tool T%d...' % tool)
         gate = self.tool_to_gate_map[tool]
-        if self.gate_status[gate] == self.GATE_EMPTY:
+        if self.gate_load_balancing:
+            balanced_gate = self._get_balanced_gate(gate, self.load_balanced_gates.get(tool))
+            if balanced_gate != gate:
+                self._log_info("Load balancing T%d to gate #%d with identical filament" % (tool, balanced_gate))
+            gate = self.load_balanced_gates[tool] = balanced_gate
+        reason = self._gate_unavailable_reason(gate)
+        if reason:
             if self.enable_endless_spool and self.endless_spool_on_load:
-                self._log_info("Gate #%d is empty!" % gate)
+                self._log_info("Gate #%d is %s!" % (gate, reason))
                 next_gate, checked_gates = self._get_next_endless_spool_gate(gate)
                 if next_gate == -1:
-                    raise MmuError("No EndlessSpool alternatives available after reviewing gates: %s" % checked_gates)
+                    raise MmuError("No EndlessSpool alternatives available after reviewing gates: %s" % checked_gates, code=self.ERR_ENDLESS_SPOOL_EXHAUSTED)
                 self._log_info("Remapping T%d to gate #%d" % (tool, next_gate))
-                gate = self._remap_tool(tool, next_gate)
+                gate = self._remap_tool(tool, next_gate, source="endless spool")
+            elif gate in self.gate_lock:
+                raise MmuError("Gate #%d is %s! Unlock with MMU_GATE_MAP GATE=%d LOCK=0" % (gate, reason, gate), code=self.ERR_GATE_LOCKED)
             else:
-                raise MmuError("Gate #%d is empty!" % gate)
+                raise MmuError("Gate #%d is %s!" % (gate, reason), code=self.ERR_GATE_EMPTY)
 
-        self._select_tool(tool, move_servo=False)
+        self._soak_dryer_zone(gate)
+        self._select_tool(tool, move_servo=False, gate=gate)
         self._load_sequence()
 
         # Activate the spool in SpoolMan, if enabled
@@ -4311,6 +7289,8 @@ class Mmu:
 
         # Restore M220 and M221 overrides
         self._restore_tool_override(self.tool_selected)
+        self.idle_retracted_tool = self.TOOL_GATE_UNKNOWN
+        self._arm_idle_retract()
 
     # Primary method to unload current tool but retains selection
     def _unload_tool(self, skip_tip=False, runout=False):
@@ -4329,7 +7309,7 @@ class Mmu:
         self._log_debug("Tool change initiated %s" % ("with slicer tip forming" if skip_tip else "with standalone MMU tip forming"))
         skip_unload = False
         initial_tool_string = "Unknown" if self.tool_selected < 0 else ("T%d" % self.tool_selected)
-        if tool == self.tool_selected and self.tool_to_gate_map[tool] == self.gate_selected and self.filament_pos == self.FILAMENT_POS_LOADED:
+        if tool == self.tool_selected and self.gate_selected in (self.tool_to_gate_map[tool], self.load_balanced_gates.get(tool)) and self.filament_pos == self.FILAMENT_POS_LOADED:
             self._log_always("Tool T%d is already loaded" % tool)
             return False
 
@@ -4345,61 +7325,103 @@ class Mmu:
             m117_msg = ("%s > T%d" % (initial_tool_string, tool))
         # Important to always inform user in case there is an error and manual recovery is necessary
         self._last_toolchange = m117_msg
-        self.gcode.run_script_from_command("M117 %s" % m117_msg)
+        self._notify('toolchange', m117_msg, tool=tool)
         self._log_always(msg)
 
         # Check TTG map. We might be mapped to same gate
         if self.tool_to_gate_map[tool] == self.gate_selected and self.filament_pos == self.FILAMENT_POS_LOADED:
             self._select_tool(tool)
-            self.gcode.run_script_from_command("M117 T%s" % tool)
+            self._notify('toolchange', "T%d" % tool, tool=tool)
             return False
 
         # Notify start of actual toolchange operation
         self.printer.send_event("mmu:toolchange", self, self._last_tool, self._next_tool)
+        if self.purge_assist:
+            self.purge_assist.release()
+        swap_start_time = time.time()
+        with self._wrap_device_phase(self.PHASE_TOOLCHANGE if in_print else None):
+            if in_print:
+                self._save_toolhead_position_and_lift("change_tool", z_hop_height=self.z_hop_height_toolchange)
+                gcode = self.printer.lookup_object('gcode_macro _MMU_PRE_UNLOAD', None)
+                if gcode is not None:
+                    self._wrap_gcode_command("_MMU_PRE_UNLOAD", exception=True)
+
+            # Identify the unitialized startup use case and make it easy for user
+            if not self.is_homed or self.tool_selected == self.TOOL_GATE_UNKNOWN:
+                self._log_info("MMU not homed, homing it before continuing...")
+                self._home(tool)
+                skip_unload = True
+
+            from_gate = self.gate_selected if not skip_unload else self.TOOL_GATE_UNKNOWN
+            self.last_extrusion_time = time.time() if in_print and not skip_unload else 0.
+            if not skip_unload:
+                if self.toolchange_overlap_length > 0:
+                    self.overlap_next_gate = self.tool_to_gate_map[tool]
+                try:
+                    self._unload_tool(skip_tip=skip_tip)
+                finally:
+                    self.overlap_next_gate = self.TOOL_GATE_UNKNOWN
+                if from_gate >= 0 and from_gate != self.tool_to_gate_map[tool]:
+                    self._auto_final_eject(from_gate, gate_swap=True)
 
-        if in_print:
-            self._save_toolhead_position_and_lift("change_tool", z_hop_height=self.z_hop_height_toolchange)
-            gcode = self.printer.lookup_object('gcode_macro _MMU_PRE_UNLOAD', None)
-            if gcode is not None:
-                self._wrap_gcode_command("_MMU_PRE_UNLOAD", exception=True)
-
-        # Identify the unitialized startup use case and make it easy for user
-        if not self.is_homed or self.tool_selected == self.TOOL_GATE_UNKNOWN:
-            self._log_info("MMU not homed, homing it before continuing...")
-            self._home(tool)
-            skip_unload = True
-
-        if not skip_unload:
-            self._unload_tool(skip_tip=skip_tip)
-
-        if in_print:
-            gcode = self.printer.lookup_object('gcode_macro _MMU_POST_UNLOAD', None)
-            if gcode is not None:
-                self._wrap_gcode_command("_MMU_POST_UNLOAD", exception=True)
-        self._select_and_load_tool(tool)
-
-        self._track_swap_completed()
-
-        if in_print:
-            gcode = self.printer.lookup_object('gcode_macro _MMU_POST_LOAD', None)
-            if gcode is not None:
-                self._wrap_gcode_command("_MMU_POST_LOAD", exception=True)
-            self._restore_toolhead_position("change_tool")
-        self._restore_tool_override(self.tool_selected) # Must be after _restore_toolhead_position()
-
-        self.gcode.run_script_from_command("M117 T%s" % tool)
+            if in_print:
+                gcode = self.printer.lookup_object('gcode_macro _MMU_POST_UNLOAD', None)
+                if gcode is not None:
+                    self._wrap_gcode_command("_MMU_POST_UNLOAD", exception=True)
+            try:
+                self._select_and_load_tool(tool)
+            except MmuError as ee:
+                # Selector failing to reach gate leaves filament unloaded so toolchange can resume from gate selection
+                if ee.code != self.ERR_SELECTOR or not self.selector_stall_recovery or self.filament_pos != self.FILAMENT_POS_UNLOADED:
+                    raise
+                self._recover_selector_stall(tool, ee)
+                self._select_and_load_tool(tool)
+            self.toolchange_purge_volume = self._calc_purge_volume(from_gate, self.gate_selected) + self._calc_stub_purge_volume(self.gate_selected)
+            if in_print:
+                self.toolchange_purge_volume = self._route_purge_to_infill(self.toolchange_purge_volume)
+
+            self._track_swap_completed()
+
+            if in_print:
+                gcode = self.printer.lookup_object('gcode_macro _MMU_POST_LOAD', None)
+                if gcode is not None:
+                    with self._wrap_device_phase(self.PHASE_POST_LOAD):
+                        self._wrap_gcode_command("_MMU_POST_LOAD PURGE_VOLUME=%.1f" % self.toolchange_purge_volume, exception=True)
+                self._prime_nozzle()
+                self._restore_toolhead_position("change_tool")
+            self._restore_tool_override(self.tool_selected) # Must be after _restore_toolhead_position()
+            self._restore_gate_pressure_advance(self.gate_selected)
+            self._persist_plr_state()
+            self._publish_event("toolchange", from_tool=self._last_tool, to_tool=self.tool_selected, gate=self.gate_selected)
+            self._publish_metric("mmu_swap", {'duration': round(time.time() - swap_start_time, 2), 'purge_volume': round(self.toolchange_purge_volume, 1)}, tool=self.tool_selected, gate=self.gate_selected, from_tool=self._last_tool)
+
+        self._notify('toolchange', "T%d" % tool, tool=tool)
         return True
 
+    # Selector stalled between gates mid toolchange so its position is unknown. Check that no filament is trapped across
+    # the selector path (which would block it again), re-home and re-derive target gate so the toolchange can resume
+    def _recover_selector_stall(self, tool, error):
+        self._log_error("Selector stalled during toolchange (%s). Attempting automatic recovery..." % str(error))
+        self._set_gate_selected(self.TOOL_GATE_UNKNOWN)
+        self._unselect_tool()
+        if self._check_filament_in_mmu():
+            raise MmuError("Selector recovery failed. Filament appears to be trapped in selector path", code=self.ERR_SELECTOR)
+        self._home_selector()
+        gate = self.tool_to_gate_map[tool]
+        self._log_info("Selector recovered. Resuming toolchange to T%d on gate #%d" % (tool, gate))
+        self._publish_event("selector_recovered", tool=tool, gate=gate, reason=str(error))
+
     def _unselect_tool(self):
         self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
         self._servo_auto()
 
-    def _select_tool(self, tool, move_servo=True):
+    # Gate defaults to TTG map but may be overridden for a single load (e.g. load balancing)
+    def _select_tool(self, tool, move_servo=True, gate=None):
         if tool < 0 or tool >= self.mmu_num_gates:
             self._log_always("Tool %d does not exist" % tool)
             return
 
-        gate = self.tool_to_gate_map[tool]
+        gate = self.tool_to_gate_map[tool] if gate is None else gate
         if tool == self.tool_selected and gate == self.gate_selected:
             return
 
@@ -4410,32 +7432,40 @@ class Mmu:
             self._servo_auto()
         self._log_info("Tool T%d enabled%s" % (tool, (" on gate #%d" % gate) if tool != gate else ""))
 
-    def _select_bypass(self):
-        if self.tool_selected == self.TOOL_GATE_BYPASS and self.gate_selected == self.TOOL_GATE_BYPASS: return
-        if self.bypass_offset == 0:
+    # Index selects between multiple bypass positions (defaults to last selected)
+    def _select_bypass(self, index=None):
+        index = self.bypass_selected if index is None else index
+        if self.tool_selected == self.TOOL_GATE_BYPASS and self.gate_selected == self.TOOL_GATE_BYPASS and index == self.bypass_selected: return
+        if index >= len(self.bypass_offsets):
             self._log_always("Bypass not configured")
             return
-        self._log_info("Selecting filament bypass...")
+        self._log_info("Selecting filament bypass%s..." % ((" %d" % (index + 1)) if len(self.bypass_offsets) > 1 else ""))
+        if index != self.bypass_selected:
+            self._set_bypass_selected(index)
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_BYPASS_SELECTED, self.bypass_selected))
+            if self.gate_selected == self.TOOL_GATE_BYPASS:
+                self.gate_selected = self.TOOL_GATE_UNKNOWN # Force selector move to new bypass position
         self._select_gate(self.TOOL_GATE_BYPASS)
         self.filament_direction = self.DIRECTION_LOAD
         self._set_tool_selected(self.TOOL_GATE_BYPASS)
+        self._restore_gate_pressure_advance(self.TOOL_GATE_BYPASS)
         self._log_info("Bypass enabled")
 
     def _select_gate(self, gate):
         if gate == self.gate_selected: return
 
-        if self.virtual_selector:
-            self.mmu_toolhead.select_gear_stepper(gate)
+        if self.vendor_driver.select_gate(self, gate):
             self._set_gate_selected(gate)
             return
 
         with self._wrap_action(self.ACTION_SELECTING):
-            self._servo_move()
-            if gate == self.TOOL_GATE_BYPASS:
-                offset = self.bypass_offset
-            else:
-                offset = self.selector_offsets[gate]
-            self._position_selector(offset)
+            with self._wrap_safety_watchdog('selector'):
+                self._servo_move()
+                if gate == self.TOOL_GATE_BYPASS:
+                    offset = self.bypass_offset
+                else:
+                    offset = self.selector_offsets[gate]
+                self._position_selector(offset)
             self._set_gate_selected(gate)
 
     def _set_gate_selected(self, gate):
@@ -4444,16 +7474,336 @@ class Mmu:
         if gate == self.TOOL_GATE_UNKNOWN or gate == self.TOOL_GATE_BYPASS:
             self._set_gate_ratio(1.)
         else:
+            self._apply_gate_driver(gate)
             self._set_gate_ratio(self._get_gate_ratio(gate))
+        if self.config_overrides:
+            self._apply_config_overrides(set(o['attr'] for o in self.config_overrides if o['gate'] is not None))
 
     def _set_tool_selected(self, tool):
         self.tool_selected = tool
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=%d" % (self.VARS_MMU_TOOL_SELECTED, self.tool_selected))
 
+    # Safe storage temperature for the set of tools is the lowest of the material limits
+    def _get_dryer_temp_for_tools(self, tools):
+        temps = {}
+        for tool in tools:
+            material = self.gate_material[self.tool_to_gate_map[tool]].upper()
+            if material in self.dryer_material_temps:
+                temps[material] = self.dryer_material_temps[material]
+            else:
+                self._log_debug("No dryer temperature defined for material '%s' (T%d)" % (material, tool))
+        return min(temps.values()) if temps else 0., temps
+
+    # Current relative humidity of dryer zone or None if not known
+    def _get_dryer_humidity(self, zone):
+        if not self.dryer_sensors: return None
+        return self.printer.lookup_object(self.dryer_sensors[zone]).get_status(self.reactor.monotonic()).get('humidity', None)
+
+    # Compare humidity of the dryer zones feeding tools used in print with the limit for their materials. If too humid
+    # the strictest policy of those materials is applied
+    def _check_dryness_for_print(self, tools):
+        if not self.dryer_sensors or not self.dryer_material_humidity: return
+        zones = {}
+        for tool in tools:
+            gate = self.tool_to_gate_map[tool]
+            zone = self._get_dryer_zone(gate)
+            material = self.gate_material[gate].upper()
+            if zone >= 0 and material in self.dryer_material_humidity:
+                zones.setdefault(zone, []).append((tool, material))
+
+        for zone, entries in zones.items():
+            humidity = self._get_dryer_humidity(zone)
+            if humidity is None: continue
+            limit = min([self.dryer_material_humidity[m] for _, m in entries])
+            if humidity <= limit:
+                self._log_debug("Dryer %s humidity %.0f%% is within %.0f%% limit" % (self._dryer_zone_to_string(zone), humidity, limit))
+                continue
+            policy = max([self.dryer_material_policy.get(m, self.dryer_policy) for _, m in entries], key=self.DRYNESS_POLICIES.index)
+            msg = "Dryer %s humidity %.0f%% exceeds %.0f%% limit for %s" % (self._dryer_zone_to_string(zone), humidity, limit, ", ".join(["%s (T%d)" % (m, t) for t, m in entries]))
+            self._publish_event("dryness", zone=zone, humidity=humidity, limit=limit, policy=policy)
+            if policy == self.DRYNESS_POLICY_DRY:
+                self._log_always("%s. Drying before print continues..." % msg)
+                self._dry_zone_for_print(zone, [t for t, _ in entries], limit)
+            elif policy == self.DRYNESS_POLICY_WARN:
+                self._log_always("Warning: %s" % msg)
+            else:
+                self._log_info("%s. Dryness requirement waived, continuing print" % msg)
+
+    # Heat dryer zone for materials and wait until humidity falls to limit or 'dryer_max_wait' expires
+    def _dry_zone_for_print(self, zone, tools, limit):
+        temp, _ = self._get_dryer_temp_for_tools(tools)
+        if temp <= 0:
+            self._log_always("Warning: No dryer temperature defined for materials. Unable to dry %s" % self._dryer_zone_to_string(zone))
+            return
+        self._set_dryer_temp(temp, zone=zone)
+        start = eventtime = self.reactor.monotonic()
+        humidity = None
+        while eventtime < start + self.dryer_max_wait:
+            humidity = self._get_dryer_humidity(zone)
+            if humidity is not None and humidity <= limit:
+                self._log_always("Dryer %s reached %.0f%% humidity after %s" % (self._dryer_zone_to_string(zone), humidity, self._seconds_to_human_string(eventtime - start)))
+                self._publish_event("drying_complete", zone=zone, humidity=round(humidity), limit=limit, reached=True)
+                return
+            eventtime = self.reactor.pause(eventtime + 10.)
+        self._log_always("Warning: Dryer %s did not reach %.0f%% humidity within %s. Continuing print" % (self._dryer_zone_to_string(zone), limit, self._seconds_to_human_string(self.dryer_max_wait)))
+        self._publish_event("drying_complete", zone=zone, humidity=round(humidity) if humidity is not None else "?", limit=limit, reached=False)
+
+    def _dryer_zone_to_string(self, zone):
+        return "unit '%s'" % self.dryer_units[zone] if self.dryer_units else "zone %d" % zone
+
+    # With 'dryer_preheat_time' the first load from each dryer zone in a print waits until the zone has been heating
+    # that long. The dryer is normally set early (MMU_DRYER in print start or MMU_PREPARE_JOB) so this rarely waits
+    def _soak_dryer_zone(self, gate):
+        if not self.dryer_preheat_time or not self.dryer_heaters or not self._is_in_print(): return
+        zone = self._get_dryer_zone(gate)
+        if zone < 0 or zone in self.dryer_soaked_zones or self.dryer_zone_start[zone] is None: return
+        self.dryer_soaked_zones.add(zone)
+        remaining = self.dryer_preheat_time - (self.reactor.monotonic() - self.dryer_zone_start[zone])
+        if remaining > 0:
+            self._log_info("Waiting %s for dryer %s to soak filament..." % (self._seconds_to_human_string(remaining), self._dryer_zone_to_string(zone)))
+            self.gcode.run_script_from_command("G4 P%d" % (remaining * 1000))
+
+    # Set zone for the materials of the tools it feeds
+    def _set_dryer_zone_for_tools(self, zone, tools):
+        zone_str = " %s" % self._dryer_zone_to_string(zone) if len(self.dryer_heaters) > 1 else ""
+        zone_tools = [t for t in tools if self._get_dryer_zone(self.tool_to_gate_map[t]) == zone]
+        temp, materials = self._get_dryer_temp_for_tools(zone_tools)
+        if temp <= 0:
+            self._log_info("No dryer temperatures defined for materials in use. Dryer%s heater turned off" % zone_str)
+            self._set_dryer_temp(0, zone=zone)
+            return
+        self._log_info("Setting dryer%s to %.1f\u00B0C for materials: %s" % (zone_str, temp, ", ".join(["%s (%.0f)" % (m, t) for m, t in materials.items()])))
+        self._set_dryer_temp(temp, zone=zone)
+
+    # Tools that have had their last toolchange in the print. Zones no longer feeding any remaining tool are turned off
+    # and others re-set for the remaining materials (which may allow a higher temperature)
+    def _dryer_tools_done(self, done):
+        if self.dryer_job_tools is None: return
+        remaining = [t for t in self.dryer_job_tools if t not in done]
+        for z in range(len(self.dryer_heaters)):
+            zone_tools = [t for t in self.dryer_job_tools if self._get_dryer_zone(self.tool_to_gate_map[t]) == z]
+            zone_remaining = [t for t in zone_tools if t in remaining]
+            if len(zone_remaining) == len(zone_tools): continue
+            if not zone_remaining:
+                self._log_info("Materials in dryer%s no longer needed. Heater turned off" % (" %s" % self._dryer_zone_to_string(z) if len(self.dryer_heaters) > 1 else ""))
+                self._set_dryer_temp(0, zone=z)
+            else:
+                self._set_dryer_zone_for_tools(z, zone_remaining)
+        self.dryer_job_tools = remaining
+
+    # Set temperature of dryer zone (or all zones if not specified), limited to the zone maximum
+    def _set_dryer_temp(self, temp, zone=None):
+        for z in ([zone] if zone is not None else range(len(self.dryer_heaters))):
+            name = self.dryer_heaters[z]
+            target = min(temp, self.dryer_zone_max_temps[z]) if self.dryer_zone_max_temps else temp
+            heater = self.printer.lookup_object('heaters').lookup_heater(name)
+            if heater.target_temp != target:
+                self._log_debug("Setting dryer zone %d heater '%s' to %.1f" % (z, name, target))
+                self.gcode.run_script_from_command("SET_HEATER_TEMPERATURE HEATER=%s TARGET=%.1f" % (name, target))
+                self.dryer_zone_start[z] = self.reactor.monotonic() if target > 0 else None
+                self._publish_event("dryer", heater=name, zone=z, target=target)
+
+    # Dryer zone heating gate. With 'dryer_units' this follows the gate's unit in the gate map, otherwise
+    # 'dryer_gate_zones'. Returns -1 if the gate is not in a dryer zone
+    def _get_dryer_zone(self, gate):
+        if self.dryer_units:
+            unit = self.gate_unit[gate]
+            return self.dryer_units.index(unit) if unit in self.dryer_units else -1
+        return self.dryer_gate_zones[gate]
+
+    def _get_dryer_zone_status(self, eventtime):
+        zones = []
+        for z, name in enumerate(self.dryer_heaters):
+            heater = self.printer.lookup_object('heaters').lookup_heater(name)
+            temp, target = heater.get_temp(eventtime)
+            zone = {
+                'heater': name,
+                'unit': self.dryer_units[z] if self.dryer_units else "",
+                'temperature': round(temp, 1),
+                'target': target,
+                'max_temp': self.dryer_zone_max_temps[z] if self.dryer_zone_max_temps else heater.max_temp,
+                'gates': [g for g in range(self.mmu_num_gates) if self._get_dryer_zone(g) == z],
+                'drying_time': int(eventtime - self.dryer_zone_start[z]) if self.dryer_zone_start[z] is not None else 0,
+            }
+            if self.dryer_sensors:
+                ambient = self.printer.lookup_object(self.dryer_sensors[z]).get_status(eventtime)
+                zone['ambient'] = {k: round(v, 1) for k, v in ambient.items() if k in ['temperature', 'humidity', 'pressure']}
+            zones.append(zone)
+        return zones
+
+    # Record observed gate ratio. With 'autotune_rotation_distance' the gate ratio follows the average of recent
+    # observations. Drift from the calibrated baseline beyond the threshold suggests mechanical wear or slipping
+    # grub screw and is reported once (until the gate recovers or is recalibrated)
+    def _track_gate_ratio(self, gate, observed):
+        if gate < 0 or not (0.8 < observed < 1.2): return
+        if self.gate_ratio_baseline[gate] <= 0:
+            self.gate_ratio_baseline[gate] = self._get_gate_ratio(gate)
+        history = self.gate_ratio_history[gate]
+        history.append(round(observed, 6))
+        del history[:-self.RATIO_DRIFT_SAMPLES]
+        average = sum(history) / len(history)
+
+        if self.autotune_rotation_distance and gate > 0 and len(history) >= 3:
+            self.variables["%s%d" % (self.VARS_MMU_CALIB_PREFIX, gate)] = average
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s%d VALUE=%.6f" % (self.VARS_MMU_CALIB_PREFIX, gate, average))
+            self._set_gate_ratio(average)
+            self._log_debug("Autotuned gate #%d ratio to %.6f (observed %.6f)" % (gate, average, observed))
+
+        drift = self._get_gate_ratio_drift(gate)
+        if drift is not None and self.rotation_distance_drift_threshold > 0:
+            if abs(drift) > self.rotation_distance_drift_threshold and not self.gate_ratio_drift_alerted[gate]:
+                self.gate_ratio_drift_alerted[gate] = True
+                self._log_always("Warning: Gate #%d rotation distance has drifted %+.1f%% from calibrated value (%.6f -> %.6f). Check for mechanical wear or loose grub screw and consider recalibrating with MMU_CALIBRATE_GATES GATE=%d" % (gate, drift, self.gate_ratio_baseline[gate], average, gate))
+                self._publish_event("rotation_distance_drift", gate=gate, baseline=self.gate_ratio_baseline[gate], ratio=round(average, 6), drift=round(drift, 2))
+            elif abs(drift) <= self.rotation_distance_drift_threshold:
+                self.gate_ratio_drift_alerted[gate] = False
+        self._persist_gate_ratio_drift()
+
+    # Percentage drift of average observed ratio from baseline or None if not enough observations
+    def _get_gate_ratio_drift(self, gate):
+        history = self.gate_ratio_history[gate]
+        if len(history) < 3 or self.gate_ratio_baseline[gate] <= 0: return None
+        return ((sum(history) / len(history)) / self.gate_ratio_baseline[gate] - 1.) * 100.
+
+    def _reset_gate_ratio_drift(self, gate, baseline):
+        self.gate_ratio_baseline[gate] = baseline
+        self.gate_ratio_history[gate] = []
+        self.gate_ratio_drift_alerted[gate] = False
+        self._persist_gate_ratio_drift()
+        if self.virtual_encoder:
+            self.virtual_encoder.reset(gate)
+
+    def _persist_gate_ratio_drift(self):
+        ratio_drift = {'baseline': self.gate_ratio_baseline, 'history': self.gate_ratio_history}
+        self.variables[self.VARS_MMU_CALIB_RATIO_DRIFT] = ratio_drift
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_CALIB_RATIO_DRIFT, ratio_drift))
+
+    def _gate_ratio_drift_to_human_string(self, detail=False):
+        msg = ""
+        for gate in range(self.mmu_num_gates):
+            drift = self._get_gate_ratio_drift(gate)
+            if drift is None: continue
+            if detail or abs(drift) > self.rotation_distance_drift_threshold:
+                history = self.gate_ratio_history[gate]
+                msg += "\nGate #%d: %+.1f%% (calibrated: %.6f, recent average: %.6f over %d loads)%s" % (gate, drift, self.gate_ratio_baseline[gate], sum(history) / len(history), len(history), " DRIFTING" if abs(drift) > self.rotation_distance_drift_threshold else "")
+        return "Rotation distance drift:%s" % msg if msg else ""
+
+    # Friction health score from a gate's profile: percentage slip of encoder measured movement over commanded movement
+    # at each speed. Every 1% of average slip, and every 1% more slip at the fastest speed than the slowest (drag that
+    # increases with speed), costs 10 points from a perfect 100
+    def _gate_health_score(self, slips):
+        speeds = sorted(slips)
+        average = sum(slips.values()) / len(slips)
+        speed_penalty = max(slips[speeds[-1]] - slips[speeds[0]], 0.)
+        return round(max(min(100. - 10. * (average + speed_penalty), 100.), 0.), 1)
+
+    # Record profile score. The first score after reset is the baseline and a drop of more than 'gate_health_threshold'
+    # suggests PTFE wear or a dirty filament path and is reported once (until the gate recovers or is reset)
+    def _track_gate_health(self, gate, score):
+        if self.gate_health_baseline[gate] < 0:
+            self.gate_health_baseline[gate] = score
+        history = self.gate_health_history[gate]
+        history.append([int(time.time()), score])
+        del history[:-self.GATE_HEALTH_SAMPLES]
+
+        degraded = self.gate_health_baseline[gate] - score
+        if self.gate_health_threshold > 0:
+            if degraded > self.gate_health_threshold and not self.gate_health_alerted[gate]:
+                self.gate_health_alerted[gate] = True
+                self._log_always("Warning: Gate #%d friction health score has degraded from %.1f to %.1f. Check PTFE bowden for wear and clean the filament path" % (gate, self.gate_health_baseline[gate], score))
+                self._publish_event("gate_health_degraded", gate=gate, baseline=self.gate_health_baseline[gate], score=score)
+            elif degraded <= self.gate_health_threshold:
+                self.gate_health_alerted[gate] = False
+        self._persist_gate_health()
+
+    def _reset_gate_health(self, gate):
+        self.gate_health_baseline[gate] = -1.
+        self.gate_health_history[gate] = []
+        self.gate_health_alerted[gate] = False
+        self._persist_gate_health()
+
+    def _persist_gate_health(self):
+        gate_health = {'baseline': self.gate_health_baseline, 'history': self.gate_health_history}
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_GATE_HEALTH, gate_health))
+
+    def _gate_health_to_human_string(self, detail=False):
+        msg = ""
+        for gate in range(self.mmu_num_gates):
+            history = self.gate_health_history[gate]
+            if not history: continue
+            degraded = self.gate_health_threshold > 0 and self.gate_health_baseline[gate] - history[-1][1] > self.gate_health_threshold
+            if detail or degraded:
+                msg += "\nGate #%d: %.1f (baseline: %.1f, profiled %d times, last %s)%s" % (gate, history[-1][1], self.gate_health_baseline[gate], len(history), time.strftime("%Y-%m-%d", time.localtime(history[-1][0])), " DEGRADED" if degraded else "")
+        return "Friction health score:%s" % msg if msg else ""
+
     def _set_gate_ratio(self, ratio=1.):
         self._log_trace("Setting MMU gear motor rotation distance ratio to %.6f" % ratio)
-        new_rotation_distance = ratio * self.ref_gear_rotation_distance
-        self.gear_stepper.set_rotation_distance(new_rotation_distance)
+        if self.gate_drivers and self.gate_selected >= 0:
+            stepper, ref_rotation_distance = self._get_gate_rotation_distance(self.gate_selected)
+            stepper.set_rotation_distance(ratio * ref_rotation_distance)
+        else:
+            new_rotation_distance = ratio * self.ref_gear_rotation_distance
+            self.gear_stepper.set_rotation_distance(new_rotation_distance)
+
+    # Name of gear stepper driving gate: mux driver routed to gate, dedicated stepper per gate or the single gear stepper
+    def _get_gate_gear_stepper_name(self, gate):
+        gear_mux = self.mmu_toolhead.gear_mux
+        if gear_mux and gate >= 0:
+            return gear_mux.get_route(gate)[0]
+        name = "stepper_mmu_gear_%d" % gate
+        if gate > 0 and name in self.gear_driver_defaults:
+            return name
+        return "stepper_mmu_gear"
+
+    # Stepper and reference rotation distance for gate. The stepper's own (or calibrated for the first gear stepper)
+    # rotation distance unless overridden by [mmu_gate_driver], scaled for a different steps per rotation
+    def _get_gate_rotation_distance(self, gate):
+        name = self._get_gate_gear_stepper_name(gate)
+        defaults = self.gear_driver_defaults.get(name, None)
+        if defaults is None:
+            return self.gear_stepper, self.ref_gear_rotation_distance
+        stepper = defaults['stepper']
+        ref = self.ref_gear_rotation_distance if stepper is self.gear_stepper else defaults['rotation_distance']
+        driver = self.gate_drivers.get(gate, None)
+        if driver:
+            if driver.rotation_distance:
+                ref = driver.rotation_distance
+            ref *= float(defaults['microsteps'] * defaults['full_steps_per_rotation']) / (
+                    (driver.microsteps or defaults['microsteps']) * (driver.full_steps_per_rotation or defaults['full_steps_per_rotation']))
+        return stepper, ref
+
+    # Apply [mmu_gate_driver] current and microstep overrides when gate is selected (or restore the
+    # driver's own settings when a gate without overrides shares the driver)
+    def _apply_gate_driver(self, gate):
+        if not self.gate_drivers or gate < 0: return
+        name = self._get_gate_gear_stepper_name(gate)
+        defaults = self.gear_driver_defaults.get(name, None)
+        if defaults is None or defaults['tmc'] is None: return
+        driver = self.gate_drivers.get(gate, None)
+        run_current = driver.run_current if driver and driver.run_current else defaults['run_current']
+        hold_current = driver.hold_current if driver and driver.hold_current else defaults['hold_current']
+        microsteps = driver.microsteps if driver and driver.microsteps else defaults['microsteps']
+        applied = self.gear_driver_applied.get(name, (defaults['run_current'], defaults['hold_current'], defaults['microsteps']))
+        if (run_current, hold_current) != applied[:2]:
+            cmd = "SET_TMC_CURRENT STEPPER=%s CURRENT=%.2f" % (name, run_current)
+            if hold_current is not None:
+                cmd += " HOLDCURRENT=%.2f" % hold_current
+            self.gcode.run_script_from_command(cmd)
+        if microsteps != applied[2]:
+            self.gcode.run_script_from_command("SET_TMC_FIELD STEPPER=%s FIELD=mres VALUE=%d" % (name, int(math.log(256 // microsteps, 2))))
+        if (run_current, hold_current, microsteps) != applied:
+            self._log_debug("Applied gear driver settings for gate #%d on %s: run_current=%.2f, microsteps=%d" % (gate, name, run_current, microsteps))
+        self.gear_driver_applied[name] = (run_current, hold_current, microsteps)
+
+    # Gear stepper and 100% run current for the selected gate (allowing for per-gate overrides)
+    def _get_gear_run_current(self):
+        if self.gate_drivers and self.gate_selected >= 0:
+            name = self._get_gate_gear_stepper_name(self.gate_selected)
+            defaults = self.gear_driver_defaults.get(name, None)
+            if defaults is not None and defaults['tmc'] is not None:
+                driver = self.gate_drivers.get(self.gate_selected, None)
+                return name, driver.run_current if driver and driver.run_current else defaults['run_current']
+        return "stepper_mmu_gear", self.gear_default_run_current
 
     def _get_gate_ratio(self, gate):
         if gate < 0: return 1.
@@ -4464,6 +7814,18 @@ class Mmu:
             self._log_always("Warning: %s%d value (%.6f) is invalid. Using reference value 1.0. Re-run MMU_CALIBRATE_GATES GATE=%d" % (self.VARS_MMU_CALIB_PREFIX, gate, ratio, gate))
             return 1.
 
+    # Synced ratio is optional and falls back to the regular gate ratio if not calibrated
+    def _get_gate_sync_ratio(self, gate):
+        if gate < 0: return 1.
+        ratio = self.variables.get("%s%d" % (self.VARS_MMU_CALIB_SYNC_PREFIX, gate), None)
+        if ratio is None:
+            return self._get_gate_ratio(gate)
+        if ratio > 0.8 and ratio < 1.2:
+            return ratio
+        else:
+            self._log_always("Warning: %s%d value (%.6f) is invalid. Using gate ratio. Re-run MMU_CALIBRATE_SYNC GATE=%d" % (self.VARS_MMU_CALIB_SYNC_PREFIX, gate, ratio, gate))
+            return self._get_gate_ratio(gate)
+
     def _spoolman_activate_spool(self, spool_id=-1):
         if not self.enable_spoolman: return
         try:
@@ -4479,6 +7841,70 @@ class Mmu:
         except Exception as e:
             self._log_error("Error while calling spoolman_set_active_spool: %s" % str(e))
 
+    # Publish MMU event via moonraker component to MQTT broker
+    def _publish_event(self, event, **kwargs):
+        self.api.notify_event(event, **kwargs) # Includes plugins
+        self._log_terse_event(event, **kwargs)
+        if event in self.NOTIFY_EVENTS:
+            self._notify(self.NOTIFY_EVENTS[event], None, **kwargs)
+        if not self.enable_mqtt: return
+        try:
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("mmu_publish_event", event=event, data=kwargs)
+        except Exception as e:
+            self._log_debug("Error while publishing MMU '%s' event: %s" % (event, str(e)))
+
+    # Route notification for event class to the targets configured in 'notify_routes'. The message is built from the
+    # class template, falling back to the default message (or the default template) if it can't be formatted
+    def _notify(self, event_class, message, **kwargs):
+        targets = self.notify_routes.get(event_class, [])
+        if not targets: return
+        fields = dict(kwargs, message=message or "")
+        try:
+            msg = self.notify_templates[event_class].format(**fields)
+        except (KeyError, IndexError, ValueError):
+            msg = message or self.NOTIFY_TEMPLATES[event_class].format(**fields)
+        msg = " ".join(msg.replace('"', "'").replace('#', '').replace(';', ',').split()) # Safe as gcode parameter
+        for target in targets:
+            try:
+                if target == 'm117':
+                    self.gcode.run_script_from_command("M117 %s" % msg)
+                elif target == 'console':
+                    self._log_always(msg)
+                elif target == 'moonraker':
+                    webhooks = self.printer.lookup_object('webhooks')
+                    webhooks.call_remote_method("notify", name=self.notify_moonraker_name, message=msg)
+                elif target == 'macro':
+                    if self.printer.lookup_object("gcode_macro %s" % self.notify_macro, None) is None:
+                        self._log_debug("Notification macro '%s' not found" % self.notify_macro)
+                        continue
+                    self.gcode.run_script_from_command('%s CLASS=%s MSG="%s"' % (self.notify_macro, event_class, msg))
+            except Exception as e:
+                self._log_debug("Error sending '%s' notification to %s: %s" % (event_class, target, str(e)))
+
+    # Send metric to moonraker component for export to InfluxDB/Prometheus. Tagged with job (print filename) if printing
+    def _publish_metric(self, measurement, fields, **tags):
+        if not self.enable_metrics: return
+        try:
+            if self.print_stats and self._is_in_print():
+                tags['job'] = self.print_stats.get_status(self.reactor.monotonic()).get('filename', '')
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("mmu_record_metric", measurement=measurement, fields=fields, tags=tags)
+        except Exception as e:
+            self._log_debug("Error while publishing MMU '%s' metric: %s" % (measurement, str(e)))
+
+    # Gate health: slippage (% difference between gear and encoder), quality grade input and retry/failure counts
+    def _publish_gate_metrics(self, gate):
+        if not self.enable_metrics or gate < 0: return
+        stats = self.gate_statistics[gate]
+        fields = {k: stats.get(k, 0) for k in ['loads', 'unloads', 'load_failures', 'unload_failures', 'servo_retries', 'pauses']}
+        if stats.get('quality', -1) >= 0:
+            fields['quality'] = round(stats['quality'], 4)
+        distance = stats.get('load_distance', 0) + stats.get('unload_distance', 0)
+        if distance > 0:
+            fields['slippage'] = round(abs(stats.get('load_delta', 0) + stats.get('unload_delta', 0)) / distance * 100., 2)
+        self._publish_metric("mmu_gate", fields, gate=gate)
+
     # Tell moonraker component we are interested in filament data
     # gate=None means all gates with spool_id, else specific gate
     def _update_filaments_from_spoolman(self, gate=None):
@@ -4498,6 +7924,97 @@ class Mmu:
             except Exception as e:
                 self._log_error("Error while retrieving spoolman info: %s" % str(e))
 
+    # Keep spoolman "location" of spool in sync with physical gate. Location is cleared when spool leaves the gate
+    def _set_spoolman_location(self, spool_id, gate=None):
+        if not self.enable_spoolman or not self.spoolman_location or spool_id is None or spool_id < 0: return
+        location = ("%s:gate %d" % (self.spoolman_printer_name, gate)) if gate is not None else ""
+        try:
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("spoolman_set_location", spool_id=spool_id, location=location)
+        except Exception as e:
+            self._log_error("Error while updating spoolman location: %s" % str(e))
+
+    # Ask moonraker component to assign spools to gates based on their spoolman location
+    def _pull_spoolman_locations(self):
+        if not self.enable_spoolman: return
+        try:
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("spoolman_pull_locations", printer=self.spoolman_printer_name)
+        except Exception as e:
+            self._log_error("Error while retrieving spoolman locations: %s" % str(e))
+
+    # Tell moonraker component to update spoolman with locally learned filament attributes (e.g. from RFID tag)
+    def _push_filament_to_spoolman(self, gate):
+        if not self.enable_spoolman or not self.spoolman_push or self.gate_spool_id[gate] < 0: return
+        try:
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("spoolman_push_filament", spool_id=self.gate_spool_id[gate], material=self.gate_material[gate], color=self.gate_color[gate])
+        except Exception as e:
+            self._log_error("Error while pushing filament info to spoolman: %s" % str(e))
+
+    # Ask moonraker component to create a new spoolman spool of the same filament as spool_id and assign it to gate
+    def _create_spoolman_spool(self, gate, spool_id):
+        if not self.enable_spoolman: return
+        try:
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("spoolman_create_spool", gate=gate, spool_id=spool_id)
+        except Exception as e:
+            self._log_error("Error while creating spoolman spool: %s" % str(e))
+
+    # A gate whose filament was physically replaced probably holds a different spool. Rather than silently keeping
+    # the old assignment ask the user to confirm it. 'spool_change_prompt: 2' also offers a dialog (Mainsail/Fluidd)
+    def _prompt_spool_change(self, gate):
+        if not self.enable_spoolman or not self.spool_change_prompt: return
+        spool_id = self.gate_spool_id[gate]
+        current = ("spool %d" % spool_id) if spool_id >= 0 else "no spool"
+        if self.spool_change_prompt == 2:
+            lines = ["prompt_begin Happy Hare",
+                     "prompt_text Filament in gate #%d was replaced. It is assigned %s" % (gate, current)]
+            if spool_id >= 0:
+                lines.append("prompt_button Keep spool %d|MMU_SPOOL_CHANGE GATE=%d KEEP=1|primary" % (spool_id, gate))
+                lines.append("prompt_button New spool of same filament|MMU_SPOOL_CHANGE GATE=%d NEW=1|secondary" % gate)
+                lines.append("prompt_button Unassign spool|MMU_SPOOL_CHANGE GATE=%d SPOOLID=-1|warning" % gate)
+            lines += ["prompt_footer_button Close|RESPOND TYPE=command MSG=action:prompt_end|secondary",
+                      "prompt_show"]
+            for line in lines:
+                self.gcode.respond_raw("// action:%s" % line)
+        msg = "Filament in gate #%d was replaced and is still assigned %s. Use 'MMU_SPOOL_CHANGE GATE=%d SPOOLID=..' to assign the new spool" % (gate, current, gate)
+        if spool_id >= 0:
+            msg += ", 'NEW=1' to create a new Spoolman spool of the same filament or 'KEEP=1' if the spool is unchanged"
+        self._log_always(msg)
+        self._publish_event("spool_change", gate=gate, spool_id=spool_id)
+
+    cmd_MMU_SPOOL_CHANGE_help = "Confirm or assign the spool after filament in a gate is replaced"
+    def cmd_MMU_SPOOL_CHANGE(self, gcmd):
+        if self._check_is_disabled(): return
+        gate = gcmd.get_int('GATE', minval=0, maxval=self.mmu_num_gates - 1)
+        spool_id = gcmd.get_int('SPOOLID', None, minval=-1)
+        new = gcmd.get_int('NEW', 0, minval=0, maxval=1)
+        keep = gcmd.get_int('KEEP', 0, minval=0, maxval=1)
+        if spool_id is None and not new and not keep:
+            self._prompt_spool_change(gate)
+            return
+        if self.spool_change_prompt == 2:
+            self.gcode.respond_raw("// action:prompt_end")
+
+        if spool_id is not None:
+            if spool_id != self.gate_spool_id[gate]:
+                self._set_spoolman_location(self.gate_spool_id[gate])
+                self._set_spoolman_location(spool_id, gate)
+                self.gate_spool_id[gate] = spool_id
+                self._persist_gate_map(self._get_command_source(gcmd))
+                self._update_filaments_from_spoolman(gate)
+            self._log_always("Gate #%d assigned %s" % (gate, ("spool %d" % spool_id) if spool_id >= 0 else "no spool"))
+        elif new:
+            if self.gate_spool_id[gate] < 0:
+                raise gcmd.error("Gate #%d has no spool to copy filament from. Use SPOOLID=.. instead" % gate)
+            if not self.enable_spoolman:
+                raise gcmd.error("Spoolman support is not enabled")
+            self._log_always("Creating new Spoolman spool for gate #%d..." % gate)
+            self._create_spoolman_spool(gate, self.gate_spool_id[gate])
+        else:
+            self._log_always("Gate #%d keeps %s" % (gate, ("spool %d" % self.gate_spool_id[gate]) if self.gate_spool_id[gate] >= 0 else "no spool"))
+
 
 ### CORE GCODE COMMANDS ##########################################################
 
@@ -4517,7 +8034,7 @@ class Mmu:
             if tool == -1:
                 self._log_always("Homed")
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
 
     cmd_MMU_SELECT_help = "Select the specified logical tool (following TTG map) or physical gate"
     def cmd_MMU_SELECT(self, gcmd):
@@ -4525,21 +8042,23 @@ class Mmu:
         if self._check_not_homed(): return
         if self._check_is_loaded(): return
         if self._check_is_calibrated(self.CALIBRATED_SELECTOR): return
-        bypass = gcmd.get_int('BYPASS', -1, minval=0, maxval=1)
+        bypass = gcmd.get_int('BYPASS', -1, minval=0, maxval=max(len(self.bypass_offsets), 1))
         tool = gcmd.get_int('TOOL', -1, minval=0, maxval=self.mmu_num_gates - 1)
         gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
         if tool == -1 and gate == -1 and bypass == -1:
             raise gcmd.error("Error on 'MMU_SELECT': missing TOOL, GATE or BYPASS")
+        if bypass == -1 and self._check_bypass_guard(): return
         self._select(bypass, tool, gate)
 
     cmd_MMU_SELECT_BYPASS_help = "Select the filament bypass"
     def cmd_MMU_SELECT_BYPASS(self, gcmd):
-        self._select(1, -1, -1)
+        bypass = gcmd.get_int('BYPASS', 1, minval=1, maxval=max(len(self.bypass_offsets), 1))
+        self._select(bypass, -1, -1)
 
     def _select(self, bypass, tool, gate):
         try:
             if bypass != -1:
-                self._select_bypass()
+                self._select_bypass(max(bypass, 1) - 1) # BYPASS=0 is legacy for first bypass
             elif tool != -1:
                 self._select_tool(tool)
             else:
@@ -4558,7 +8077,7 @@ class Mmu:
                     if not tool_found:
                         self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
         finally:
             self._servo_auto()
 
@@ -4566,6 +8085,7 @@ class Mmu:
     def cmd_MMU_CHANGE_TOOL(self, gcmd):
         if self._check_is_disabled(): return
         if self._check_in_bypass(): return
+        if self._check_bypass_guard(): return
         if self._check_is_calibrated(): return
 
         quiet = gcmd.get_int('QUIET', 0, minval=0, maxval=1)
@@ -4590,29 +8110,83 @@ class Mmu:
             self._last_tool = self.tool_selected
             self._next_tool = tool
             attempts = 2 if self.retry_tool_change_on_error and (self._is_in_print(force_in_print) or standalone) else 1
+            grace = self.toolchange_grace_mode and self._is_printing(force_in_print)
+            if grace:
+                attempts = max(attempts, 1 + self.toolchange_grace_retries)
+            self._start_journal(tool, self._is_printing(force_in_print), skip_tip)
             try:
                 for i in range(attempts):
                     try:
                         if self._change_tool(tool, self._is_printing(force_in_print), skip_tip):
                             self._dump_statistics(job=not quiet, gate=not quiet)
-                        continue
+                        break
                     except MmuError as ee:
                         if i == attempts - 1:
-                            self._mmu_pause("%s.\nOccured when changing tool: %s" % (str(ee), self._last_toolchange))
+                            if grace:
+                                ee = self._toolchange_grace_window(tool, ee, self._is_printing(force_in_print), skip_tip)
+                                if ee is None:
+                                    break
+                            self._mmu_pause("%s.\nOccured when changing tool: %s" % (str(ee), self._last_toolchange), code=ee.code)
                             return
-                        self._log_error("%s.\nOccured when changing tool: %s. Retrying..." % (str(ee), self._last_toolchange))
+                        if grace:
+                            self._log_debug("%s. Occured when changing tool: %s. Retrying quietly (attempt %d of %d)..." % (str(ee), self._last_toolchange, i + 2, attempts))
+                        else:
+                            self._log_error("%s.\nOccured when changing tool: %s. Retrying..." % (str(ee), self._last_toolchange))
                         # Try again but recover_filament_pos will ensure conservative treatment of unload
                         self._recover_filament_pos()
     
                 self._sync_gear_to_extruder(self.sync_to_extruder and self._is_in_print(force_in_print), servo=True, current=self._is_in_print(force_in_print))
+                if self.sync_to_extruder and self._is_in_print(force_in_print) and self.gate_selected != self.TOOL_GATE_BYPASS:
+                    try:
+                        self._verify_gear_synced()
+                    except MmuError as ee:
+                        self._mmu_pause(str(ee), code=ee.code)
             finally:
                 self._next_tool = self.TOOL_GATE_UNKNOWN
+                self._end_journal()
+
+    # Grace mode: rather than immediately pausing and parking, hold the print (hotend stays at temperature) for up to
+    # 'toolchange_grace_window' seconds to give the operator a chance to fix the problem. A change in any filament sensor
+    # (e.g. re-seating filament at the gate) triggers another attempt. Returns None if resolved else the last error
+    def _toolchange_grace_window(self, tool, error, in_print, skip_tip):
+        if self.toolchange_grace_window <= 0:
+            return error
+        gate = self.tool_to_gate_map[tool]
+        self._log_error("%s.\nOccured when changing tool: %s. Waiting up to %s for operator before pausing print" % (str(error), self._last_toolchange, self._seconds_to_human_string(self.toolchange_grace_window)))
+        self._publish_event("toolchange_grace", tool=tool, gate=gate, code=error.code, reason=str(error), window=self.toolchange_grace_window)
+        with self._wrap_action(self.ACTION_WAITING):
+            sensors = self._grace_sensor_states(gate)
+            end_time = self.reactor.monotonic() + self.toolchange_grace_window
+            while self.reactor.monotonic() < end_time:
+                self.reactor.pause(self.reactor.monotonic() + 1.)
+                if self._grace_sensor_states(gate) == sensors:
+                    continue
+                self._log_info("Operator intervention detected. Retrying tool change...")
+                self.reactor.pause(self.reactor.monotonic() + 2.) # Allow filament to settle
+                try:
+                    self._recover_filament_pos()
+                    self._change_tool(tool, in_print, skip_tip)
+                    self._log_always("Tool change recovered during grace window")
+                    return None
+                except MmuError as ee:
+                    error = ee
+                    self._log_error("%s.\nRetry failed. Still waiting for operator..." % str(ee))
+                sensors = self._grace_sensor_states(gate)
+        return error
+
+    def _grace_sensor_states(self, gate):
+        states = self._check_all_sensors()
+        sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+        if sensor is not None:
+            states['pre_gate'] = sensor.runout_helper.filament_present
+        return states
 
     cmd_MMU_LOAD_help = "Loads filament on current tool/gate or optionally loads just the extruder for bypass or recovery usage (EXTUDER_ONLY=1)"
     def cmd_MMU_LOAD(self, gcmd):
         if self._check_is_disabled(): return
         in_bypass = self.gate_selected == self.TOOL_GATE_BYPASS
         extruder_only = bool(gcmd.get_int('EXTRUDER_ONLY', 0, minval=0, maxval=1) or in_bypass)
+        if not extruder_only and self._check_bypass_guard(): return
         with self._wrap_disable_encoder(): # Don't want runout accidently triggering during filament load
             try:
                 if not extruder_only:
@@ -4622,7 +8196,7 @@ class Mmu:
                 else:
                     self._log_always("Filament already loaded")
             except MmuError as ee:
-                self._mmu_pause(str(ee))
+                self._mmu_pause(str(ee), code=ee.code)
                 if self.tool_selected == self.TOOL_GATE_BYPASS:
                     self._set_filament_pos_state(self.FILAMENT_POS_UNKNOWN)
 
@@ -4638,6 +8212,7 @@ class Mmu:
             try:
                 if not extruder_only:
                     self._unload_tool(skip_tip=skip_tip)
+                    self._auto_final_eject(self.gate_selected)
                 elif extruder_only and self.filament_pos != self.FILAMENT_POS_UNLOADED:
                     self._set_filament_pos_state(self.FILAMENT_POS_IN_EXTRUDER, silent=True) # Ensure tool tip is performed
                     self._unload_sequence(length=0, skip_tip=skip_tip, extruder_only=True)
@@ -4647,110 +8222,835 @@ class Mmu:
                 else:
                     self._log_always("Filament not loaded")
             except MmuError as ee:
-                self._mmu_pause(str(ee))
+                self._mmu_pause(str(ee), code=ee.code)
+
+    cmd_MMU_FILAMENT_CHANGE_help = "Manual filament change (M600). Unloads current tool so spool can be replaced and reloads it on RESUME"
+    def cmd_MMU_FILAMENT_CHANGE(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_is_calibrated(): return
+        if self._check_in_bypass(): return
+        pending = self.filament_change_tool >= 0
+        tool = self.filament_change_tool if pending else self.tool_selected
+        if tool < 0:
+            raise gcmd.error("Filament change requires an MMU tool to be selected")
+        gate = gcmd.get_int('GATE', self.filament_change_gate if pending else self.tool_to_gate_map[tool], minval=0, maxval=self.mmu_num_gates - 1)
+        material = gcmd.get('MATERIAL', None)
+        color = gcmd.get('COLOR', None)
+        if color is not None:
+            color = self._validate_color(color)
+            if color is None:
+                raise gcmd.error("Color specification must be %s" % COLOR_FORMATS)
+        spool_id = gcmd.get_int('SPOOLID', None, minval=-1)
+        temperature = gcmd.get_int('TEMP', None, minval=-1)
+
+        if not pending:
+            if self._is_printing():
+                self._log_info("Filament change requested for T%d. Pausing print" % tool)
+                self._wrap_gcode_command("PAUSE", None) # Parks toolhead with user's PAUSE macro
+            with self._wrap_disable_encoder():
+                try:
+                    self._unload_tool()
+                except MmuError as ee:
+                    self._mmu_pause("Filament change unload failed: %s" % str(ee), code=ee.code)
+                    return
+
+        # Update gate map for new spool
+        if material is not None:
+            self.gate_material[gate] = "".join(material.split()).replace('#', '').upper()[:10]
+        if color is not None:
+            self.gate_color[gate] = color
+        if spool_id is not None and spool_id != self.gate_spool_id[gate]:
+            self._set_spoolman_location(self.gate_spool_id[gate])
+            self._set_spoolman_location(spool_id, gate)
+            self.gate_spool_id[gate] = spool_id
+        if temperature is not None:
+            self.gate_temperature[gate] = temperature
+        self._update_gate_color(self.gate_color)
+        self._persist_gate_map(self._get_command_source(gcmd))
+
+        if self._is_in_print():
+            self.filament_change_tool, self.filament_change_gate = tool, gate
+            self._log_always("Replace the spool in gate #%d (or use 'MMU_FILAMENT_CHANGE GATE=..' to choose another gate) and optionally update with 'MMU_GATE_MAP'. Then call 'RESUME' to reload T%d and continue printing" % (gate, tool))
+        else:
+            if gate != self.tool_to_gate_map[tool]:
+                self._remap_tool(tool, gate, source=self._get_command_source(gcmd))
+            self._log_always("T%d unloaded. Replace the spool in gate #%d and reload with 'MMU_CHANGE_TOOL TOOL=%d'" % (tool, gate, tool))
+        self._publish_event("filament_change", tool=tool, gate=gate, state="unloaded")
+
+    # Reload tool after manual filament change, remapping it if the user chose a different gate
+    def _reload_after_filament_change(self):
+        tool, gate = self.filament_change_tool, self.filament_change_gate
+        self.filament_change_tool = self.filament_change_gate = self.TOOL_GATE_UNKNOWN
+        self._log_info("Filament change: reloading T%d from gate #%d" % (tool, gate))
+        with self._wrap_disable_encoder():
+            try:
+                if gate != self.tool_to_gate_map[tool]:
+                    self._remap_tool(tool, gate, source="filament change")
+                if self.gate_status[gate] == self.GATE_EMPTY:
+                    self._set_gate_status(gate, self.GATE_UNKNOWN, source="filament change") # User has just replaced the spool
+                self._select_and_load_tool(tool)
+                self._publish_event("filament_change", tool=tool, gate=gate, state="loaded")
+                return True
+            except MmuError as ee:
+                self._mmu_pause("Failed to reload T%d after filament change: %s" % (tool, str(ee)), code=ee.code)
+                return False
 
     cmd_MMU_PRINT_START_help = "Initialize MMU state and ready for print"
     def cmd_MMU_PRINT_START(self, gcmd):
+        tools = gcmd.get('TOOLS', None)
+        check = gcmd.get_int('CHECK_GATES', self.print_start_check_gates, minval=0, maxval=1)
+        check_dryness = gcmd.get_int('CHECK_DRYNESS', 1, minval=0, maxval=1)
+        toolchanges = gcmd.get_int('TOOLCHANGES', 0, minval=0)
+        initial_tool = gcmd.get_int('INITIAL_TOOL', None, minval=0, maxval=self.mmu_num_gates - 1)
+        grams = gcmd.get('GRAMS', None)
+        check_sufficiency = gcmd.get_int('CHECK_SUFFICIENCY', self.print_start_check_sufficiency, minval=0, maxval=2)
+        check_filament = gcmd.get_int('CHECK_FILAMENT', self.print_start_check_filament, minval=0, maxval=2)
+        materials = gcmd.get('MATERIALS', None)
+        manifest = gcmd.get_int('TTG_MANIFEST', self.print_start_ttg_manifest, minval=0, maxval=2)
         self._on_print_start()
+        if toolchanges > 0:
+            self.job_expected_swaps = toolchanges
+            self._log_debug("Expecting %d toolchanges in this print" % toolchanges)
+        if materials is not None and manifest and self.is_enabled:
+            materials = [m.strip().upper() for m in materials.split(',')]
+            colors = [c.strip() for c in gcmd.get('COLORS', "").split(',')]
+            if not self._apply_job_ttg_manifest(tools, materials, colors, manifest == 2):
+                return
+        if tools is not None and self.is_enabled:
+            try:
+                tools = [int(t) for t in tools.split(',') if t.strip() != ""]
+            except ValueError:
+                raise gcmd.error("Invalid TOOLS parameter: %s" % tools)
+            tools = [t for t in tools if t >= 0 and t < self.mmu_num_gates]
+            if check_dryness:
+                self._check_dryness_for_print(tools)
+            if check and tools:
+                self._log_info("Checking filament path for tools used in print...")
+                self.gcode.run_script_from_command("MMU_CHECK_GATE TOOLS=%s" % ",".join(map(str, tools)))
+        if self.is_enabled and any(d > 0 for d in self.gate_ready_park):
+            self._ready_park_gates(tools if isinstance(tools, list) else None)
+        if grams is not None and check_sufficiency and self.is_enabled and self.enable_spoolman:
+            grams = self._parse_tool_grams(gcmd, grams)
+            if grams:
+                self._request_filament_sufficiency(grams, check_sufficiency == 2)
+
+        # Reload filament that was retracted to gate whilst idle
+        tool = self.idle_retracted_tool
+        if tool >= 0 and self.idle_retract_reload and self.is_enabled and self.filament_pos == self.FILAMENT_POS_UNLOADED and self.tool_selected == tool:
+            self._log_info("Reloading T%d that was retracted whilst idle" % tool)
+            try:
+                self._select_and_load_tool(tool)
+            except MmuError as ee:
+                self._mmu_pause("Failed to reload T%d after idle retraction: %s" % (tool, str(ee)), force_in_print=True, code=ee.code)
 
-    cmd_MMU_PRINT_END_help = "Cleans up state after after print end"
-    def cmd_MMU_PRINT_END(self, gcmd):
-        end_state = gcmd.get('STATE', "complete")
-        if end_state in ["complete", "error", "cancelled", "ready", "standby"]:
-            self._on_print_end(end_state)
-        else:
-            raise gcmd.error("Unknown endstate '%s'" % end_state)
+        if initial_tool is not None and check_filament and self.is_enabled:
+            self._check_loaded_filament_for_print(initial_tool, gcmd.get('MATERIAL', "").strip().upper(), gcmd.get('COLOR', "").strip(), check_filament == 1)
 
-    cmd_MMU_PAUSE_help = "Pause the current print and lock the MMU operations"
-    def cmd_MMU_PAUSE(self, gcmd):
-        if self._check_is_disabled(): return
-        if self._check_in_bypass(): return
-        force_in_print = bool(gcmd.get_int('FORCE_IN_PRINT', 0, minval=0, maxval=1))
-        self._mmu_pause("MMU_PAUSE macro was directly called", force_in_print)
+    # Convert slicer tool material/color manifest into a job scoped TTG map (restored at print end) by remapping tools
+    # onto gates holding matching filament. Only tools used in print are considered if TOOLS is known. Returns False
+    # if strict and the manifest can't be satisfied (print is paused)
+    def _apply_job_ttg_manifest(self, tools, materials, colors, strict):
+        try:
+            used = [int(t) for t in tools.split(',') if t.strip() != ""] if tools else range(len(materials))
+        except ValueError:
+            used = range(len(materials))
+        needs = {t: (materials[t], colors[t] if t < len(colors) else "") for t in used if t < min(len(materials), self.mmu_num_gates) and materials[t]}
+        if not needs: return True
+
+        if self.job_saved_ttg_map is None:
+            self.job_saved_ttg_map = list(self.tool_to_gate_map)
+        self._remap_ttg_for_job(needs)
+        problems = self._verify_gates_for_job(needs)
+        if not problems:
+            self._log_info("Slicer filament manifest satisfied by job tool-to-gate map: %s" % ", ".join("T%d>#%d" % (t, self.tool_to_gate_map[t]) for t in sorted(needs)))
+            return True
+        msg = "Slicer filament manifest can't be satisfied:\n  %s" % "\n  ".join(problems)
+        if strict:
+            self._mmu_pause(msg, force_in_print=True, code=self.ERR_FILAMENT_MISMATCH)
+            return False
+        self._log_error(msg)
+        return True
 
-    cmd_MMU_UNLOCK_help = "Wakeup the MMU prior to resume to restore temperatures and timeouts"
-    def cmd_MMU_UNLOCK(self, gcmd):
-        if self._check_is_disabled(): return
-        if self._is_mmu_pause_locked():
-            self._mmu_unlock()
+    def _restore_job_ttg_map(self):
+        if self.job_saved_ttg_map is None: return
+        if self.job_saved_ttg_map != self.tool_to_gate_map:
+            self.tool_to_gate_map = self.job_saved_ttg_map
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+            self._record_map_change("job TTG map restore")
+            self._log_info("Restored tool-to-gate map after job scoped remap")
+        self.job_saved_ttg_map = None
+
+    # Verify filament left loaded (e.g. from yesterday's print) is what the slicer expects for the first tool. If not,
+    # either swap to the correct tool (remapping onto a gate with matching filament if necessary) or pause
+    def _check_loaded_filament_for_print(self, tool, material, color, swap):
+        if self.filament_pos != self.FILAMENT_POS_LOADED or self.gate_selected < 0: return
+        def mismatch():
+            if self.tool_selected != tool:
+                return "T%d is loaded but print starts with T%d" % (self.tool_selected, tool)
+            if not self._gate_matches_need(self.gate_selected, material, color):
+                return "Loaded filament (%s %s) on gate #%d does not match slicer's %s %s for T%d" % (
+                    self.gate_material[self.gate_selected] or "unknown", self.gate_color[self.gate_selected], self.gate_selected, material, color, tool)
+            return None
 
-    # Not a user facing command - used in automatic wrapper
-    cmd_MMU_RESUME_help = "Wrapper around default RESUME macro"
-    def cmd_MMU_RESUME(self, gcmd):
-        if not self.is_enabled:
-            self._wrap_gcode_command("__RESUME", None) # User defined or Klipper default behavior
+        reason = mismatch()
+        if reason is None:
+            self._log_debug("Loaded filament matches slicer requirements for initial tool T%d" % tool)
             return
+        if swap:
+            self._log_always("%s. Swapping to correct filament before print starts..." % reason)
+            if material:
+                self._remap_ttg_for_job({tool: (material, color)})
+            self.gcode.run_script_from_command("MMU_CHANGE_TOOL TOOL=%d QUIET=1" % tool)
+            if self.filament_pos == self.FILAMENT_POS_LOADED:
+                reason = mismatch()
+                if reason is None: return
+        if not self._is_mmu_paused():
+            self._mmu_pause("Print start filament check failed: %s" % reason, force_in_print=True, code=self.ERR_FILAMENT_MISMATCH)
 
-        self._log_trace("MMU RESUME wrapper called")
-        if not self._is_paused() and not self._is_mmu_paused():
-            self._log_always("Print is not paused. Resume ignored.")
+    cmd_MMU_PREPARE_JOB_help = "Prepare MMU for an upcoming job: verify gates, remap tools, pre-dry and optionally preload"
+    def cmd_MMU_PREPARE_JOB(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_in_print():
+            self._log_always("Cannot prepare MMU for next job whilst a print is in progress")
             return
+        job = gcmd.get('JOB', "next job")
+        tools = gcmd.get('TOOLS', "")
+        materials = [m.strip().upper() for m in gcmd.get('MATERIALS', "").split(',')]
+        colors = [c.strip() for c in gcmd.get('COLORS', "").split(',')]
+        remap = gcmd.get_int('REMAP', self.prepare_job_remap, minval=0, maxval=1)
+        dry = gcmd.get_int('DRY', self.prepare_job_dry, minval=0, maxval=1)
+        preload = gcmd.get_int('PRELOAD', self.prepare_job_preload, minval=0, maxval=1)
+        try:
+            tools = [int(t) for t in tools.split(',') if t.strip() != ""]
+        except ValueError:
+            raise gcmd.error("Invalid TOOLS parameter: %s" % tools)
+        tools = [t for t in tools if t >= 0 and t < self.mmu_num_gates]
+        needs = {t: (materials[t] if t < len(materials) else "", colors[t] if t < len(colors) else "") for t in tools}
+
+        self._log_always("Preparing MMU for %s (%s)..." % (job, ", ".join("T%d" % t for t in tools) or "no tools referenced"))
+        if remap:
+            self._remap_ttg_for_job(needs)
+        problems = self._verify_gates_for_job(needs)
+        if not problems and tools:
+            if dry and self.dryer_heaters:
+                self.gcode.run_script_from_command("MMU_DRYER TOOLS=%s" % ",".join(map(str, tools)))
+            if preload:
+                tool = tools[0]
+                if self.tool_selected != tool or self.filament_pos != self.FILAMENT_POS_LOADED:
+                    self.gcode.run_script_from_command("MMU_CHANGE_TOOL TOOL=%d QUIET=1" % tool)
+                if self.tool_selected != tool or self.filament_pos != self.FILAMENT_POS_LOADED:
+                    problems.append("T%d could not be preloaded" % tool)
+
+        ready = len(problems) == 0
+        if ready:
+            self._log_always("MMU ready for %s" % job)
+        else:
+            self._log_error("MMU not ready for %s:\n  %s" % (job, "\n  ".join(problems)))
+        self._publish_event("job_prepared", job=job, tools=tools, ready=ready, problems=problems)
 
-        if self._is_mmu_pause_locked():
-            self._mmu_unlock()
+    # Whether gate holds filament of required material and (if both known) similar color
+    def _gate_matches_need(self, gate, material, color):
+        if material and self.gate_material[gate].upper() != material:
+            return False
+        if color and self.gate_color[gate] and self.prepare_job_color_tolerance > 0:
+            return colors_match(self.gate_color[gate], color, tolerance=self.prepare_job_color_tolerance)
+        return True
 
-        if self._is_mmu_paused():
-            # Sanity check we are ready to go
-            if self._is_in_print() and self.filament_pos != self.FILAMENT_POS_LOADED:
-                if self._check_sensor(self.ENDSTOP_TOOLHEAD) is True:
-                    self._set_filament_pos_state(self.FILAMENT_POS_LOADED, silent=True)
-                    self._log_always("Automatically set filament state to LOADED based on toolhead sensor")
+    # Point each tool with a known material at an available gate holding that filament. Gates already satisfying a
+    # tool are left alone and a gate is only used for one tool of the job
+    def _remap_ttg_for_job(self, needs):
+        claimed = set()
+        pending = []
+        for tool, (material, color) in sorted(needs.items()):
+            gate = self.tool_to_gate_map[tool]
+            if not material or (self.gate_status[gate] != self.GATE_EMPTY and gate not in claimed and gate not in self.gate_lock and self._gate_matches_need(gate, material, color)):
+                claimed.add(gate)
+            else:
+                pending.append((tool, material, color))
+        for tool, material, color in pending:
+            gate = next((g for g in range(self.mmu_num_gates) if g not in claimed and self.gate_status[g] != self.GATE_EMPTY and
+                            g not in self.gate_maintenance and g not in self.gate_lock and self._gate_matches_need(g, material, color)), None)
+            if gate is None:
+                continue
+            claimed.add(gate)
+            if gate != self.tool_to_gate_map[tool]:
+                self._log_info("Remapping T%d to gate #%d (%s) for job" % (tool, gate, material))
+                self._set_tool_to_gate(tool, gate, source="job TTG manifest")
+
+    def _verify_gates_for_job(self, needs):
+        problems = []
+        for tool, (material, color) in sorted(needs.items()):
+            gate = self.tool_to_gate_map[tool]
+            if self.gate_status[gate] == self.GATE_EMPTY:
+                problems.append("T%d: Gate #%d is empty" % (tool, gate))
+            elif gate in self.gate_maintenance:
+                problems.append("T%d: Gate #%d is in maintenance" % (tool, gate))
+            elif gate in self.gate_lock:
+                problems.append("T%d: Gate #%d is %s" % (tool, gate, self._gate_unavailable_reason(gate)))
+            elif material and not self._gate_matches_need(gate, material, color):
+                problems.append("T%d: Gate #%d has %s %s but job needs %s %s" % (tool, gate, self.gate_material[gate] or "unknown", self.gate_color[gate], material, color))
+        return problems
+
+    cmd_MMU_CHECK_FILAMENT_SUFFICIENCY_help = "Report tools at risk of running out of filament in print based on spoolman weights"
+    def cmd_MMU_CHECK_FILAMENT_SUFFICIENCY(self, gcmd):
+        if self._check_is_disabled(): return
+        grams = self._parse_tool_grams(gcmd, gcmd.get('GRAMS', ""))
+        pause = bool(gcmd.get_int('PAUSE', 0, minval=0, maxval=1))
+        remaining = gcmd.get('REMAINING', None) # Hidden option for spoolman reply from moonraker component
+        if not grams:
+            self._log_always("No filament requirements supplied. Use GRAMS=.. with the weight needed by each tool")
+            return
 
-        self._wrap_gcode_command("__RESUME", None)
-        self._mmu_resume()
-        # Continue printing...
+        # Spool weights are retrieved asynchronously. Moonraker component calls back with REMAINING
+        if remaining is None:
+            if self.enable_spoolman:
+                self._request_filament_sufficiency(grams, pause)
+            else:
+                self._log_always("Spoolman not enabled. Unable to check filament sufficiency")
+            return
+        try:
+            remaining = ast.literal_eval(remaining)
+        except (SyntaxError, ValueError) as e:
+            raise gcmd.error("Invalid REMAINING parameter: %s" % str(e))
+        self._check_filament_sufficiency(grams, remaining, pause)
 
-    # Not a user facing command - used in automatic wrapper
-    cmd_PAUSE_help = "Wrapper around default PAUSE macro"
-    def cmd_PAUSE(self, gcmd):
-        if self.is_enabled:
-            self._log_trace("MMU PAUSE wrapper called")
-            if not self.paused_extruder_temp: # Only save the initial pause temp
-                self.paused_extruder_temp = self.printer.lookup_object(self.extruder_name).heater.target_temp
-        self._wrap_gcode_command("__PAUSE", None) # User defined or Klipper default behavior
+    # Per-tool grams (indexed by tool as in slicer '; filament used [g]'). Returns {tool: grams} for tools that are used
+    def _parse_tool_grams(self, gcmd, grams):
+        try:
+            grams = [float(g) if g.strip() != "" else 0. for g in grams.split(',')]
+        except ValueError:
+            raise gcmd.error("Invalid GRAMS parameter: %s" % grams)
+        return {t: g for t, g in enumerate(grams) if g > 0 and t < self.mmu_num_gates}
 
-    # Not a user facing command - used in automatic wrapper
-    cmd_CLEAR_PAUSE_help = "Wrapper around default CLEAR_PAUSE macro"
-    def cmd_CLEAR_PAUSE(self, gcmd):
-        if self.is_enabled:
-            self._log_trace("MMU CLEAR_PAUSE wrapper called")
-        self._wrap_gcode_command("__CLEAR_PAUSE", None) # User defined or Klipper default behavior
+    def _request_filament_sufficiency(self, grams, pause):
+        spools = {gate: spool_id for gate, spool_id in enumerate(self.gate_spool_id) if spool_id >= 0}
+        grams_str = ",".join("%.2f" % grams.get(t, 0.) for t in range(max(grams) + 1))
+        try:
+            webhooks = self.printer.lookup_object('webhooks')
+            webhooks.call_remote_method("spoolman_check_sufficiency", spools=spools, grams=grams_str, pause=int(pause))
+        except Exception as e:
+            self._log_error("Error while requesting spoolman weights for filament check: %s" % str(e))
+
+    # Compare filament required by each tool with remaining weight of the assigned spool (with margin), falling back to
+    # the combined weight of EndlessSpool alternatives. Optionally pause if any tool is at risk of running out
+    def _check_filament_sufficiency(self, grams, remaining, pause):
+        factor = 1. + self.filament_sufficiency_margin / 100.
+        weight = lambda gate: "%.1fg" % remaining[gate] if remaining.get(gate) is not None else "?"
+        msg = "Filament sufficiency check (%.0f%% margin):" % self.filament_sufficiency_margin
+        msg += "\n  Tool | Gate | Required |  Remaining |     Margin | EndlessSpool backup"
+        at_risk = []
+        for tool, required in sorted(grams.items()):
+            gate = self.tool_to_gate_map[tool]
+            left = remaining.get(gate)
+            backups = []
+            if self.enable_endless_spool:
+                backups = [g for g in range(self.mmu_num_gates) if g != gate and self.endless_spool_groups[g] == self.endless_spool_groups[gate] and
+                              g not in self.gate_maintenance and g not in self.gate_lock and self.gate_status[g] != self.GATE_EMPTY]
+            available = (left or 0.) + sum(remaining.get(g) or 0. for g in backups)
+            if left is None and not backups:
+                status = "Unknown"
+            elif (left or 0.) >= required * factor:
+                status = "Ok"
+            elif available >= required * factor:
+                status = "Ok with EndlessSpool"
+            else:
+                status = "AT RISK"
+                at_risk.append("T%d" % tool)
+            margin = "%+.1fg" % (left - required) if left is not None else "?"
+            backup_str = ", ".join("#%d (%s)" % (g, weight(g)) for g in backups) or "None"
+            msg += "\n  T%-3d | #%-3d | %7.1fg | %10s | %10s | %s - %s" % (tool, gate, required, weight(gate), margin, backup_str, status)
+        self._log_always(msg)
+        self._publish_event("filament_sufficiency", at_risk=at_risk)
+        if at_risk:
+            reason = "Insufficient filament for %s to complete print" % ", ".join(at_risk)
+            if pause:
+                self._mmu_pause(reason, force_in_print=True, code=self.ERR_FILAMENT_INSUFFICIENT)
+            else:
+                self._log_error("Warning: %s" % reason)
 
-    # Not a user facing command - used in automatic wrapper
-    cmd_MMU_CANCEL_PRINT_help = "Wrapper around default CANCEL_PRINT macro"
-    def cmd_MMU_CANCEL_PRINT(self, gcmd):
-        if not self.is_enabled:
-            self._wrap_gcode_command("__CANCEL_PRINT", None) # User defined or Klipper default behavior
+    cmd_MMU_PREHEAT_help = "Start heating extruder for an upcoming toolchange (normally inserted by gcode preprocessor)"
+    def cmd_MMU_PREHEAT(self, gcmd):
+        if self._check_is_disabled(): return
+        tool = gcmd.get_int('TOOL', minval=0, maxval=self.mmu_num_gates - 1)
+        temp = gcmd.get_float('TEMP', 0., minval=0.)
+        if not self.tool_preheat or not self._is_printing() or tool == self.tool_selected:
             return
+        if temp == 0:
+            temp = self.gate_temperature[self.tool_to_gate_map[tool]]
+        extruder = self.printer.lookup_object(self.extruder_name)
+        current_target_temp = extruder.heater.target_temp
 
-        self._log_debug("MMU_CANCEL_PRINT wrapper called")
-        self._save_toolhead_position_and_lift(z_hop_height=self.z_hop_height_error)
-        self._wrap_gcode_command("__CANCEL_PRINT", None)
-        self._on_print_end("cancelled")
+        # Only ramp up. A cooler next tool is left to the slicer so current tool doesn't under extrude
+        if temp > current_target_temp:
+            self._log_info("Preheating extruder to %.1f for upcoming T%d" % (temp, tool))
+            self.gcode.run_script_from_command("SET_HEATER_TEMPERATURE HEATER=extruder TARGET=%.1f" % temp)
 
-    cmd_MMU_RECOVER_help = "Recover the filament location and set MMU state after manual intervention/movement"
-    def cmd_MMU_RECOVER(self, gcmd):
+    cmd_MMU_PURGE_INFILL_help = "Mark start/end of sparse infill that can absorb purge and toolchanges it follows (normally inserted by gcode preprocessor)"
+    def cmd_MMU_PURGE_INFILL(self, gcmd):
         if self._check_is_disabled(): return
-        tool = gcmd.get_int('TOOL', self.TOOL_GATE_UNKNOWN, minval=-2, maxval=self.mmu_num_gates - 1)
-        mod_gate = gcmd.get_int('GATE', self.TOOL_GATE_UNKNOWN, minval=-2, maxval=self.mmu_num_gates - 1)
-        loaded = gcmd.get_int('LOADED', -1, minval=0, maxval=1)
-        strict = gcmd.get_int('STRICT', 0, minval=0, maxval=1)
-
-        if (tool == self.TOOL_GATE_BYPASS or mod_gate == self.TOOL_GATE_BYPASS) and self.bypass_offset == 0:
-            self._log_always("Bypass not configured")
+        start = gcmd.get_int('START', 0, minval=0, maxval=1)
+        end = gcmd.get_int('END', 0, minval=0, maxval=1)
+        next_infill = gcmd.get_int('NEXT', 0, minval=0, maxval=1)
+        if not self.purge_router:
             return
+        if next_infill:
+            self.purge_router.set_next_infill(self._is_printing())
+        if start == end:
+            return
+        in_infill = bool(start)
+        if in_infill and self.printer.lookup_object('exclude_object', None):
+            # Only infill of an object that is being printed (using EXCLUDE_OBJECT metadata if available)
+            status = self.printer.lookup_object('exclude_object').get_status(self.reactor.monotonic())
+            if status.get('objects') and (not status.get('current_object') or status.get('current_object') in status.get('excluded_objects', [])):
+                in_infill = False
+        self.purge_router.set_infill(in_infill and self._is_printing())
+
+    cmd_MMU_IDLE_RETRACT_help = "Internal: retract filament to gate after period of inactivity"
+    def cmd_MMU_IDLE_RETRACT(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_in_print() or self.filament_pos == self.FILAMENT_POS_UNLOADED or self.gate_selected < 0: return
+        tool = self.tool_selected
+        self._log_info("MMU idle for %s. Retracting %s filament back to gate to protect from moisture..."
+                % (self._seconds_to_human_string(self.idle_retract_time), self.gate_material[self.gate_selected] or "unknown"))
+        with self._wrap_disable_encoder():
+            try:
+                self._unload_tool()
+                if self.idle_retract_length > 0:
+                    self._servo_down()
+                    self._trace_filament_move("Idle retract to spool", -self.idle_retract_length)
+                    self._servo_auto()
+                self.idle_retracted_tool = tool
+            except MmuError as ee:
+                self._mmu_pause("Idle retraction failed: %s" % str(ee), code=ee.code)
 
-        if tool == self.TOOL_GATE_BYPASS:
-            self._set_gate_selected(self.TOOL_GATE_BYPASS)
-            self._set_tool_selected(self.TOOL_GATE_BYPASS)
-            self._set_selector_pos(self.bypass_offset) # In case selector stepper was turned off
-        elif tool >= 0: # If tool is specified then use and optionally override the gate
-            self._set_tool_selected(tool)
-            gate = self.tool_to_gate_map[tool]
+    cmd_MMU_IDLE_CARE_help = "Internal: periodically move filament at each gate to prevent it taking a set whilst idle"
+    def cmd_MMU_IDLE_CARE(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_in_print() or self.filament_pos != self.FILAMENT_POS_UNLOADED or not self.is_homed: return
+        gates = [g for g in range(self.mmu_num_gates) if self.gate_status[g] != self.GATE_EMPTY and
+                    (not self.idle_care_materials or self.gate_material[g].upper() in self.idle_care_materials)]
+        if not gates: return
+        self._log_info("MMU idle spool care: moving filament %.1fmm at gates %s" % (self.idle_care_length, ", ".join(map(str, gates))))
+        initial_gate = self.gate_selected
+        with self._wrap_disable_encoder():
+            try:
+                for gate in gates:
+                    self._select_gate(gate)
+                    self._servo_down()
+                    self._trace_filament_move("Idle spool care pull", -self.idle_care_length, speed=self.gear_short_move_speed)
+                    self._trace_filament_move("Idle spool care return", self.idle_care_length, speed=self.gear_short_move_speed)
+                    self._servo_up()
+                if initial_gate >= 0 or initial_gate == self.TOOL_GATE_BYPASS:
+                    self._select_gate(initial_gate)
+            except MmuError as ee:
+                self._log_error("Idle spool care failed: %s" % str(ee))
+            finally:
+                self._servo_auto()
+
+    cmd_MMU_DEEP_PARK_help = "Internal: return gates parked at ready position after print to normal parking position"
+    def cmd_MMU_DEEP_PARK(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_in_print() or not self.is_homed: return
+        gates = [g for g in sorted(self.ready_parked_gates) if not (g == self.gate_selected and self.filament_pos != self.FILAMENT_POS_UNLOADED)]
+        self.ready_parked_gates.clear()
+        if not gates: return
+        self._log_info("Returning filament at gates %s from ready to normal park position" % ", ".join(map(str, gates)))
+        initial_gate = self.gate_selected
+        with self._wrap_disable_encoder():
+            try:
+                for gate in gates:
+                    self._select_gate(gate)
+                    self._servo_down()
+                    self._trace_filament_move("Deep park", -(self.gate_parking_distance - self.gate_ready_park[gate]), speed=self.gear_short_move_speed)
+                    self._servo_up()
+                if initial_gate >= 0 or initial_gate == self.TOOL_GATE_BYPASS:
+                    self._select_gate(initial_gate)
+            except MmuError as ee:
+                self._log_error("Return to deep park failed: %s" % str(ee))
+            finally:
+                self._servo_auto()
+
+    cmd_MMU_SAFETY_help = "Report safety watchdog status of selector and servo or re-arm tripped components"
+    def cmd_MMU_SAFETY(self, gcmd):
+        if self._check_is_disabled(): return
+        reset = bool(gcmd.get_int('RESET', 0, minval=0, maxval=1))
+        if reset:
+            if not self.safety_tripped:
+                self._log_always("No components are disabled by safety watchdog")
+                return
+            for component in self.safety_tripped:
+                self.safety_failures[component] = 0
+            self._log_always("Safety watchdog re-armed: %s" % ", ".join(self.safety_tripped))
+            self.safety_tripped = {}
+            return
+
+        msg = "Safety watchdog is %s" % ("ENABLED" if self.safety_watchdog else "DISABLED")
+        if self.safety_watchdog:
+            msg += " (timeout %.1fs, trips after %d consecutive failures)" % (self.safety_watchdog_timeout, self.safety_watchdog_failures)
+        for component in self.SAFETY_COMPONENTS:
+            state = "TRIPPED: %s" % self.safety_tripped[component] if component in self.safety_tripped else "ok"
+            msg += "\n%s: %s, consecutive failures: %d, total trips: %d" % (component.capitalize(), state, self.safety_failures[component], self.safety_trips[component])
+        self._log_always(msg)
+
+    cmd_MMU_SELF_CHECK_help = "Run lightweight self-check of sensors, selector homing and occupied gates and report summary"
+    def cmd_MMU_SELF_CHECK(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_in_print():
+            raise gcmd.error("MMU_SELF_CHECK cannot be run during a print")
+        scheduled = bool(gcmd.get_int('SCHEDULED', 0, minval=0, maxval=1))
+        check_gates = bool(gcmd.get_int('CHECK_GATES', self.self_check_gates, minval=0, maxval=1))
+        issues, notes = [], []
+        start = self.reactor.monotonic()
+        self._log_info("Running %sMMU self-check..." % ("scheduled " if scheduled else ""))
+
+        with self._wrap_action(self.ACTION_CHECKING):
+            issues.extend(self._self_check_sensors())
+
+            unloaded = self.filament_pos == self.FILAMENT_POS_UNLOADED
+            if not unloaded:
+                notes.append("Filament is loaded so selector homing and gate checks were skipped")
+            elif self.virtual_selector or self.tool_selected == self.TOOL_GATE_BYPASS:
+                notes.append("Selector homing skipped")
+            else:
+                try:
+                    self._home(tool=self.tool_selected)
+                    if not self.is_homed:
+                        issues.append("Selector failed to home")
+                except MmuError as ee:
+                    issues.append("Selector homing failed: %s" % str(ee))
+
+            if unloaded and check_gates and self.is_homed:
+                gates = [g for g in range(self.mmu_num_gates) if self.gate_status[g] != self.GATE_EMPTY and g not in self.gate_maintenance]
+                if gates:
+                    try:
+                        self.gcode.run_script_from_command("MMU_CHECK_GATE GATES=%s QUIET=1" % ",".join(map(str, gates)))
+                    except Exception as e:
+                        issues.append("Gate check failed: %s" % str(e))
+                    for gate in gates:
+                        if self.gate_status[gate] == self.GATE_EMPTY:
+                            issues.append("Gate #%d was marked available but filament was not found" % gate)
+                    if self.filament_pos != self.FILAMENT_POS_UNLOADED:
+                        issues.append("Filament could not be parked after checking gates")
+                self._persist_gate_statistics()
+
+        self.self_check_last = {
+            'time': time.time(),
+            'passed': not issues,
+            'issues': issues,
+            'duration': round(self.reactor.monotonic() - start, 1),
+        }
+        msg = "MMU self-check %s in %s" % ("PASSED" if not issues else "FAILED with %d issue(s)" % len(issues), self._seconds_to_human_string(self.self_check_last['duration']))
+        for issue in issues:
+            msg += "\n- %s" % issue
+        for note in notes:
+            msg += "\n(%s)" % note
+        if issues:
+            self._log_error(msg)
+        else:
+            self._log_always(msg)
+        self._publish_event("self_check", passed=not issues, issues=issues, scheduled=scheduled)
+
+    # Look for sensor readings that contradict the MMU's idea of filament position and gate map
+    def _self_check_sensors(self):
+        issues = []
+        sensors = self._check_all_sensors()
+        if self.filament_pos == self.FILAMENT_POS_UNLOADED:
+            for name in [self.ENDSTOP_GATE, self.ENDSTOP_EXTRUDER, self.ENDSTOP_TOOLHEAD] + [n for _, n in self.path_sensors]:
+                if sensors.get(name, None) is True and not (name == self.ENDSTOP_GATE and self.gate_parking_distance < 0):
+                    issues.append("'%s' sensor detects filament but MMU is unloaded (stuck sensor or filament fragment?)" % name)
+        elif self.filament_pos == self.FILAMENT_POS_LOADED:
+            for name in [self.ENDSTOP_EXTRUDER, self.ENDSTOP_TOOLHEAD]:
+                if sensors.get(name, None) is False:
+                    issues.append("'%s' sensor does not detect filament but MMU is loaded" % name)
+        for gate in range(self.mmu_num_gates):
+            sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+            if sensor is not None and sensor.runout_helper.sensor_enabled and gate not in self.gate_maintenance:
+                detected = sensor.runout_helper.filament_present
+                if detected and self.gate_status[gate] == self.GATE_EMPTY:
+                    issues.append("Pre-gate sensor detects filament at gate #%d but gate is marked empty" % gate)
+                elif not detected and self.gate_status[gate] != self.GATE_EMPTY:
+                    issues.append("Pre-gate sensor does not detect filament at gate #%d but gate is marked available" % gate)
+        return issues
+
+    cmd_MMU_PRINT_END_help = "Cleans up state after after print end"
+    def cmd_MMU_PRINT_END(self, gcmd):
+        end_state = gcmd.get('STATE', "complete")
+        if end_state in ["complete", "error", "cancelled", "ready", "standby"]:
+            self._on_print_end(end_state)
+        else:
+            raise gcmd.error("Unknown endstate '%s'" % end_state)
+
+    cmd_MMU_SLICER_TIP_PARAMETERS_help = "Set per-tool tip forming parameters from slicer filament profiles (!slicer_tip_parameters! placeholder)"
+    def cmd_MMU_SLICER_TIP_PARAMETERS(self, gcmd):
+        reset = bool(gcmd.get_int('RESET', 0, minval=0, maxval=1))
+        profiles = gcmd.get('PROFILES', None)
+        if reset:
+            self.slicer_tip_params = {}
+        elif profiles is not None:
+            if "!slicer_tip_parameters!" in profiles:
+                self._log_debug("Slicer tip parameters not available (gcode preprocessor not enabled)")
+                return
+            try:
+                profiles = ast.literal_eval(profiles)
+                self.slicer_tip_params = {int(tool): {str(k).upper(): str(v) for k, v in params.items()} for tool, params in profiles.items()}
+            except (ValueError, SyntaxError, AttributeError) as e:
+                raise gcmd.error("Invalid PROFILES parameter: %s" % str(e))
+        if not self.slicer_tip_params:
+            self._log_always("No slicer tip forming parameters set")
+            return
+        msg = "Slicer tip forming parameters%s:" % ("" if self.slicer_tip_passthrough else " (passthrough disabled)")
+        for tool, params in sorted(self.slicer_tip_params.items()):
+            msg += "\nT%d: %s" % (tool, " ".join("%s=%s" % (k, v) for k, v in sorted(params.items())))
+        self._log_always(msg)
+
+    # Parameters to pass to standalone tip forming macro derived from slicer's filament profile for current tool
+    def _get_slicer_tip_params(self):
+        if not self.slicer_tip_passthrough or not self._is_in_print():
+            return {}
+        params = self.slicer_tip_params.get(self.tool_selected, {})
+        if not params:
+            return {}
+        self._log_debug("Using slicer tip forming parameters for T%d" % self.tool_selected)
+        if 'RAMMING_SPEEDS' in params:
+            params = dict(params, SS_RAMMING=0) # Slicer isn't ramming so ensure macro does
+        return params
+
+    # Tip forming profile for gate. Gate specific profile takes precedence over material then default
+    def _get_tip_profile(self, gate):
+        if gate < 0:
+            return self.form_tip_profile
+        if self.form_tip_profile_gates[gate]:
+            return self.form_tip_profile_gates[gate]
+        return self.form_tip_profile_materials.get(self.gate_material[gate].upper(), self.form_tip_profile)
+
+    # Cooling move parameters for the configured hotend type and the material loaded in gate
+    def _get_cooling_params(self, gate):
+        if not self.hotend_type:
+            return {}
+        return get_cooling_profile(self.hotend_type, self.gate_material[gate] if gate >= 0 else '')
+
+    # Fully resolved parameters to pass to standalone tip forming macro. Hotend cooling parameters are overridden
+    # by the profile and slicer parameters override both
+    def _get_form_tip_params(self):
+        params = self._get_cooling_params(self.gate_selected)
+        profile = self._get_tip_profile(self.gate_selected)
+        if profile:
+            self._log_debug("Using '%s' tip forming profile" % profile)
+            params.update(get_tip_profile(profile))
+        params.update(self._get_slicer_tip_params())
+        # Calibrated pin travel is only passed to a cutter macro that declares it
+        travel = self.cutter_travel_trial if self.cutter_travel_trial is not None else self.cutter_travel
+        if travel is not None and self.cutter_location == self.CUTTER_TOOLHEAD:
+            gcode_macro = self.printer.lookup_object("gcode_macro %s" % self.form_tip_macro, None)
+            if gcode_macro is not None and 'cut_travel' in gcode_macro.variables:
+                params['CUT_TRAVEL'] = travel
+        return " ".join("%s=%s" % (k, v) for k, v in sorted(params.items()))
+
+    cmd_MMU_TIP_PROFILES_help = "List the built-in tip forming profiles or display the parameters of one"
+    def cmd_MMU_TIP_PROFILES(self, gcmd):
+        profile = gcmd.get('PROFILE', None)
+        if profile is not None:
+            params = get_tip_profile(profile)
+            if params is None:
+                raise gcmd.error("Unknown tip forming profile '%s'. Options are: %s" % (profile, get_tip_profile_names()))
+            msg = "Tip forming profile '%s' (%s):" % (profile.lower(), get_tip_profile_description(profile))
+            for k, v in sorted(params.items()):
+                msg += "\n%s=%s" % (k, v)
+            self._log_always(msg)
+            return
+
+        msg = "Built-in tip forming profiles:"
+        for name in get_tip_profile_names():
+            msg += "\n%s - %s" % (name, get_tip_profile_description(name))
+        msg += "\nProfile used for each gate (default '%s'):" % (self.form_tip_profile or "none")
+        for gate in range(self.mmu_num_gates):
+            msg += "\nGate %d (%s): %s" % (gate, self.gate_material[gate] or "unknown material", self._get_tip_profile(gate) or "none (macro variables)")
+        if self.hotend_type:
+            msg += "\nCooling moves for hotend type '%s' (%s), overridden by profile:" % (self.hotend_type, get_hotend_description(self.hotend_type))
+            for gate in range(self.mmu_num_gates):
+                msg += "\nGate %d: %s" % (gate, " ".join("%s=%s" % (k, v) for k, v in sorted(self._get_cooling_params(gate).items())))
+        self._log_always(msg)
+
+    cmd_MMU_PAUSE_help = "Pause the current print and lock the MMU operations"
+    def cmd_MMU_PAUSE(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_in_bypass(): return
+        force_in_print = bool(gcmd.get_int('FORCE_IN_PRINT', 0, minval=0, maxval=1))
+        self._mmu_pause("MMU_PAUSE macro was directly called", force_in_print, code=self.ERR_MANUAL_PAUSE)
+
+    cmd_MMU_UNLOCK_help = "Wakeup the MMU prior to resume to restore temperatures and timeouts"
+    def cmd_MMU_UNLOCK(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_mmu_pause_locked():
+            self._mmu_unlock()
+
+    # Not a user facing command - used in automatic wrapper
+    cmd_MMU_RESUME_help = "Wrapper around default RESUME macro"
+    def cmd_MMU_RESUME(self, gcmd):
+        if not self.is_enabled:
+            self._wrap_gcode_command("__RESUME", None) # User defined or Klipper default behavior
+            return
+
+        self._log_trace("MMU RESUME wrapper called")
+        if not self._is_paused() and not self._is_mmu_paused():
+            self._log_always("Print is not paused. Resume ignored.")
+            return
+
+        if self._is_mmu_pause_locked():
+            self._mmu_unlock()
+
+        if self._is_mmu_paused():
+            # Sanity check we are ready to go
+            if self._is_in_print() and self.filament_pos != self.FILAMENT_POS_LOADED:
+                if self._check_sensor(self.ENDSTOP_TOOLHEAD) is True:
+                    self._set_filament_pos_state(self.FILAMENT_POS_LOADED, silent=True)
+                    self._log_always("Automatically set filament state to LOADED based on toolhead sensor")
+            if self._is_in_print() and not self._verify_resume_state():
+                return # Remain paused
+
+        if self.filament_change_tool >= 0:
+            if not self._reload_after_filament_change():
+                return # Remain paused
+
+        self._wrap_gcode_command("__RESUME", None)
+        self._mmu_resume()
+        # Continue printing...
+
+    # After a long MMU pause filament may have been handled. Check sensors agree with the loaded gate, the restored
+    # extruder temperature matches the gate map and re-establish the filament position from the toolhead sensor before
+    # the print continues. A failed check leaves the print paused but isn't repeated on the next RESUME
+    def _verify_resume_state(self):
+        paused = time.time() - self.pause_start_time
+        if self.resume_verify_time <= 0 or not self.pause_start_time or paused < self.resume_verify_time:
+            return True
+        if self.resume_verify_failed:
+            self.resume_verify_failed = False
+            self._log_info("Resuming without repeating state verification")
+            return True
+
+        self._log_info("MMU was paused for %s. Verifying state before resuming..." % self._seconds_to_human_string(paused))
+        issues = []
+        gate = self.gate_selected
+        if self.filament_pos == self.FILAMENT_POS_LOADED and gate >= 0:
+            for name in [self.ENDSTOP_GATE, self.ENDSTOP_EXTRUDER, self.ENDSTOP_TOOLHEAD]:
+                if self._check_sensor(name) is False and not (name == self.ENDSTOP_GATE and self.gate_parking_distance < 0):
+                    issues.append("'%s' sensor does not detect filament but gate #%d is loaded" % (name, gate))
+            sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+            if sensor is not None and sensor.runout_helper.sensor_enabled and not sensor.runout_helper.filament_present:
+                issues.append("Pre-gate sensor does not detect filament at loaded gate #%d" % gate)
+
+            target_temp = self.paused_extruder_temp or self._get_extruder_target_temp()
+            gate_temp = self.gate_temperature[gate]
+            if self.resume_verify_temp_tolerance > 0 and gate_temp > 0 and abs(target_temp - gate_temp) > self.resume_verify_temp_tolerance:
+                issues.append("Extruder temperature %.0f doesn't match gate map temperature %.0f of gate #%d (was the spool changed?)" % (target_temp, gate_temp, gate))
+
+            if not issues and self._has_sensor(self.ENDSTOP_TOOLHEAD):
+                try:
+                    self._rehome_to_toolhead_sensor()
+                except MmuError as ee:
+                    issues.append(str(ee))
+
+        if issues:
+            self.resume_verify_failed = True
+            self._log_error("Resume verification failed:\n- %s\nFix and RESUME again to continue (verification will not be repeated)" % "\n- ".join(issues))
+            return False
+        self._log_info("Resume verification passed")
+        return True
+
+    # Retract loaded filament until toolhead sensor releases and reload to nozzle so position is known again
+    def _rehome_to_toolhead_sensor(self):
+        self._log_info("Re-homing filament to toolhead sensor...")
+        length = self.toolhead_sensor_to_nozzle + self.toolhead_unload_safety_margin
+        with self._wrap_action(self.ACTION_LOADING_EXTRUDER):
+            self._servo_up()
+            _,homed,_,_ = self._trace_filament_move("Reverse homing to toolhead sensor", -length, speed=self.extruder_unload_speed, motor="extruder", homing_move=-1, endstop_name=self.ENDSTOP_TOOLHEAD)
+            if not homed:
+                self._set_filament_pos_state(self.FILAMENT_POS_UNKNOWN)
+                raise MmuError("Toolhead sensor did not release after retracting %.1fmm" % length, code=self.ERR_TOOLHEAD_HOME_TIMEOUT)
+            self._set_filament_pos_state(self.FILAMENT_POS_HOMED_TS)
+            self._trace_filament_move("Loading filament to nozzle", self.toolhead_sensor_to_nozzle, speed=self.extruder_load_speed, motor="extruder", wait=True)
+            self._set_filament_pos_state(self.FILAMENT_POS_LOADED)
+
+    # Not a user facing command - used in automatic wrapper
+    cmd_PAUSE_help = "Wrapper around default PAUSE macro"
+    def cmd_PAUSE(self, gcmd):
+        if self.is_enabled:
+            self._log_trace("MMU PAUSE wrapper called")
+            if not self.paused_extruder_temp: # Only save the initial pause temp
+                self.paused_extruder_temp = self._get_extruder_target_temp()
+        self._wrap_gcode_command(("__PAUSE %s" % gcmd.get_raw_command_parameters()).strip(), None) # User defined or Klipper default behavior (passing MMU_ERROR code)
+
+    # Not a user facing command - used in automatic wrapper
+    cmd_CLEAR_PAUSE_help = "Wrapper around default CLEAR_PAUSE macro"
+    def cmd_CLEAR_PAUSE(self, gcmd):
+        if self.is_enabled:
+            self._log_trace("MMU CLEAR_PAUSE wrapper called")
+        self._wrap_gcode_command("__CLEAR_PAUSE", None) # User defined or Klipper default behavior
+
+    # Not a user facing command - used in automatic wrapper
+    cmd_MMU_CANCEL_PRINT_help = "Wrapper around default CANCEL_PRINT macro"
+    def cmd_MMU_CANCEL_PRINT(self, gcmd):
+        if not self.is_enabled:
+            self._wrap_gcode_command("__CANCEL_PRINT", None) # User defined or Klipper default behavior
+            return
+
+        self._log_debug("MMU_CANCEL_PRINT wrapper called")
+        self._save_toolhead_position_and_lift(z_hop_height=self.z_hop_height_error)
+        if self._is_mmu_paused():
+            self._track_pause_end()
+        self._cancel_print_unload()
+        self._wrap_gcode_command("__CANCEL_PRINT", None)
+        self._on_print_end("cancelled")
+        self._dump_statistics(job=True, gate=True)
+
+    # Apply 'cancel_print_unload' policy whilst the nozzle is still hot. When unloading is enabled a toolchange interrupted
+    # by an error (filament left part way along the path) is rolled back to the gate first so the MMU is left in a known
+    # state. Nothing is moved if the filament position is unknown
+    def _cancel_print_unload(self):
+        if self.cancel_print_unload == 0 or self.gate_selected == self.TOOL_GATE_BYPASS or not self.is_homed:
+            return
+        if self.filament_pos == self.FILAMENT_POS_UNLOADED:
+            return
+        if self.filament_pos == self.FILAMENT_POS_UNKNOWN:
+            self._log_always("Filament position is unknown so it was not unloaded after print cancel. Use MMU_RECOVER to establish state")
+            return
+        in_flight = self.filament_pos != self.FILAMENT_POS_LOADED
+        try:
+            if in_flight:
+                self._log_info("Rolling back interrupted toolchange %s..." % self._last_toolchange)
+                self._unload_tool()
+            elif self.cancel_print_unload == 1:
+                self._log_info("Unloading filament to gate after print cancel...")
+                self._unload_tool()
+            else:
+                self._log_info("Unloading extruder after print cancel...")
+                self._unload_sequence(extruder_only=True)
+        except MmuError as ee:
+            self._log_error("Failed to unload filament after print cancel: %s" % str(ee))
+
+    cmd_MMU_RECOVER_help = "Recover the filament location and set MMU state after manual intervention/movement"
+    def cmd_MMU_RECOVER(self, gcmd):
+        if self._check_is_disabled(): return
+        tool = gcmd.get_int('TOOL', self.TOOL_GATE_UNKNOWN, minval=-2, maxval=self.mmu_num_gates - 1)
+        mod_gate = gcmd.get_int('GATE', self.TOOL_GATE_UNKNOWN, minval=-2, maxval=self.mmu_num_gates - 1)
+        loaded = gcmd.get_int('LOADED', -1, minval=0, maxval=1)
+        strict = gcmd.get_int('STRICT', 0, minval=0, maxval=1)
+
+        if (tool == self.TOOL_GATE_BYPASS or mod_gate == self.TOOL_GATE_BYPASS) and self.bypass_offset == 0:
+            self._log_always("Bypass not configured")
+            return
+
+        if tool == self.TOOL_GATE_BYPASS:
+            self._set_gate_selected(self.TOOL_GATE_BYPASS)
+            self._set_tool_selected(self.TOOL_GATE_BYPASS)
+            self._set_selector_pos(self.bypass_offset) # In case selector stepper was turned off
+        elif tool >= 0: # If tool is specified then use and optionally override the gate
+            self._set_tool_selected(tool)
+            gate = self.tool_to_gate_map[tool]
             if mod_gate >= 0:
                 gate = mod_gate
             if gate >= 0:
-                self._remap_tool(tool, gate, loaded)
+                self._remap_tool(tool, gate, loaded, source=self._get_command_source(gcmd))
                 self._set_gate_selected(gate)
                 self._set_selector_pos(self.selector_offsets[self.gate_selected]) # In case selector stepper was turned off
         elif tool == self.TOOL_GATE_UNKNOWN and self.tool_selected == self.TOOL_GATE_BYPASS and loaded == -1:
@@ -4774,6 +9074,102 @@ class Mmu:
         self._recover_filament_pos(strict=strict, message=True)
         self._servo_auto()
 
+    cmd_MMU_PLR_SAVE_help = "Persist MMU state for power-loss recovery (add to slicer layer change gcode)"
+    def cmd_MMU_PLR_SAVE(self, gcmd):
+        if self._check_is_disabled(): return
+        layer = gcmd.get_int('LAYER', None, minval=0)
+        self._persist_plr_state(layer)
+
+    cmd_MMU_PLR_RESUME_help = "Reconcile saved power-loss recovery state with actual MMU state prior to resuming print"
+    def cmd_MMU_PLR_RESUME(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_in_bypass(): return
+        load = bool(gcmd.get_int('LOAD', 1, minval=0, maxval=1))
+        plr_state = self.variables.get(self.VARS_MMU_PLR_STATE, None)
+        if not plr_state:
+            raise gcmd.error("No power-loss recovery state has been saved")
+
+        tool = plr_state.get('tool', self.TOOL_GATE_UNKNOWN)
+        gate = plr_state.get('gate', self.TOOL_GATE_UNKNOWN)
+        saved_pos = plr_state.get('filament_pos', self.FILAMENT_POS_UNKNOWN)
+        self._log_always("Power-loss recovery state: T%d on gate #%d, filament_pos: %d (layer %d)" % (tool, gate, saved_pos, plr_state.get('layer', -1)))
+
+        try:
+            ttg_map = plr_state.get('ttg_map', [])
+            if len(ttg_map) == self.mmu_num_gates:
+                self.tool_to_gate_map = ttg_map
+                self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+                self._record_map_change(self._get_command_source(gcmd))
+
+            if tool >= 0 and gate >= 0:
+                self._set_tool_selected(tool)
+                self._set_gate_selected(gate)
+                self._set_selector_pos(self.selector_offsets[gate]) # Assume selector was not touched
+            self._recover_filament_pos(message=True)
+
+            if saved_pos == self.FILAMENT_POS_LOADED and self.filament_pos != self.FILAMENT_POS_LOADED:
+                if tool < 0:
+                    raise MmuError("Saved state indicates filament was loaded but tool is unknown", code=self.ERR_STATE)
+                if not load:
+                    raise MmuError("Saved state indicates T%d was loaded but filament_pos is now: %d" % (tool, self.filament_pos), code=self.ERR_STATE)
+                self._log_always("Filament is not where it was saved, reloading T%d..." % tool)
+                self._home(tool)
+                self._select_and_load_tool(tool)
+            elif saved_pos == self.FILAMENT_POS_UNLOADED and self.filament_pos != self.FILAMENT_POS_UNLOADED:
+                raise MmuError("Saved state indicates MMU was unloaded but filament was detected. Please fix manually", code=self.ERR_STATE)
+            self._log_always("MMU state reconciled for power-loss recovery. Ready to run resume macro")
+        except MmuError as ee:
+            self._mmu_pause("Power-loss recovery failed: %s" % str(ee))
+
+    cmd_MMU_DRYER_help = "Preheat the MMU dryer to a safe storage temperature for the materials used in print"
+    def cmd_MMU_DRYER(self, gcmd):
+        if self._check_is_disabled(): return
+        if not self.dryer_heaters:
+            raise gcmd.error("Dryer heater not configured ('dryer_heater' parameter)")
+        zone = gcmd.get_int('ZONE', None, minval=0, maxval=len(self.dryer_heaters) - 1)
+        unit = gcmd.get('UNIT', None)
+        if unit is not None:
+            if unit not in self.dryer_units:
+                raise gcmd.error("Unit '%s' is not heated by a dryer zone. Configured units: %s" % (unit, ", ".join(self.dryer_units)))
+            zone = self.dryer_units.index(unit)
+        zones = [zone] if zone is not None else range(len(self.dryer_heaters))
+        if gcmd.get_int('OFF', 0, minval=0, maxval=1):
+            for z in zones:
+                self._set_dryer_temp(0, zone=z)
+            if zone is None:
+                self.dryer_job_tools = None
+            self._log_info("Dryer heater%s turned off" % (" for %s" % self._dryer_zone_to_string(zone) if zone is not None else ""))
+            return
+        done = gcmd.get('DONE', None)
+        if done is not None:
+            try:
+                done = [int(t) for t in done.split(',') if t.strip() != ""]
+            except ValueError:
+                raise gcmd.error("Invalid DONE parameter: %s" % done)
+            self._dryer_tools_done(done)
+            return
+        tools = gcmd.get('TOOLS', ",".join(map(str, range(self.mmu_num_gates))))
+        soak = gcmd.get_int('SOAK', 0, minval=0)
+        fixed_temp = gcmd.get_float('TEMP', None, minval=0.)
+        try:
+            tools = [int(t) for t in tools.split(',') if t.strip() != ""]
+            tools = [t for t in tools if t >= 0 and t < self.mmu_num_gates]
+        except ValueError:
+            raise gcmd.error("Invalid TOOLS parameter: %s" % tools)
+
+        # Each zone is set for the materials in the gates it feeds
+        for z in zones:
+            if fixed_temp is not None:
+                self._log_info("Setting dryer%s to %.1f\u00B0C" % (" %s" % self._dryer_zone_to_string(z) if len(self.dryer_heaters) > 1 else "", fixed_temp))
+                self._set_dryer_temp(fixed_temp, zone=z)
+            else:
+                self._set_dryer_zone_for_tools(z, tools)
+        if fixed_temp is None and zone is None:
+            self.dryer_job_tools = tools
+        if soak > 0:
+            self._log_info("Soaking filament for %s before continuing..." % self._seconds_to_human_string(soak))
+            self.gcode.run_script_from_command("G4 P%d" % (soak * 1000))
+
 
 ### GCODE COMMANDS INTENDED FOR TESTING #####################################
 
@@ -4834,7 +9230,7 @@ class Mmu:
                             self._unload_tool()
             self._select_tool(0)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
 
     cmd_MMU_TEST_GRIP_help = "Test the MMU grip for a Tool"
     def cmd_MMU_TEST_GRIP(self, gcmd):
@@ -4899,14 +9295,210 @@ class Mmu:
         actual,_,measured,_ = self._move_cmd(gcmd, "Test move")
         self._log_always("Moved %.1fmm%s" % (actual, (" (measured %.1fmm)" % measured) if self._can_use_encoder() else ""))
 
-    cmd_MMU_TEST_HOMING_MOVE_help = "Test filament homing move to help debug setup / options"
-    def cmd_MMU_TEST_HOMING_MOVE(self, gcmd):
-        if self._check_is_disabled(): return
-        actual,homed,measured,_ = self._homing_move_cmd(gcmd, "Test homing move")
-        self._log_always("%s after %.1fmm%s" % (("Homed" if homed else "Did not home"), actual, (" (measured %.1fmm)" % measured) if self._can_use_encoder() else ""))
+    cmd_MMU_JOG_help = "Jog gear or selector stepper with MMU state tracking and safety interlocks"
+    def cmd_MMU_JOG(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._is_printing():
+            self._log_always("MMU_JOG is not permitted whilst printing")
+            return
+        stepper = gcmd.get('STEPPER', "gear").lower()
+        distance = gcmd.get_float('DISTANCE')
+        speed = gcmd.get_float('SPEED', None, above=0.)
+        match = re.match(r'gate_(\d+)$', stepper)
+        if stepper not in ["gear", "selector"] and not match:
+            raise gcmd.error("Valid steppers are 'gear', 'selector' or 'gate_n'")
+
+        try:
+            if stepper == "selector":
+                if not self.vendor_driver.HAS_SELECTOR:
+                    raise gcmd.error("MMU does not have a selector")
+                if self._check_not_homed(): return
+                if self.filament_pos != self.FILAMENT_POS_UNLOADED:
+                    self._log_error("Refusing to jog selector with filament loaded in the selector. Unload first")
+                    return
+                max_speed = self.mmu_toolhead.get_selector_limits()[0]
+                pos = self.mmu_toolhead.get_position()[0]
+                target = min(max(pos + distance, 0.), self._get_max_selector_movement())
+                if target != pos + distance:
+                    self._log_always("Selector jog clamped to %.1fmm to stay within travel" % (target - pos))
+                self._servo_move()
+                self._trace_selector_move("Jogging selector", target, speed=min(speed or self.selector_move_speed, max_speed))
+                # Selector is no longer known to be aligned with a gate
+                if self.gate_selected == self.TOOL_GATE_BYPASS or self.gate_selected < 0 or abs(target - self.selector_offsets[self.gate_selected]) > 0.1:
+                    self._set_gate_selected(self.TOOL_GATE_UNKNOWN)
+                    self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
+                self._log_always("Selector jogged %.1fmm to position %.1fmm" % (target - pos, target))
+                return
+
+            gate = int(match.group(1)) if match else self.gate_selected
+            if gate < 0 or gate >= self.mmu_num_gates:
+                raise gcmd.error("Gear jog requires a valid gate (gate %d selected)" % gate)
+            if gate != self.gate_selected and self.vendor_driver.HAS_SELECTOR:
+                self._log_error("Refusing to jog gear through closed gate #%d (gate %s is selected). Select the gate first" % (gate, self._selected_gate_string()))
+                return
+            if gate in self.gate_maintenance:
+                self._log_error("Refusing to jog gear for gate #%d which is in maintenance" % gate)
+                return
+            if self.filament_pos >= self.FILAMENT_POS_EXTRUDER_ENTRY and gate == self.gate_selected and not self.mmu_toolhead.is_gear_synced_to_extruder():
+                self._log_always("Warning: Filament is loaded in extruder and gear is not synced. Jogging gear may grind filament")
+            if abs(distance) > self.jog_max_distance:
+                distance = math.copysign(self.jog_max_distance, distance)
+                self._log_always("Gear jog clamped to 'jog_max_distance' of %.1fmm" % self.jog_max_distance)
+            max_speed = self.mmu_toolhead.get_gear_limits()[0]
+
+            self._servo_down()
+            if gate != self.gate_selected:
+                self.mmu_toolhead.select_gear_stepper(gate)
+            try:
+                actual,_,measured,_ = self._trace_filament_move("Jogging gear for gate #%d" % gate, distance, speed=min(speed or self.gear_short_move_speed, max_speed))
+            finally:
+                if gate != self.gate_selected:
+                    self.mmu_toolhead.select_gear_stepper(self.gate_selected)
+            self._log_always("Gear for gate #%d jogged %.1fmm%s" % (gate, actual, (" (measured %.1fmm)" % measured) if self._can_use_encoder() and gate == self.gate_selected else ""))
+        except MmuError as ee:
+            self._log_error("Jog failed: %s" % str(ee))
+
+    cmd_MMU_TEST_HOMING_MOVE_help = "Test filament homing move to help debug setup / options"
+    def cmd_MMU_TEST_HOMING_MOVE(self, gcmd):
+        if self._check_is_disabled(): return
+        actual,homed,measured,_ = self._homing_move_cmd(gcmd, "Test homing move")
+        self._log_always("%s after %.1fmm%s" % (("Homed" if homed else "Did not home"), actual, (" (measured %.1fmm)" % measured) if self._can_use_encoder() else ""))
+
+    cmd_MMU_TEST_CONFIG_help = "Runtime adjustment of MMU configuration for testing or in-print tweaking purposes"
+    def cmd_MMU_TEST_CONFIG(self, gcmd):
+        if gcmd.get_int('LIST', 0, minval=0, maxval=1):
+            self._log_always(self._format_config_overrides())
+            return
+        scope = gcmd.get('SCOPE', self.CONFIG_SCOPE_SESSION).lower()
+        if scope not in self.CONFIG_SCOPES:
+            raise gcmd.error("SCOPE is invalid. Options are: %s" % self.CONFIG_SCOPES)
+        if gcmd.get_int('CLEAR', 0, minval=0, maxval=1):
+            self._clear_config_overrides(scope if gcmd.get('SCOPE', None) else None)
+            self._log_always(self._format_config_overrides())
+            return
+        gate = None
+        if scope == self.CONFIG_SCOPE_GATE:
+            gate = gcmd.get_int('GATE', self.gate_selected, minval=0, maxval=self.mmu_num_gates - 1)
+            if gate < 0:
+                raise gcmd.error("GATE must be specified when no gate is selected")
+        elif scope == self.CONFIG_SCOPE_JOB and not self._is_in_print():
+            raise gcmd.error("SCOPE=job can only be used while a print is in progress")
+        before = self._get_config_override_candidates(gcmd)
+
+        self._set_runtime_config(gcmd)
+        self._record_config_overrides(before, scope, gate)
+
+        msg = "SPEEDS:"
+        msg += "\ngear_from_buffer_speed = %.1f" % self.gear_from_buffer_speed
+        msg += "\ngear_from_buffer_accel = %.1f" % self.gear_from_buffer_accel
+        msg += "\ngear_from_spool_speed = %.1f" % self.gear_from_spool_speed
+        msg += "\ngear_from_spool_accel = %.1f" % self.gear_from_spool_accel
+        msg += "\ngear_short_move_speed = %.1f" % self.gear_short_move_speed
+        msg += "\ngear_short_move_accel = %.1f" % self.gear_short_move_accel
+        msg += "\ngear_short_move_threshold = %.1f" % self.gear_short_move_threshold
+        msg += "\ngear_homing_speed = %.1f" % self.gear_homing_speed
+        msg += "\nhoming_creep_speed = %.1f" % self.homing_creep_speed
+        msg += "\nhoming_creep_accel = %.1f" % self.homing_creep_accel
+        msg += "\nhoming_creep_backoff = %.1f" % self.homing_creep_backoff
+        msg += "\nhoming_creep_count = %d" % self.homing_creep_count
+        msg += "\nextruder_homing_speed = %.1f" % self.extruder_homing_speed
+        msg += "\nextruder_load_speed = %.1f" % self.extruder_load_speed
+        msg += "\nextruder_unload_speed = %.1f" % self.extruder_unload_speed
+        msg += "\nextruder_sync_load_speed = %.1f" % self.extruder_sync_load_speed
+        msg += "\nextruder_sync_unload_speed = %.1f" % self.extruder_sync_unload_speed
+        msg += "\nextruder_accel = %.1f" % self.extruder_accel
+        msg += "\nselector_move_speed = %.1f" % self.selector_move_speed
+        msg += "\nselector_homing_speed = %.1f" % self.selector_homing_speed
+        msg += "\nselector_touch_speed = %.1f" % self.selector_touch_speed
+        msg += "\nselector_touch_enable = %d" % self.selector_touch_enable
+
+        msg += "\n\nTMC & MOTOR SYNC CONTROL:"
+        msg += "\nsync_to_extruder = %d" % self.sync_to_extruder
+        msg += "\nsync_form_tip = %d" % self.sync_form_tip
+        msg += "\nsync_gear_current = %d" % self.sync_gear_current
+        msg += "\nextruder_homing_current = %d" % self.extruder_homing_current
+        msg += "\nextruder_form_tip_current = %d" % self.extruder_form_tip_current
+
+        msg += "\n\nLOADING/UNLOADING:"
+        msg += "\ngate_homing_endstop = %s" % self.gate_homing_endstop
+        if self.gate_homing_endstop == self.ENDSTOP_GATE:
+            msg += "\ngate_endstop_to_encoder = %s" % self.gate_endstop_to_encoder
+        msg += "\ngate_parking_distance = %s" % self.gate_parking_distance
+        msg += "\ntoolchange_overlap_length = %.1f" % self.toolchange_overlap_length
+        if self._has_encoder():
+            msg += "\nbowden_apply_correction = %d" % self.bowden_apply_correction
+            msg += "\nbowden_allowable_load_delta = %d" % self.bowden_allowable_load_delta
+            msg += "\nbowden_pre_unload_test = %d" % self.bowden_pre_unload_test
+        msg += "\nbowden_live_adjust = %d" % self.bowden_live_adjust
+        msg += "\nbowden_live_adjust_samples = %d" % self.bowden_live_adjust_samples
+        msg += "\nbowden_live_adjust_max = %.1f" % self.bowden_live_adjust_max
+        msg += "\nbowden_segment_checks = %d" % self.bowden_segment_checks
+        msg += "\nbowden_segment_length = %.1f" % self.bowden_segment_length
+        msg += "\nbowden_segment_max_overhead = %.1f" % self.bowden_segment_max_overhead
+        msg += "\nextruder_force_homing = %d" % self.extruder_force_homing
+        msg += "\nextruder_homing_endstop = %s" % self.extruder_homing_endstop
+        msg += "\nextruder_homing_max = %.1f" % self.extruder_homing_max
+        msg += "\ntoolhead_sync_unload = %d" % self.toolhead_sync_unload
+        msg += "\ntoolhead_homing_max = %.1f" % self.toolhead_homing_max
+        msg += "\ntoolhead_homing_retries = %d" % self.toolhead_homing_retries
+        msg += "\ntoolhead_homing_retract = %.1f" % self.toolhead_homing_retract
+        msg += "\ntoolhead_homing_jog = %.1f" % self.toolhead_homing_jog
+        msg += "\ntoolhead_extruder_to_nozzle = %.1f" % self.toolhead_extruder_to_nozzle
+        msg += "\ntoolhead_sensor_to_nozzle = %.1f" % self.toolhead_sensor_to_nozzle
+        msg += "\ngcode_load_sequence = %d" % self.gcode_load_sequence
+        msg += "\ngcode_unload_sequence = %d" % self.gcode_unload_sequence
+
+        msg += "\n\nOTHER:"
+        msg += "\nz_hop_height_error = %.1f" % self.z_hop_height_error
+        msg += "\nz_hop_height_toolchange = %.1f" % self.z_hop_height_toolchange
+        msg += "\nz_hop_speed = %.1f" % self.z_hop_speed
+        if self._has_encoder():
+            msg += "\nenable_clog_detection = %d" % self.enable_clog_detection
+        msg += "\nenable_endless_spool = %d" % self.enable_endless_spool
+        msg += "\nendless_spool_on_load = %d" % self.endless_spool_on_load
+        msg += "\ngate_load_balancing = %d" % self.gate_load_balancing
+        msg += "\npurge_volume = %.1f" % self.purge_volume
+        msg += "\npurge_similar_color_threshold = %.1f" % self.purge_similar_color_threshold
+        msg += "\npurge_similar_volume_percent = %.1f" % self.purge_similar_volume_percent
+        msg += "\nprime_mode = %s" % self.prime_mode
+        msg += "\nprime_length = %.1f" % self.prime_length
+        msg += "\nprime_max_length = %.1f" % self.prime_max_length
+        msg += "\nprime_ooze_rate = %.2f" % self.prime_ooze_rate
+        msg += "\nprime_speed = %.1f" % self.prime_speed
+        msg += "\ntoolhead_residual_filament = %.1f" % self.toolhead_residual_filament
+        msg += "\nenable_spoolman = %d" % self.enable_spoolman
+        msg += "\nslicer_tip_park_pos = %.1f" % self.slicer_tip_park_pos
+        msg += "\nforce_form_tip_standalone = %d" % self.force_form_tip_standalone
+        msg += "\nslicer_tip_passthrough = %d" % self.slicer_tip_passthrough
+        msg += "\nform_tip_profile = %s" % self.form_tip_profile
+        msg += "\nhotend_type = %s" % self.hotend_type
+        if self._has_encoder():
+            msg += "\nstrict_filament_recovery = %d" % self.strict_filament_recovery
+            msg += "\nencoder_move_validation = %d" % self.encoder_move_validation
+            msg += "\nauto_calibrate_gates = %d" % self.auto_calibrate_gates
+            msg += "\nautotune_rotation_distance = %d" % self.autotune_rotation_distance
+            msg += "\nrotation_distance_drift_threshold = %.1f" % self.rotation_distance_drift_threshold
+        msg += "\nretry_tool_change_on_error = %d" % self.retry_tool_change_on_error
+        msg += "\ntoolchange_grace_mode = %d" % self.toolchange_grace_mode
+        msg += "\ntoolchange_grace_retries = %d" % self.toolchange_grace_retries
+        msg += "\ntoolchange_grace_window = %d" % self.toolchange_grace_window
+        msg += "\nprint_start_detection = %d" % self.print_start_detection
+        msg += "\nlog_level = %d" % self.log_level
+        msg += "\nlog_visual = %d" % self.log_visual
+        msg += "\nlog_statistics = %d" % self.log_statistics
+        msg += "\npause_macro = %s" % self.pause_macro
+        msg += "\nform_tip_macro = %s" % self.form_tip_macro
+
+        msg += "\n\nCALIBRATION:"
+        msg += "\nmmu_calibration_bowden_length = %.1f" % self.calibrated_bowden_length
+        if self._has_encoder():
+            msg += "\nmmu_calibration_clog_length = %.1f" % self.encoder_sensor.get_clog_detection_length()
+        if self.config_overrides:
+            msg += "\n\n%s" % self._format_config_overrides()
+        self._log_info(msg)
 
-    cmd_MMU_TEST_CONFIG_help = "Runtime adjustment of MMU configuration for testing or in-print tweaking purposes"
-    def cmd_MMU_TEST_CONFIG(self, gcmd):
+    # Update all settings that are safe to change at runtime from gcmd parameters (MMU_TEST_CONFIG and MMU_RELOAD_CONFIG)
+    def _set_runtime_config(self, gcmd):
         # Filament Speeds
         self.gear_from_buffer_speed = gcmd.get_float('GEAR_FROM_BUFFER_SPEED', self.gear_from_buffer_speed, minval=10.)
         self.gear_from_buffer_accel = gcmd.get_float('GEAR_FROM_BUFFER_ACCEL', self.gear_from_buffer_accel, minval=10.)
@@ -4916,6 +9508,10 @@ class Mmu:
         self.gear_short_move_accel = gcmd.get_float('GEAR_SHORT_MOVE_ACCEL', self.gear_short_move_accel, minval=10.)
         self.gear_short_move_threshold = gcmd.get_float('GEAR_SHORT_MOVE_THRESHOLD', self.gear_short_move_threshold, minval=0.)
         self.gear_homing_speed = gcmd.get_float('GEAR_HOMING_SPEED', self.gear_homing_speed, above=1.)
+        self.homing_creep_speed = gcmd.get_float('HOMING_CREEP_SPEED', self.homing_creep_speed, minval=1.)
+        self.homing_creep_accel = gcmd.get_float('HOMING_CREEP_ACCEL', self.homing_creep_accel, minval=10.)
+        self.homing_creep_backoff = gcmd.get_float('HOMING_CREEP_BACKOFF', self.homing_creep_backoff, minval=0.5)
+        self.homing_creep_count = gcmd.get_int('HOMING_CREEP_COUNT', self.homing_creep_count, minval=0, maxval=5)
         self.extruder_homing_speed = gcmd.get_float('EXTRUDER_HOMING_SPEED', self.extruder_homing_speed, above=1.)
         self.extruder_load_speed = gcmd.get_float('EXTRUDER_LOAD_SPEED', self.extruder_load_speed, above=1.)
         self.extruder_unload_speed = gcmd.get_float('EXTRUDER_UNLOAD_SPEED', self.extruder_unload_speed, above=1.)
@@ -4935,25 +9531,36 @@ class Mmu:
         # TMC current control
         self.sync_gear_current = gcmd.get_int('SYNC_GEAR_CURRENT', self.sync_gear_current, minval=10, maxval=100)
         self.extruder_homing_current = gcmd.get_int('EXTRUDER_HOMING_CURRENT', self.extruder_homing_current, minval=10, maxval=100)
+        self.gate_homing_current = gcmd.get_int('GATE_HOMING_CURRENT', self.gate_homing_current, minval=10, maxval=100)
         self.extruder_form_tip_current = gcmd.get_int('EXTRUDER_FORM_TIP_CURRENT', self.extruder_form_tip_current, minval=100, maxval=150)
 
         # Homing, loading and unloading controls
         self.gate_homing_endstop = gcmd.get('GATE_HOMING_ENDSTOP', self.gate_homing_endstop)
         if self.gate_homing_endstop not in self.GATE_ENDSTOPS:
-            raise gmd.error("gate_homing_endstop is invalid. Options are: %s" % self.GATE_ENSTOPS)
+            raise gcmd.error("gate_homing_endstop is invalid. Options are: %s" % self.GATE_ENDSTOPS)
         self.gate_endstop_to_encoder = gcmd.get_float('GATE_ENDSTOP_TO_ENCODER', self.gate_endstop_to_encoder)
         self.gate_parking_distance = gcmd.get_float('GATE_PARKING_DISTANCE', self.gate_parking_distance)
+        self.toolchange_overlap_length = gcmd.get_float('TOOLCHANGE_OVERLAP_LENGTH', self.toolchange_overlap_length, minval=0.)
         self.bowden_apply_correction = gcmd.get_int('BOWDEN_APPLY_CORRECTION', self.bowden_apply_correction, minval=0, maxval=1)
         self.bowden_allowable_unload_delta = self.bowden_allowable_load_delta = gcmd.get_float('BOWDEN_ALLOWABLE_LOAD_DELTA', self.bowden_allowable_load_delta, minval=1., maxval=50.)
+        self.bowden_live_adjust = gcmd.get_int('BOWDEN_LIVE_ADJUST', self.bowden_live_adjust, minval=0, maxval=1)
+        self.bowden_live_adjust_samples = gcmd.get_int('BOWDEN_LIVE_ADJUST_SAMPLES', self.bowden_live_adjust_samples, minval=2, maxval=50)
+        self.bowden_live_adjust_max = gcmd.get_float('BOWDEN_LIVE_ADJUST_MAX', self.bowden_live_adjust_max, minval=0., maxval=50.)
+        self.bowden_segment_checks = gcmd.get_int('BOWDEN_SEGMENT_CHECKS', self.bowden_segment_checks, minval=0, maxval=1)
+        self.bowden_segment_length = gcmd.get_float('BOWDEN_SEGMENT_LENGTH', self.bowden_segment_length, minval=20.)
+        self.bowden_segment_max_overhead = gcmd.get_float('BOWDEN_SEGMENT_MAX_OVERHEAD', self.bowden_segment_max_overhead, minval=0.5, maxval=50.)
         self.bowden_pre_unload_test = gcmd.get_int('BOWDEN_PRE_UNLOAD_TEST', self.bowden_pre_unload_test, minval=0, maxval=1)
 
         self.extruder_homing_endstop = gcmd.get('EXTRUDER_HOMING_ENDSTOP', self.extruder_homing_endstop)
         if self.extruder_homing_endstop not in self.EXTRUDER_ENDSTOPS:
-            raise gmd.error("extruder_homing_endstop is invalid. Options are: %s" % self.EXTRUDER_ENDSTOPS)
+            raise gcmd.error("extruder_homing_endstop is invalid. Options are: %s" % self.EXTRUDER_ENDSTOPS)
         self.extruder_homing_max = gcmd.get_float('EXTRUDER_HOMING_MAX', self.extruder_homing_max, above=10.)
         self.extruder_force_homing = gcmd.get_int('EXTRUDER_FORCE_HOMING', self.extruder_force_homing, minval=0, maxval=1)
 
         self.toolhead_homing_max = gcmd.get_float('TOOLHEAD_HOMING_MAX', self.toolhead_homing_max, minval=0.)
+        self.toolhead_homing_retries = gcmd.get_int('TOOLHEAD_HOMING_RETRIES', self.toolhead_homing_retries, minval=0, maxval=5)
+        self.toolhead_homing_retract = gcmd.get_float('TOOLHEAD_HOMING_RETRACT', self.toolhead_homing_retract, minval=0.)
+        self.toolhead_homing_jog = gcmd.get_float('TOOLHEAD_HOMING_JOG', self.toolhead_homing_jog, minval=0.)
         self.toolhead_sync_unload = gcmd.get_int('TOOLHEAD_SYNC_UNLOAD', self.toolhead_sync_unload, minval=0, maxval=1)
         self.toolhead_extruder_to_nozzle = gcmd.get_float('TOOLHEAD_EXTRUDER_TO_NOZZLE', self.toolhead_extruder_to_nozzle, minval=0.)
         self.toolhead_sensor_to_nozzle = gcmd.get_float('TOOLHEAD_SENSOR_TO_NOZZLE', self.toolhead_sensor_to_nozzle, minval=0.)
@@ -4967,16 +9574,44 @@ class Mmu:
         self.selector_touch = self.ENDSTOP_SELECTOR_TOUCH in self.selector_rail.get_extra_endstop_names() and self.selector_touch_enable
         self.enable_endless_spool = gcmd.get_int('ENABLE_ENDLESS_SPOOL', self.enable_endless_spool, minval=0, maxval=1)
         self.endless_spool_on_load = gcmd.get_int('ENDLESS_SPOOL_ON_LOAD', self.endless_spool_on_load, minval=0, maxval=1)
+        self.gate_load_balancing = gcmd.get_int('GATE_LOAD_BALANCING', self.gate_load_balancing, minval=0, maxval=2)
+        self.purge_volume = gcmd.get_float('PURGE_VOLUME', self.purge_volume, minval=0.)
+        self.purge_similar_color_threshold = gcmd.get_float('PURGE_SIMILAR_COLOR_THRESHOLD', self.purge_similar_color_threshold, minval=0., maxval=100.)
+        self.purge_similar_volume_percent = gcmd.get_float('PURGE_SIMILAR_VOLUME_PERCENT', self.purge_similar_volume_percent, minval=0., maxval=100.)
+        prime_mode = gcmd.get('PRIME_MODE', self.prime_mode).lower()
+        if prime_mode in self.PRIME_MODES:
+            self.prime_mode = prime_mode
+        else:
+            raise gcmd.error("PRIME_MODE is invalid. Options are: %s" % self.PRIME_MODES)
+        self.prime_length = gcmd.get_float('PRIME_LENGTH', self.prime_length, minval=0.)
+        self.prime_max_length = gcmd.get_float('PRIME_MAX_LENGTH', self.prime_max_length, minval=0.)
+        self.prime_ooze_rate = gcmd.get_float('PRIME_OOZE_RATE', self.prime_ooze_rate, minval=0.)
+        self.prime_speed = gcmd.get_float('PRIME_SPEED', self.prime_speed, above=0.)
+        self.toolhead_residual_filament = gcmd.get_float('TOOLHEAD_RESIDUAL_FILAMENT', self.toolhead_residual_filament, minval=0.)
         self.enable_spoolman = gcmd.get_int('ENABLE_SPOOLMAN', self.enable_spoolman, minval=0, maxval=1)
         self.log_level = gcmd.get_int('LOG_LEVEL', self.log_level, minval=0, maxval=4)
         self.log_visual = gcmd.get_int('LOG_VISUAL', self.log_visual, minval=0, maxval=2)
         self.log_statistics = gcmd.get_int('LOG_STATISTICS', self.log_statistics, minval=0, maxval=1)
         self.slicer_tip_park_pos = gcmd.get_float('SLICER_TIP_PARK_POS', self.slicer_tip_park_pos, minval=0.)
         self.force_form_tip_standalone = gcmd.get_int('FORCE_FORM_TIP_STANDALONE', self.force_form_tip_standalone, minval=0, maxval=1)
+        self.slicer_tip_passthrough = gcmd.get_int('SLICER_TIP_PASSTHROUGH', self.slicer_tip_passthrough, minval=0, maxval=1)
+        form_tip_profile = gcmd.get('FORM_TIP_PROFILE', self.form_tip_profile).lower()
+        if form_tip_profile and get_tip_profile(form_tip_profile) is None:
+            raise gcmd.error("FORM_TIP_PROFILE '%s' is invalid. Options are: %s" % (form_tip_profile, get_tip_profile_names()))
+        self.form_tip_profile = form_tip_profile
+        hotend_type = gcmd.get('HOTEND_TYPE', self.hotend_type).lower()
+        if hotend_type and get_cooling_profile(hotend_type) is None:
+            raise gcmd.error("HOTEND_TYPE '%s' is invalid. Options are: %s" % (hotend_type, get_hotend_types()))
+        self.hotend_type = hotend_type
         self.strict_filament_recovery = gcmd.get_int('STRICT_FILAMENT_RECOVERY', self.strict_filament_recovery, minval=0, maxval=1)
         self.encoder_move_validation = gcmd.get_int('ENCODER_MOVE_VALIDATION', self.encoder_move_validation, minval=0, maxval=1)
         self.auto_calibrate_gates = gcmd.get_int('AUTO_CALIBRATE_GATES', self.auto_calibrate_gates, minval=0, maxval=1)
+        self.autotune_rotation_distance = gcmd.get_int('AUTOTUNE_ROTATION_DISTANCE', self.autotune_rotation_distance, minval=0, maxval=1)
+        self.rotation_distance_drift_threshold = gcmd.get_float('ROTATION_DISTANCE_DRIFT_THRESHOLD', self.rotation_distance_drift_threshold, minval=0.)
         self.retry_tool_change_on_error = gcmd.get_int('RETRY_TOOL_CHANGE_ON_ERROR', self.retry_tool_change_on_error, minval=0, maxval=1)
+        self.toolchange_grace_mode = gcmd.get_int('TOOLCHANGE_GRACE_MODE', self.toolchange_grace_mode, minval=0, maxval=1)
+        self.toolchange_grace_retries = gcmd.get_int('TOOLCHANGE_GRACE_RETRIES', self.toolchange_grace_retries, minval=0, maxval=5)
+        self.toolchange_grace_window = gcmd.get_int('TOOLCHANGE_GRACE_WINDOW', self.toolchange_grace_window, minval=0)
         self.print_start_detection = gcmd.get_int('PRINT_START_DETECTION', self.print_start_detection, minval=0, maxval=1)
         self.pause_macro = gcmd.get('PAUSE_MACRO', self.pause_macro)
         form_tip_macro = gcmd.get('FORM_TIP_MACRO', self.form_tip_macro)
@@ -4998,80 +9633,164 @@ class Mmu:
         # Currently hidden and testing options
         self.test_random_failures = gcmd.get_int('TEST_RANDOM_FAILURES', self.test_random_failures, minval=0, maxval=1)
 
-        msg = "SPEEDS:"
-        msg += "\ngear_from_buffer_speed = %.1f" % self.gear_from_buffer_speed
-        msg += "\ngear_from_buffer_accel = %.1f" % self.gear_from_buffer_accel
-        msg += "\ngear_from_spool_speed = %.1f" % self.gear_from_spool_speed
-        msg += "\ngear_from_spool_accel = %.1f" % self.gear_from_spool_accel
-        msg += "\ngear_short_move_speed = %.1f" % self.gear_short_move_speed
-        msg += "\ngear_short_move_accel = %.1f" % self.gear_short_move_accel
-        msg += "\ngear_short_move_threshold = %.1f" % self.gear_short_move_threshold
-        msg += "\ngear_homing_speed = %.1f" % self.gear_homing_speed
-        msg += "\nextruder_homing_speed = %.1f" % self.extruder_homing_speed
-        msg += "\nextruder_load_speed = %.1f" % self.extruder_load_speed
-        msg += "\nextruder_unload_speed = %.1f" % self.extruder_unload_speed
-        msg += "\nextruder_sync_load_speed = %.1f" % self.extruder_sync_load_speed
-        msg += "\nextruder_sync_unload_speed = %.1f" % self.extruder_sync_unload_speed
-        msg += "\nextruder_accel = %.1f" % self.extruder_accel
-        msg += "\nselector_move_speed = %.1f" % self.selector_move_speed
-        msg += "\nselector_homing_speed = %.1f" % self.selector_homing_speed
-        msg += "\nselector_touch_speed = %.1f" % self.selector_touch_speed
-        msg += "\nselector_touch_enable = %d" % self.selector_touch_enable
-
-        msg += "\n\nTMC & MOTOR SYNC CONTROL:"
-        msg += "\nsync_to_extruder = %d" % self.sync_to_extruder
-        msg += "\nsync_form_tip = %d" % self.sync_form_tip
-        msg += "\nsync_gear_current = %d" % self.sync_gear_current
-        msg += "\nextruder_homing_current = %d" % self.extruder_homing_current
-        msg += "\nextruder_form_tip_current = %d" % self.extruder_form_tip_current
-
-        msg += "\n\nLOADING/UNLOADING:"
-        msg += "\ngate_homing_endstop = %s" % self.gate_homing_endstop
-        if self.gate_homing_endstop == self.ENDSTOP_GATE:
-            msg += "\ngate_endstop_to_encoder = %s" % self.gate_endstop_to_encoder
-        msg += "\ngate_parking_distance = %s" % self.gate_parking_distance
-        if self._has_encoder():
-            msg += "\nbowden_apply_correction = %d" % self.bowden_apply_correction
-            msg += "\nbowden_allowable_load_delta = %d" % self.bowden_allowable_load_delta
-            msg += "\nbowden_pre_unload_test = %d" % self.bowden_pre_unload_test
-        msg += "\nextruder_force_homing = %d" % self.extruder_force_homing
-        msg += "\nextruder_homing_endstop = %s" % self.extruder_homing_endstop
-        msg += "\nextruder_homing_max = %.1f" % self.extruder_homing_max
-        msg += "\ntoolhead_sync_unload = %d" % self.toolhead_sync_unload
-        msg += "\ntoolhead_homing_max = %.1f" % self.toolhead_homing_max
-        msg += "\ntoolhead_extruder_to_nozzle = %.1f" % self.toolhead_extruder_to_nozzle
-        msg += "\ntoolhead_sensor_to_nozzle = %.1f" % self.toolhead_sensor_to_nozzle
-        msg += "\ngcode_load_sequence = %d" % self.gcode_load_sequence
-        msg += "\ngcode_unload_sequence = %d" % self.gcode_unload_sequence
-
-        msg += "\n\nOTHER:"
-        msg += "\nz_hop_height_error = %.1f" % self.z_hop_height_error
-        msg += "\nz_hop_height_toolchange = %.1f" % self.z_hop_height_toolchange
-        msg += "\nz_hop_speed = %.1f" % self.z_hop_speed
-        if self._has_encoder():
-            msg += "\nenable_clog_detection = %d" % self.enable_clog_detection
-        msg += "\nenable_endless_spool = %d" % self.enable_endless_spool
-        msg += "\nendless_spool_on_load = %d" % self.endless_spool_on_load
-        msg += "\nenable_spoolman = %d" % self.enable_spoolman
-        msg += "\nslicer_tip_park_pos = %.1f" % self.slicer_tip_park_pos
-        msg += "\nforce_form_tip_standalone = %d" % self.force_form_tip_standalone
-        if self._has_encoder():
-            msg += "\nstrict_filament_recovery = %d" % self.strict_filament_recovery
-            msg += "\nencoder_move_validation = %d" % self.encoder_move_validation
-            msg += "\nauto_calibrate_gates = %d" % self.auto_calibrate_gates
-        msg += "\nretry_tool_change_on_error = %d" % self.retry_tool_change_on_error
-        msg += "\nprint_start_detection = %d" % self.print_start_detection
-        msg += "\nlog_level = %d" % self.log_level
-        msg += "\nlog_visual = %d" % self.log_visual
-        msg += "\nlog_statistics = %d" % self.log_statistics
-        msg += "\npause_macro = %s" % self.pause_macro
-        msg += "\nform_tip_macro = %s" % self.form_tip_macro
+    # Settings explicitly passed to MMU_TEST_CONFIG (with value before change) that can be tracked as overrides
+    def _get_config_override_candidates(self, gcmd):
+        candidates = {}
+        for param in gcmd.get_command_parameters():
+            if param in ['SCOPE', 'GATE', 'LIST', 'CLEAR']: continue
+            attr = 'calibrated_bowden_length' if param == 'MMU_CALIBRATION_BOWDEN_LENGTH' else param.lower()
+            if isinstance(getattr(self, attr, None), (int, float, str)):
+                candidates[attr] = getattr(self, attr)
+        return candidates
+
+    def _add_config_override(self, attr, value, scope, gate, origin):
+        self.config_override_base.setdefault(attr, getattr(self, attr))
+        self.config_overrides = [o for o in self.config_overrides if not (o['attr'] == attr and o['scope'] == scope and o['gate'] == gate)]
+        self.config_overrides.append({'attr': attr, 'value': value, 'scope': scope, 'gate': gate, 'origin': origin})
+
+    def _record_config_overrides(self, before, scope, gate):
+        if not before: return
+        origin = time.strftime("%H:%M:%S")
+        if scope == self.CONFIG_SCOPE_JOB:
+            origin += " job '%s'" % self.print_stats.get_status(self.reactor.monotonic()).get('filename', '')
+        for attr, value in before.items():
+            new_value = getattr(self, attr)
+            setattr(self, attr, value) # Base is captured from pre-change value
+            self._add_config_override(attr, new_value, scope, gate, origin)
+        self._apply_config_overrides(before.keys())
+        if scope == self.CONFIG_SCOPE_PERSIST:
+            self._save_config_overrides()
+        elif scope == self.CONFIG_SCOPE_GATE and gate != self.gate_selected:
+            self._log_info("Override for gate %d will apply when that gate is selected" % gate)
+
+    # Recalculate effective value of overridden settings: base, then global overrides in order, then
+    # any override for the currently selected gate
+    def _apply_config_overrides(self, attrs=None):
+        attrs = list(self.config_override_base.keys()) if attrs is None else list(attrs)
+        for attr in attrs:
+            value = self.config_override_base.get(attr, getattr(self, attr))
+            gate_value = None
+            for o in self.config_overrides:
+                if o['attr'] != attr: continue
+                if o['gate'] is None:
+                    value = o['value']
+                elif o['gate'] == self.gate_selected:
+                    gate_value = o['value']
+            setattr(self, attr, value if gate_value is None else gate_value)
+
+        # Settings with side effects
+        if 'bowden_allowable_load_delta' in attrs:
+            self.bowden_allowable_unload_delta = self.bowden_allowable_load_delta
+        if 'selector_touch_enable' in attrs:
+            self.selector_touch = self.ENDSTOP_SELECTOR_TOUCH in self.selector_rail.get_extra_endstop_names() and self.selector_touch_enable
+        if 'enable_clog_detection' in attrs and self._has_encoder():
+            self.encoder_sensor.set_mode(self.enable_clog_detection)
+        if 'form_tip_macro' in attrs:
+            self.form_tip_vars = None
+
+    # Remove overrides of scope (or all) restoring underlying value. Returns number removed
+    def _clear_config_overrides(self, scope=None):
+        removed = [o for o in self.config_overrides if scope is None or o['scope'] == scope]
+        if not removed: return 0
+        self.config_overrides = [o for o in self.config_overrides if o not in removed]
+        attrs = set(o['attr'] for o in removed)
+        self._apply_config_overrides(attrs)
+        for attr in attrs:
+            if not any(o['attr'] == attr for o in self.config_overrides):
+                del self.config_override_base[attr]
+        if any(o['scope'] == self.CONFIG_SCOPE_PERSIST for o in removed):
+            self._save_config_overrides()
+        return len(removed)
+
+    def _save_config_overrides(self):
+        overrides = {o['attr']: o['value'] for o in self.config_overrides if o['scope'] == self.CONFIG_SCOPE_PERSIST}
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_CONFIG_OVERRIDES, overrides))
+
+    def _format_config_overrides(self):
+        if not self.config_overrides:
+            return "No MMU_TEST_CONFIG overrides are active"
+        msg = "CONFIG OVERRIDES (oldest first, later entries win, gate entries win while gate is selected):"
+        for o in self.config_overrides:
+            scope = "gate %d" % o['gate'] if o['gate'] is not None else o['scope']
+            active = "" if o['gate'] is None or o['gate'] == self.gate_selected else " (inactive)"
+            msg += "\n%s = %s [%s, set %s, base %s]%s" % (o['attr'], o['value'], scope, o['origin'], self.config_override_base.get(o['attr']), active)
+        return msg
 
-        msg += "\n\nCALIBRATION:"
-        msg += "\nmmu_calibration_bowden_length = %.1f" % self.calibrated_bowden_length
-        if self._has_encoder():
-            msg += "\nmmu_calibration_clog_length = %.1f" % clog_length
-        self._log_info(msg)
+    cmd_MMU_RELOAD_CONFIG_help = "Re-read MMU configuration and apply changed settings that don't require a restart"
+    def cmd_MMU_RELOAD_CONFIG(self, gcmd):
+        if self._check_is_disabled(): return
+        try:
+            new_config = self.printer.lookup_object('configfile').read_main_config()
+        except Exception as e:
+            raise gcmd.error("Unable to read configuration: %s" % str(e))
+        section = self.config.get_name()
+        if not new_config.has_section(section):
+            raise gcmd.error("Section [%s] not found in configuration" % section)
+
+        # MMU parameters. The runtime safe subset is whatever MMU_TEST_CONFIG would consume
+        changed = self._get_changed_config_options(self.config, new_config.getsection(section))
+        params = TrackedParams({option.upper(): value for option, value in changed.items() if value is not None})
+        reload_gcmd = self.gcode.create_gcode_command("MMU_RELOAD_CONFIG", "MMU_RELOAD_CONFIG", params)
+        self._set_runtime_config(reload_gcmd)
+        applied = [option for option in changed if option.upper() in params.accessed]
+        restart = [option for option in changed if option not in applied]
+
+        # Reloaded value becomes the new base for any MMU_TEST_CONFIG override
+        overridden = [attr for attr in applied if attr in self.config_override_base]
+        for attr in overridden:
+            self.config_override_base[attr] = getattr(self, attr)
+        self._apply_config_overrides(overridden)
+        for option in applied:
+            self.config_reloaded[(section, option)] = changed[option]
+
+        # Macro variables are applied like SET_GCODE_VARIABLE. Changed gcode needs a restart
+        for macro_section in new_config.get_prefix_sections('gcode_macro '):
+            name = macro_section.get_name()
+            macro = self.printer.lookup_object(name, None)
+            if macro is None or not self.config.has_section(name):
+                restart.append("[%s]" % name)
+                continue
+            for option, value in self._get_changed_config_options(self.config.getsection(name), macro_section).items():
+                if not option.startswith('variable_') or value is None:
+                    restart.append("[%s] %s" % (name, option))
+                    continue
+                try:
+                    literal = ast.literal_eval(value)
+                except (ValueError, SyntaxError) as e:
+                    raise gcmd.error("Unable to parse '%s' in [%s]: %s" % (option, name, str(e)))
+                variables = dict(macro.variables)
+                variables[option[9:]] = literal
+                macro.variables = variables
+                if name == "gcode_macro %s" % self.form_tip_macro and self.form_tip_vars is not None:
+                    self.form_tip_vars[option[9:]] = literal # New default for MMU_FORM_TIP RESET=1
+                self.config_reloaded[(name, option)] = value
+                applied.append("[%s] %s" % (name, option))
+
+        if not applied and not restart:
+            self._log_always("No configuration changes found")
+            return
+        msg = ""
+        if applied:
+            msg += "Applied configuration changes:\n%s" % "\n".join(applied)
+        if restart:
+            msg += "%sThe following changes require a restart to take effect:\n%s" % ("\n" if msg else "", "\n".join(restart))
+        self._log_always(msg)
+        self._publish_event('config_reloaded', applied=applied, restart=restart)
+
+    # Options in new config section that differ from what is currently in effect (startup or last reload).
+    # Removed options have value None
+    def _get_changed_config_options(self, old_section, new_section):
+        name = old_section.get_name()
+        new_options = new_section.get_prefix_options('')
+        changed = {}
+        for option in new_options:
+            value = new_section.get(option)
+            if value != self.config_reloaded.get((name, option), old_section.get(option, None)):
+                changed[option] = value
+        for option in old_section.get_prefix_options(''):
+            if option not in new_options:
+                changed[option] = None
+        return changed
 
 
 ###########################################
@@ -5079,11 +9798,13 @@ class Mmu:
 ###########################################
 
     def _handle_runout(self, force_runout=False):
+        if self.gate_selected == self.TOOL_GATE_BYPASS and force_runout:
+            return self._handle_bypass_runout()
         if self.tool_selected < 0:
-            raise MmuError("Filament runout or clog on an unknown or bypass tool - manual intervention is required")
+            raise MmuError("Filament runout or clog on an unknown or bypass tool - manual intervention is required", code=self.ERR_RUNOUT)
 
         if self.filament_pos != self.FILAMENT_POS_LOADED and not force_runout:
-            raise MmuError("Filament runout or clog when filament is not fully loaded - manual intervention is required")
+            raise MmuError("Filament runout or clog when filament is not fully loaded - manual intervention is required", code=self.ERR_RUNOUT)
 
         self._log_info("Issue on tool T%d" % self.tool_selected)
         self._save_toolhead_position_and_lift("runout", z_hop_height=self.z_hop_height_toolchange)
@@ -5093,23 +9814,24 @@ class Mmu:
         if not force_runout and self._check_filament_at_gate():
             if self._has_encoder():
                 self.encoder_sensor.update_clog_detection_length()
-            raise MmuError("A clog has been detected and requires manual intervention")
+            raise MmuError("A clog has been detected and requires manual intervention", code=self.ERR_CLOG_SUSPECTED)
 
         # We have a filament runout
         with self._wrap_disable_encoder(): # Don't want runout accidently triggering during swap
             self._log_always("A runout has been detected")
+            self._publish_event("runout", tool=self.tool_selected, gate=self.gate_selected)
 
             if self.enable_endless_spool:
-                self._set_gate_status(self.gate_selected, self.GATE_EMPTY) # Indicate current gate is empty
+                self._set_gate_status(self.gate_selected, self.GATE_EMPTY, source="runout") # Indicate current gate is empty
                 next_gate, checked_gates = self._get_next_endless_spool_gate(self.gate_selected)
 
                 if next_gate == -1:
-                    raise MmuError("No EndlessSpool alternatives available after reviewing gates: %s" % checked_gates)
+                    raise MmuError("No EndlessSpool alternatives available after reviewing gates: %s" % checked_gates, code=self.ERR_ENDLESS_SPOOL_EXHAUSTED)
                 self._log_info("Remapping T%d to gate #%d" % (self.tool_selected, next_gate))
 
                 # Save the extruder temperature for the resume after swapping filaments.
                 if not self.paused_extruder_temp: # Only save the initial pause temp
-                    self.paused_extruder_temp = self.printer.lookup_object(self.extruder_name).heater.target_temp
+                    self.paused_extruder_temp = self._get_extruder_target_temp()
 
                 gcode = self.printer.lookup_object('gcode_macro _MMU_ENDLESS_SPOOL_PRE_UNLOAD', None)
                 if gcode is not None:
@@ -5119,7 +9841,7 @@ class Mmu:
                 if not detected:
                     self._log_info("Filament didn't reach encoder after tip forming move")
                 self._unload_tool(skip_tip=True, runout=True)
-                self._remap_tool(self.tool_selected, next_gate)
+                self._remap_tool(self.tool_selected, next_gate, source="endless spool")
                 self._select_and_load_tool(self.tool_selected)
 
                 gcode = self.printer.lookup_object('gcode_macro _MMU_ENDLESS_SPOOL_POST_LOAD', None)
@@ -5128,37 +9850,241 @@ class Mmu:
                 self._restore_toolhead_position("EndlessSpool")
 
                 self._sync_gear_to_extruder(self.sync_to_extruder and self._is_in_print(force_runout), servo=True, current=self._is_in_print())
+                if self.sync_to_extruder and self._is_in_print(force_runout):
+                    self._verify_gear_synced()
                 self._initialize_filament_position()    # Encoder 0000
                 # Continue printing...
             else:
-                raise MmuError("EndlessSpool mode is off - manual intervention is required")
+                raise MmuError("EndlessSpool mode is off - manual intervention is required", code=self.ERR_RUNOUT)
+
+    # Runout reported by external bypass sensor. Pause (nozzle is parked) unless 'bypass_runout_gate' designates
+    # a gate with matching filament in which case the extruder is cleared and that gate's tool loaded to continue
+    def _handle_bypass_runout(self):
+        self._log_info("Issue on bypass")
+        self._save_toolhead_position_and_lift("runout", z_hop_height=self.z_hop_height_toolchange)
+
+        with self._wrap_disable_encoder(): # Don't want runout accidently triggering during swap
+            self._log_always("A runout has been detected on bypass")
+            self._publish_event("runout", tool=self.tool_selected, gate=self.gate_selected)
+
+            gate = self.bypass_runout_gate
+            if gate < 0:
+                raise MmuError("Filament runout on bypass - manual intervention is required", code=self.ERR_RUNOUT)
+            reason = self._gate_unavailable_reason(gate)
+            if reason:
+                raise MmuError("Filament runout on bypass but gate #%d is %s - manual intervention is required" % (gate, reason), code=self.ERR_RUNOUT)
+            tools = [t for t, g in enumerate(self.tool_to_gate_map) if g == gate]
+            if not tools:
+                raise MmuError("Filament runout on bypass but no tool is mapped to gate #%d - manual intervention is required" % gate, code=self.ERR_RUNOUT)
+            self._log_info("Switching from bypass to T%d on gate #%d" % (tools[0], gate))
+
+            # Save the extruder temperature for the resume after swapping filaments.
+            if not self.paused_extruder_temp: # Only save the initial pause temp
+                self.paused_extruder_temp = self._get_extruder_target_temp()
+
+            gcode = self.printer.lookup_object('gcode_macro _MMU_ENDLESS_SPOOL_PRE_UNLOAD', None)
+            if gcode is not None:
+                self._wrap_gcode_command("_MMU_ENDLESS_SPOOL_PRE_UNLOAD", exception=True)
+
+            self._form_tip_standalone(extruder_only=True)
+            self._set_filament_pos_state(self.FILAMENT_POS_IN_EXTRUDER, silent=True)
+            self._unload_sequence(length=0, skip_tip=True, extruder_only=True)
+            self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED)
+            self._select_and_load_tool(tools[0])
 
+            gcode = self.printer.lookup_object('gcode_macro _MMU_ENDLESS_SPOOL_POST_LOAD', None)
+            if gcode is not None:
+                self._wrap_gcode_command("_MMU_ENDLESS_SPOOL_POST_LOAD", exception=True)
+            self._restore_toolhead_position("EndlessSpool")
+
+            self._sync_gear_to_extruder(self.sync_to_extruder and self._is_in_print(True), servo=True, current=self._is_in_print())
+            if self.sync_to_extruder and self._is_in_print(True):
+                self._verify_gear_synced()
+            self._initialize_filament_position()    # Encoder 0000
+            # Continue printing...
+
+    # Available gates in group are ranked by the first 'endless_spool_match' rule they satisfy against the filament that
+    # ran out, then by 'endless_spool_priorities' (lowest first) and then by position in the rotation after the current
+    # gate. If none are available the 'endless_spool_fallback' group is tried, and so on
     def _get_next_endless_spool_gate(self, gate):
         group = self.endless_spool_groups[gate]
-        self._log_info("EndlessSpool checking for additional gates in Group_%d..." % group)
-        next_gate = -1
         checked_gates = []
-        for i in range(self.mmu_num_gates - 1):
-            check = (gate + i + 1) % self.mmu_num_gates
-            if self.endless_spool_groups[check] == group:
-                checked_gates.append(check)
-                if self.gate_status[check] != self.GATE_EMPTY:
-                    next_gate = check
-                    break
-        return next_gate, checked_gates
+        visited_groups = []
+        while group is not None and group not in visited_groups:
+            visited_groups.append(group)
+            self._log_info("EndlessSpool checking for additional gates in Group_%d..." % group)
+            candidates = []
+            for i in range(self.mmu_num_gates - 1):
+                check = (gate + i + 1) % self.mmu_num_gates
+                if self.endless_spool_groups[check] == group and check not in self.gate_maintenance and check not in self.gate_lock:
+                    checked_gates.append(check)
+                    rank = self._get_endless_spool_match_rank(gate, check)
+                    if self.gate_status[check] != self.GATE_EMPTY and rank is not None:
+                        candidates.append((rank, self.endless_spool_priorities[check], i, check))
+            if candidates:
+                return min(candidates)[-1], checked_gates
+            group = self.endless_spool_fallback.get(group, None)
+        return -1, checked_gates
+
+    # Index of first 'endless_spool_match' rule that candidate gate satisfies or None if it matches none
+    def _get_endless_spool_match_rank(self, gate, candidate):
+        material = self.gate_material[gate].upper()
+        same_material = bool(material) and self.gate_material[candidate].upper() == material
+        same_color = bool(self.gate_color[gate]) and bool(self.gate_color[candidate]) and colors_match(self.gate_color[candidate], self.gate_color[gate])
+        for rank, rule in enumerate(self.endless_spool_match):
+            if (rule == self.ES_MATCH_ANY or (rule == self.ES_MATCH_EXACT and same_material and same_color) or
+                    (rule == self.ES_MATCH_COLOR and same_color) or (rule == self.ES_MATCH_MATERIAL and same_material)):
+                return rank
+        return None
+
+    # Find alternative gate with identical filament (material and color) to spread wear and drain spools evenly.
+    # Policy 1 alternates to the duplicate gate after the one last used, policy 2 picks the duplicate gate with fewest
+    # completed loads
+    def _get_balanced_gate(self, gate, last_gate=None):
+        material, color = self.gate_material[gate].upper(), self.gate_color[gate]
+        if not material or not color:
+            return gate
+        duplicates = []
+        for i in range(self.mmu_num_gates):
+            check = (gate + i) % self.mmu_num_gates
+            if check == gate or (self.gate_status[check] != self.GATE_EMPTY and check not in self.gate_maintenance and check not in self.gate_lock and
+                    self.gate_material[check].upper() == material and colors_match(self.gate_color[check], color)):
+                duplicates.append(check)
+        if len(duplicates) < 2:
+            return gate
+        if self.gate_load_balancing == 1:
+            last = duplicates.index(last_gate) if last_gate in duplicates else 0
+            return duplicates[(last + 1) % len(duplicates)]
+        return min(duplicates, key=lambda g: (self.gate_statistics[g]['full_loads'], g != gate))
+
+    # Perceptual distance between two colors as CIEDE2000 deltaE (0 = identical, 100 = black/white)
+    def _color_distance(self, color1, color2):
+        return delta_e(color1, color2)
+
+    # Purge volume for transition between gates scaled up for filament degraded by idling in the hot nozzle
+    def _calc_purge_volume(self, from_gate, to_gate):
+        volume = self._calc_transition_purge_volume(from_gate, to_gate)
+        scale = self._calc_idle_purge_scale(to_gate)
+        if volume > 0. and scale > 1.:
+            self._log_debug("Nozzle idle for %s, increasing purge by %.0f%%" % (self._seconds_to_human_string(time.time() - self.last_extrusion_time), (scale - 1.) * 100.))
+        return volume * scale
+
+    # Full 'purge_volume' unless both filaments are the same material and their colors are within
+    # 'purge_similar_color_threshold' in which case it is reduced (or skipped)
+    def _calc_transition_purge_volume(self, from_gate, to_gate):
+        if self.purge_volume <= 0. or to_gate < 0:
+            return 0.
+        if from_gate < 0 or self.purge_similar_color_threshold <= 0.:
+            return self.purge_volume
+        if from_gate == to_gate:
+            return self.purge_volume * self.purge_similar_volume_percent / 100.
+        from_material, to_material = self.gate_material[from_gate].upper(), self.gate_material[to_gate].upper()
+        from_color, to_color = self.gate_color[from_gate], self.gate_color[to_gate]
+        if not from_color or not to_color or from_material != to_material:
+            return self.purge_volume
+        distance = self._color_distance(from_color, to_color)
+        if distance <= self.purge_similar_color_threshold:
+            self._log_debug("Gate #%d and #%d colors are similar (deltaE %.1f), reducing purge" % (from_gate, to_gate, distance))
+            return self.purge_volume * self.purge_similar_volume_percent / 100.
+        return self.purge_volume
+
+    # Purge multiplier for time since the print last extruded. Percent is interpolated along 'purge_idle_curve' (100%
+    # at 0 minutes, last point held beyond the curve) and the increase scaled by the 'purge_idle_materials' factor
+    def _calc_idle_purge_scale(self, gate):
+        if not self.purge_idle_curve or not self.last_extrusion_time or gate < 0:
+            return 1.
+        idle_minutes = (time.time() - self.last_extrusion_time) / 60.
+        percent = self.purge_idle_curve[-1][1]
+        last_minutes, last_percent = 0., 100.
+        for minutes, point_percent in self.purge_idle_curve:
+            if idle_minutes < minutes:
+                percent = last_percent + (point_percent - last_percent) * (idle_minutes - last_minutes) / (minutes - last_minutes)
+                break
+            last_minutes, last_percent = minutes, point_percent
+        factor = self.purge_idle_materials.get(self.gate_material[gate].upper(), 1.)
+        return 1. + (percent - 100.) * factor / 100.
+
+    # Move up to 'purge_to_infill_percent' (limited by the material cap) of the purge into the sparse infill that follows the
+    # toolchange. Only possible when the first extrusion after the toolchange is known to be sparse infill (flagged by
+    # 'MMU_PURGE_INFILL NEXT=1'), otherwise purge is unchanged
+    def _route_purge_to_infill(self, purge_volume):
+        if not self.purge_router:
+            return purge_volume
+        next_infill = self.purge_router.take_next_infill()
+        if purge_volume <= 0.:
+            return purge_volume
+        if self.purge_router.budget > 0.:
+            self._log_debug("Purge router: %.1fmm of previous routed purge was not used in infill" % self.purge_router.budget)
+            self.purge_router.set_budget(0.)
+        if not next_infill:
+            return purge_volume
+        material = self.gate_material[self.gate_selected].upper() if self.gate_selected >= 0 else ""
+        percent = min(self.purge_to_infill_percent, self.purge_to_infill_caps.get(material, 100.))
+        routed = purge_volume * percent / 100.
+        if routed <= 0.:
+            return purge_volume
+        length = routed / self.printer.lookup_object(self.extruder_name).filament_area
+        self.purge_router.set_budget(length)
+        self._log_debug("Purge router: routing %.1fmm^3 (%.1fmm) of %.1fmm^3 purge into infill" % (routed, length, purge_volume))
+        return purge_volume - routed
+
+    def _purge_routing_complete(self):
+        self._log_debug("Purge router: routed purge fully used in infill")
+
+    # Small prime extrusion (distinct from purge) just before control returns to the print. 'auto' replaces the
+    # residual filament lost from nozzle plus ooze proportional to time since the print last extruded
+    def _calc_prime_length(self):
+        if self.prime_mode == self.PRIME_FIXED:
+            return self.prime_length
+        elif self.prime_mode == self.PRIME_AUTO:
+            idle_minutes = (time.time() - self.last_extrusion_time) / 60. if self.last_extrusion_time else 0.
+            return min(self.toolhead_residual_filament + idle_minutes * self.prime_ooze_rate, self.prime_max_length)
+        return 0.
+
+    def _prime_nozzle(self):
+        length = self._calc_prime_length()
+        if length <= 0.: return
+        self._log_debug("Priming nozzle with %.1fmm of filament (%s)" % (length, self.prime_mode))
+        self._trace_filament_move("Priming nozzle", length, speed=self.prime_speed, motor="extruder", wait=True)
+
+    # Why gate can't be used for loading or None if it can
+    def _gate_unavailable_reason(self, gate):
+        if gate in self.gate_maintenance:
+            return "in maintenance"
+        if gate in self.gate_lock:
+            return "locked by '%s'" % self.gate_lock[gate] if self.gate_lock[gate] else "locked"
+        if self.gate_status[gate] == self.GATE_EMPTY:
+            return "empty"
+        return None
 
-    def _set_tool_to_gate(self, tool, gate):
+    def _persist_gate_stub_length(self):
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_STUB_LENGTH, self.gate_stub_length))
+
+    def _persist_gate_lock(self):
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE=\"%s\"" % (self.VARS_MMU_GATE_LOCK, self.gate_lock))
+
+    def _set_tool_to_gate(self, tool, gate, source=None):
         self.tool_to_gate_map[tool] = gate
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+        self._record_map_change(source)
 
-    def _set_gate_status(self, gate, state):
+    def _set_gate_status(self, gate, state, source=None):
         if gate >= 0:
             if state != self.gate_status[gate]:
+                if state == self.GATE_EMPTY:
+                    self._set_spoolman_location(self.gate_spool_id[gate]) # Spool ejected
+                elif self.gate_status[gate] == self.GATE_EMPTY:
+                    self._set_spoolman_location(self.gate_spool_id[gate], gate)
                 self.gate_status[gate] = state
                 self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_STATUS, self.gate_status))
+                self._record_map_change(source)
                 gcode = self.printer.lookup_object('gcode_macro _MMU_GATE_MAP_CHANGED', None)
                 if gcode is not None:
                     self._wrap_gcode_command("_MMU_GATE_MAP_CHANGED GATE='%d'" % gate)
+                self._publish_event("gate_map", gate=gate, gate_status=self.gate_status)
+
+    def _has_pre_gate_sensors(self):
+        return any(self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None) is not None for gate in range(self.mmu_num_gates))
 
     # Use pre-gate sensors (if fitted) to "correct" gate status
     # Return True if update made
@@ -5200,11 +10126,14 @@ class Mmu:
                     es = " Group_%s: " % group
                     prefix = ""
                     starting_gate = self.tool_to_gate_map[i]
-                    for j in range(num_tools): # Gates
+                    members = [(j > 0, self.endless_spool_priorities[(j + starting_gate) % num_tools], j) for j in range(num_tools)
+                               if self.endless_spool_groups[(j + starting_gate) % num_tools] == group]
+                    for _, _, j in sorted(members): # Gates in priority order
                         gate = (j + starting_gate) % num_tools
-                        if self.endless_spool_groups[gate] == group:
-                            es += "%s%d(%s)" % (prefix, gate, self._get_filament_char(self.gate_status[gate], show_source=False))
-                            prefix = " > "
+                        es += "%s%d(%s)" % (prefix, gate, self._get_filament_char(self.gate_status[gate], show_source=False))
+                        prefix = " > "
+                    if group in self.endless_spool_fallback:
+                        es += " then Group_%d" % self.endless_spool_fallback[group]
                     msg += es
                 if i == self.tool_selected:
                     msg += " [SELECTED]"
@@ -5274,29 +10203,186 @@ class Mmu:
             if self.enable_spoolman:
                 spool_id = str(self.gate_spool_id[g]) if self.gate_spool_id[g] > 0 else "n/a"
                 msg += (", SpoolID: %s" % (spool_id))
+            if self.gate_temperature[g] > 0:
+                msg += (", Temp: %d\u00B0C" % self.gate_temperature[g])
+            if self.gate_unit[g]:
+                msg += (", Unit: %s" % self.gate_unit[g])
+            if self.gate_pressure_advance[g] >= 0 or self.gate_smooth_time[g] >= 0:
+                msg += (", PA: %s" % self._get_gate_pa_string(g))
+            if g in self.gate_lock:
+                msg += ", LOCKED%s" % (" (%s)" % self.gate_lock[g] if self.gate_lock[g] else "")
             if detail and g == self.gate_selected:
                 msg += " [SELECTED]"
         return msg
 
-    def _remap_tool(self, tool, gate, available=None):
-        self._set_tool_to_gate(tool, gate)
+    # Rich console view of gate map with 256-color swatches, TTG arrows and EndlessSpool group brackets
+    def _gate_map_to_visual_string(self):
+        msg = "MMU Gates / Filaments:"
+        ansi_color = lambda rgb : 16 + 36 * int(round(rgb[0] * 5)) + 6 * int(round(rgb[1] * 5)) + int(round(rgb[2] * 5))
+        for g in range(self.mmu_num_gates):
+            tools = ",".join(["T%d" % t for t in range(self.mmu_num_gates) if self.tool_to_gate_map[t] == g])
+            msg += "\n%s %s #%-2d " % (tools.rjust(11), ("-->" if tools else "   "), g)
+            if self.gate_color[g] != "":
+                msg += "\033[48;5;%dm    \033[0m" % ansi_color(self._color_to_rgb(self.gate_color[g]))
+            else:
+                msg += "[  ]"
+            msg += " %s %-10s" % (self._get_filament_char(self.gate_status[g], no_space=True, show_source=True), (self.gate_material[g] or "n/a"))
+            if self.enable_endless_spool:
+                group = self.endless_spool_groups[g]
+                members = [i for i in range(self.mmu_num_gates) if self.endless_spool_groups[i] == group]
+                if len(members) > 1:
+                    bracket = "\u250c" if g == members[0] else "\u2514" if g == members[-1] else "\u251c"
+                    msg += " %s ES%d" % (bracket, group)
+                else:
+                    msg += "      "
+            if self.gate_pressure_advance[g] >= 0 or self.gate_smooth_time[g] >= 0:
+                msg += " PA:%s" % self._get_gate_pa_string(g)
+            if g in self.gate_lock:
+                msg += " LOCKED%s" % (":%s" % self.gate_lock[g] if self.gate_lock[g] else "")
+            if g == self.gate_selected:
+                msg += " <= %s" % (("T%d" % self.tool_selected) if self.tool_selected >= 0 else "SELECTED")
+        for i in range(len(self.bypass_offsets)):
+            name = "Bypass" if len(self.bypass_offsets) == 1 else "Bypass %d" % (i + 1)
+            selected = self.gate_selected == self.TOOL_GATE_BYPASS and i == self.bypass_selected
+            msg += "\n%s    %s%s" % ("".rjust(11), name, " <= SELECTED" if selected else "")
+        return msg
+
+    def _get_gate_pa_string(self, gate):
+        pa = ("%.4f" % self.gate_pressure_advance[gate]) if self.gate_pressure_advance[gate] >= 0 else "default"
+        if self.gate_smooth_time[gate] >= 0:
+            pa += "/%.3f" % self.gate_smooth_time[gate]
+        return pa
+
+    def _remap_tool(self, tool, gate, available=None, source=None):
+        self._set_tool_to_gate(tool, gate, source=source)
         if available is not None:
-            self._set_gate_status(gate, available)
+            self._set_gate_status(gate, available, source=source)
         return gate
 
-    def _reset_ttg_mapping(self):
+    def _reset_ttg_mapping(self, source=None):
         self._log_debug("Resetting TTG map")
         self.tool_to_gate_map = self.default_tool_to_gate_map
         self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+        self._record_map_change(source)
         self._unselect_tool()
 
-    def _reset_gate_map(self):
+    def _reset_gate_map(self, source=None):
         self._log_debug("Resetting gate map")
         self.gate_status = self._validate_gate_status(list(self.default_gate_status))
         self.gate_material = list(self.default_gate_material)
         self._update_gate_color(list(self.default_gate_color))
         self.gate_spool_id = list(self.default_gate_spool_id)
-        self._persist_gate_map()
+        self.gate_pressure_advance = list(self.default_gate_pressure_advance)
+        self.gate_smooth_time = list(self.default_gate_smooth_time)
+        self.gate_temperature = list(self.default_gate_temperature)
+        self.gate_unit = list(self.default_gate_unit)
+        self._persist_gate_map(source)
+
+    # Gate map templates are named files ('<name>.cfg' in 'gate_map_template_dir') capturing a full filament lineup.
+    # Each '[gate N]' section lists the filament expected in that gate; gates without a section are expected empty
+    def _gate_map_template_path(self, name):
+        if not re.match(r'^[A-Za-z0-9_-]+$', name):
+            raise MmuError("Invalid template name '%s'. Use only letters, numbers, '_' and '-'" % name)
+        return os.path.join(self.gate_map_template_dir, "%s.cfg" % name)
+
+    def _get_gate_map_template_names(self):
+        if not os.path.isdir(self.gate_map_template_dir):
+            return []
+        return sorted(f[:-4] for f in os.listdir(self.gate_map_template_dir) if f.endswith(".cfg"))
+
+    def _load_gate_map_template(self, name):
+        path = self._gate_map_template_path(name)
+        if not os.path.exists(path):
+            names = self._get_gate_map_template_names()
+            raise MmuError("Gate map template '%s' not found. Available: %s" % (name, ", ".join(names) if names else "none"))
+        parser = configparser.RawConfigParser()
+        try:
+            parser.read(path)
+            template = {}
+            for section in parser.sections():
+                match = re.match(r'^gate\s+(\d+)$', section.strip().lower())
+                if not match or int(match.group(1)) >= self.mmu_num_gates:
+                    raise MmuError("Gate map template '%s' has invalid section [%s]" % (name, section))
+                color = self._validate_color(parser.get(section, 'color', fallback=""))
+                if color is None:
+                    raise MmuError("Gate map template '%s' [%s] color must be %s" % (name, section, COLOR_FORMATS))
+                template[int(match.group(1))] = {
+                    'material': "".join(parser.get(section, 'material', fallback="").split()).replace('#', '').upper()[:10],
+                    'color': color,
+                    'spool_id': parser.getint(section, 'spool_id', fallback=-1),
+                    'pa': parser.getfloat(section, 'pa', fallback=-1.),
+                    'smooth_time': parser.getfloat(section, 'smooth_time', fallback=-1.),
+                    'temp': parser.getint(section, 'temp', fallback=-1),
+                    'unit': "".join(parser.get(section, 'unit', fallback="").split()),
+                }
+        except (configparser.Error, ValueError) as e:
+            raise MmuError("Unable to parse gate map template '%s': %s" % (name, str(e)))
+        return template
+
+    def _save_gate_map_template(self, name):
+        path = self._gate_map_template_path(name)
+        lines = ["# Happy Hare gate map template '%s' saved %s" % (name, time.strftime("%Y-%m-%d %H:%M:%S"))]
+        for gate in range(self.mmu_num_gates):
+            if self.gate_status[gate] == self.GATE_EMPTY:
+                continue
+            lines += ["", "[gate %d]" % gate,
+                      "material: %s" % self.gate_material[gate],
+                      "color: %s" % self.gate_color[gate],
+                      "spool_id: %d" % self.gate_spool_id[gate],
+                      "pa: %s" % self.gate_pressure_advance[gate],
+                      "smooth_time: %s" % self.gate_smooth_time[gate],
+                      "temp: %d" % self.gate_temperature[gate],
+                      "unit: %s" % self.gate_unit[gate]]
+        try:
+            if not os.path.isdir(self.gate_map_template_dir):
+                os.makedirs(self.gate_map_template_dir)
+            with open(path, 'w') as f:
+                f.write("\n".join(lines) + "\n")
+        except (IOError, OSError) as e:
+            raise MmuError("Unable to write gate map template '%s': %s" % (path, str(e)))
+        return path
+
+    # Pre-gate sensors that disagree with whether the template expects filament in the gate
+    def _check_gate_map_template(self, template):
+        issues = []
+        for gate in range(self.mmu_num_gates):
+            sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+            if sensor is None or not sensor.runout_helper.sensor_enabled:
+                continue
+            detected = sensor.runout_helper.filament_present
+            if gate in template and not detected:
+                issues.append("Template expects %s in gate #%d but pre-gate sensor does not detect filament" % (template[gate]['material'] or "filament", gate))
+            elif gate not in template and detected:
+                issues.append("Template expects gate #%d to be empty but pre-gate sensor detects filament" % gate)
+        return issues
+
+    def _prompt_gate_map_template(self, name, issues):
+        lines = ["prompt_begin Happy Hare",
+                 "prompt_text Gate map template '%s' doesn't match loaded filament:" % name]
+        lines += ["prompt_text %s" % issue for issue in issues]
+        lines += ["prompt_footer_button Apply anyway|MMU_GATE_MAP TEMPLATE=%s CONFIRM=1|warning" % name,
+                  "prompt_footer_button Cancel|RESPOND TYPE=command MSG=action:prompt_end|secondary",
+                  "prompt_show"]
+        for line in lines:
+            self.gcode.respond_raw("// action:%s" % line)
+
+    def _apply_gate_map_template(self, template, source=None):
+        for gate in range(self.mmu_num_gates):
+            fil = template.get(gate, {'material': "", 'color': "", 'spool_id': -1, 'pa': -1., 'smooth_time': -1., 'temp': -1, 'unit': ""})
+            if fil['spool_id'] != self.gate_spool_id[gate]:
+                self._set_spoolman_location(self.gate_spool_id[gate])
+                if gate in template:
+                    self._set_spoolman_location(fil['spool_id'], gate)
+            self.gate_status[gate] = self.GATE_AVAILABLE if gate in template else self.GATE_EMPTY
+            self.gate_material[gate] = fil['material']
+            self.gate_color[gate] = fil['color']
+            self.gate_spool_id[gate] = fil['spool_id']
+            self.gate_pressure_advance[gate] = fil['pa']
+            self.gate_smooth_time[gate] = fil['smooth_time']
+            self.gate_temperature[gate] = fil['temp']
+            self.gate_unit[gate] = fil['unit']
+        self._update_gate_color(self.gate_color)
+        self._persist_gate_map(source)
 
 
 ### GCODE COMMANDS FOR RUNOUT, TTG MAP, GATE MAP and GATE LOGIC ##################################
@@ -5308,7 +10394,7 @@ class Mmu:
         try:
             self._handle_runout(force_runout)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
 
     cmd_MMU_ENCODER_RUNOUT_help = "Internal encoder filament runout handler"
     def cmd_MMU_ENCODER_RUNOUT(self, gcmd):
@@ -5316,7 +10402,7 @@ class Mmu:
         try:
             self._handle_runout()
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
 
     cmd_MMU_ENCODER_INSERT_help = "Internal encoder filament insert detection handler"
     def cmd_MMU_ENCODER_INSERT(self, gcmd):
@@ -5326,7 +10412,28 @@ class Mmu:
         #try:
         #    self._handle_detection()
         #except MmuError as ee:
-        #    self._mmu_pause(str(ee))
+        #    self._mmu_pause(str(ee), code=ee.code)
+
+    cmd_MMU_GEAR_SLIP_help = "Internal gear slip whilst synced handler"
+    def cmd_MMU_GEAR_SLIP(self, gcmd):
+        if self._check_is_disabled(): return
+        length = gcmd.get_float('LENGTH', self.gear_slip_length)
+        sensor = "sync tension" if self._has_sensor(self.SENSOR_SYNC_TENSION) else "extruder entry"
+        msg = "Gear slip suspected on gate #%d: %s sensor indicated filament was not being fed for %.1fmm of synced extrusion whilst the extruder kept extruding (not a clog)" % (self.gate_selected, sensor, length)
+        msg += ". Consider increasing 'sync_gear_current' (currently %d%%), checking gear grip/tension or recalibrating gear rotation_distance with MMU_CALIBRATE_GEAR" % self.sync_gear_current
+        self._publish_event("gear_slip", gate=self.gate_selected, length=length)
+        if self.gear_slip_detection == 2:
+            self._mmu_pause(msg, code=self.ERR_GEAR_SLIP)
+        else:
+            self._log_error(msg)
+
+    cmd_MMU_GEAR_THERMAL_help = "Internal gear motor thermal control handler"
+    def cmd_MMU_GEAR_THERMAL(self, gcmd):
+        if self._check_is_disabled(): return
+        from_percent = gcmd.get_int('FROM', self.sync_gear_current)
+        # Only if still synced and current wasn't changed by another operation in the meantime
+        if self.mmu_toolhead.is_gear_synced_to_extruder() and self.gear_percentage_run_current == from_percent:
+            self._adjust_gear_current(self._get_sync_gear_current(), "for extruder syncing (gear motor thermal control)")
 
     cmd_MMU_GATE_RUNOUT_help = "Internal gate filament runout handler"
     def cmd_MMU_GATE_RUNOUT(self, gcmd):
@@ -5334,7 +10441,23 @@ class Mmu:
         try:
             self._handle_runout(True)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
+
+    cmd_MMU_BYPASS_RUNOUT_help = "Internal bypass filament runout handler"
+    def cmd_MMU_BYPASS_RUNOUT(self, gcmd):
+        if self._check_is_disabled(): return
+        if self.gate_selected != self.TOOL_GATE_BYPASS: return # Only relevant when printing from bypass
+        try:
+            self._handle_runout(True)
+        except MmuError as ee:
+            self._mmu_pause(str(ee), code=ee.code)
+
+    cmd_MMU_BYPASS_INSERT_help = "Internal bypass filament insert detection handler"
+    def cmd_MMU_BYPASS_INSERT(self, gcmd):
+        if self._check_is_disabled(): return
+        if not self.bypass_guard or self.tool_selected == self.TOOL_GATE_BYPASS: return
+        self._log_always("Filament inserted into bypass but bypass is not selected. To print from bypass run MMU_SELECT_BYPASS. To use the MMU run MMU_SELECT_BYPASS then MMU_UNLOAD to eject it first")
+        self._publish_event("bypass_inserted")
 
     cmd_MMU_GATE_INSERT_help = "Internal gate filament insert detection handler"
     def cmd_MMU_GATE_INSERT(self, gcmd):
@@ -5350,11 +10473,14 @@ class Mmu:
         try:
             gate = gcmd.get_int('GATE')
             self._log_debug("Filament runout detected by pre-gate sensor on gate #%d" % gate)
-            self._set_gate_status(gate, self.GATE_EMPTY)
+            if gate in self.final_eject_gates:
+                self.final_eject_gates.discard(gate) # Spool deliberately rewound past sensor so gate remains available
+                return
+            self._set_gate_status(gate, self.GATE_EMPTY, source="runout (pre-gate sensor)")
             if self._is_in_print() and active and gate == self.gate_selected:
                 self._handle_runout(True)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
         
     # This callback is not protected by klipper "is printing" check so be careful
     cmd_MMU_PRE_GATE_INSERT_help = "Internal pre-gate filament detection handler"
@@ -5364,11 +10490,20 @@ class Mmu:
         try:
             gate = gcmd.get_int('GATE')
             self._log_debug("Filament insertion detected by pre-gate sensor on gate #%d" % gate)
-            self._set_gate_status(gate, self.GATE_UNKNOWN)
+            replaced = self.gate_status[gate] == self.GATE_EMPTY
+            self._set_gate_status(gate, self.GATE_UNKNOWN, source="autoload (pre-gate sensor)")
+            if self.rfid_pending_tag is not None:
+                tag, scan_time = self.rfid_pending_tag
+                self.rfid_pending_tag = None
+                if self.reactor.monotonic() - scan_time < self.RFID_PENDING_TIMEOUT:
+                    self._apply_rfid_tag(gate, tag)
+                    replaced = replaced and 'spool_id' not in tag # Tag already identified the spool
             if not self._is_in_print() and not active:
                 self.cmd_MMU_PRELOAD(gcmd)
+                if replaced:
+                    self._prompt_spool_change(gate)
         except MmuError as ee:
-            self._mmu_pause(str(ee))
+            self._mmu_pause(str(ee), code=ee.code)
 
     cmd_MMU_M400_help = "Wait on both move queues"
     def cmd_MMU_M400(self, gcmd):
@@ -5379,46 +10514,271 @@ class Mmu:
         if self._check_is_disabled(): return
         quiet = bool(gcmd.get_int('QUIET', 0, minval=0, maxval=1))
         reset = bool(gcmd.get_int('RESET', 0, minval=0, maxval=1))
+        if reset and self._check_print_protection(gcmd): return
         ttg_map = gcmd.get('MAP', "!")
         gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
         tool = gcmd.get_int('TOOL', -1, minval=0, maxval=self.mmu_num_gates - 1)
         available = gcmd.get_int('AVAILABLE', self.GATE_UNKNOWN, minval=self.GATE_EMPTY, maxval=self.GATE_AVAILABLE)
 
         if reset == 1:
-            self._reset_ttg_mapping()
+            self._reset_ttg_mapping(self._get_command_source(gcmd))
         elif ttg_map != "!":
             ttg_map = gcmd.get('MAP').split(",")
             if len(ttg_map) != self.mmu_num_gates:
                 self._log_always("The number of map values (%d) is not the same as number of gates (%d)" % (len(ttg_map), self.mmu_num_gates))
                 return
-            self.tool_to_gate_map = []
-            for gate in ttg_map:
-                if gate.isdigit():
-                    self.tool_to_gate_map.append(int(gate))
-                else:
-                    self.tool_to_gate_map.append(0)
+            new_map = [int(gate) if gate.isdigit() else 0 for gate in ttg_map]
+            locked = ["T%d" % t for t, g in enumerate(new_map) if g in self.gate_lock and g != self.tool_to_gate_map[t]]
+            if locked:
+                raise gcmd.error("Cannot map %s to locked gates. Unlock with MMU_GATE_MAP GATE=.. LOCK=0 first" % ", ".join(locked))
+            self.tool_to_gate_map = new_map
             self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+            self._record_map_change(self._get_command_source(gcmd))
         elif gate != -1:
             status = self.gate_status[gate]
             if not available == self.GATE_UNKNOWN or (available == self.GATE_UNKNOWN and status == self.GATE_EMPTY):
                 status = available
             if tool == -1:
-                self._set_gate_status(gate, status)
+                self._set_gate_status(gate, status, source=self._get_command_source(gcmd))
+            elif gate in self.gate_lock and self.tool_to_gate_map[tool] != gate:
+                raise gcmd.error("Gate #%d is %s. Unlock with MMU_GATE_MAP GATE=%d LOCK=0 first" % (gate, self._gate_unavailable_reason(gate), gate))
             else:
-                self._remap_tool(tool, gate, status)
+                self._remap_tool(tool, gate, status, source=self._get_command_source(gcmd))
         else:
             quiet = False # Display current TTG map
         if not quiet:
             self._log_info(self._tool_to_gate_map_to_human_string())
 
+    # Total selector travel for toolchange sequence given TTG mapping. 'transitions' is dict of (from_tool, to_tool) -> count
+    def _selector_travel(self, ttg_map, transitions):
+        return sum(abs(self.selector_offsets[ttg_map[a]] - self.selector_offsets[ttg_map[b]]) * n for (a, b), n in transitions.items())
+
+    # Remap tools used in the toolchange sequence onto interchangeable gates (identical material and color) to minimize
+    # total selector travel. Simple local search of tool moves and swaps that is good enough for typical gate counts
+    def _optimize_ttg_map(self, sequence):
+        transitions = {}
+        for a, b in zip(sequence, sequence[1:]):
+            if a != b:
+                transitions[(a, b)] = transitions.get((a, b), 0) + 1
+        used = sorted(set(sequence))
+        candidates = {}
+        for t in used:
+            g = self.tool_to_gate_map[t]
+            material, color = self.gate_material[g].upper(), self.gate_color[g]
+            candidates[t] = [g] if not material or not color else [
+                c for c in range(self.mmu_num_gates) if c == g or (self.gate_status[c] != self.GATE_EMPTY and c not in self.gate_lock and
+                        self.gate_material[c].upper() == material and colors_match(self.gate_color[c], color))]
+
+        ttg_map = list(self.tool_to_gate_map)
+        best = self._selector_travel(ttg_map, transitions)
+        improved = True
+        while improved:
+            improved = False
+            for t in used:
+                for g in candidates[t]:
+                    if g == ttg_map[t]:
+                        continue
+                    trial = list(ttg_map)
+                    other = next((u for u in used if u != t and ttg_map[u] == g), None)
+                    if other is not None:
+                        if ttg_map[t] not in candidates[other]:
+                            continue
+                        trial[other] = ttg_map[t]
+                    trial[t] = g
+                    travel = self._selector_travel(trial, transitions)
+                    if travel < best - 0.01:
+                        ttg_map, best, improved = trial, travel, True
+        return ttg_map, self._selector_travel(self.tool_to_gate_map, transitions), best
+
+    cmd_MMU_OPTIMIZE_TTG_help = "Reorder TTG map across interchangeable gates to minimize selector travel for a toolchange sequence"
+    def cmd_MMU_OPTIMIZE_TTG(self, gcmd):
+        if self._check_is_disabled(): return
+        if not self.vendor_driver.HAS_SELECTOR:
+            self._log_always("MMU has no selector. Nothing to optimize")
+            return
+        apply = gcmd.get_int('APPLY', 0, minval=0, maxval=1)
+        try:
+            sequence = [int(t) for t in gcmd.get('SEQUENCE').split(',') if t.strip() != ""]
+        except ValueError:
+            raise gcmd.error("Invalid SEQUENCE parameter. Must be comma separated list of tools in toolchange order")
+        if any(t < 0 or t >= self.mmu_num_gates for t in sequence):
+            raise gcmd.error("SEQUENCE contains invalid tool")
+
+        ttg_map, current, optimized = self._optimize_ttg_map(sequence)
+        saved = current - optimized
+        msg = "Selector travel for %d toolchanges: current %.0fmm, optimized %.0fmm" % (len(sequence) - 1, current, optimized)
+        if saved <= 0.:
+            self._log_always(msg + ". Current TTG map is already optimal")
+            return
+        msg += " (estimated %s saved)" % self._seconds_to_human_string(saved / self.selector_move_speed)
+        changes = ["T%d: Gate #%d -> #%d" % (t, self.tool_to_gate_map[t], g) for t, g in enumerate(ttg_map) if g != self.tool_to_gate_map[t]]
+        msg += "\nRemapping %s" % ", ".join(changes)
+        if apply:
+            if self._is_in_print():
+                raise gcmd.error("TTG map cannot be optimized whilst printing")
+            for t, g in enumerate(ttg_map):
+                if g != self.tool_to_gate_map[t]:
+                    self.tool_to_gate_map[t] = g
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_TOOL_TO_GATE_MAP, self.tool_to_gate_map))
+            self._record_map_change(self._get_command_source(gcmd))
+            msg += "\nOptimized TTG map applied"
+        else:
+            msg += "\nRun again with APPLY=1 to apply"
+        self._log_always(msg)
+
+    cmd_MMU_AUDIT_help = "Cross-check filament sensors, gate map and Spoolman assignments and optionally fix inconsistencies"
+    def cmd_MMU_AUDIT(self, gcmd):
+        if self._check_is_disabled(): return
+        fix = bool(gcmd.get_int('FIX', 0, minval=0, maxval=1))
+        locations = gcmd.get('LOCATIONS', None) # Hidden option for spoolman reply from moonraker component
+
+        if locations is not None:
+            try:
+                locations = ast.literal_eval(locations)
+            except (SyntaxError, ValueError) as e:
+                raise gcmd.error("Invalid LOCATIONS parameter: %s" % str(e))
+            issues = self._audit_spoolman(locations, fix)
+            title = "Spoolman audit"
+        else:
+            issues = self._audit_sensors(fix, source=self._get_command_source(gcmd))
+            title = "Sensor audit"
+        if fix and issues:
+            self._persist_gate_map(self._get_command_source(gcmd))
+        msg = "%s %s" % (title, ("found %d inconsistencies%s:" % (len(issues), " (fixed where possible)" if fix else "")) if issues else "found no inconsistencies")
+        for issue in issues:
+            msg += "\n- %s" % issue
+        if issues and not fix:
+            msg += "\nRun 'MMU_AUDIT FIX=1' to correct"
+        self._log_always(msg)
+
+        # Spoolman is audited asynchronously. Moonraker component calls back with LOCATIONS
+        if locations is None:
+            if self.enable_spoolman:
+                try:
+                    webhooks = self.printer.lookup_object('webhooks')
+                    webhooks.call_remote_method("spoolman_audit_locations", printer=self.spoolman_printer_name, fix=int(fix))
+                except Exception as e:
+                    self._log_error("Error while requesting spoolman locations for audit: %s" % str(e))
+            else:
+                self._log_always("Spoolman not enabled. Spool assignments not audited")
+
+    # Physical pre-gate/gate sensors are the truth for presence of filament. Fixes gate map status
+    def _audit_sensors(self, fix, source=None):
+        issues = []
+        for gate in range(self.mmu_num_gates):
+            sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+            if sensor is None or not sensor.runout_helper.sensor_enabled or gate in self.gate_maintenance:
+                continue
+            detected = sensor.runout_helper.filament_present
+            if detected and self.gate_status[gate] == self.GATE_EMPTY:
+                issues.append("Gate #%d is marked empty but pre-gate sensor detects filament%s" % (gate, " - marked unknown" if fix else ""))
+                if fix:
+                    self._set_gate_status(gate, self.GATE_UNKNOWN, source=source)
+            elif not detected and self.gate_status[gate] != self.GATE_EMPTY:
+                issues.append("Gate #%d is marked %s but pre-gate sensor does not detect filament%s" % (gate, "unknown" if self.gate_status[gate] == self.GATE_UNKNOWN else "available", " - marked empty" if fix else ""))
+                if fix:
+                    self._set_gate_status(gate, self.GATE_EMPTY, source=source)
+        if self.gate_selected >= 0 and self.filament_pos == self.FILAMENT_POS_LOADED and self._check_sensor(self.ENDSTOP_GATE) is False:
+            issues.append("Gate #%d is loaded but '%s' sensor does not detect filament (check manually)" % (self.gate_selected, self.ENDSTOP_GATE))
+        return issues
+
+    # Spoolman location is the truth for which spool is in a gate but not whether the gate has filament
+    def _audit_spoolman(self, locations, fix):
+        issues = []
+        for gate in range(self.mmu_num_gates):
+            spool_ids = locations.get(gate, [])
+            spool_id = self.gate_spool_id[gate]
+            if len(spool_ids) > 1:
+                issues.append("Spoolman has multiple spools (%s) located in gate #%d (fix in Spoolman)" % (", ".join(str(s) for s in spool_ids), gate))
+                continue
+            if spool_ids and spool_ids[0] != spool_id:
+                issues.append("Spoolman has spool %d in gate #%d but gate map has %s%s" % (spool_ids[0], gate, ("spool %d" % spool_id) if spool_id >= 0 else "no spool", " - assigned spool %d" % spool_ids[0] if fix else ""))
+                if fix:
+                    self.gate_spool_id[gate] = spool_ids[0]
+                    self._update_filaments_from_spoolman(gate)
+                spool_id = spool_ids[0]
+            if spool_ids and self.gate_status[gate] == self.GATE_EMPTY:
+                msg = "Spool %d is assigned to gate #%d in Spoolman but gate is marked empty" % (spool_id, gate)
+                sensor = self.printer.lookup_object("filament_switch_sensor mmu_pre_gate_%d" % gate, None)
+                if sensor is not None and sensor.runout_helper.sensor_enabled and not sensor.runout_helper.filament_present:
+                    if fix:
+                        msg += " - cleared Spoolman location"
+                        self._set_spoolman_location(spool_id)
+                else:
+                    msg += " (check manually)"
+                issues.append(msg)
+            elif not spool_ids and spool_id >= 0 and self.gate_status[gate] != self.GATE_EMPTY and self.spoolman_location:
+                issues.append("Gate #%d has spool %d but Spoolman location is not this gate%s" % (gate, spool_id, " - updated Spoolman location" if fix else ""))
+                if fix:
+                    self._set_spoolman_location(spool_id, gate)
+        return issues
+
     cmd_MMU_GATE_MAP_help = "Display or define the type and color of filaments on each gate"
     def cmd_MMU_GATE_MAP(self, gcmd):
         if self._check_is_disabled(): return
         quiet = bool(gcmd.get_int('QUIET', 0, minval=0, maxval=1))
         reset = bool(gcmd.get_int('RESET', 0, minval=0, maxval=1))
+        if reset and self._check_print_protection(gcmd): return
+        history = bool(gcmd.get_int('HISTORY', 0, minval=0, maxval=1))
+        undo = bool(gcmd.get_int('UNDO', 0, minval=0, maxval=1))
         gates = gcmd.get('GATES', "!")
         gmapstr = gcmd.get('MAP', "{}") # Hidden option for bulk update from moonraker component
         gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
+        template = gcmd.get('TEMPLATE', None)
+        if template is not None and self._check_print_protection(gcmd): return
+        save_template = gcmd.get('SAVE_TEMPLATE', None)
+        confirm = bool(gcmd.get_int('CONFIRM', 0, minval=0, maxval=1))
+        fmt = gcmd.get('FORMAT', "text").lower()
+        if fmt not in ["text", "visual"]:
+            raise gcmd.error("FORMAT must be 'text' or 'visual'")
+
+        if history:
+            msg = "Gate map / TTG map change history (most recent last):" if self.map_history else "No gate map or TTG map changes recorded"
+            for h in self.map_history:
+                msg += "\n%s by %s:" % (time.strftime("%H:%M:%S", time.localtime(h['time'])), h['source'])
+                for change in h['changes']:
+                    msg += "\n  %s" % change
+            self._log_always(msg)
+            return
+
+        if undo:
+            if not self.map_history:
+                raise gcmd.error("No gate map or TTG map changes to undo")
+            h = self.map_history.pop()
+            self._restore_map_snapshot(h['previous'])
+            msg = "Reverted change made at %s by %s:" % (time.strftime("%H:%M:%S", time.localtime(h['time'])), h['source'])
+            for change in h['changes']:
+                msg += "\n  %s" % change
+            self._log_always(msg)
+            return
+
+        if save_template is not None:
+            try:
+                path = self._save_gate_map_template(save_template)
+            except MmuError as ee:
+                raise gcmd.error(str(ee))
+            self._log_always("Gate map saved as template '%s' (%s)" % (save_template, path))
+            return
+
+        if template is not None:
+            try:
+                gate_template = self._load_gate_map_template(template) if template else None
+            except MmuError as ee:
+                raise gcmd.error(str(ee))
+            if gate_template is None:
+                names = self._get_gate_map_template_names()
+                self._log_always("Gate map templates in %s: %s" % (self.gate_map_template_dir, ", ".join(names) if names else "none"))
+                return
+            issues = self._check_gate_map_template(gate_template)
+            if issues and not confirm:
+                self._prompt_gate_map_template(template, issues)
+                self._log_always("Gate map template '%s' not applied:\n%s\nRun 'MMU_GATE_MAP TEMPLATE=%s CONFIRM=1' to apply anyway" % (template, "\n".join(issues), template))
+                return
+            self._apply_gate_map_template(gate_template, source=self._get_command_source(gcmd))
+            self._log_info("Applied gate map template '%s'" % template)
+            if not quiet:
+                self._log_info(self._gate_map_to_visual_string() if fmt == "visual" else self._gate_map_to_human_string())
+            return
 
         try:
             gate_map = ast.literal_eval(gmapstr)
@@ -5426,18 +10786,20 @@ class Mmu:
             self._log_debug("Exception whilst parsing gate map in MMU_GATE_MAP: %s" % str(e))
 
         if reset:
-            self._reset_gate_map()
+            self._reset_gate_map(self._get_command_source(gcmd))
 
         elif not gate_map == {}:
             for gate, fil in gate_map.items():
                 if self.gate_spool_id[gate] == fil['spool_id']:
                     self.gate_material[gate] = fil['material']
-                    self.gate_color[gate] = fil['color']
+                    color = self._validate_color(fil['color'])
+                    if color is not None:
+                        self.gate_color[gate] = color
                 else:
                     self._log_debug("Assertion failure: Spool_id changed for gate #%d in MMU_GATE_MAP. Dict=%s" % (gate, fil))
 
             self._update_gate_color(self.gate_color)
-            self._persist_gate_map() # This will also update LED status
+            self._persist_gate_map(gcmd.get('SOURCE', "spoolman sync")) # This will also update LED status
 
         elif gates != "!" or gate >= 0:
             gatelist = []
@@ -5454,26 +10816,105 @@ class Mmu:
                 # Specifying one gate (filament)
                 gatelist.append(gate)
 
+            lock_changed = False
             for gate in gatelist:
                 available = gcmd.get_int('AVAILABLE', self.gate_status[gate], minval=-1, maxval=2)
                 material = "".join(gcmd.get('MATERIAL', self.gate_material[gate]).split()).replace('#', '').upper()[:10]
-                color = "".join(gcmd.get('COLOR', self.gate_color[gate]).split()).replace('#', '').lower()
+                color = gcmd.get('COLOR', self.gate_color[gate])
                 spool_id = gcmd.get_int('SPOOLID', self.gate_spool_id[gate], minval=-1)
+                pa = gcmd.get_float('PA', self.gate_pressure_advance[gate], minval=-1)
+                smooth_time = gcmd.get_float('SMOOTH_TIME', self.gate_smooth_time[gate], minval=-1, maxval=0.2)
+                temperature = gcmd.get_int('TEMP', self.gate_temperature[gate], minval=-1)
+                unit = "".join(gcmd.get('UNIT', self.gate_unit[gate]).split())
+                lock = gcmd.get_int('LOCK', int(gate in self.gate_lock), minval=0, maxval=1)
+                owner = re.sub(r'[^A-Za-z0-9_ .-]', '', gcmd.get('OWNER', self.gate_lock.get(gate, "")))[:20]
                 color = self._validate_color(color)
                 if color is None:
-                    raise gcmd.error("Color specification must be in form 'rrggbb' hexadecimal value (no '#') or valid color name or empty string")
+                    raise gcmd.error("Color specification must be %s" % COLOR_FORMATS)
                 self.gate_material[gate] = material
                 self.gate_color[gate] = color
                 self.gate_status[gate] = available
+                if spool_id != self.gate_spool_id[gate]:
+                    self._set_spoolman_location(self.gate_spool_id[gate])
+                    self._set_spoolman_location(spool_id, gate)
                 self.gate_spool_id[gate] = spool_id
-
+                self.gate_pressure_advance[gate] = pa
+                self.gate_smooth_time[gate] = smooth_time
+                self.gate_temperature[gate] = temperature
+                self.gate_unit[gate] = unit
+                if lock and self.gate_lock.get(gate) != owner:
+                    self.gate_lock[gate] = owner
+                    lock_changed = True
+                    if gate == self.gate_selected and self.filament_pos != self.FILAMENT_POS_UNLOADED:
+                        self._log_info("Gate #%d is locked but remains loaded. Lock will prevent it being loaded again" % gate)
+                elif not lock and gate in self.gate_lock:
+                    del self.gate_lock[gate]
+                    lock_changed = True
+                if gate == self.gate_selected and self.filament_pos == self.FILAMENT_POS_LOADED:
+                    self._restore_gate_pressure_advance(gate)
+
+            if lock_changed:
+                self._persist_gate_lock()
             self._update_gate_color(self.gate_color)
-            self._persist_gate_map() # This will also update LED status
+            self._persist_gate_map(self._get_command_source(gcmd)) # This will also update LED status
         else:
             quiet = False # Display current map
 
         if not quiet:
-            self._log_info(self._gate_map_to_human_string())
+            self._log_info(self._gate_map_to_visual_string() if fmt == "visual" else self._gate_map_to_human_string())
+
+    # Update gate map from scanned spool tag
+    def _apply_rfid_tag(self, gate, tag):
+        if 'spool_id' in tag:
+            if tag['spool_id'] != self.gate_spool_id[gate]:
+                self._set_spoolman_location(self.gate_spool_id[gate])
+                self._set_spoolman_location(tag['spool_id'], gate)
+            self.gate_spool_id[gate] = tag['spool_id']
+        if 'material' in tag:
+            self.gate_material[gate] = tag['material']
+        if 'color' in tag and self._validate_color(tag['color']):
+            self.gate_color[gate] = self._validate_color(tag['color'])
+        if 'temperature' in tag:
+            self.gate_temperature[gate] = tag['temperature']
+        self._log_info("Gate #%d updated from RFID tag: %s" % (gate, ", ".join(["%s=%s" % (k, v) for k, v in tag.items()])))
+        self._update_gate_color(self.gate_color)
+        self._persist_gate_map("rfid tag")
+        if 'material' in tag or 'color' in tag:
+            self._push_filament_to_spoolman(gate)
+        elif 'spool_id' in tag:
+            self._update_filaments_from_spoolman(gate)
+
+    cmd_MMU_RFID_TAG_help = "Update gate map from a scanned RFID/NFC spool tag"
+    def cmd_MMU_RFID_TAG(self, gcmd):
+        if self._check_is_disabled(): return
+        gate = gcmd.get_int('GATE', -1, minval=-1, maxval=self.mmu_num_gates - 1)
+        tag = {}
+        spool_id = gcmd.get_int('SPOOLID', None, minval=-1)
+        if spool_id is not None:
+            tag['spool_id'] = spool_id
+        material = gcmd.get('MATERIAL', None)
+        if material:
+            tag['material'] = "".join(material.split()).replace('#', '').upper()[:10]
+        color = gcmd.get('COLOR', None)
+        if color:
+            color = self._validate_color(color)
+            if not color:
+                raise gcmd.error("Color specification must be %s" % COLOR_FORMATS)
+            tag['color'] = color
+        temperature = gcmd.get_int('TEMP', None, minval=-1)
+        if temperature is not None:
+            tag['temperature'] = temperature
+        if not tag:
+            raise gcmd.error("Tag contains no filament information")
+
+        if gate < 0:
+            # Single reader at buffer inlet. Apply to next gate insertion or currently selected gate if not printing
+            if self._has_pre_gate_sensors() or self.gate_selected < 0 or self._is_in_print():
+                self.rfid_pending_tag = (tag, self.reactor.monotonic())
+                self._log_info("RFID tag scanned. Insert filament into gate to assign")
+                return
+            gate = self.gate_selected
+        self._apply_rfid_tag(gate, tag)
 
     cmd_MMU_ENDLESS_SPOOL_help = "Diplay or Manage EndlessSpool functionality and groups"
     def cmd_MMU_ENDLESS_SPOOL(self, gcmd):
@@ -5481,7 +10922,9 @@ class Mmu:
         enabled = gcmd.get_int('ENABLE', -1, minval=0, maxval=1)
         quiet = bool(gcmd.get_int('QUIET', 0, minval=0, maxval=1))
         reset = bool(gcmd.get_int('RESET', 0, minval=0, maxval=1))
+        if reset and self._check_print_protection(gcmd): return
         groups = gcmd.get('GROUPS', "!")
+        priorities = gcmd.get('PRIORITIES', "!")
 
         if enabled >= 0:
             self.enable_endless_spool = enabled
@@ -5495,18 +10938,27 @@ class Mmu:
             self._log_debug("Resetting EndlessSpool groups")
             self.enable_endless_spool = self.default_enable_endless_spool
             self.endless_spool_groups = self.default_endless_spool_groups
-        elif groups != "!":
-            groups = gcmd.get('GROUPS', ",".join(map(str, self.endless_spool_groups))).split(",")
-            if len(groups) != self.mmu_num_gates:
-                self._log_always("The number of group values (%d) is not the same as number of gates (%d)" % (len(groups), self.mmu_num_gates))
-                return
-            self.endless_spool_groups = []
-            for group in groups:
-                if group.isdigit():
-                    self.endless_spool_groups.append(int(group))
-                else:
-                    self.endless_spool_groups.append(0)
-            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_ENDLESS_SPOOL_GROUPS, self.endless_spool_groups))
+            self.endless_spool_priorities = list(self.default_endless_spool_priorities)
+        elif groups != "!" or priorities != "!":
+            if groups != "!":
+                groups = groups.split(",")
+                if len(groups) != self.mmu_num_gates:
+                    self._log_always("The number of group values (%d) is not the same as number of gates (%d)" % (len(groups), self.mmu_num_gates))
+                    return
+                self.endless_spool_groups = []
+                for group in groups:
+                    if group.isdigit():
+                        self.endless_spool_groups.append(int(group))
+                    else:
+                        self.endless_spool_groups.append(0)
+                self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_ENDLESS_SPOOL_GROUPS, self.endless_spool_groups))
+            if priorities != "!":
+                priorities = priorities.split(",")
+                if len(priorities) != self.mmu_num_gates:
+                    self._log_always("The number of priority values (%d) is not the same as number of gates (%d)" % (len(priorities), self.mmu_num_gates))
+                    return
+                self.endless_spool_priorities = [int(p) if p.isdigit() else 0 for p in priorities]
+                self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_ENDLESS_SPOOL_PRIORITIES, self.endless_spool_priorities))
         else:
             quiet = False # Display current map
         if not quiet:
@@ -5549,6 +11001,103 @@ class Mmu:
         msg += "|\n"
         self._log_always(msg)
 
+    cmd_MMU_MAINTENANCE_help = "Take a gate out of service for cleaning or clearing a jam (or return it with ENABLE=1)"
+    def cmd_MMU_MAINTENANCE(self, gcmd):
+        if self._check_is_disabled(): return
+        gate = gcmd.get_int('GATE', -1, minval=0, maxval=self.mmu_num_gates - 1)
+        enable = gcmd.get_int('ENABLE', 0, minval=0, maxval=1)
+        if gate < 0:
+            if self.gate_maintenance:
+                self._log_always("Gates in maintenance: %s" % ", ".join("#%d" % g for g in sorted(self.gate_maintenance)))
+            else:
+                self._log_always("No gates in maintenance")
+            return
+
+        if enable:
+            if gate not in self.gate_maintenance:
+                self._log_always("Gate #%d is not in maintenance" % gate)
+                return
+            self.gate_maintenance.remove(gate)
+            self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_MAINTENANCE, self.gate_maintenance))
+            self._set_gate_status(gate, self.GATE_UNKNOWN, source=self._get_command_source(gcmd))
+            if self.filament_pos == self.FILAMENT_POS_UNLOADED and self.is_homed and not self._check_is_calibrated(silent=True):
+                self._log_always("Gate #%d returned to service. Verifying filament..." % gate)
+                self.gcode.run_script_from_command("MMU_CHECK_GATE GATE=%d" % gate)
+            else:
+                self._log_always("Gate #%d returned to service. Verify with 'MMU_CHECK_GATE GATE=%d' when no filament is loaded or mark available with 'MMU_GATE_MAP GATE=%d AVAILABLE=1'" % (gate, gate, gate))
+            return
+
+        if self._check_is_calibrated(): return
+        if self._check_in_bypass(): return
+        if gate in self.gate_maintenance:
+            self._log_always("Gate #%d is already in maintenance" % gate)
+            return
+        if gate == self.gate_selected and self.filament_pos != self.FILAMENT_POS_UNLOADED and self._is_printing():
+            raise gcmd.error("Gate #%d is in use by the print. Pause the print before servicing it" % gate)
+        other_loaded = gate != self.gate_selected and self.filament_pos != self.FILAMENT_POS_UNLOADED
+        if other_loaded and self.vendor_driver.HAS_SELECTOR:
+            raise gcmd.error("Filament from gate #%d is loaded and the selector would have to cross it. Unload before servicing gate #%d" % (self.gate_selected, gate))
+        eject_length = gcmd.get_float('LENGTH', max(self.endless_spool_final_eject, 50.), minval=0.)
+
+        with self._wrap_disable_encoder():
+            previous_gate = self.gate_selected
+            previous_sync = self.mmu_toolhead.is_gear_synced_to_extruder()
+            try:
+                if gate == self.gate_selected and self.filament_pos != self.FILAMENT_POS_UNLOADED:
+                    self._log_info("Unloading filament from gate #%d" % gate)
+                    self._unload_tool()
+
+                # Eject filament completely clear of gate so it can be removed
+                if self.gate_status[gate] != self.GATE_EMPTY and eject_length > 0.:
+                    self._select_gate(gate)
+                    self._servo_down()
+                    self._log_info("Ejecting filament from gate #%d..." % gate)
+                    self._trace_filament_move("Maintenance eject", -eject_length)
+                    self._servo_up()
+
+                # Park selector away from gate so it can be serviced
+                if self.gate_selected == gate:
+                    if self.tool_selected >= 0 and self.tool_to_gate_map[self.tool_selected] == gate:
+                        self._set_tool_selected(self.TOOL_GATE_UNKNOWN)
+                    available = [g for g in range(self.mmu_num_gates) if g != gate and g not in self.gate_maintenance]
+                    park_gate = previous_gate if previous_gate in available else (min(available, key=lambda g: abs(g - gate)) if available else -1)
+                    if park_gate >= 0:
+                        self._select_gate(park_gate)
+                self._disable_gate_gear_stepper(gate)
+            except MmuError as ee:
+                self._mmu_pause("Failed to prepare gate #%d for maintenance: %s" % (gate, str(ee)), code=ee.code)
+                return
+            finally:
+                # Gear of the still loaded gate must be left driving it as before (sync, servo and current)
+                if other_loaded:
+                    try:
+                        if self.gate_selected != previous_gate:
+                            self._select_gate(previous_gate)
+                        self._sync_gear_to_extruder(previous_sync, servo=True, current=previous_sync)
+                    except MmuError as ee:
+                        self._mmu_pause("Failed to restore gate #%d after maintenance eject: %s" % (previous_gate, str(ee)), code=ee.code)
+
+        self.gate_maintenance.append(gate)
+        self.gcode.run_script_from_command("SAVE_VARIABLE VARIABLE=%s VALUE='%s'" % (self.VARS_MMU_GATE_MAINTENANCE, self.gate_maintenance))
+        self._set_gate_status(gate, self.GATE_EMPTY, source=self._get_command_source(gcmd))
+        self._log_always("Gate #%d is now in maintenance and will not be used (including by EndlessSpool). When serviced run 'MMU_MAINTENANCE GATE=%d ENABLE=1'" % (gate, gate))
+
+    # Disable the gear driver of a gate on designs with a gear stepper per gate. Klipper re-enables the driver
+    # on next movement but the gate is excluded from use whilst in maintenance
+    def _disable_gate_gear_stepper(self, gate):
+        gear_mux = self.mmu_toolhead.gear_mux
+        if gear_mux:
+            if gear_mux.selected_gate == gate:
+                self.mmu_toolhead.select_gear_stepper(-1)
+            return
+        stepper_enable = self.printer.lookup_object('stepper_enable')
+        steppers = stepper_enable.get_steppers()
+        if "stepper_mmu_gear_1" not in steppers:
+            return # Single shared gear stepper
+        name = self.mmu_toolhead.get_gear_stepper_name(gate)
+        if name in steppers:
+            stepper_enable.lookup_enable(name).motor_disable(self.mmu_toolhead.get_last_move_time())
+
     cmd_MMU_CHECK_GATE_help = "Automatically inspects gate(s), parks filament and marks availability"
     def cmd_MMU_CHECK_GATE(self, gcmd):
         if self._check_is_disabled(): return
@@ -5616,7 +11165,7 @@ class Mmu:
                         self._log_info("Unloading current tool prior to checking gates")
                         self._unload_tool()
                 except MmuError as ee:
-                    self._mmu_pause(str(ee))
+                    self._mmu_pause(str(ee), code=ee.code)
                     return
     
                 for gate, tool in gates_tools:
@@ -5630,18 +11179,18 @@ class Mmu:
                             self._log_info("Tool T%d - Filament detected. Gate #%d marked available" % (tool, gate))
                         else:
                             self._log_info("Gate #%d - Filament detected. Marked available" % gate)
-                        self._set_gate_status(gate, max(self.gate_status[gate], self.GATE_AVAILABLE))
+                        self._set_gate_status(gate, max(self.gate_status[gate], self.GATE_AVAILABLE), source=self._get_command_source(gcmd))
                         try:
                             self._unload_gate()
                         except MmuError as ee:
                             msg = "Failure during check gate #%d %s: %s" % (gate, "(T%d)" % tool if tool >= 0 else "", str(ee))
                             if self._is_in_print():
-                                self._mmu_pause(msg)
+                                self._mmu_pause(msg, code=ee.code)
                             else:
                                 self._log_always(msg)
                             return
                     except MmuError as ee:
-                        self._set_gate_status(gate, self.GATE_EMPTY)
+                        self._set_gate_status(gate, self.GATE_EMPTY, source=self._get_command_source(gcmd))
                         self._set_filament_pos_state(self.FILAMENT_POS_UNLOADED, silent=True)
                         if tool >= 0:
                             msg = "Tool T%d on gate #%d marked EMPTY" % (tool, gate)
@@ -5649,7 +11198,7 @@ class Mmu:
                             msg = "Gate #%d marked EMPTY" % gate
                         if self._is_in_print():
                             # Use case of in-print verification of all tools used in print
-                            self._mmu_pause(msg)
+                            self._mmu_pause(msg, code=self.ERR_GATE_EMPTY)
                             return
                         else:
                             self._log_info(msg)
@@ -5674,6 +11223,85 @@ class Mmu:
             finally:
                 self._servo_auto()
 
+    # Start: Filament must be unloaded
+    # End: Filament unloaded with original tool selected
+    cmd_MMU_PROFILE_GATES_help = "Profile friction of gate bowden paths with standardized moves and record health score"
+    def cmd_MMU_PROFILE_GATES(self, gcmd):
+        if self._check_is_disabled(): return
+        if self._check_print_protection(gcmd): return
+        if self._check_has_encoder(): return
+        if self._check_not_homed(): return
+        if self._check_in_bypass(): return
+        if self._check_is_loaded(): return
+        if self._check_is_calibrated(): return
+
+        reset = gcmd.get_int('RESET', 0, minval=0, maxval=1)
+        length = gcmd.get_float('LENGTH', 100., above=10.)
+        try:
+            gates = [int(g) for g in gcmd.get('GATES', "").split(',') if g.strip() != ""]
+            speeds = sorted(set(float(v) for v in gcmd.get('SPEEDS', "50,100,150").split(',') if v.strip() != ""))
+        except ValueError:
+            raise gcmd.error("Invalid GATES or SPEEDS parameter")
+        if any(g < 0 or g >= self.mmu_num_gates for g in gates):
+            raise gcmd.error("GATES must be in range 0 to %d" % (self.mmu_num_gates - 1))
+        if not speeds or speeds[0] <= 0:
+            raise gcmd.error("SPEEDS must be a list of positive speeds")
+        if 2 * length > self.calibrated_bowden_length:
+            raise gcmd.error("LENGTH must be less than half of calibrated bowden length (%.1fmm)" % self.calibrated_bowden_length)
+
+        if reset:
+            for gate in gates or range(self.mmu_num_gates):
+                self._reset_gate_health(gate)
+            self._log_always("Friction health history reset")
+            return
+        if not gates:
+            gates = [g for g in range(self.mmu_num_gates) if self.gate_status[g] != self.GATE_EMPTY and g not in self.gate_maintenance]
+
+        tool_selected = self.tool_selected
+        results = []
+        with self._wrap_action(self.ACTION_CHECKING):
+            try:
+                with self._require_encoder():
+                    for gate in gates:
+                        slips = self._profile_gate_friction(gate, speeds, length)
+                        score = self._gate_health_score(slips)
+                        results.append((gate, slips, score))
+                        self._track_gate_health(gate, score)
+                if tool_selected >= 0:
+                    self._select_tool(tool_selected)
+            except MmuError as ee:
+                self._mmu_pause("Gate friction profiling failed: %s" % str(ee), code=ee.code)
+                return
+            finally:
+                self._servo_auto()
+
+        msg = "Gate friction profile (slip at %s mm/s):" % ", ".join("%d" % v for v in speeds)
+        for gate, slips, score in results:
+            msg += "\nGate #%d: %s, score: %.1f (baseline: %.1f)" % (gate, ", ".join("%.1f%%" % slips[v] for v in speeds), score, self.gate_health_baseline[gate])
+        self._log_always(msg)
+
+    # Run standardized moves over 'length' of bowden in each direction at each speed and return the percentage of
+    # commanded movement not seen by the encoder. Friction in the path causes the gear to slip more, especially at speed.
+    # Filament is first advanced 'length' into the bowden so that it is always through the encoder while measuring
+    def _profile_gate_friction(self, gate, speeds, length):
+        self._log_info("Profiling gate #%d friction..." % gate)
+        self._select_gate(gate)
+        self._initialize_filament_position(dwell=True)
+        try:
+            self.calibrating = True # To suppress visual filament position
+            self._load_gate(allow_retry=False, adjust_servo_on_error=False)
+            self._trace_filament_move("Advancing into bowden for profiling", length)
+            slips = {}
+            for speed in speeds:
+                _,_,measured,_ = self._trace_filament_move("Profiling forward move at %dmm/s" % speed, length, speed=speed, encoder_dwell=True)
+                _,_,measured_back,_ = self._trace_filament_move("Profiling reverse move at %dmm/s" % speed, -length, speed=speed, encoder_dwell=True)
+                slips[speed] = round(max((1. - (measured + measured_back) / (2. * length)) * 100., 0.), 2)
+            self._trace_filament_move("Retracting after profiling", -length)
+            self._unload_gate()
+        finally:
+            self.calibrating = False
+        return slips
+
     cmd_MMU_PRELOAD_help = "Preloads filament at specified or current gate"
     def cmd_MMU_PRELOAD(self, gcmd):
         if self._check_is_disabled(): return
@@ -5704,7 +11332,7 @@ class Mmu:
                     except MmuError as ee:
                         # Exception just means filament is not loaded yet, so continue
                         self._log_trace("Exception on encoder load move: %s" % str(ee))
-                self._set_gate_status(gate, self.GATE_EMPTY)
+                self._set_gate_status(gate, self.GATE_EMPTY, source="autoload (preload)")
                 self._log_always("Filament not detected in gate #%d" % gate)
             except MmuError as ee:
                 self._log_always("Filament preload for gate #%d failed: %s" % (gate, str(ee)))
@@ -0,0 +1,124 @@
+# Happy Hare MMU Software
+# Stable Python API for other Klipper extras (custom panels, conditional gcode engines, etc)
+#
+# Available as printer.lookup_object('mmu').api once Klipper has loaded the [mmu] section. Unlike the internals
+# of mmu.py, the methods below are versioned and will not change incompatibly without incrementing API_VERSION:
+#
+#   get_version() / is_enabled() / is_homed() / is_printing()
+#   get_gate_map()               - list of dicts (one per gate) with status, material, color, spool_id, temperature, unit
+#   get_tool_to_gate_map()       - list of gates, one per tool
+#   get_tool() / get_gate()      - currently selected tool and gate (-1 unknown, -2 bypass)
+#   get_filament_position()      - dict of 'pos' (FILAMENT_POS_* state), 'filament' (Loaded/Unloaded/Unknown) and
+#                                  'position' (mm moved along filament path by gear)
+#   get_status()                 - same dict as 'printer.mmu'
+#   change_tool(tool), load(), unload(), select_gate(gate), set_gate_status(gate, status)
+#                                - drive the MMU using the same code path (and error handling) as the gcode commands.
+#                                  Must be called outside of a gcode command (e.g. reactor callback or webhook)
+#   register_listener(event_cb) / unregister_listener(event_cb)
+#                                - event_cb(event, **kwargs) receives every event Happy Hare publishes (plugins
+#                                  receive events through this same mechanism)
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import logging
+
+MMU_API_VERSION = 1 # Increment when the facade changes in an incompatible way
+
+class MmuApi:
+    API_VERSION = MMU_API_VERSION
+
+    def __init__(self, mmu):
+        self.mmu = mmu
+        self.listeners = []
+
+    # Query functions
+
+    def get_version(self):
+        return self.mmu.VERSION
+
+    def is_enabled(self):
+        return self.mmu.is_enabled
+
+    def is_homed(self):
+        return self.mmu.is_homed
+
+    def is_printing(self):
+        return self.mmu._is_printing()
+
+    def get_num_gates(self):
+        return self.mmu.mmu_num_gates
+
+    def get_gate_map(self):
+        mmu = self.mmu
+        return [{
+            'gate': gate,
+            'status': mmu.gate_status[gate],
+            'material': mmu.gate_material[gate],
+            'color': mmu.gate_color[gate],
+            'spool_id': mmu.gate_spool_id[gate],
+            'temperature': mmu.gate_temperature[gate],
+            'unit': mmu.gate_unit[gate],
+        } for gate in range(mmu.mmu_num_gates)]
+
+    def get_tool_to_gate_map(self):
+        return list(self.mmu.tool_to_gate_map)
+
+    def get_tool(self):
+        return self.mmu.tool_selected
+
+    def get_gate(self):
+        return self.mmu.gate_selected
+
+    def get_filament_position(self):
+        status = self.get_status()
+        return {'pos': status['filament_pos'], 'filament': status['filament'], 'position': status['filament_position']}
+
+    def get_status(self):
+        return self.mmu.get_status(self.mmu.reactor.monotonic())
+
+    # Control functions. These run the equivalent gcode command so all checks, pausing on error and
+    # state persistence behave exactly as if the user had typed it. Raises gcode error on bad parameters
+
+    def change_tool(self, tool):
+        self._run("MMU_CHANGE_TOOL TOOL=%d" % tool)
+
+    def load(self):
+        self._run("MMU_LOAD")
+
+    def unload(self):
+        self._run("MMU_UNLOAD")
+
+    def select_gate(self, gate):
+        self._run("MMU_SELECT GATE=%d" % gate)
+
+    def set_gate_status(self, gate, status):
+        self._run("MMU_GATE_MAP GATE=%d AVAILABLE=%d QUIET=1" % (gate, status))
+
+    # Events
+
+    def register_listener(self, event_cb):
+        if event_cb not in self.listeners:
+            self.listeners.append(event_cb)
+
+    def unregister_listener(self, event_cb):
+        if event_cb in self.listeners:
+            self.listeners.remove(event_cb)
+
+    def notify_event(self, event, **kwargs):
+        for event_cb in list(self.listeners):
+            try:
+                event_cb(event, **kwargs)
+            except Exception as e:
+                logging.warning("mmu_api: Listener failed handling '%s' event: %s" % (event, str(e)))
+
+    # Always acquires the gcode mutex so must not be called from within a gcode command handler (which
+    # should simply run the equivalent gcode command itself)
+    def _run(self, script):
+        self.mmu.gcode.run_script(script)
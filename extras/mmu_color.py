@@ -0,0 +1,189 @@
+# Happy Hare MMU Software
+# Filament color parsing, normalization and comparison
+#
+# Gate colors arrive in many forms: W3C names from the user, 'rrggbb' from Spoolman, '#RRGGBBAA' from slicers
+# and RFID tags, or 'r,g,b' from LED configuration. Everything is normalized to either a lowercase W3C color
+# name or lowercase 'rrggbb' hex string (the form persisted in the gate map) and compared perceptually in CIE
+# L*a*b* space using the CIEDE2000 color difference (deltaE). As a guide:
+#
+#   deltaE < 1    indistinguishable
+#   deltaE 1-5    noticeable only side-by-side (e.g. two batches of "red")
+#   deltaE > 10   clearly different colors
+#   deltaE 100    black vs white
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import math, re
+
+W3C_COLORS = [('aliceblue','#F0F8FF'), ('antiquewhite','#FAEBD7'), ('aqua','#00FFFF'), ('aquamarine','#7FFFD4'), ('azure','#F0FFFF'), ('beige','#F5F5DC'),
+              ('bisque','#FFE4C4'), ('black','#000000'), ('blanchedalmond','#FFEBCD'), ('blue','#0000FF'), ('blueviolet','#8A2BE2'), ('brown','#A52A2A'),
+              ('burlywood','#DEB887'), ('cadetblue','#5F9EA0'), ('chartreuse','#7FFF00'), ('chocolate','#D2691E'), ('coral','#FF7F50'),
+              ('cornflowerblue','#6495ED'), ('cornsilk','#FFF8DC'), ('crimson','#DC143C'), ('cyan','#00FFFF'), ('darkblue','#00008B'), ('darkcyan','#008B8B'),
+              ('darkgoldenrod','#B8860B'), ('darkgray','#A9A9A9'), ('darkgreen','#006400'), ('darkgrey','#A9A9A9'), ('darkkhaki','#BDB76B'),
+              ('darkmagenta','#8B008B'), ('darkolivegreen','#556B2F'), ('darkorange','#FF8C00'), ('darkorchid','#9932CC'), ('darkred','#8B0000'),
+              ('darksalmon','#E9967A'), ('darkseagreen','#8FBC8F'), ('darkslateblue','#483D8B'), ('darkslategray','#2F4F4F'), ('darkslategrey','#2F4F4F'),
+              ('darkturquoise','#00CED1'), ('darkviolet','#9400D3'), ('deeppink','#FF1493'), ('deepskyblue','#00BFFF'), ('dimgray','#696969'),
+              ('dimgrey','#696969'), ('dodgerblue','#1E90FF'), ('firebrick','#B22222'), ('floralwhite','#FFFAF0'), ('forestgreen','#228B22'),
+              ('fuchsia','#FF00FF'), ('gainsboro','#DCDCDC'), ('ghostwhite','#F8F8FF'), ('gold','#FFD700'), ('goldenrod','#DAA520'), ('gray','#808080'),
+              ('green','#008000'), ('greenyellow','#ADFF2F'), ('grey','#808080'), ('honeydew','#F0FFF0'), ('hotpink','#FF69B4'), ('indianred','#CD5C5C'),
+              ('indigo','#4B0082'), ('ivory','#FFFFF0'), ('khaki','#F0E68C'), ('lavender','#E6E6FA'), ('lavenderblush','#FFF0F5'), ('lawngreen','#7CFC00'),
+              ('lemonchiffon','#FFFACD'), ('lightblue','#ADD8E6'), ('lightcoral','#F08080'), ('lightcyan','#E0FFFF'), ('lightgoldenrodyellow','#FAFAD2'),
+              ('lightgray','#D3D3D3'), ('lightgreen','#90EE90'), ('lightgrey','#D3D3D3'), ('lightpink','#FFB6C1'), ('lightsalmon','#FFA07A'),
+              ('lightseagreen','#20B2AA'), ('lightskyblue','#87CEFA'), ('lightslategray','#778899'), ('lightslategrey','#778899'),
+              ('lightsteelblue','#B0C4DE'), ('lightyellow','#FFFFE0'), ('lime','#00FF00'), ('limegreen','#32CD32'), ('linen','#FAF0E6'),
+              ('magenta','#FF00FF'), ('maroon','#800000'), ('mediumaquamarine','#66CDAA'), ('mediumblue','#0000CD'), ('mediumorchid','#BA55D3'),
+              ('mediumpurple','#9370DB'), ('mediumseagreen','#3CB371'), ('mediumslateblue','#7B68EE'), ('mediumspringgreen','#00FA9A'),
+              ('mediumturquoise','#48D1CC'), ('mediumvioletred','#C71585'), ('midnightblue','#191970'), ('mintcream','#F5FFFA'), ('mistyrose','#FFE4E1'),
+              ('moccasin','#FFE4B5'), ('navajowhite','#FFDEAD'), ('navy','#000080'), ('oldlace','#FDF5E6'), ('olive','#808000'),
+              ('olivedrab','#6B8E23'), ('orange','#FFA500'), ('orangered','#FF4500'), ('orchid','#DA70D6'), ('palegoldenrod','#EEE8AA'),
+              ('palegreen','#98FB98'), ('paleturquoise','#AFEEEE'), ('palevioletred','#DB7093'), ('papayawhip','#FFEFD5'), ('peachpuff','#FFDAB9'),
+              ('peru','#CD853F'), ('pink','#FFC0CB'), ('plum','#DDA0DD'), ('powderblue','#B0E0E6'), ('purple','#800080'), ('red','#FF0000'),
+              ('rosybrown','#BC8F8F'), ('royalblue','#4169E1'), ('saddlebrown','#8B4513'), ('salmon','#FA8072'), ('sandybrown','#F4A460'),
+              ('seagreen','#2E8B57'), ('seashell','#FFF5EE'), ('sienna','#A0522D'), ('silver','#C0C0C0'), ('skyblue','#87CEEB'), ('slateblue','#6A5ACD'),
+              ('slategray','#708090'), ('slategrey','#708090'), ('snow','#FFFAFA'), ('springgreen','#00FF7F'), ('steelblue','#4682B4'),
+              ('tan','#D2B48C'), ('teal','#008080'), ('thistle','#D8BFD8'), ('tomato','#FF6347'), ('turquoise','#40E0D0'), ('violet','#EE82EE'),
+              ('wheat','#F5DEB3'), ('white','#FFFFFF'), ('whitesmoke','#F5F5F5'), ('yellow','#FFFF00'), ('yellowgreen','#9ACD32')]
+
+W3C_COLOR_MAP = dict(W3C_COLORS)
+
+COLOR_FORMATS = "W3C color name, 'rrggbb', '#rrggbb', 'rgb', 'rrggbbaa' (alpha ignored), 'r,g,b' (0-255) or empty string"
+
+# Return normalized color (W3C name or 'rrggbb') or None if not recognized. Empty string means "no color"
+def normalize_color(color):
+    if color is None:
+        return None
+    color = "".join(str(color).split()).lower()
+    if color == "":
+        return ""
+
+    # Try w3c named color
+    if color in W3C_COLOR_MAP:
+        return color
+
+    # Try 'r,g,b' or 'rgb(r,g,b)' decimal form
+    x = re.search(r"^(?:rgba?\()?(\d{1,3}),(\d{1,3}),(\d{1,3})(?:,[\d.]+)?\)?$", color)
+    if x is not None:
+        rgb = [int(v) for v in x.groups()]
+        if max(rgb) > 255:
+            return None
+        return "%02x%02x%02x" % tuple(rgb)
+
+    # Try hex color with optional '#' or '0x' prefix. Alpha channel (slicer and RFID tags) is discarded
+    color = color.lstrip('#')
+    if color.startswith('0x'):
+        color = color[2:]
+    if re.search(r"^[0-9a-f]+$", color) is not None:
+        if len(color) in (3, 4):
+            return "".join(c * 2 for c in color[:3])
+        if len(color) in (6, 8):
+            return color[:6]
+    return None
+
+# Return (r, g, b) tuple of 0-255 ints. Unrecognized or empty color is black
+def color_to_rgb255(color):
+    color = normalize_color(color)
+    if not color:
+        return (0, 0, 0)
+    if color in W3C_COLOR_MAP:
+        color = W3C_COLOR_MAP[color].lstrip('#').lower()
+    return tuple(int(color[i:i + 2], 16) for i in (0, 2, 4))
+
+# Convenient RGB spec for controlling LEDs in form (0.32, 0.56, 1.00)
+def color_to_rgb(color):
+    return tuple(round(v / 255., 2) for v in color_to_rgb255(color))
+
+# Return 'rrggbb' hex form of any supported color
+def color_to_hex(color):
+    return "%02x%02x%02x" % color_to_rgb255(color)
+
+# Return exact W3C name for color if one exists else the normalized color
+def color_to_name(color):
+    color = normalize_color(color)
+    if not color or color in W3C_COLOR_MAP:
+        return color
+    for name, hex_rgb in W3C_COLORS:
+        if hex_rgb.lstrip('#').lower() == color:
+            return name
+    return color
+
+# Closest W3C color name (useful for console and logging) and its deltaE
+def closest_color_name(color):
+    lab = rgb_to_lab(color_to_rgb255(color))
+    return min(((name, _ciede2000(lab, rgb_to_lab(color_to_rgb255(name)))) for name, _ in W3C_COLORS), key=lambda x: x[1])
+
+# sRGB (0-255) to CIE L*a*b* (D65 illuminant)
+def rgb_to_lab(rgb):
+    def linear(c):
+        c = c / 255.
+        return c / 12.92 if c <= 0.04045 else ((c + 0.055) / 1.055) ** 2.4
+    r, g, b = (linear(c) for c in rgb)
+    x = (r * 0.4124564 + g * 0.3575761 + b * 0.1804375) / 0.95047
+    y = (r * 0.2126729 + g * 0.7151522 + b * 0.0721750)
+    z = (r * 0.0193339 + g * 0.1191920 + b * 0.9503041) / 1.08883
+    def f(t):
+        return t ** (1. / 3.) if t > 0.008856 else 7.787 * t + 16. / 116.
+    fx, fy, fz = f(x), f(y), f(z)
+    return (116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz))
+
+# Perceptual difference between two colors in any supported format (CIEDE2000)
+def delta_e(color1, color2):
+    return _ciede2000(rgb_to_lab(color_to_rgb255(color1)), rgb_to_lab(color_to_rgb255(color2)))
+
+# True if both colors are set and perceptually the same within 'tolerance' deltaE
+def colors_match(color1, color2, tolerance=1.):
+    color1, color2 = normalize_color(color1), normalize_color(color2)
+    if not color1 or not color2:
+        return False
+    return color1 == color2 or delta_e(color1, color2) <= tolerance
+
+def _ciede2000(lab1, lab2):
+    l1, a1, b1 = lab1
+    l2, a2, b2 = lab2
+    c1, c2 = math.hypot(a1, b1), math.hypot(a2, b2)
+    c_avg7 = ((c1 + c2) / 2.) ** 7
+    g = 0.5 * (1 - math.sqrt(c_avg7 / (c_avg7 + 25. ** 7)))
+    a1p, a2p = a1 * (1 + g), a2 * (1 + g)
+    c1p, c2p = math.hypot(a1p, b1), math.hypot(a2p, b2)
+    h1p = math.degrees(math.atan2(b1, a1p)) % 360. if c1p else 0.
+    h2p = math.degrees(math.atan2(b2, a2p)) % 360. if c2p else 0.
+
+    dlp = l2 - l1
+    dcp = c2p - c1p
+    if c1p * c2p == 0:
+        dhp = 0.
+    elif abs(h2p - h1p) <= 180:
+        dhp = h2p - h1p
+    elif h2p - h1p > 180:
+        dhp = h2p - h1p - 360
+    else:
+        dhp = h2p - h1p + 360
+    dhp_big = 2 * math.sqrt(c1p * c2p) * math.sin(math.radians(dhp / 2.))
+
+    lp_avg = (l1 + l2) / 2.
+    cp_avg = (c1p + c2p) / 2.
+    if c1p * c2p == 0:
+        hp_avg = h1p + h2p
+    elif abs(h1p - h2p) <= 180:
+        hp_avg = (h1p + h2p) / 2.
+    elif h1p + h2p < 360:
+        hp_avg = (h1p + h2p + 360) / 2.
+    else:
+        hp_avg = (h1p + h2p - 360) / 2.
+
+    t = (1 - 0.17 * math.cos(math.radians(hp_avg - 30)) + 0.24 * math.cos(math.radians(2 * hp_avg))
+         + 0.32 * math.cos(math.radians(3 * hp_avg + 6)) - 0.20 * math.cos(math.radians(4 * hp_avg - 63)))
+    d_theta = 30 * math.exp(-(((hp_avg - 275) / 25.) ** 2))
+    cp_avg7 = cp_avg ** 7
+    rc = 2 * math.sqrt(cp_avg7 / (cp_avg7 + 25. ** 7))
+    sl = 1 + (0.015 * (lp_avg - 50) ** 2) / math.sqrt(20 + (lp_avg - 50) ** 2)
+    sc = 1 + 0.045 * cp_avg
+    sh = 1 + 0.015 * cp_avg * t
+    rt = -math.sin(math.radians(2 * d_theta)) * rc
+    return math.sqrt((dlp / sl) ** 2 + (dcp / sc) ** 2 + (dhp_big / sh) ** 2 + rt * (dcp / sc) * (dhp_big / sh))
@@ -0,0 +1,50 @@
+# Happy Hare MMU Software
+# Per-gate gear driver overrides for multi-motor designs where gates use different motors
+#
+# Each '[mmu_gate_driver N]' section declares the settings for gate N that differ from the gear stepper that drives it
+# (the dedicated 'stepper_mmu_gear_N', the mmu_gear_mux driver routed to the gate or the single 'stepper_mmu_gear').
+# When the gate is selected Happy Hare applies the current with SET_TMC_CURRENT, changes the TMC microstep resolution
+# (if different) and sets the effective rotation distance so that moves are correct for the declared motor:
+#
+#   effective_rotation_distance = rotation_distance * stepper_steps_per_rotation / gate_steps_per_rotation
+#
+# where gate_steps_per_rotation is the stepper's value scaled by declared microsteps and full_steps_per_rotation. The
+# per-gate calibration ratio from MMU_CALIBRATE_GATES is still applied on top. Unspecified options use the stepper's
+# own configuration
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+
+class MmuGateDriver:
+    def __init__(self, config):
+        try:
+            self.gate = int(config.get_name().split()[-1])
+        except ValueError:
+            raise config.error("Section name must be in form '[mmu_gate_driver <gate>]'")
+        self.run_current = config.getfloat('run_current', None, above=0.)
+        self.hold_current = config.getfloat('hold_current', None, above=0.)
+        self.rotation_distance = config.getfloat('rotation_distance', None, above=0.)
+        self.microsteps = config.getint('microsteps', None, minval=1, maxval=256)
+        if self.microsteps is not None and self.microsteps & (self.microsteps - 1):
+            raise config.error("microsteps must be a power of two in [%s]" % config.get_name())
+        self.full_steps_per_rotation = config.getint('full_steps_per_rotation', None, minval=1)
+
+    def get_status(self, eventtime):
+        return {
+            'gate': self.gate,
+            'run_current': self.run_current,
+            'hold_current': self.hold_current,
+            'rotation_distance': self.rotation_distance,
+            'microsteps': self.microsteps,
+            'full_steps_per_rotation': self.full_steps_per_rotation,
+        }
+
+def load_config_prefix(config):
+    return MmuGateDriver(config)
@@ -0,0 +1,68 @@
+# Happy Hare MMU Software
+# Gear stepper multiplexer for multi-motor (type-B) designs that share stepper drivers between gates
+#
+# Each physical driver is one of the gear steppers ('stepper_mmu_gear', 'stepper_mmu_gear_1', ..) and its motor
+# outputs are switched between gates by a mux board controlled with binary encoded select pins. With two select
+# pins each driver can be routed to four gates, so three drivers can serve twelve gates:
+#
+#   gate  = driver_index * channels_per_driver + channel
+#
+# Before a new gate is selected all gear motion is completed and every gear driver disabled so that the mux
+# never switches under a powered motor. Only the driver routed to the selected gate is then placed on the gear
+# rail so no other gate's motor can move
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import logging
+
+class MmuGearMux:
+    def __init__(self, config):
+        self.printer = config.get_printer()
+        ppins = self.printer.lookup_object('pins')
+        self.select_pins = []
+        for pin in config.getlist('select_pins'):
+            mcu_pin = ppins.setup_pin('digital_out', pin)
+            mcu_pin.setup_max_duration(0.)
+            mcu_pin.setup_start_value(0, 0)
+            self.select_pins.append(mcu_pin)
+        self.channels_per_driver = config.getint('channels_per_driver', 2 ** len(self.select_pins), minval=1, maxval=2 ** len(self.select_pins))
+        self.drivers = list(config.getlist('drivers', ['stepper_mmu_gear']))
+        self.settle_time = config.getfloat('settle_time', 0.05, minval=0.)
+        self.selected_gate = -1
+        self.selected_channel = None
+
+    def get_num_gates(self):
+        return len(self.drivers) * self.channels_per_driver
+
+    def get_route(self, gate):
+        if gate < 0 or gate >= self.get_num_gates():
+            raise self.printer.command_error("Gate %d is not routed by mmu_gear_mux (%d drivers x %d channels)" % (gate, len(self.drivers), self.channels_per_driver))
+        return self.drivers[gate // self.channels_per_driver], gate % self.channels_per_driver
+
+    def get_driver_names(self):
+        return list(self.drivers)
+
+    # Caller is responsible for the interlock (motion complete and drivers disabled)
+    def set_channel(self, print_time, gate, channel):
+        if channel != self.selected_channel:
+            for i, mcu_pin in enumerate(self.select_pins):
+                mcu_pin.set_digital(print_time, (channel >> i) & 1)
+            self.selected_channel = channel
+            logging.info("mmu_gear_mux: Switched to channel %d for gate %d" % (channel, gate))
+        self.selected_gate = gate
+
+    def clear_selection(self):
+        self.selected_gate = -1
+
+    def get_status(self, eventtime):
+        return {'gate': self.selected_gate, 'channel': self.selected_channel if self.selected_channel is not None else -1}
+
+def load_config(config):
+    return MmuGearMux(config)
@@ -0,0 +1,135 @@
+# Happy Hare MMU Software
+# Command journal for post-mortem replay of failed toolchanges
+#
+# When enabled every toolchange is journaled: the starting MMU state, every filament and selector move the MMU
+# issues (with result), every sensor read and every state transition or error, each with precise klipper
+# monotonic time 't'. Only the current toolchange is kept in memory. If it fails the journal is written to
+# 'mmu_journal/<time>_T<tool>.jsonl' alongside klippy.log (one JSON object per line, the most recent
+# 'journal_max_files' are kept) so it can be replayed offline against the mock environment with:
+#
+#   python3 -m test.extras.replay_journal <file>
+#
+# Replay feeds the recorded move results and sensor reads back to the state machine in order so the same decisions
+# are taken and reports where (if anywhere) the code now behaves differently. Record kinds:
+#
+#   start      - toolchange start: tool, in_print, skip_tip and starting state
+#   move       - filament move: trace, dist, motor, homing_move, endstop and result [actual, homed, measured, delta]
+#   selector   - selector move: target, homing_move, endstop and result [position, homed]
+#   sensor     - sensor read: name and result (None if sensor disabled)
+#   sensors    - read of all sensors: result dict of name to state
+#   transition - state transition: type, old, new, cause
+#   error      - error that paused the MMU: code, reason
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import inspect, json, logging, os, time
+
+class MmuJournal:
+    MAX_RECORDS = 20000 # Safety limit on records for one toolchange
+
+    def __init__(self, dirname, max_files):
+        self.dirname = dirname
+        self.max_files = max_files
+        self.records = []
+        self.tool = None
+        self.saved = False
+
+    # Wrap the MMU methods that issue moves and read sensors so their calls and results are journaled
+    def instrument(self, mmu):
+        trace_filament_move = mmu._trace_filament_move
+        trace_selector_move = mmu._trace_selector_move
+        check_sensor = mmu._check_sensor
+        check_all_sensors = mmu._check_all_sensors
+        monotonic = mmu.reactor.monotonic
+
+        def journal_filament_move(*args, **kwargs):
+            result = trace_filament_move(*args, **kwargs)
+            a = call_arguments(trace_filament_move, args, kwargs)
+            self.record(monotonic(), "move", trace=a['trace_str'], dist=round(a['dist'], 2), motor=a['motor'], homing_move=a['homing_move'],
+                        endstop=a['endstop_name'], result=rounded(result))
+            return result
+
+        def journal_selector_move(*args, **kwargs):
+            result = trace_selector_move(*args, **kwargs)
+            a = call_arguments(trace_selector_move, args, kwargs)
+            self.record(monotonic(), "selector", target=round(a['new_pos'], 2), homing_move=a['homing_move'], endstop=a['endstop_name'], result=rounded(result))
+            return result
+
+        def journal_sensor(name):
+            result = check_sensor(name)
+            self.record(monotonic(), "sensor", name=name, result=result)
+            return result
+
+        def journal_all_sensors():
+            result = check_all_sensors()
+            self.record(monotonic(), "sensors", result=dict(result))
+            return result
+
+        mmu._trace_filament_move = journal_filament_move
+        mmu._trace_selector_move = journal_selector_move
+        mmu._check_sensor = journal_sensor
+        mmu._check_all_sensors = journal_all_sensors
+
+    def is_recording(self):
+        return self.tool is not None
+
+    def start(self, eventtime, tool, **fields):
+        self.records = []
+        self.tool = tool
+        self.saved = False
+        self.record(eventtime, "start", tool=tool, time=time.time(), **fields)
+
+    def end(self):
+        self.tool = None
+
+    def record(self, eventtime, kind, **fields):
+        if self.tool is None or len(self.records) >= self.MAX_RECORDS: return
+        fields['t'] = round(eventtime, 6)
+        fields['kind'] = kind
+        self.records.append(fields)
+
+    # Write the journal of the current (or last) toolchange. Returns filename or None if nothing to save
+    def save(self):
+        if not self.records or self.saved:
+            return None
+        try:
+            if not os.path.isdir(self.dirname):
+                os.makedirs(self.dirname)
+            files = self.list_files()
+            for f in files[:max(len(files) - self.max_files + 1, 0)]:
+                os.remove(os.path.join(self.dirname, f))
+            filename = "%s_T%d.jsonl" % (time.strftime("%Y%m%d-%H%M%S"), self.records[0]['tool'])
+            with open(os.path.join(self.dirname, filename), 'w') as f:
+                for rec in self.records:
+                    f.write(json.dumps(rec, sort_keys=True) + "\n")
+        except (IOError, OSError) as e:
+            logging.warning("mmu_journal: Unable to write to '%s': %s" % (self.dirname, str(e)))
+            return None
+        self.saved = True
+        return filename
+
+    def list_files(self):
+        try:
+            return sorted(f for f in os.listdir(self.dirname) if f.endswith(".jsonl"))
+        except OSError:
+            return []
+
+# All arguments of call by name including defaults
+def call_arguments(method, args, kwargs):
+    bound = inspect.signature(method).bind(*args, **kwargs)
+    bound.apply_defaults()
+    return bound.arguments
+
+def rounded(result):
+    return [round(r, 2) if isinstance(r, float) else r for r in result]
+
+def load_journal(path):
+    with open(path) as f:
+        return [json.loads(line) for line in f if line.strip()]
@@ -0,0 +1,150 @@
+# Happy Hare MMU Software
+# Plugin support for third-party extensions (e.g. Blobifier, EREC)
+#
+# Plugins are python modules placed in the 'mmu_plugins' directory (or 'plugin_dir') and enabled by name
+# with the 'plugins' option in [mmu]. Each module must define a 'load_plugin(mmu)' function that returns an
+# instance of a class derived from MmuPlugin. A plugin can:
+#
+#   register_commands(gcode)     - add its own gcode commands
+#   on_connect() / on_ready()    - lifecycle callbacks (also on_disconnect())
+#   pre_load(gate) / post_load(gate), pre_unload(gate) / post_unload(gate)
+#                                - hooks into the load/unload sequence. Raising MmuError will abort the sequence
+#   on_event(event, **kwargs)    - receives every event that Happy Hare publishes (toolchange, gate_map, etc). It is
+#                                  registered as an 'mmu.api' listener
+#   get_status(eventtime)        - contribute fields to 'printer.mmu.plugins.<name>'
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import logging, os
+
+PLUGIN_API_VERSION = 1 # Increment when the plugin interface changes in an incompatible way
+
+class MmuPlugin:
+    NAME = None                     # Defaults to module name
+    VERSION = 1.0                   # Version of the plugin itself
+    API_VERSION = PLUGIN_API_VERSION # Plugin API the plugin was written against
+    MIN_HAPPY_HARE_VERSION = 2.3    # Minimum Happy Hare version required
+
+    def __init__(self, mmu):
+        self.mmu = mmu
+
+    def register_commands(self, gcode):
+        pass
+
+    def on_connect(self):
+        pass
+
+    def on_ready(self):
+        pass
+
+    def on_disconnect(self):
+        pass
+
+    def pre_load(self, gate):
+        pass
+
+    def post_load(self, gate):
+        pass
+
+    def pre_unload(self, gate):
+        pass
+
+    def post_unload(self, gate):
+        pass
+
+    def on_event(self, event, **kwargs):
+        pass
+
+    def get_status(self, eventtime):
+        return {}
+
+class MmuPluginManager:
+    def __init__(self, mmu, plugin_dir, names, mmu_error):
+        self.mmu = mmu
+        self.mmu_error = mmu_error
+        self.plugin_dir = plugin_dir
+        self.plugins = {}
+        self.errors = []
+        for name in names:
+            try:
+                self._load_plugin(name)
+            except Exception as e:
+                self.errors.append("Plugin '%s' not loaded: %s" % (name, str(e)))
+        for msg in self.errors:
+            logging.warning("mmu_plugin: %s" % msg)
+
+    def _load_plugin(self, name):
+        path = os.path.join(self.plugin_dir, "%s.py" % name)
+        if not os.path.exists(path):
+            raise Exception("%s not found" % path)
+        module = self._import_module("mmu_plugins.%s" % name, path)
+        if not hasattr(module, 'load_plugin'):
+            raise Exception("module does not define 'load_plugin(mmu)'")
+        plugin = module.load_plugin(self.mmu)
+        if plugin.API_VERSION != PLUGIN_API_VERSION:
+            raise Exception("written for plugin API v%d but Happy Hare provides v%d" % (plugin.API_VERSION, PLUGIN_API_VERSION))
+        if plugin.MIN_HAPPY_HARE_VERSION > self.mmu.VERSION:
+            raise Exception("requires Happy Hare v%.1f or later" % plugin.MIN_HAPPY_HARE_VERSION)
+        plugin.NAME = plugin.NAME or name
+        plugin.register_commands(self.mmu.gcode)
+        self.mmu.api.register_listener(plugin.on_event)
+        self.plugins[plugin.NAME] = plugin
+        logging.info("mmu_plugin: Loaded plugin '%s' v%s" % (plugin.NAME, plugin.VERSION))
+
+    def _import_module(self, module_name, path):
+        try:
+            import importlib.util
+            spec = importlib.util.spec_from_file_location(module_name, path)
+            module = importlib.util.module_from_spec(spec)
+            spec.loader.exec_module(module)
+            return module
+        except ImportError:
+            import imp # Python 2
+            return imp.load_source(module_name, path)
+
+    def on_connect(self):
+        for msg in self.errors:
+            self.mmu._log_error(msg)
+        self._call_all('on_connect')
+
+    def on_ready(self):
+        self._call_all('on_ready')
+
+    def on_disconnect(self):
+        self._call_all('on_disconnect')
+
+    # Hooks are allowed to raise MmuError to abort the current operation. Other failures are logged
+    # so that a misbehaving plugin cannot break the MMU
+    def call_hook(self, hook, *args):
+        for plugin in self.plugins.values():
+            try:
+                getattr(plugin, hook)(*args)
+            except self.mmu_error:
+                raise
+            except Exception as e:
+                self.mmu._log_error("Plugin '%s' failed in %s(): %s" % (plugin.NAME, hook, str(e)))
+
+    def _call_all(self, method):
+        for plugin in self.plugins.values():
+            try:
+                getattr(plugin, method)()
+            except Exception as e:
+                self.mmu._log_error("Plugin '%s' failed in %s(): %s" % (plugin.NAME, method, str(e)))
+
+    def get_status(self, eventtime):
+        status = {}
+        for name, plugin in self.plugins.items():
+            try:
+                plugin_status = dict(plugin.get_status(eventtime))
+            except Exception:
+                plugin_status = {}
+            plugin_status['version'] = plugin.VERSION
+            status[name] = plugin_status
+        return status
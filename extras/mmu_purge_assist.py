@@ -0,0 +1,50 @@
+# Happy Hare MMU Software
+# Gear assist for long slicer driven purge extrusions (e.g. on the wipe tower)
+#
+# When 'sync_to_extruder' is off the extruder alone has to drag filament through the full bowden. That is fine for
+# normal printing but long purge extrusions can under extrude. This move transform watches each gcode move and, if a
+# single move extrudes at least 'purge_assist_length' outside of MMU operations, temporarily syncs the gear stepper to
+# the extruder. Sync is released on the first non-purge move once 'purge_assist_hold' seconds have passed since the
+# last long extrusion (so the travel moves between purge lines don't cause repeated sync/unsync)
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+
+class MmuPurgeAssist:
+    def __init__(self, mmu, length, hold):
+        self.mmu = mmu
+        self.reactor = mmu.printer.get_reactor()
+        self.length = length
+        self.hold = hold
+        self.next_transform = None
+        self.engaged = False
+        self.last_purge_time = 0.
+
+    def install(self, gcode_move):
+        self.next_transform = gcode_move.set_move_transform(self, force=True)
+
+    def get_position(self):
+        return self.next_transform.get_position()
+
+    def move(self, newpos, speed):
+        e_move = newpos[3] - self.next_transform.get_position()[3]
+        if e_move >= self.length and (self.engaged or self.mmu._purge_assist_allowed()):
+            self.last_purge_time = self.reactor.monotonic()
+            if not self.engaged:
+                self.engaged = True
+                self.mmu._engage_purge_assist(e_move)
+        elif self.engaged and self.reactor.monotonic() - self.last_purge_time >= self.hold:
+            self.release()
+        self.next_transform.move(newpos, speed)
+
+    def release(self):
+        if self.engaged:
+            self.engaged = False
+            self.mmu._release_purge_assist()
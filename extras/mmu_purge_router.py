@@ -0,0 +1,69 @@
+# Happy Hare MMU Software
+# Purge router for moving part of the toolchange purge into sparse infill
+#
+# When the slicer (or the moonraker gcode preprocessor) brackets sparse infill with 'MMU_PURGE_INFILL START=1' and
+# 'MMU_PURGE_INFILL END=1' this move transform over-extrudes the infill moves that follow a toolchange by up to
+# 'flow_percent' until the routed part of the purge has been used up. The remaining purge (on the wipe tower or
+# by _MMU_POST_LOAD) can be correspondingly smaller. This is only done when 'MMU_PURGE_INFILL NEXT=1' has flagged
+# that the first extrusion after the toolchange is sparse infill, otherwise the features printed before the infill
+# would be contaminated. Moves are shifted by the extra filament extruded so the gcode extruder position is never
+# affected
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+
+class MmuPurgeRouter:
+    def __init__(self, mmu, flow_percent):
+        self.mmu = mmu
+        self.flow_percent = flow_percent
+        self.next_transform = None
+        self.e_offset = 0. # Extra filament extruded so far. Never reset because gcode position is relative to it
+        self.reset()
+
+    def reset(self):
+        self.in_infill = False
+        self.next_infill = False # First extrusion after upcoming toolchange is sparse infill
+        self.budget = 0. # mm of filament still to be routed into infill
+
+    def install(self, gcode_move):
+        self.next_transform = gcode_move.set_move_transform(self, force=True)
+
+    def set_infill(self, in_infill):
+        self.in_infill = in_infill
+
+    def set_next_infill(self, next_infill):
+        self.next_infill = next_infill
+
+    # Whether the toolchange being performed is followed by sparse infill. Only applies to one toolchange
+    def take_next_infill(self):
+        next_infill, self.next_infill = self.next_infill, False
+        return next_infill
+
+    def set_budget(self, length):
+        self.budget = length
+
+    def get_position(self):
+        pos = list(self.next_transform.get_position())
+        pos[3] -= self.e_offset
+        return pos
+
+    def move(self, newpos, speed):
+        if self.in_infill and self.budget > 0.:
+            last = self.get_position()
+            e_move = newpos[3] - last[3]
+            if e_move > 0. and (newpos[0] != last[0] or newpos[1] != last[1]):
+                extra = min(e_move * self.flow_percent / 100., self.budget)
+                self.budget -= extra
+                self.e_offset += extra
+                if self.budget <= 0.:
+                    self.mmu._purge_routing_complete()
+        pos = list(newpos)
+        pos[3] += self.e_offset
+        self.next_transform.move(pos, speed)
@@ -0,0 +1,140 @@
+# Happy Hare MMU Software
+# Support for RFID/NFC spool tag readers
+#
+# Readers are serial devices (typically a small USB microcontroller driving a PN532 or RC522 over I2C/SPI) that
+# write one line per scanned tag. Either a reader per gate ('gate_serials') or a single reader at the buffer inlet
+# ('serial') can be configured. With a single reader the tag is applied to the next gate that sees filament
+# insertion on its pre-gate sensor or, failing that, the currently selected gate. Recognized tag formats are
+# OpenSpool JSON and simple key=value lists, e.g.:
+#
+#   {"protocol":"openspool","type":"PLA","color_hex":"FF0000","min_temp":"200","max_temp":"220"}
+#   spool_id=12,material=PETG,color=00ff00,temp=240
+#
+# Other readers (e.g. ESPHome) can update the gate map directly with the MMU_RFID_TAG command
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import logging, json
+from extras.mmu_color import normalize_color
+
+# Parse tag payload into dict of spool_id, material, color and temperature. Returns None if not recognized
+def parse_tag(payload):
+    payload = payload.strip()
+    if not payload:
+        return None
+    if payload.startswith('{'):
+        try:
+            data = json.loads(payload)
+        except ValueError:
+            return None
+    else:
+        data = {}
+        for item in payload.split(','):
+            if '=' in item:
+                key, value = item.split('=', 1)
+                data[key.strip().lower()] = value.strip()
+    if not isinstance(data, dict) or not data:
+        return None
+
+    tag = {}
+    try:
+        spool_id = data.get('spool_id', data.get('spoolid'))
+        if spool_id is not None:
+            tag['spool_id'] = int(spool_id)
+        material = data.get('material', data.get('type'))
+        if material:
+            tag['material'] = "".join(str(material).split()).upper()[:10]
+        color = data.get('color', data.get('color_hex'))
+        if color and normalize_color(color):
+            tag['color'] = normalize_color(color)
+        temp = data.get('temp', data.get('temperature'))
+        if temp is not None:
+            tag['temperature'] = int(float(temp))
+        elif data.get('min_temp') is not None and data.get('max_temp') is not None:
+            tag['temperature'] = int((float(data['min_temp']) + float(data['max_temp'])) / 2)
+    except (TypeError, ValueError):
+        return None
+    return tag or None
+
+class MmuRfid:
+    def __init__(self, config):
+        self.printer = config.get_printer()
+        self.reactor = self.printer.get_reactor()
+        self.baud = config.getint('baud', 115200)
+        self.poll_interval = config.getfloat('poll_interval', 0.5, minval=0.1)
+        self.serial = config.get('serial', None)
+        self.gate_serials = list(config.getlist('gate_serials', []))
+        if self.serial and self.gate_serials:
+            raise config.error("Specify either 'serial' (single reader) or 'gate_serials' (reader per gate) but not both")
+        self.readers = []
+        self.poll_timer = None
+        self.printer.register_event_handler('klippy:ready', self.handle_ready)
+        self.printer.register_event_handler('klippy:disconnect', self.handle_disconnect)
+
+    def handle_ready(self):
+        self.gcode = self.printer.lookup_object('gcode')
+        ports = [(self.serial, -1)] if self.serial else [(p, gate) for gate, p in enumerate(self.gate_serials) if p]
+        for port, gate in ports:
+            try:
+                import serial
+                handle = serial.Serial(port, self.baud, timeout=0)
+                self.readers.append({'port': port, 'gate': gate, 'handle': handle, 'buffer': ""})
+            except Exception as e:
+                logging.warning("mmu_rfid: Unable to open RFID reader on %s: %s" % (port, str(e)))
+        if self.readers:
+            self.poll_timer = self.reactor.register_timer(self._poll_readers, self.reactor.NOW)
+
+    def handle_disconnect(self):
+        for reader in self.readers:
+            try:
+                reader['handle'].close()
+            except Exception:
+                pass
+        self.readers = []
+
+    def _poll_readers(self, eventtime):
+        for reader in self.readers:
+            try:
+                data = reader['handle'].read(256)
+            except Exception as e:
+                logging.warning("mmu_rfid: Error reading RFID reader on %s: %s" % (reader['port'], str(e)))
+                continue
+            if not data:
+                continue
+            reader['buffer'] += data.decode('utf-8', 'ignore')
+            while '\n' in reader['buffer']:
+                line, reader['buffer'] = reader['buffer'].split('\n', 1)
+                tag = parse_tag(line)
+                if tag is None:
+                    logging.info("mmu_rfid: Ignoring unrecognized tag data from %s: %s" % (reader['port'], line.strip()))
+                    continue
+                self._report_tag(reader['gate'], tag)
+        return eventtime + self.poll_interval
+
+    def _report_tag(self, gate, tag):
+        cmd = "MMU_RFID_TAG GATE=%d" % gate
+        if 'spool_id' in tag:
+            cmd += " SPOOLID=%d" % tag['spool_id']
+        if 'material' in tag:
+            cmd += " MATERIAL=%s" % tag['material']
+        if 'color' in tag:
+            cmd += " COLOR=%s" % tag['color']
+        if 'temperature' in tag:
+            cmd += " TEMP=%d" % tag['temperature']
+        try:
+            self.gcode.run_script(cmd)
+        except Exception as e:
+            logging.warning("mmu_rfid: Error updating gate map from tag: %s" % str(e))
+
+    def get_status(self, eventtime):
+        return {'readers': len(self.readers)}
+
+def load_config(config):
+    return MmuRfid(config)
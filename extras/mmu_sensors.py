@@ -14,6 +14,10 @@
 # extruder & toolhead sensor:
 #   Wrapper around `filament_switch_sensor` disabling all functionality - just for visability
 #   Named `extruder` & `toolhead`
+#
+# custom path sensors:
+#   Additional user named sensors (e.g. `bowden_mid`) with a position along the filament path measured from the gate.
+#   Used by Happy Hare as checkpoints when loading/unloading and to locate the filament on error
 # 
 # Copyright (C) 2023  moggieuk#6538 (discord)
 #                     moggieuk@hotmail.com
@@ -111,9 +115,13 @@ class MmuSensors:
     ENDSTOP_GATE      = "mmu_gate"
     ENDSTOP_EXTRUDER  = "extruder"
     ENDSTOP_TOOLHEAD  = "toolhead"
+    SENSOR_BYPASS     = "mmu_bypass"
+    SYNC_FEEDBACK_TENSION     = "sync_feedback_tension"
+    SYNC_FEEDBACK_COMPRESSION = "sync_feedback_compression"
 
     def __init__(self, config):
         printer = config.get_printer()
+        self.path_sensors = []
 
         # Setup and pre-gate sensors that are defined...
         for gate in range(23):
@@ -169,6 +177,51 @@ class MmuSensors:
             config.fileconfig.set(section, "pause_on_runout", "False")
             fs = printer.load_object(config, section)
 
+        # Setup bypass runout sensor (external sensor on filament path when printing from bypass)...
+        switch_pin = config.get('bypass_switch_pin', None)
+        if switch_pin:
+            # Automatically create necessary filament_switch_sensors
+            section = "filament_switch_sensor %s_sensor" % self.SENSOR_BYPASS
+            config.fileconfig.add_section(section)
+            config.fileconfig.set(section, "switch_pin", switch_pin)
+            config.fileconfig.set(section, "pause_on_runout", "False")
+            config.fileconfig.set(section, "runout_gcode", "__MMU_BYPASS_RUNOUT")
+            config.fileconfig.set(section, "insert_gcode", "__MMU_BYPASS_INSERT")
+            fs = printer.load_object(config, section)
+
+        # Setup sync feedback buffer sensors (tension and compression)...
+        for name in [self.SYNC_FEEDBACK_TENSION, self.SYNC_FEEDBACK_COMPRESSION]:
+            switch_pin = config.get('%s_pin' % name, None)
+            if switch_pin:
+                # Automatically create necessary filament_switch_sensors
+                section = "filament_switch_sensor %s_sensor" % name
+                config.fileconfig.add_section(section)
+                config.fileconfig.set(section, "switch_pin", switch_pin)
+                config.fileconfig.set(section, "pause_on_runout", "False")
+                fs = printer.load_object(config, section)
+
+        # Setup custom filament path sensors...
+        names = list(config.getlist('path_sensor_names', []))
+        pins = list(config.getlist('path_sensor_pins', []))
+        positions = list(config.getfloatlist('path_sensor_positions', []))
+        if len(pins) != len(names) or len(positions) != len(names):
+            raise config.error("path_sensor_names, path_sensor_pins and path_sensor_positions must have the same number of entries")
+        reserved = [self.ENDSTOP_GATE, self.ENDSTOP_EXTRUDER, self.ENDSTOP_TOOLHEAD, self.SENSOR_BYPASS, self.SYNC_FEEDBACK_TENSION, self.SYNC_FEEDBACK_COMPRESSION]
+        for name, switch_pin, position in zip(names, pins, positions):
+            if name in reserved or name.startswith(self.ENDSTOP_PRE_GATE):
+                raise config.error("Path sensor name '%s' is reserved" % name)
+            # Automatically create necessary filament_switch_sensors
+            section = "filament_switch_sensor %s_sensor" % name
+            config.fileconfig.add_section(section)
+            config.fileconfig.set(section, "switch_pin", switch_pin)
+            config.fileconfig.set(section, "pause_on_runout", "False")
+            fs = printer.load_object(config, section)
+            self.path_sensors.append((name, position))
+
+    # List of (name, position) of custom path sensors
+    def get_path_sensors(self):
+        return list(self.path_sensors)
+
 def load_config(config):
     return MmuSensors(config)
 
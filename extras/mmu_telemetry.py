@@ -0,0 +1,127 @@
+# Happy Hare MMU Software
+# Per-job telemetry recorder for diagnosing rare intermittent failures
+#
+# When enabled every sensor edge, homing result, filament move summary and state transition is appended as one
+# JSON object per line to 'mmu_telemetry/<start time>_<job>.jsonl' alongside klippy.log. A new file is started for
+# each print and only the most recent 'telemetry_max_jobs' files are kept. Each record carries the klipper
+# monotonic time 't' (so it can be correlated with klippy.log) and its 'kind':
+#
+#   job        - start/end of job with wall clock time and filename
+#   sensor     - sensor edge: name, present
+#   homing     - homing move result: endstop, motor, dist, actual, homed, measured
+#   move       - filament move summary: motor, dist, measured, delta
+#   transition - state transition: type (print_state, filament_pos, error, action), old, new, cause
+#
+# Records are written on a background thread so recording never blocks the reactor. Unlike trace logging this
+# has negligible impact on timing so can be left enabled until the failure recurs
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import json, logging, os, re, threading, time, queue
+
+class MmuTelemetry:
+    def __init__(self, dirname, max_jobs):
+        self.dirname = dirname
+        self.max_jobs = max_jobs
+        self.job_file = None
+        self.bg_queue = queue.Queue()
+        self.bg_thread = threading.Thread(target=self._bg_thread)
+        self.bg_thread.start()
+
+    def _bg_thread(self):
+        handle = None
+        while True:
+            item = self.bg_queue.get(True)
+            if item is None:
+                break
+            path, line = item
+            try:
+                if handle is None or handle.name != path:
+                    if handle is not None:
+                        handle.close()
+                    handle = open(path, 'a')
+                handle.write(line + "\n")
+                handle.flush()
+            except Exception as e:
+                logging.warning("mmu_telemetry: Unable to write to '%s': %s" % (path, str(e)))
+                handle = None
+        if handle is not None:
+            handle.close()
+
+    def stop(self):
+        self.bg_queue.put_nowait(None)
+        self.bg_thread.join()
+
+    def is_recording(self):
+        return self.job_file is not None
+
+    def start_job(self, eventtime, job):
+        if self.job_file is not None:
+            self.end_job(eventtime, "restarted")
+        try:
+            if not os.path.isdir(self.dirname):
+                os.makedirs(self.dirname)
+            self._purge_old_jobs(self.max_jobs - 1)
+        except OSError as e:
+            logging.warning("mmu_telemetry: Unable to prepare '%s': %s" % (self.dirname, str(e)))
+            return
+        safe_job = re.sub(r'[^A-Za-z0-9_.-]', '_', os.path.splitext(os.path.basename(job or "job"))[0])[:64]
+        self.job_file = os.path.join(self.dirname, "%s_%s.jsonl" % (time.strftime("%Y%m%d-%H%M%S"), safe_job))
+        self.record(eventtime, "job", event="start", job=job, time=time.time())
+
+    def end_job(self, eventtime, state):
+        if self.job_file is None: return
+        self.record(eventtime, "job", event="end", state=state, time=time.time())
+        self.job_file = None
+
+    def record(self, eventtime, kind, **fields):
+        if self.job_file is None: return
+        fields['t'] = round(eventtime, 4)
+        fields['kind'] = kind
+        self.bg_queue.put_nowait((self.job_file, json.dumps(fields, sort_keys=True)))
+
+    def list_jobs(self):
+        try:
+            return sorted(f for f in os.listdir(self.dirname) if f.endswith(".jsonl"))
+        except OSError:
+            return []
+
+    def _purge_old_jobs(self, keep):
+        jobs = self.list_jobs()
+        for f in jobs[:max(len(jobs) - keep, 0)]:
+            os.remove(os.path.join(self.dirname, f))
+
+    # Summarize a recorded job into counts of each record kind, failed homing moves,
+    # sensor edges, errors and the largest move discrepancy
+    def summarize(self, filename):
+        summary = {'records': 0, 'kinds': {}, 'sensor_edges': {}, 'homing_failures': [], 'errors': [], 'max_delta': None, 'start': None, 'end': None, 'state': None}
+        with open(os.path.join(self.dirname, filename)) as f:
+            for line in f:
+                try:
+                    rec = json.loads(line)
+                except ValueError:
+                    continue
+                summary['records'] += 1
+                kind = rec.get('kind')
+                summary['kinds'][kind] = summary['kinds'].get(kind, 0) + 1
+                if summary['start'] is None:
+                    summary['start'] = rec.get('t')
+                summary['end'] = rec.get('t')
+                if kind == "job" and rec.get('event') == "end":
+                    summary['state'] = rec.get('state')
+                elif kind == "sensor":
+                    summary['sensor_edges'][rec.get('name')] = summary['sensor_edges'].get(rec.get('name'), 0) + 1
+                elif kind == "homing" and not rec.get('homed'):
+                    summary['homing_failures'].append(rec)
+                elif kind == "transition" and rec.get('type') == "error":
+                    summary['errors'].append(rec)
+                elif kind == "move" and (summary['max_delta'] is None or abs(rec.get('delta', 0)) > abs(summary['max_delta'].get('delta', 0))):
+                    summary['max_delta'] = rec
+        return summary
@@ -0,0 +1,145 @@
+# Happy Hare MMU Software
+# Library of named tip forming profiles for the standalone tip forming macro
+#
+# Each profile is a set of '_MMU_FORM_TIP_STANDALONE' parameters (upper case, as passed on the macro command line)
+# that are known to work for a hotend/MMU combination. A profile is selected with 'form_tip_profile' (default),
+# 'form_tip_profile_materials' (by gate material) or 'form_tip_profile_gates' (by gate) and Happy Hare passes the
+# fully resolved parameters to the macro. Any parameter not in the profile falls back to the macro variable, and
+# slicer supplied parameters (MMU_SLICER_TIP_PARAMETERS) take precedence over the profile. Values are starting
+# points derived from commonly shared community settings and can be refined with MMU_FORM_TIP
+#
+# Cooling move parameters also depend on the material being unloaded so a second table holds, for each 'hotend_type',
+# the cooling parameters to use by gate material ('default' for materials not listed). These are applied before the
+# named profile so a profile or the slicer can still override them
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+
+TIP_PROFILES = {
+    'ercf_classic': ("ERCF with Dragon ST style hotend (original ERCF recommendation)", {
+        'UNLOADING_SPEED_START': 80, 'UNLOADING_SPEED': 18, 'RAMMING_VOLUME': 0,
+        'COOLING_TUBE_POSITION': 35, 'COOLING_TUBE_LENGTH': 15, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 30, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+    'tradrack_ptfe': ("Tradrack with PTFE lined hotend, gentle cooling to avoid PTFE snagging", {
+        'UNLOADING_SPEED_START': 60, 'UNLOADING_SPEED': 20, 'RAMMING_VOLUME': 5,
+        'COOLING_TUBE_POSITION': 33, 'COOLING_TUBE_LENGTH': 15, 'INITIAL_COOLING_SPEED': 8, 'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3,
+        'USE_SKINNYDIP': 0,
+    }),
+    'revo_hf': ("E3D Revo High Flow nozzle", {
+        'UNLOADING_SPEED_START': 80, 'UNLOADING_SPEED': 18, 'RAMMING_VOLUME': 9,
+        'COOLING_TUBE_POSITION': 35, 'COOLING_TUBE_LENGTH': 10, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 31, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+    'dragon_st': ("Phaetus Dragon Standard Flow", {
+        'UNLOADING_SPEED_START': 80, 'UNLOADING_SPEED': 18, 'RAMMING_VOLUME': 0,
+        'COOLING_TUBE_POSITION': 35, 'COOLING_TUBE_LENGTH': 15, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 30, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+    'dragon_hf': ("Phaetus Dragon High Flow", {
+        'UNLOADING_SPEED_START': 80, 'UNLOADING_SPEED': 18, 'RAMMING_VOLUME': 5,
+        'COOLING_TUBE_POSITION': 30, 'COOLING_TUBE_LENGTH': 10, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 26, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+    'dragon_uhf': ("Phaetus Dragon Ultra High Flow (long melt zone needs more ramming)", {
+        'UNLOADING_SPEED_START': 100, 'UNLOADING_SPEED': 20, 'RAMMING_VOLUME': 13,
+        'COOLING_TUBE_POSITION': 45, 'COOLING_TUBE_LENGTH': 10, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 5,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 40, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+    'mosquito': ("Slice Engineering Mosquito", {
+        'UNLOADING_SPEED_START': 80, 'UNLOADING_SPEED': 18, 'RAMMING_VOLUME': 2,
+        'COOLING_TUBE_POSITION': 30, 'COOLING_TUBE_LENGTH': 20, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 26, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+    'rapido_hf': ("Phaetus Rapido High Flow", {
+        'UNLOADING_SPEED_START': 80, 'UNLOADING_SPEED': 18, 'RAMMING_VOLUME': 9,
+        'COOLING_TUBE_POSITION': 43, 'COOLING_TUBE_LENGTH': 22, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4,
+        'USE_SKINNYDIP': 1, 'SKINNYDIP_DISTANCE': 38, 'DIP_INSERTION_SPEED': 30, 'DIP_EXTRACTION_SPEED': 70,
+    }),
+}
+
+COOLING_PROFILES = {
+    'v6': ("E3D V6 and clones", {
+        'default': {'COOLING_TUBE_POSITION': 35, 'COOLING_TUBE_LENGTH': 15, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3},
+        'ABS': {'COOLING_MOVES': 5},
+        'ASA': {'COOLING_MOVES': 5},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 2},
+    }),
+    'revo': ("E3D Revo (short heater core, short cooling tube)", {
+        'default': {'COOLING_TUBE_POSITION': 35, 'COOLING_TUBE_LENGTH': 10, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3},
+        'ABS': {'COOLING_MOVES': 5},
+        'ASA': {'COOLING_MOVES': 5},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 2},
+    }),
+    'dragon_st': ("Phaetus Dragon Standard Flow", {
+        'default': {'COOLING_TUBE_POSITION': 35, 'COOLING_TUBE_LENGTH': 15, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3},
+        'ABS': {'COOLING_MOVES': 5},
+        'ASA': {'COOLING_MOVES': 5},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 2},
+    }),
+    'dragon_hf': ("Phaetus Dragon High Flow", {
+        'default': {'COOLING_TUBE_POSITION': 30, 'COOLING_TUBE_LENGTH': 10, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3},
+        'ABS': {'COOLING_MOVES': 5},
+        'ASA': {'COOLING_MOVES': 5},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 2},
+    }),
+    'dragon_uhf': ("Phaetus Dragon Ultra High Flow (long melt zone, cooling tube further up)", {
+        'default': {'COOLING_TUBE_POSITION': 45, 'COOLING_TUBE_LENGTH': 10, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 5},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 4},
+        'ABS': {'COOLING_MOVES': 6},
+        'ASA': {'COOLING_MOVES': 6},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 3},
+    }),
+    'rapido': ("Phaetus Rapido", {
+        'default': {'COOLING_TUBE_POSITION': 43, 'COOLING_TUBE_LENGTH': 22, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3},
+        'ABS': {'COOLING_MOVES': 5},
+        'ASA': {'COOLING_MOVES': 5},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 2},
+    }),
+    'mosquito': ("Slice Engineering Mosquito", {
+        'default': {'COOLING_TUBE_POSITION': 30, 'COOLING_TUBE_LENGTH': 20, 'INITIAL_COOLING_SPEED': 10, 'FINAL_COOLING_SPEED': 50, 'COOLING_MOVES': 4},
+        'PETG': {'FINAL_COOLING_SPEED': 40, 'COOLING_MOVES': 3},
+        'ABS': {'COOLING_MOVES': 5},
+        'ASA': {'COOLING_MOVES': 5},
+        'TPU': {'INITIAL_COOLING_SPEED': 5, 'FINAL_COOLING_SPEED': 20, 'COOLING_MOVES': 2},
+    }),
+}
+
+def get_tip_profile_names():
+    return sorted(TIP_PROFILES.keys())
+
+def get_tip_profile(name):
+    profile = TIP_PROFILES.get(name.lower())
+    return dict(profile[1]) if profile else None
+
+def get_tip_profile_description(name):
+    profile = TIP_PROFILES.get(name.lower())
+    return profile[0] if profile else None
+
+def get_hotend_types():
+    return sorted(COOLING_PROFILES.keys())
+
+def get_hotend_description(hotend):
+    cooling = COOLING_PROFILES.get(hotend.lower())
+    return cooling[0] if cooling else None
+
+# Cooling move parameters for hotend and material. Material specific values are merged over the hotend default
+def get_cooling_profile(hotend, material=''):
+    cooling = COOLING_PROFILES.get(hotend.lower())
+    if not cooling:
+        return None
+    params = dict(cooling[1]['default'])
+    params.update(cooling[1].get((material or '').upper(), {}))
+    return params
@@ -37,6 +37,7 @@ class MmuToolHead(toolhead.ToolHead, object):
 
         self.gear_motion_queue = self.extruder_synced_to_gear = None # Happy Hare: For bi-directional syncing of gear and extruder
         self.prev_rail_steppers = self.prev_g_sk = self.prev_sk = self.prev_trapq = None # Happy Hare: for stepper switching
+        self.opposed_gear_steppers = [] # Happy Hare: gear steppers temporarily reversed for overlapped moves
 
         # MMU velocity and acceleration control
         self.gear_max_velocity = config.getfloat('gear_max_velocity', 300, above=0.)
@@ -125,6 +126,14 @@ class MmuToolHead(toolhead.ToolHead, object):
                     self.old_ext_options[i] = config.fileconfig.get('extruder', i)
                     config.fileconfig.remove_option('extruder', i)
 
+        # Optional multiplexer for designs that share gear stepper drivers between gates
+        self.gear_mux = None
+        if config.has_section('mmu_gear_mux'):
+            self.gear_mux = self.printer.load_object(config, 'mmu_gear_mux')
+            for name in self.gear_mux.get_driver_names():
+                if name not in [s.get_name() for s in self.all_gear_rail_steppers]:
+                    raise config.error("mmu_gear_mux driver '%s' is not a gear stepper" % name)
+
         self.printer.register_event_handler('klippy:connect', self.handle_connect)
 
         # Add useful debugging command
@@ -161,12 +170,49 @@ class MmuToolHead(toolhead.ToolHead, object):
         return self.gear_max_velocity, self.gear_max_accel
 
     def select_gear_stepper(self, gate): # TODO untested WIP
-        if gate < 0:
+        if self.gear_mux:
+            self._select_muxed_gear_stepper(gate)
+        elif gate < 0:
             self.select_gear_steppers(None)
         else:
-            self.select_gear_steppers(["mmu_gear_%d" % gate])
+            self.select_gear_steppers([self.get_gear_stepper_name(gate)])
         return
 
+    # Gear stepper driving gate on designs with a gear motor per gate ('stepper_mmu_gear' drives gate #0)
+    def get_gear_stepper_name(self, gate):
+        return "stepper_mmu_gear_%d" % gate if gate > 0 else "stepper_mmu_gear"
+
+    # Place two gate's gear steppers on the gear rail with the retracting one reversed so that a positive gear move
+    # feeds 'feed_gate' whilst parking 'retract_gate'. Not possible if gates share a driver through a mux
+    def select_opposed_gear_steppers(self, feed_gate, retract_gate):
+        if self.gear_mux or feed_gate < 0 or retract_gate < 0 or feed_gate == retract_gate:
+            return False
+        retract = self.get_gear_stepper_name(retract_gate)
+        self.select_gear_steppers([self.get_gear_stepper_name(feed_gate), retract])
+        for s in self.get_kinematics().rails[1].steppers:
+            if s.get_name() == retract:
+                invert_dir, _ = s.get_dir_inverted()
+                s.set_dir_inverted(not invert_dir)
+                self.opposed_gear_steppers.append(s)
+        return True
+
+    # Interlock: wait for all gear motion to complete and disable every gear driver before the mux is switched,
+    # then only place the driver routed to the gate on the gear rail
+    def _select_muxed_gear_stepper(self, gate):
+        self.wait_moves()
+        print_time = self.get_last_move_time()
+        stepper_enable = self.printer.lookup_object('stepper_enable')
+        for name in self.gear_mux.get_driver_names():
+            stepper_enable.lookup_enable(name).motor_disable(print_time)
+        if gate < 0:
+            self.gear_mux.clear_selection()
+            self.select_gear_steppers(None)
+            return
+        driver, channel = self.gear_mux.get_route(gate)
+        self.gear_mux.set_channel(print_time, gate, channel)
+        self.dwell(self.gear_mux.settle_time)
+        self.select_gear_steppers([driver])
+
     def select_gear_steppers(self, selected_steppers): # TODO untested WIP
         # Unsync first to simplify transition
         gear_motion_queue = self.gear_motion_queue
@@ -178,6 +224,10 @@ class MmuToolHead(toolhead.ToolHead, object):
         printer_toolhead = self.printer.lookup_object('toolhead')
         printer_toolhead.flush_step_generation()
         self.flush_step_generation()
+        for s in self.opposed_gear_steppers: # Restore direction after overlapped move
+            _, orig_invert_dir = s.get_dir_inverted()
+            s.set_dir_inverted(orig_invert_dir)
+        self.opposed_gear_steppers = []
         gear_rail = self.get_kinematics().rails[1]
         g_pos = gear_rail.get_commanded_position()
         gear_rail.steppers = []
@@ -254,6 +304,25 @@ class MmuToolHead(toolhead.ToolHead, object):
 
             self.gear_motion_queue = None
 
+    # Verify that synced state is real: every gear stepper must be using the extruder trapq and have its step generator
+    # registered with the printer toolhead. Returns description of problem or None if correctly synced
+    def check_gear_synced_to_extruder(self):
+        if not self.gear_motion_queue:
+            return "gear is not synced to extruder"
+        extruder = self.printer.lookup_object(self.gear_motion_queue, None)
+        if extruder is None:
+            return "'%s' is not a valid extruder" % self.gear_motion_queue
+        printer_toolhead = self.printer.lookup_object('toolhead')
+        gear_steppers = self.get_kinematics().rails[1].get_steppers()
+        if not gear_steppers:
+            return "no gear steppers are selected"
+        for s in gear_steppers:
+            if s.get_trapq() != extruder.get_trapq():
+                return "'%s' is not on the extruder motion queue" % s.get_name()
+            if s.generate_steps not in printer_toolhead.step_generators:
+                return "'%s' step generator is not registered with printer toolhead" % s.get_name()
+        return None
+
     def resync_gear_position_to_extruder(self):
         if self.gear_motion_queue:
             extruder = self.printer.lookup_object(self.gear_motion_queue, None)
@@ -619,7 +688,7 @@ def MmuLookupMultiRail(config, need_position_minmax=True, default_position_endst
     for i in range(23):
         if not config.has_section(config.get_name() + "_" + str(i)):
             continue
-        rail.add_extra_stepper(config.getsection(config.get_name() + str(i)))
+        rail.add_extra_stepper(config.getsection(config.get_name() + "_" + str(i)))
     return rail
 
 
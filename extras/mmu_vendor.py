@@ -0,0 +1,219 @@
+# Happy Hare MMU Software
+# Vendor specific MMU drivers
+#
+# Each supported MMU design implements this small driver interface that captures its CAD defaults and
+# behavioral quirks so that the main module does not need to be littered with vendor conditionals:
+#
+#   set_defaults(mmu)                 - set CAD and tuning defaults (can still be overridden by `cad_*` config)
+#   get_max_selector_movement(mmu, n) - maximum selector travel to reach gate n
+#   select_gate(mmu, gate)            - return True if driver handled gate selection (no linear selector)
+#                                       (or runs SELECT_MACRO for designs that select with external hardware)
+#   grip(mmu) / release(mmu)          - return True if driver handled filament grip/release (no servo)
+#   eject(mmu)                        - additional movement after filament is unloaded to gate
+#
+# Copyright (C) 2022  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+
+BMG_CIRC = 23.
+
+class MmuVendor:
+    NAME = "Other"
+    HAS_SELECTOR = True
+    HAS_SERVO = True
+    SELECT_MACRO = None # Macro that performs gate selection if driver delegates it to gcode
+
+    def __init__(self, version_string, version):
+        self.version_string = version_string
+        self.version = version
+
+    def set_defaults(self, mmu):
+        # Some arbitary starting values for "Other" designs
+        mmu.cad_gate0_pos = 1.
+        mmu.cad_gate_width = 20.
+        mmu.cad_bypass_offset = 1.
+        mmu.cad_last_gate_offset = 10.
+        mmu.gate_parking_distance = 20.
+
+    def get_max_selector_movement(self, mmu, n):
+        return mmu.cad_gate0_pos + (n * mmu.cad_gate_width)
+
+    def select_gate(self, mmu, gate):
+        return False
+
+    def grip(self, mmu):
+        return False
+
+    def release(self, mmu):
+        return False
+
+    def eject(self, mmu):
+        pass
+
+class ErcfVendor(MmuVendor):
+    NAME = "ERCF"
+
+    def set_defaults(self, mmu):
+        if self.version >= 2.0: # V2 community edition
+            mmu.cad_gate0_pos = 4.0
+            mmu.cad_gate_width = 23.
+            mmu.cad_bypass_offset = 0.72
+            mmu.cad_last_gate_offset = 14.4
+
+            # Non CAD default parameters
+            mmu.gate_parking_distance = 13.
+            mmu.encoder_default_resolution = BMG_CIRC / (2 * 12) # Binky 12 tooth disc with BMG gear
+
+            # Modifications:
+            #  h = ThumperBlocks filament blocks
+            if "h" in self.version_string:
+                mmu.cad_gate_width = 21.
+                mmu.gate_parking_distance = 11.
+
+        else: # V1.1 original
+            # Modifications:
+            #  t = TripleDecky filament blocks
+            #  s = Springy sprung servo selector
+            #  b = Binky encoder upgrade
+            if "t" in self.version_string:
+                mmu.cad_gate_width = 23. # Triple Decky is wider filament block
+                mmu.cad_block_width = 0. # Bearing blocks are not used
+                mmu.gate_parking_distance = 13. # Filament trap in block
+
+            if "s" in self.version_string:
+                mmu.cad_last_gate_offset = 1.2 # Springy has additional bump stops
+
+            if "b" in self.version_string:
+                mmu.encoder_default_resolution = BMG_CIRC / (2 * 12) # Binky 12 tooth disc with BMG gear
+
+    def get_max_selector_movement(self, mmu, n):
+        if self.version >= 2.0 or "t" in self.version_string:
+            return mmu.cad_gate0_pos + (n * mmu.cad_gate_width)
+        # ERCF v1.1 has bearing blocks every three gates
+        return mmu.cad_gate0_pos + (n * mmu.cad_gate_width) + (n//3) * mmu.cad_block_width
+
+class TradrackVendor(MmuVendor):
+    NAME = "Tradrack"
+
+    def set_defaults(self, mmu):
+        mmu.cad_gate0_pos = 0.5
+        mmu.cad_gate_width = 17.
+        mmu.cad_bypass_offset = 0 # Doesn't have bypass
+        mmu.cad_last_gate_offset = 1. # TODO this is a guess
+
+        mmu.gate_parking_distance = 17. # Using Gate switch (had user reports from 15 - 17.5)
+        mmu.encoder_default_resolution = BMG_CIRC / (2 * 12) # If fitted, assumed to by Binky
+
+        # Modifications:
+        #  e = has encoder modification
+        #      Note: if have encoder but want to use gate sensor to part, then `gate_endstop_to_encoder`
+        #            would need to be set and `gate_parking_distance` set back to original
+        if "e" in self.version_string:
+            mmu.gate_parking_distance = 39. # Assume using encoder if we have it
+            mmu.gate_endstop_to_encoder = 15. # TODO this is a guess
+
+class PicoMmuVendor(MmuVendor):
+    NAME = "PicoMMU"
+
+    # Compact servo-less design with a rotating selector that also clamps the filament. Starting values
+    # only, MMU_CALIBRATE_SELECTOR will measure actual gate positions
+    HAS_SERVO = False
+
+    def set_defaults(self, mmu):
+        mmu.cad_gate0_pos = 2.
+        mmu.cad_gate_width = 14.
+        mmu.cad_bypass_offset = 0 # Doesn't have bypass
+        mmu.cad_last_gate_offset = 2.
+        mmu.gate_parking_distance = 15.
+
+    # Filament is clamped when the selector is aligned with the gate and released half a gate away from it
+    def grip(self, mmu):
+        if mmu.gate_selected >= 0 and mmu.servo_state != mmu.SERVO_DOWN_STATE:
+            mmu._trace_selector_move("Gripping filament", mmu.selector_offsets[mmu.gate_selected])
+        mmu.servo_state = mmu.SERVO_DOWN_STATE
+        return True
+
+    def release(self, mmu):
+        if mmu.gate_selected >= 0 and mmu.servo_state != mmu.SERVO_UP_STATE:
+            mmu._trace_selector_move("Releasing filament", mmu.selector_offsets[mmu.gate_selected] + mmu.cad_gate_width / 2.)
+        mmu.servo_state = mmu.SERVO_UP_STATE
+        return True
+
+# Designs with a dedicated gear motor per gate ('stepper_mmu_gear_N' steppers) merged through a Y-splitter
+# so there is no selector or servo. Motors may also share drivers through a [mmu_gear_mux] board
+class MultiGearVendor(MmuVendor):
+    HAS_SELECTOR = False
+    HAS_SERVO = False
+
+    def set_defaults(self, mmu):
+        mmu.virtual_selector = True # No selector so CAD dimensions are unused
+        mmu.cad_bypass_offset = 0 # Doesn't have bypass
+        mmu.gate_parking_distance = 10. # Back from gate sensor to clear Y-splitter
+
+    def get_max_selector_movement(self, mmu, n):
+        return 0.
+
+    def select_gate(self, mmu, gate):
+        mmu.mmu_toolhead.select_gear_stepper(gate)
+        return True
+
+    def grip(self, mmu):
+        return True
+
+    def release(self, mmu):
+        return True
+
+class ThreeMsVendor(MultiGearVendor):
+    NAME = "3MS"
+
+class NightOwlVendor(MultiGearVendor):
+    NAME = "NightOwl"
+
+    def set_defaults(self, mmu):
+        super(NightOwlVendor, self).set_defaults(mmu)
+        mmu.gate_parking_distance = 20. # Longer merge path than 3MS
+
+# Single motor design where the filament is chosen by a cam that the controller rotates when its button is pressed.
+# Gate selection is delegated to the '_MMU_CHAMELEON_SELECT GATE=n' macro (e.g. pulsing the button pin) and the
+# one gear motor then feeds whichever filament is engaged
+class ChameleonVendor(MmuVendor):
+    NAME = "3DChameleon"
+    HAS_SELECTOR = False
+    HAS_SERVO = False
+    SELECT_MACRO = "_MMU_CHAMELEON_SELECT"
+
+    def set_defaults(self, mmu):
+        mmu.virtual_selector = True # No selector so CAD dimensions are unused
+        mmu.cad_bypass_offset = 0 # Doesn't have bypass
+        mmu.gate_parking_distance = 15. # Back from gate sensor to clear the cam
+
+    def get_max_selector_movement(self, mmu, n):
+        return 0.
+
+    def select_gate(self, mmu, gate):
+        if gate >= 0:
+            mmu._wrap_gcode_command("%s GATE=%d" % (self.SELECT_MACRO, gate), exception=True)
+        return True
+
+    def grip(self, mmu):
+        return True # Cam only engages the selected filament
+
+    def release(self, mmu):
+        return True
+
+class PrusaVendor(MmuVendor):
+    NAME = "Prusa" # In progress
+
+VENDOR_DRIVERS = [ErcfVendor, TradrackVendor, PicoMmuVendor, ThreeMsVendor, NightOwlVendor, ChameleonVendor, PrusaVendor, MmuVendor]
+
+def get_vendor_driver(vendor, version_string, version):
+    for driver in VENDOR_DRIVERS:
+        if driver.NAME.lower() == vendor.lower():
+            return driver(version_string, version)
+    return MmuVendor(version_string, version)
@@ -0,0 +1,59 @@
+# Happy Hare MMU Software
+# Virtual encoder for MMU designs without an encoder
+#
+# Without an encoder there is no measurement of actual filament movement. This provides a watered-down substitute from
+# the sensor transitions along the filament path: the commanded distance from the gate needed to reach a sensor
+# (extruder entry or toolhead) is learned per gate and later transitions are measured against it. Needing more
+# movement than usual suggests slip. While printing
+# a clog is inferred when the sync feedback buffer stays compressed (gear delivering filament the extruder isn't taking)
+# for 'clog_length' of commanded extrusion
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+
+class MmuVirtualEncoder:
+    def __init__(self, samples, clog_length):
+        self.samples = samples
+        self.clog_length = clog_length
+        self.transitions = {} # (gate, sensor) -> commanded distances used to establish baseline
+        self.slip_events = 0
+        self.clog_start = None # Extruder position when buffer was first seen compressed
+
+    def reset(self, gate=None):
+        self.transitions = {k: v for k, v in self.transitions.items() if gate is not None and k[0] != gate}
+        self.clog_start = None
+
+    # Returns (measured, delta) for a sensor transition reached after 'commanded' mm. 'measured' is the learned physical
+    # distance and 'delta' how much further the motor had to move (positive = slip). Delta is 0 while learning
+    def measure(self, gate, sensor, commanded):
+        history = self.transitions.setdefault((gate, sensor), [])
+        if len(history) < self.samples:
+            history.append(commanded)
+            return commanded, 0.
+        measured = sum(history) / len(history)
+        return measured, commanded - measured
+
+    # Returns True once the buffer has been compressed for 'clog_length' of extrusion
+    def check_clog(self, compressed, extruder_pos):
+        if not compressed:
+            self.clog_start = None
+            return False
+        if self.clog_start is None:
+            self.clog_start = extruder_pos
+        elif extruder_pos - self.clog_start >= self.clog_length:
+            self.clog_start = None
+            return True
+        return False
+
+    def get_status(self):
+        return {
+            'slip_events': self.slip_events,
+            'baselines': {"%d:%s" % k: round(sum(v) / len(v), 1) for k, v in self.transitions.items() if len(v) >= self.samples},
+        }
@@ -1,7 +1,8 @@
+import asyncio
 import os
 import shutil
 import unittest
-from unittest.mock import MagicMock
+from unittest.mock import AsyncMock, MagicMock
 
 from components.mmu_server import MmuServer
 
@@ -71,6 +72,18 @@ class TestMmuServerFileProcessor(unittest.TestCase):
             file_contents = f.read()
             self.assertIn('; start_gcode: PRINT_START MMU_TOOLS_USED=!mmu_inject_referenced_tools!', file_contents)
 
+    def test_enumerate_used_tools_counts_toolchanges(self):
+        _, tools_used, num_toolchanges = self.subject._enumerate_used_tools(self.TOOLCHANGE_FILEPATH)
+
+        self.assertEqual(tools_used, [0, 1, 3, 4, 5, 12])
+        self.assertEqual(num_toolchanges, 5)
+
+    def test_enumerate_used_tools_when_no_toolchanges(self):
+        _, tools_used, num_toolchanges = self.subject._enumerate_used_tools(self.NO_TOOLCHANGE_FILEPATH)
+
+        self.assertEqual(tools_used, [])
+        self.assertEqual(num_toolchanges, 0)
+
     def test_inject_tool_usage_called_if_placeholder(self):
         self.subject._inject_tool_usage = MagicMock()
 
@@ -86,3 +99,367 @@ class TestMmuServerFileProcessor(unittest.TestCase):
         self.subject._write_mmu_metadata(self.TOOLCHANGE_FILEPATH)
 
         self.subject._inject_tool_usage.assert_not_called()
+
+
+class TestMmuServerMqttPublisher(unittest.TestCase):
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        self.subject.mqtt_topic_prefix = 'happy_hare/mmu'
+        self.mqtt = MagicMock()
+        self.mqtt.publish_topic = AsyncMock()
+
+    def test_publish_event_sends_json_payload(self):
+        self.subject.server.lookup_component = MagicMock(return_value=self.mqtt)
+
+        result = asyncio.run(self.subject.publish_event('toolchange', {'from_tool': 0, 'to_tool': 1}))
+
+        self.assertTrue(result)
+        self.mqtt.publish_topic.assert_called_once_with('happy_hare/mmu/toolchange', {'event': 'toolchange', 'data': {'from_tool': 0, 'to_tool': 1}})
+
+    def test_publish_event_without_mqtt_component(self):
+        self.subject.server.lookup_component = MagicMock(return_value=None)
+
+        result = asyncio.run(self.subject.publish_event('error'))
+
+        self.assertFalse(result)
+
+    def test_publish_event_handles_broker_errors(self):
+        self.mqtt.publish_topic.side_effect = Exception("broker unavailable")
+        self.subject.server.lookup_component = MagicMock(return_value=self.mqtt)
+
+        result = asyncio.run(self.subject.publish_event('runout', {'gate': 2}))
+
+        self.assertFalse(result)
+
+
+class TestMmuServerSpoolmanPush(unittest.TestCase):
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        self.spoolman = MagicMock()
+        self.spoolman.spoolman_url = 'http://spoolman:7912/api'
+        self.spoolman.http_client.request = AsyncMock()
+        self.subject.server.lookup_component = MagicMock(return_value=self.spoolman)
+
+    def test_push_filament_patches_filament_record(self):
+        result = asyncio.run(self.subject.push_filament(5, material='PETG', color='00ff00'))
+
+        self.assertTrue(result)
+        self.spoolman.http_client.request.assert_called_once_with(method='PATCH', url='http://spoolman:7912/api/v1/filament/5', body={'material': 'PETG', 'color_hex': '00ff00'})
+
+    def test_push_filament_with_nothing_to_update(self):
+        result = asyncio.run(self.subject.push_filament(5))
+
+        self.assertFalse(result)
+        self.spoolman.http_client.request.assert_not_called()
+
+    def test_set_location_patches_spool_record(self):
+        result = asyncio.run(self.subject.set_location(7, 'voron:gate 2'))
+
+        self.assertTrue(result)
+        self.spoolman.http_client.request.assert_called_once_with(method='PATCH', url='http://spoolman:7912/api/v1/spool/7', body={'location': 'voron:gate 2'})
+
+    def test_pull_locations_assigns_gates_for_this_printer(self):
+        response = MagicMock()
+        response.json.return_value = [
+            {'id': 3, 'location': 'voron:gate 1', 'filament': {'material': 'PLA', 'color_hex': 'ff0000'}},
+            {'id': 4, 'location': 'prusa:gate 1', 'filament': {'material': 'PETG', 'color_hex': '00ff00'}},
+            {'id': 5, 'location': None, 'filament': {}},
+        ]
+        self.spoolman.http_client.request = AsyncMock(return_value=response)
+        self.spoolman.run_gcode = AsyncMock()
+
+        result = asyncio.run(self.subject.pull_locations('voron'))
+
+        self.assertEqual(result, {1: 3})
+        self.spoolman.run_gcode.assert_called_once_with("MMU_GATE_MAP GATE=1 SPOOLID=3 MATERIAL='PLA' COLOR='ff0000' QUIET=1")
+
+    def test_audit_locations_returns_spoolman_assignments_to_mmu(self):
+        response = MagicMock()
+        response.json.return_value = [
+            {'id': 3, 'location': 'voron:gate 1', 'filament': {}},
+            {'id': 6, 'location': 'voron:gate 1', 'filament': {}},
+            {'id': 4, 'location': 'prusa:gate 2', 'filament': {}},
+        ]
+        self.spoolman.http_client.request = AsyncMock(return_value=response)
+        self.spoolman.run_gcode = AsyncMock()
+
+        result = asyncio.run(self.subject.audit_locations('voron', fix=1))
+
+        self.assertTrue(result)
+        self.spoolman.run_gcode.assert_called_once_with('MMU_AUDIT FIX=1 LOCATIONS="{1: [3, 6]}"')
+
+    def test_audit_locations_handles_spoolman_errors(self):
+        self.spoolman.http_client.request.side_effect = Exception("spoolman unavailable")
+        self.spoolman.run_gcode = AsyncMock()
+
+        result = asyncio.run(self.subject.audit_locations('voron'))
+
+        self.assertFalse(result)
+        self.spoolman.run_gcode.assert_not_called()
+
+    def test_check_sufficiency_returns_remaining_weights_to_mmu(self):
+        response = MagicMock()
+        response.json.return_value = [
+            {'id': 3, 'remaining_weight': 250.04},
+            {'id': 4, 'remaining_weight': None},
+            {'id': 5, 'remaining_weight': 80.0},
+        ]
+        self.spoolman.http_client.request = AsyncMock(return_value=response)
+        self.spoolman.run_gcode = AsyncMock()
+
+        result = asyncio.run(self.subject.check_sufficiency({'0': 3, '1': 4, '2': 5}, '12.50,3.25', pause=1))
+
+        self.assertTrue(result)
+        self.spoolman.run_gcode.assert_called_once_with('MMU_CHECK_FILAMENT_SUFFICIENCY GRAMS=12.50,3.25 PAUSE=1 REMAINING="{0: 250.0, 2: 80.0}"')
+
+    def test_create_spool_copies_filament_and_assigns_gate(self):
+        existing, created = MagicMock(), MagicMock()
+        existing.json.return_value = {'id': 3, 'filament': {'id': 9}}
+        created.json.return_value = {'id': 12, 'filament': {'id': 9}}
+        self.spoolman.http_client.request = AsyncMock(side_effect=[existing, created])
+        self.spoolman.run_gcode = AsyncMock()
+
+        result = asyncio.run(self.subject.create_spool(2, 3))
+
+        self.assertEqual(result, 12)
+        self.spoolman.http_client.request.assert_called_with(method='POST', url='http://spoolman:7912/api/v1/spool', body={'filament_id': 9})
+        self.spoolman.run_gcode.assert_called_once_with('MMU_SPOOL_CHANGE GATE=2 SPOOLID=12')
+
+    def test_push_filament_handles_spoolman_errors(self):
+        self.spoolman.http_client.request.side_effect = Exception("spoolman unavailable")
+
+        result = asyncio.run(self.subject.push_filament(5, material='PLA'))
+
+        self.assertFalse(result)
+
+
+class TestMmuServerSlicerTipParameters(unittest.TestCase):
+    SLICER_CONFIG_FILEPATH = 'test/support/slicer_config.gcode'
+
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        shutil.copyfile('test/support/slicer_config.orig.gcode', self.SLICER_CONFIG_FILEPATH)
+
+    def tearDown(self):
+        os.remove(self.SLICER_CONFIG_FILEPATH)
+
+    def test_parse_slicer_tip_parameters_per_tool(self):
+        tip_parameters = self.subject._parse_slicer_tip_parameters(self.SLICER_CONFIG_FILEPATH)
+
+        self.assertEqual(sorted(tip_parameters.keys()), [0, 1])
+        self.assertEqual(tip_parameters[0]['UNLOADING_SPEED'], '90')
+        self.assertEqual(tip_parameters[1]['UNLOADING_SPEED_START'], '80')
+        self.assertEqual(tip_parameters[1]['COOLING_MOVES'], '2')
+        self.assertEqual(tip_parameters[0]['COOLING_TUBE_LENGTH'], '5')
+        self.assertEqual(tip_parameters[1]['COOLING_TUBE_POSITION'], '91.5')
+
+    def test_parse_slicer_ramming_converts_to_linear_speed(self):
+        tip_parameters = self.subject._parse_slicer_tip_parameters(self.SLICER_CONFIG_FILEPATH)
+
+        self.assertEqual(tip_parameters[0]['RAMMING_SPEEDS'], '2.74,2.83,2.99')
+        self.assertNotIn('RAMMING_SPEEDS', tip_parameters[1]) # All zero speeds means no ramming
+
+    def test_write_mmu_metadata_injects_tip_parameters(self):
+        self.subject._write_mmu_metadata(self.SLICER_CONFIG_FILEPATH)
+
+        with open(self.SLICER_CONFIG_FILEPATH, 'r') as f:
+            first_line = f.readline()
+            self.assertNotIn('!slicer_tip_parameters!', first_line)
+            self.assertIn("0:{'COOLING_MOVES':'4',", first_line)
+            self.assertNotIn(' ', first_line.split('PROFILES=')[1])
+
+    def test_write_mmu_metadata_injects_filament_grams(self):
+        self.subject._write_mmu_metadata(self.SLICER_CONFIG_FILEPATH)
+
+        with open(self.SLICER_CONFIG_FILEPATH, 'r') as f:
+            self.assertIn('MMU_CHECK_FILAMENT_SUFFICIENCY GRAMS=12.50,3.25\n', f.read())
+
+    def test_write_mmu_metadata_injects_filament_manifest(self):
+        self.subject._write_mmu_metadata(self.SLICER_CONFIG_FILEPATH)
+
+        with open(self.SLICER_CONFIG_FILEPATH, 'r') as f:
+            self.assertIn('_MMU_PRINT_START MATERIALS=PLA,PETG COLORS=FF0000,00ff00\n', f.read())
+
+
+class TestMmuServerPreheatHints(unittest.TestCase):
+    PREHEAT_FILEPATH = 'test/support/preheat.gcode'
+
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        self.subject.preheat_lookahead = 1.5
+        shutil.copyfile('test/support/preheat.orig.gcode', self.PREHEAT_FILEPATH)
+
+    def tearDown(self):
+        os.remove(self.PREHEAT_FILEPATH)
+
+    def test_insert_preheat_hints_ahead_of_toolchanges(self):
+        result = self.subject._insert_preheat_hints(self.PREHEAT_FILEPATH)
+
+        self.assertTrue(result)
+        with open(self.PREHEAT_FILEPATH, 'r') as f:
+            lines = f.read().splitlines()
+        self.assertEqual(lines[5:8], ['MMU_PREHEAT TOOL=1 TEMP=250', 'G1 X0 Y100 E5', 'T1'])
+        self.assertEqual(lines[10:13], ['T1', 'MMU_PREHEAT TOOL=0 TEMP=215', 'G1 X100 Y100 E5']) # Never before previous toolchange
+
+    def test_insert_preheat_hints_only_once(self):
+        self.subject._insert_preheat_hints(self.PREHEAT_FILEPATH)
+
+        result = self.subject._insert_preheat_hints(self.PREHEAT_FILEPATH)
+
+        self.assertFalse(result)
+        with open(self.PREHEAT_FILEPATH, 'r') as f:
+            self.assertEqual(f.read().count('MMU_PREHEAT'), 2)
+
+
+class TestMmuServerPurgeInfillMarkers(unittest.TestCase):
+    PURGE_INFILL_FILEPATH = 'test/support/purge_infill.gcode'
+
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        shutil.copyfile('test/support/purge_infill.orig.gcode', self.PURGE_INFILL_FILEPATH)
+
+    def tearDown(self):
+        os.remove(self.PURGE_INFILL_FILEPATH)
+
+    def test_insert_purge_infill_markers_around_sparse_infill(self):
+        result = self.subject._insert_purge_infill_markers(self.PURGE_INFILL_FILEPATH)
+
+        self.assertTrue(result)
+        with open(self.PURGE_INFILL_FILEPATH, 'r') as f:
+            lines = f.read().splitlines()
+        self.assertEqual(lines[4:10], [';TYPE:Internal infill', 'MMU_PURGE_INFILL START=1', 'G1 X100 Y0 E5', 'G1 X100 Y100 E5', 'MMU_PURGE_INFILL END=1', 'T1'])
+        self.assertEqual(lines[-3:], ['; FEATURE: Sparse infill', 'MMU_PURGE_INFILL START=1', 'G1 X0 Y0 E5'])
+
+    def test_insert_purge_infill_markers_only_once(self):
+        self.subject._insert_purge_infill_markers(self.PURGE_INFILL_FILEPATH)
+
+        result = self.subject._insert_purge_infill_markers(self.PURGE_INFILL_FILEPATH)
+
+        self.assertFalse(result)
+        with open(self.PURGE_INFILL_FILEPATH, 'r') as f:
+            self.assertEqual(f.read().count('MMU_PURGE_INFILL START=1'), 2)
+
+    def test_flag_toolchange_followed_by_infill(self):
+        with open(self.PURGE_INFILL_FILEPATH, 'w') as f:
+            f.write("T0\n;TYPE:Perimeter\nG1 X0 Y0 E5\nT1\n;TYPE:Wipe tower\nG1 X10 Y0 E5\n;TYPE:Internal infill\nG1 X100 Y0 E5\nT2\n;TYPE:Perimeter\nG1 X0 Y100 E5\n")
+
+        self.subject._insert_purge_infill_markers(self.PURGE_INFILL_FILEPATH)
+
+        with open(self.PURGE_INFILL_FILEPATH, 'r') as f:
+            lines = f.read().splitlines()
+        self.assertEqual(lines.count('MMU_PURGE_INFILL NEXT=1'), 1)
+        self.assertEqual(lines[3:5], ['MMU_PURGE_INFILL NEXT=1', 'T1'])
+
+class TestMmuServerDryerDoneMarkers(unittest.TestCase):
+    DRYER_FILEPATH = 'test/support/dryer.gcode'
+
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        shutil.copyfile('test/support/preheat.orig.gcode', self.DRYER_FILEPATH)
+
+    def tearDown(self):
+        os.remove(self.DRYER_FILEPATH)
+
+    def test_insert_dryer_done_marker_after_last_use_of_tool(self):
+        with open(self.DRYER_FILEPATH, 'w') as f:
+            f.write("T0\nG1 X0 Y0 E5\nT1\nG1 X10 Y0 E5\nT0\nG1 X0 Y10 E5\nT2\nG1 X10 Y10 E5\n")
+
+        result = self.subject._insert_dryer_done_markers(self.DRYER_FILEPATH)
+
+        self.assertTrue(result)
+        with open(self.DRYER_FILEPATH, 'r') as f:
+            lines = f.read().splitlines()
+        self.assertEqual(lines[4:6], ['T0', 'MMU_DRYER DONE=1'])
+        self.assertEqual(lines[7:9], ['T2', 'MMU_DRYER DONE=0'])
+        self.assertEqual(len([l for l in lines if l.startswith('MMU_DRYER')]), 2) # Final tool left to print end
+
+    def test_insert_dryer_done_markers_only_once(self):
+        self.subject._insert_dryer_done_markers(self.DRYER_FILEPATH)
+
+        result = self.subject._insert_dryer_done_markers(self.DRYER_FILEPATH)
+
+        self.assertFalse(result)
+        with open(self.DRYER_FILEPATH, 'r') as f:
+            self.assertEqual(f.read().count('MMU_DRYER DONE='), 1)
+
+class TestMmuServerJobQueue(unittest.TestCase):
+    SLICER_CONFIG_FILEPATH = 'test/support/slicer_config.gcode'
+
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        self.subject.file_manager.get_directory = MagicMock(return_value='test/support')
+        self.kapis = MagicMock()
+        self.kapis.run_gcode = AsyncMock()
+        self.job_queue = MagicMock()
+        self.subject.server.lookup_component = MagicMock(side_effect=lambda name, *args: self.job_queue if name == 'job_queue' else self.kapis)
+        shutil.copyfile('test/support/slicer_config.orig.gcode', self.SLICER_CONFIG_FILEPATH)
+
+    def tearDown(self):
+        os.remove(self.SLICER_CONFIG_FILEPATH)
+
+    def test_parse_slicer_filaments_per_tool(self):
+        materials, colors = self.subject._parse_slicer_filaments(self.SLICER_CONFIG_FILEPATH)
+
+        self.assertEqual(materials, ['PLA', 'PETG'])
+        self.assertEqual(colors, ['FF0000', '00ff00'])
+
+    def test_job_complete_prepares_next_queued_job(self):
+        self.job_queue.queued_jobs = {'job1': MagicMock(filename='slicer_config.gcode')}
+
+        asyncio.run(self.subject._job_complete({}, {}))
+
+        self.kapis.run_gcode.assert_called_once_with("MMU_PREPARE_JOB JOB='slicer_config.gcode' TOOLS=0,1 MATERIALS='PLA,PETG' COLORS='FF0000,00ff00'")
+
+    def test_job_complete_with_empty_queue(self):
+        self.job_queue.queued_jobs = {}
+
+        asyncio.run(self.subject._job_complete({}, {}))
+
+        self.kapis.run_gcode.assert_not_called()
+
+    def test_prepare_job_with_missing_file(self):
+        result = asyncio.run(self.subject.prepare_job('missing.gcode'))
+
+        self.assertFalse(result)
+        self.kapis.run_gcode.assert_not_called()
+
+
+class TestMmuServerMetrics(unittest.TestCase):
+    def setUp(self):
+        self.subject = MmuServer(MagicMock())
+        self.subject.metrics_influxdb_url = 'http://influxdb:8086/api/v2/write?bucket=mmu'
+        self.subject.metrics_influxdb_token = 'secret'
+        self.http_client = MagicMock()
+        self.http_client.request = AsyncMock(return_value=MagicMock())
+        self.subject.server.lookup_component = MagicMock(return_value=self.http_client)
+
+    def test_to_line_protocol_formats_tags_and_fields(self):
+        line = self.subject._to_line_protocol('mmu_swap', {'duration': 42.5, 'count': 3}, {'gate': 1, 'job': 'my part.gcode'}, 1700000000)
+
+        self.assertEqual(line, 'mmu_swap,gate=1,job=my\\ part.gcode count=3i,duration=42.5 1700000000000000000')
+
+    def test_record_metric_pushes_to_influxdb(self):
+        result = asyncio.run(self.subject.record_metric('mmu_phase', {'duration': 12.3}, {'phase': 'load', 'gate': 2}, timestamp=1700000000))
+
+        self.assertTrue(result)
+        self.http_client.request.assert_called_once_with(method='POST', url='http://influxdb:8086/api/v2/write?bucket=mmu',
+            body='mmu_phase,gate=2,phase=load duration=12.3 1700000000000000000',
+            headers={'Content-Type': 'text/plain; charset=utf-8', 'Authorization': 'Token secret'})
+
+    def test_record_metric_handles_influxdb_errors(self):
+        self.http_client.request.side_effect = Exception("influxdb unavailable")
+
+        result = asyncio.run(self.subject.record_metric('mmu_gate', {'loads': 5}, {'gate': 0}))
+
+        self.assertFalse(result)
+
+    def test_prometheus_exposes_latest_values_without_job_label(self):
+        self.subject.metrics_influxdb_url = None
+        asyncio.run(self.subject.record_metric('mmu_gate', {'slippage': 1.5}, {'gate': 0, 'job': 'a.gcode'}))
+        asyncio.run(self.subject.record_metric('mmu_gate', {'slippage': 2.5}, {'gate': 0, 'job': 'b.gcode'}))
+        asyncio.run(self.subject.record_metric('mmu_gate', {'slippage': 0.5}, {'gate': 1}))
+
+        text = self.subject._to_prometheus()
+
+        self.assertEqual(text, '# TYPE mmu_gate_slippage gauge\nmmu_gate_slippage{gate="0"} 2.5\nmmu_gate_slippage{gate="1"} 0.5\n')
+        self.http_client.request.assert_not_called()
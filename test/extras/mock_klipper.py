@@ -0,0 +1,1080 @@
+# Happy Hare MMU Software
+# Mock klipper environment for exercising the MMU state machine without a printer
+#
+# The klipper modules that Happy Hare builds on (toolhead, stepper, homing, extruder, chelper) are replaced with
+# stubs so 'extras.mmu' can be imported, and 'build_mmu()' constructs a real Mmu object wired to fake printer
+# objects. All filament movement is driven through a single 'FilamentSim' that models the filament as a segment
+# [tail, tip] along the path measured from the gate sensor:
+#
+#   gear (-gear_offset) -> gate sensor (0) -> encoder -> extruder sensor (bowden) -> extruder gears -> toolhead sensor -> nozzle
+#
+# Sensors, the encoder and homing endstops all report from the simulated filament so a test only has to set the
+# physical situation (filament in gate, stuck filament, runout, blocked selector) and call MMU commands
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# (\_/)
+# ( *,*)
+# (")_(") Happy Hare Ready
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import ast, copy, math, re, sys, types
+from unittest.mock import MagicMock
+
+##########################################
+# KLIPPER MODULE STUBS (before importing) #
+##########################################
+
+class _Stub(object):
+    def __init__(self, *args, **kwargs):
+        pass
+
+class _StubHoming(_Stub):
+    def __init__(self, printer=None, *args, **kwargs):
+        self.printer = printer
+        self.axes = []
+
+    def set_axes(self, axes):
+        self.axes = axes
+
+    def get_axes(self):
+        return self.axes
+
+def _stub_module(name, **attrs):
+    if name in sys.modules:
+        return sys.modules[name]
+    module = types.ModuleType(name)
+    for k, v in attrs.items():
+        setattr(module, k, v)
+    sys.modules[name] = module
+    return module
+
+_stub_module('chelper', get_ffi=MagicMock(return_value=(MagicMock(), MagicMock())))
+_stub_module('stepper', PrinterRail=type('PrinterRail', (_Stub,), {}), PrinterStepper=MagicMock())
+_stub_module('toolhead', ToolHead=type('ToolHead', (_Stub,), {}))
+_stub_module('extras.homing', Homing=_StubHoming, HomingMove=type('HomingMove', (_Stub,), {}))
+_stub_module('kinematics')
+_stub_module('kinematics.extruder', PrinterExtruder=type('PrinterExtruder', (_Stub,), {}),
+             DummyExtruder=type('DummyExtruder', (_Stub,), {}), ExtruderStepper=type('ExtruderStepper', (_Stub,), {}))
+_stub_module('extras.pulse_counter', FrequencyCounter=type('FrequencyCounter', (_Stub,), {}))
+
+class _StubControlPID(object):
+    def __init__(self, heater, config):
+        self.pid = tuple(config.getfloat(o) for o in ['pid_Kp', 'pid_Ki', 'pid_Kd'])
+
+_stub_module('extras.heaters', ControlPID=_StubControlPID)
+
+import extras.mmu as mmu_module
+from extras.mmu import Mmu, MmuError
+from extras.mmu_toolhead import MmuToolHead
+
+class CommandError(Exception):
+    pass
+
+_REQUIRED = object()
+
+###############################
+# PHYSICAL FILAMENT SIMULATION #
+###############################
+
+class FilamentSim:
+    STEP = 0.5 # mm resolution of simulated movement
+
+    def __init__(self, bowden_length=400., gear_offset=40., encoder_offset=8., extruder_entry_to_gears=10.,
+                 extruder_to_sensor=10., extruder_to_nozzle=70.):
+        self.gear_pos = -gear_offset
+        self.gate_pos = 0.
+        self.encoder_pos = encoder_offset
+        self.extruder_sensor_pos = bowden_length
+        self.extruder_gears_pos = bowden_length + extruder_entry_to_gears
+        self.toolhead_sensor_pos = self.extruder_gears_pos + extruder_to_sensor
+        self.nozzle_pos = self.extruder_gears_pos + extruder_to_nozzle
+
+        # Filament of each gate as [tail, tip] or None if gate is empty. Only the selected gate is in the path
+        self.gates = {}
+        self.selected_gate = None
+        self.gate_offsets = []
+        self.gear_engaged = True
+
+        # Failure injection
+        self.jam_pos = None         # Filament tip cannot advance past this position
+        self.stuck = False          # Filament is seized and won't move in either direction
+        self.runout_after = None    # Tail of spool is released after this much more gear movement
+        self.encoder_slip = 0.      # Fraction of movement not seen by encoder
+        self.gear_slip = 0.         # Fraction of gear movement not delivered to filament (when extruder isn't driving)
+        self.selector_obstacle = None # Selector stalls at this position (one shot unless 'selector_blocked')
+        self.selector_blocked = False # Selector home fails
+
+        self.encoder_distance = 0.
+        self.gear_moved = 0.
+        self.extruder_moved = 0.
+
+    # Filament setup ------------------------------------------------------
+
+    def insert_filament(self, gate, tip=-10., tail=None):
+        self.gates[gate] = [tail if tail is not None else float('-inf'), tip]
+
+    def remove_filament(self, gate):
+        self.gates.pop(gate, None)
+
+    def set_tip(self, pos, gate=None):
+        gate = self.selected_gate if gate is None else gate
+        self.gates[gate][1] = pos
+
+    def tip(self, gate=None):
+        filament = self.gates.get(self.selected_gate if gate is None else gate)
+        return filament[1] if filament else None
+
+    def load_to_nozzle(self, gate):
+        self.insert_filament(gate, tip=self.nozzle_pos)
+
+    # The gate in the filament path is whichever the selector is aligned with
+    def align_selector(self, pos):
+        matches = [gate for gate, offset in enumerate(self.gate_offsets) if abs(offset - pos) < 1.]
+        self.selected_gate = matches[0] if matches else None
+
+    def _filament(self):
+        return self.gates.get(self.selected_gate)
+
+    def present(self, pos):
+        filament = self._filament()
+        return filament is not None and filament[0] <= pos <= filament[1]
+
+    # Sensors -------------------------------------------------------------
+
+    def sensor_position(self, name):
+        return {
+            'mmu_gate': self.gate_pos,
+            'extruder': self.extruder_sensor_pos,
+            'toolhead': self.toolhead_sensor_pos,
+        }.get(name)
+
+    def sensor_present(self, name):
+        pos = self.sensor_position(name)
+        return pos is not None and self.present(pos)
+
+    # Movement ------------------------------------------------------------
+
+    # Move the gear and/or extruder steppers by 'dist'. The filament moves if any stepper that moves has grip on it
+    def move(self, dist, gear=False, extruder=False, stop=None):
+        remaining = dist
+        step = self.STEP if dist > 0 else -self.STEP
+        moved = 0.
+        while abs(remaining) > 1e-9:
+            d = step if abs(remaining) > self.STEP else remaining
+            if stop is not None and stop():
+                return moved, True
+            self._move_step(d, gear, extruder)
+            remaining -= d
+            moved += d
+        if stop is not None and stop():
+            return moved, True
+        return moved, False
+
+    def _move_step(self, d, gear, extruder):
+        if gear:
+            self.gear_moved += d
+        if extruder:
+            self.extruder_moved += d
+        filament = self._filament()
+        if filament is None:
+            return
+        driven = (gear and self.gear_engaged and self.present(self.gear_pos)) or (extruder and self.present(self.extruder_gears_pos))
+        if not driven or self.stuck:
+            return
+        if gear and not (extruder and self.present(self.extruder_gears_pos)):
+            d *= 1. - self.gear_slip
+        if d > 0 and self.jam_pos is not None and filament[1] + d > self.jam_pos:
+            d = max(self.jam_pos - filament[1], 0.)
+        if gear and self.runout_after is not None:
+            self.runout_after -= abs(d)
+            if self.runout_after <= 0 and filament[0] == float('-inf'):
+                filament[0] = self.gear_pos - 1. # Spool end has left the spool, tail now just behind gear
+                self.runout_after = None
+        encoder_saw = self.present(self.encoder_pos)
+        filament[0] += d
+        filament[1] += d
+        encoder_saw = encoder_saw or self.present(self.encoder_pos)
+        if encoder_saw:
+            self.encoder_distance += abs(d) * (1. - self.encoder_slip)
+
+        # Filament pulled completely clear of the gear is lost from the gate
+        if filament[1] < self.gear_pos - 50.:
+            self.gates.pop(self.selected_gate)
+
+
+##############################
+# FAKE KLIPPER PRINTER OBJECTS #
+##############################
+
+class FakeReactor:
+    NEVER = 9999999999999999.
+    NOW = 0.
+
+    def __init__(self):
+        self.now = 1000.
+        self.timers = []
+        self.callbacks = []
+
+    def monotonic(self):
+        return self.now
+
+    def pause(self, waketime):
+        self.now = max(self.now, waketime)
+        return self.now
+
+    def register_timer(self, callback, waketime=NEVER):
+        timer = [callback, waketime]
+        self.timers.append(timer)
+        return timer
+
+    def update_timer(self, timer, waketime):
+        timer[1] = waketime
+
+    def unregister_timer(self, timer):
+        if timer in self.timers:
+            self.timers.remove(timer)
+
+    def register_callback(self, callback, waketime=NOW):
+        self.callbacks.append(callback)
+
+    def completion(self):
+        return MagicMock()
+
+class FakeConfig:
+    error = CommandError
+
+    def __init__(self, printer, values=None, name='mmu', sections=None):
+        self.printer = printer
+        self.values = values or {}
+        self.name = name
+        self.sections = sections or {}
+        self.fileconfig = MagicMock()
+
+    def get_printer(self):
+        return self.printer
+
+    def get_name(self):
+        return self.name
+
+    def _get(self, option, default):
+        if option in self.values:
+            return self.values[option]
+        if default is _REQUIRED:
+            raise self.error("Option '%s' in section '%s' must be specified" % (option, self.name))
+        return default
+
+    def get(self, option, default=_REQUIRED, **kwargs):
+        return self._get(option, default)
+
+    getint = getfloat = getboolean = get
+
+    def getchoice(self, option, choices, default=_REQUIRED, **kwargs):
+        return self._get(option, default)
+
+    def getlist(self, option, default=_REQUIRED, **kwargs):
+        return self._get(option, default)
+
+    getintlist = getfloatlist = getlist
+
+    def getsection(self, name):
+        return FakeConfig(self.printer, self.sections.get(name, {}), name, self.sections)
+
+    def has_section(self, name):
+        return name in self.sections
+
+    def get_prefix_sections(self, prefix):
+        return [self.getsection(s) for s in self.sections if s.startswith(prefix)]
+
+    def get_prefix_options(self, prefix):
+        return [o for o in self.values if o.startswith(prefix)]
+
+class FakeGCodeCommand:
+    def __init__(self, gcode, command, params, commandline):
+        self.gcode = gcode
+        self.command = command
+        self.params = params
+        self.commandline = commandline
+        self.error = gcode.error
+
+    def get_command(self):
+        return self.command
+
+    def get_commandline(self):
+        return self.commandline
+
+    def get_command_parameters(self):
+        return self.params
+
+    def get_raw_command_parameters(self):
+        return self.commandline[len(self.command):].strip()
+
+    def respond_info(self, msg, log=True):
+        self.gcode.respond_info(msg, log)
+
+    def respond_raw(self, msg):
+        self.gcode.respond_raw(msg)
+
+    def _get(self, name, default, parser, minval=None, maxval=None, above=None, below=None):
+        value = self.params.get(name.upper())
+        if value is None:
+            if default is _REQUIRED:
+                raise self.error("Error on '%s': missing %s" % (self.commandline, name))
+            return default
+        try:
+            value = parser(value)
+        except ValueError:
+            raise self.error("Error on '%s': unable to parse %s" % (self.commandline, value))
+        if (minval is not None and value < minval) or (maxval is not None and value > maxval) or \
+                (above is not None and value <= above) or (below is not None and value >= below):
+            raise self.error("Error on '%s': %s out of range" % (self.commandline, name))
+        return value
+
+    def get(self, name, default=_REQUIRED, parser=str, **kwargs):
+        return self._get(name, default, parser, **kwargs)
+
+    def get_int(self, name, default=_REQUIRED, **kwargs):
+        return self._get(name, default, int, **kwargs)
+
+    def get_float(self, name, default=_REQUIRED, **kwargs):
+        return self._get(name, default, float, **kwargs)
+
+class FakeGCode:
+    def __init__(self, printer):
+        self.printer = printer
+        self.error = CommandError
+        self.ready_gcode_handlers = {}
+        self.gcode_help = {}
+        self.messages = []
+        self.scripts = []
+        for cmd in ['PAUSE', 'RESUME', 'CLEAR_PAUSE', 'CANCEL_PRINT']:
+            self.register_command(cmd, self._cmd_noop)
+
+    def _cmd_noop(self, gcmd):
+        pass
+
+    def register_command(self, cmd, func, when_not_ready=False, desc=None):
+        prev = self.ready_gcode_handlers.get(cmd)
+        if func is None:
+            self.ready_gcode_handlers.pop(cmd, None)
+        else:
+            self.ready_gcode_handlers[cmd] = func
+            if desc is not None:
+                self.gcode_help[cmd] = desc
+        return prev
+
+    def register_mux_command(self, cmd, key, value, func, desc=None):
+        self.ready_gcode_handlers[cmd] = func
+
+    def create_gcode_command(self, command, commandline, params):
+        return FakeGCodeCommand(self, command, params, commandline)
+
+    def respond_info(self, msg, log=True):
+        self.messages.append(msg)
+
+    def respond_raw(self, msg):
+        self.messages.append(msg)
+
+    def run_script(self, script):
+        self.run_script_from_command(script)
+
+    def run_script_from_command(self, script):
+        for line in script.split('\n'):
+            line = line.strip()
+            if not line:
+                continue
+            self.scripts.append(line)
+            parts = line.split()
+            cmd = parts[0].upper()
+            params = {}
+            for arg in parts[1:]:
+                if '=' in arg:
+                    k, v = arg.split('=', 1)
+                    params[k.upper()] = v.strip("'\"")
+            gcmd = FakeGCodeCommand(self, cmd, params, line)
+            handler = self.ready_gcode_handlers.get(cmd)
+            if handler is not None:
+                handler(gcmd)
+                continue
+            macro = self.printer.objects.get("gcode_macro %s" % cmd)
+            if macro is not None and macro.handler is not None:
+                macro.handler(gcmd)
+
+class FakeMacro:
+    def __init__(self, handler=None, variables=None):
+        self.handler = handler
+        self.variables = dict(variables or {})
+
+# Config as it would be re-read from disk. Tests edit 'sections' to simulate changes to the config files
+class FakeConfigFile:
+    def __init__(self, printer, sections):
+        self.printer = printer
+        self.sections = copy.deepcopy(sections)
+
+    def read_main_config(self):
+        return FakeConfig(self.printer, {}, 'printer', copy.deepcopy(self.sections))
+
+class FakeSaveVariables:
+    def __init__(self, gcode, variables):
+        self.allVariables = dict(variables)
+        gcode.register_command('SAVE_VARIABLE', self.cmd_SAVE_VARIABLE)
+
+    def cmd_SAVE_VARIABLE(self, gcmd):
+        value = re.search(r"VALUE=(.*)$", gcmd.get_commandline()).group(1).strip("'\"")
+        try:
+            value = ast.literal_eval(value)
+        except (ValueError, SyntaxError):
+            pass
+        self.allVariables[gcmd.get('VARIABLE')] = value
+
+class FakeRunoutHelper:
+    def __init__(self, sim, name):
+        self.sim = sim
+        self.name = name
+        self.sensor_enabled = True
+        self.runout_pause = True
+        self.runout_gcode = self.insert_gcode = None
+
+    @property
+    def filament_present(self):
+        return self.sim.sensor_present(self.name)
+
+    def note_filament_present(self, *args, **kwargs):
+        pass
+
+class FakeSensor:
+    def __init__(self, sim, name):
+        self.runout_helper = FakeRunoutHelper(sim, name)
+
+    def get_status(self, eventtime):
+        return {'filament_detected': self.runout_helper.filament_present, 'enabled': self.runout_helper.sensor_enabled}
+
+class FakeEncoder:
+    def __init__(self, sim):
+        self.sim = sim
+        self.resolution = 1.
+        self.enabled = False
+        self.mode = 0
+        self.clog_length = 15.
+        self.offset = 0.
+
+    def get_distance(self):
+        return self.sim.encoder_distance + self.offset
+
+    def set_distance(self, distance):
+        self.offset = distance - self.sim.encoder_distance
+
+    def get_counts(self):
+        return int(self.get_distance() / self.resolution)
+
+    def reset_counts(self):
+        self.set_distance(0.)
+
+    def set_resolution(self, resolution):
+        self.resolution = resolution
+
+    def get_resolution(self):
+        return self.resolution
+
+    def get_clog_detection_length(self):
+        return self.clog_length
+
+    def set_clog_detection_length(self, clog_length):
+        self.clog_length = clog_length
+
+    def update_clog_detection_length(self):
+        pass
+
+    def set_mode(self, mode):
+        self.mode = mode
+
+    def set_extruder(self, extruder_name):
+        pass
+
+    def set_logger(self, log):
+        pass
+
+    def enable(self):
+        self.enabled = True
+
+    def disable(self):
+        self.enabled = False
+
+    def is_enabled(self):
+        return self.enabled
+
+    def get_status(self, eventtime):
+        return {'encoder_pos': round(self.get_distance(), 1), 'detection_length': self.clog_length, 'min_headroom': 0.,
+                'headroom': 0., 'desired_headroom': 0., 'detection_mode': self.mode, 'enabled': self.enabled, 'flow_rate': 100}
+
+class FakeTmcFields:
+    def __init__(self):
+        self.sg_result = 250
+
+    def lookup_register(self, field, default=None):
+        return "SG_RESULT" if field == "sg_result" else default
+
+    def get_field(self, field, reg_value=None, reg_name=None):
+        return self.sg_result
+
+class FakeTmc:
+    def __init__(self, gcode, run_current=1.):
+        self.run_current = run_current
+        self.drv_status = {}
+        self.fields = FakeTmcFields()
+        self.mcu_tmc = MagicMock()
+        gcode.register_command('SET_TMC_CURRENT', self.cmd_SET_TMC_CURRENT)
+
+    def cmd_SET_TMC_CURRENT(self, gcmd):
+        self.run_current = gcmd.get_float('CURRENT')
+
+    def get_status(self, eventtime):
+        return {'run_current': self.run_current, 'drv_status': dict(self.drv_status)}
+
+class FakeTemperatureSensor:
+    def __init__(self, temperature=25.):
+        self.temperature = temperature
+
+    def get_status(self, eventtime):
+        return {'temperature': self.temperature}
+
+class FakeServo:
+    def __init__(self, sim, down_angle):
+        self.sim = sim
+        self.down_angle = down_angle
+        self.angle = None
+
+    def set_value(self, angle=None, duration=None, **kwargs):
+        self.angle = angle
+        self.sim.gear_engaged = angle == self.down_angle
+
+    def get_status(self, eventtime):
+        return {'value': self.angle}
+
+class FakeEndstop:
+    def __init__(self, query):
+        self.query = query
+
+    def add_stepper(self, *args, **kwargs):
+        pass
+
+    def query_endstop(self, print_time=None):
+        return self.query()
+
+class FakeStepper:
+    def __init__(self, name, position=lambda: 0.):
+        self.name = name
+        self.position = position
+        self.rotation_distance = 22.7
+        self.dir_inverted = False
+
+    def get_name(self):
+        return self.name
+
+    def get_dir_inverted(self):
+        return self.dir_inverted, False
+
+    def set_dir_inverted(self, invert_dir):
+        self.dir_inverted = invert_dir
+
+    def get_commanded_position(self):
+        return self.position()
+
+    def get_mcu_position(self):
+        return int(self.position() * 100)
+
+    def get_step_dist(self):
+        return 0.01
+
+    def get_rotation_distance(self):
+        return self.rotation_distance, 200
+
+    def set_rotation_distance(self, rotation_distance):
+        self.rotation_distance = rotation_distance
+
+class FakeRail:
+    def __init__(self, steppers):
+        self.steppers = steppers
+        self.endstops = []
+        self.extra_endstops = []
+        self.virtual_endstops = []
+
+    def get_steppers(self):
+        return self.steppers
+
+    def get_endstops(self):
+        return self.endstops
+
+    def add_extra_endstop(self, mcu_endstop, name, virtual=False):
+        self.extra_endstops.append((mcu_endstop, name))
+        if virtual:
+            self.virtual_endstops.append(name)
+        return mcu_endstop
+
+    def get_extra_endstop_names(self):
+        return [x[1] for x in self.extra_endstops]
+
+    def get_extra_endstop(self, name):
+        matches = [x for x in self.extra_endstops if x[1] == name]
+        return list(matches) if matches else None
+
+    def is_endstop_virtual(self, name):
+        return name in self.virtual_endstops if name else False
+
+class FakeKinematics:
+    def __init__(self, toolhead, sim, gear_steppers=1):
+        self.toolhead = toolhead
+        self.sim = sim
+        gear_names = [MmuToolHead.get_gear_stepper_name(None, gate) for gate in range(gear_steppers)]
+        self.rails = [
+            FakeRail([FakeStepper('stepper_mmu_selector', lambda: toolhead.position[0])]),
+            FakeRail([FakeStepper(name, lambda: toolhead.position[1]) for name in gear_names]),
+        ]
+
+    def home(self, homing_state):
+        if self.sim.selector_blocked:
+            raise CommandError("No trigger on mmu_selector after full movement")
+        self.sim.selector_obstacle = None
+        self.toolhead.position[0] = 0.
+        self.sim.align_selector(0.)
+
+    def set_accel_limit(self, accel):
+        return accel
+
+    def get_status(self, eventtime):
+        return {}
+
+# Fake of klipper's HomingMove that steps the simulation until the endstop reaches the requested state
+class FakeHomingMove:
+    def __init__(self, printer, endstops, toolhead=None):
+        self.printer = printer
+        self.endstops = endstops
+        self.toolhead = toolhead
+
+    def homing_move(self, movepos, speed, probe_pos=False, triggered=True, check_triggered=True):
+        endstop = self.endstops[0][0]
+        stop = lambda: endstop.query_endstop() == triggered
+        axis = 0 if movepos[0] != self.toolhead.position[0] else 1
+        dist = movepos[axis] - self.toolhead.position[axis]
+        moved, hit = self.toolhead.homing_step(axis, dist, stop)
+        if not hit and check_triggered:
+            raise CommandError("No trigger on %s after full movement" % self.endstops[0][1])
+        return list(self.toolhead.position)
+
+    def check_no_movement(self):
+        return None
+
+class FakeMmuToolHead:
+    # Gear stepper selection logic is the real MmuToolHead's
+    get_gear_stepper_name = MmuToolHead.get_gear_stepper_name
+    select_opposed_gear_steppers = MmuToolHead.select_opposed_gear_steppers
+
+    def select_gear_stepper(self, gate):
+        MmuToolHead.select_gear_stepper(self, gate)
+        if len(self.all_gear_rail_steppers) > 1: # Gear per gate so selected gear decides the filament path
+            self.sim.selected_gate = gate if gate >= 0 else None
+
+    def __init__(self, printer, sim, gear_steppers=1):
+        self.printer = printer
+        self.sim = sim
+        self.position = [0., 0., 0., 0.]
+        self.kin = FakeKinematics(self, sim, gear_steppers)
+        self.mmu_extruder_stepper = types.SimpleNamespace(stepper=FakeStepper('extruder', lambda: printer.toolhead.position[3]))
+        self.gear_mux = None
+        self.all_gear_rail_steppers = list(self.kin.rails[1].steppers)
+        self.opposed_gear_steppers = []
+        self.gear_motion_queue = None
+        self.extruder_synced_to_gear = None
+        self.extruder_only = False
+
+    def get_kinematics(self):
+        return self.kin
+
+    def get_position(self):
+        return list(self.position)
+
+    def set_position(self, newpos, homing_axes=()):
+        self.position = list(newpos) + [0.] * (4 - len(newpos))
+
+    def get_last_move_time(self):
+        return self.printer.reactor.now
+
+    def wait_moves(self):
+        pass
+
+    def dwell(self, delay):
+        self.printer.reactor.now += delay
+
+    def get_selector_limits(self):
+        return 200., 1000.
+
+    def get_gear_limits(self):
+        return 300., 1500.
+
+    def _selector_move(self, dist, stop=None):
+        target = self.position[0] + dist
+        obstacle = self.sim.selector_obstacle
+        if obstacle is not None and min(self.position[0], target) <= obstacle <= max(self.position[0], target):
+            self.position[0] = obstacle # Stalled
+        else:
+            self.position[0] = target
+        self.sim.align_selector(self.position[0])
+
+    def _filament_move(self, dist, stop=None):
+        if self.opposed_gear_steppers:
+            return self._opposed_filament_move(dist)
+        gear = self.extruder_synced_to_gear is None or not self.extruder_only
+        extruder = self.extruder_synced_to_gear is not None
+        moved, hit = self.sim.move(dist, gear=gear, extruder=extruder, stop=stop)
+        self.position[1] += moved
+        if extruder:
+            self.printer.toolhead.position[3] += moved
+        return moved, hit
+
+    # Each gear on the rail drives its own gate's filament, reversed gears in the opposite direction
+    def _opposed_filament_move(self, dist):
+        selected = self.sim.selected_gate
+        for s in self.kin.rails[1].steppers:
+            self.sim.selected_gate = self.all_gear_rail_steppers.index(s)
+            self.sim.move(-dist if s.dir_inverted else dist, gear=True)
+        self.sim.selected_gate = selected
+        self.position[1] += dist
+        return dist, False
+
+    def move(self, newpos, speed):
+        if newpos[0] != self.position[0]:
+            self._selector_move(newpos[0] - self.position[0])
+        if newpos[1] != self.position[1]:
+            self._filament_move(newpos[1] - self.position[1])
+
+    def homing_step(self, axis, dist, stop):
+        if axis == 0:
+            stop_pos = self.sim.selector_obstacle
+            self._selector_move(dist)
+            return dist, stop_pos is not None and self.position[0] == stop_pos
+        return self._filament_move(dist, stop)
+
+    def select_gear_steppers(self, selected_steppers):
+        for s in self.opposed_gear_steppers:
+            s.set_dir_inverted(False)
+        self.opposed_gear_steppers = []
+        gear_rail = self.kin.rails[1]
+        gear_rail.steppers = [s for s in self.all_gear_rail_steppers if s.get_name() in (selected_steppers or [])]
+        if selected_steppers and not gear_rail.steppers:
+            raise CommandError("None of these `%s` gear steppers where found!" % selected_steppers)
+
+    def is_gear_synced_to_extruder(self):
+        return self.gear_motion_queue is not None
+
+    def is_extruder_synced_to_gear(self):
+        return self.extruder_synced_to_gear is not None
+
+    def is_synced(self):
+        return self.is_gear_synced_to_extruder() or self.is_extruder_synced_to_gear()
+
+    def sync_gear_to_extruder(self, extruder_name):
+        if self.extruder_synced_to_gear:
+            self.sync_extruder_to_gear(None)
+        self.gear_motion_queue = extruder_name
+
+    def check_gear_synced_to_extruder(self):
+        return None if self.gear_motion_queue else "gear is not synced to extruder"
+
+    def sync_extruder_to_gear(self, extruder_name, extruder_only=False):
+        if self.gear_motion_queue:
+            self.sync_gear_to_extruder(None)
+        self.extruder_synced_to_gear = extruder_name
+        self.extruder_only = extruder_only if extruder_name else False
+
+class FakeToolHead:
+    def __init__(self, printer, sim):
+        self.printer = printer
+        self.sim = sim
+        self.position = [0., 0., 0., 0.]
+        self.step_generators = []
+
+    def get_position(self):
+        return list(self.position)
+
+    def set_position(self, newpos, homing_axes=()):
+        self.position = list(newpos)
+
+    # Each gear on the rail drives its own gate's filament, reversed gears in the opposite direction
+    def _opposed_filament_move(self, dist):
+        selected = self.sim.selected_gate
+        for s in self.kin.rails[1].steppers:
+            self.sim.selected_gate = self.all_gear_rail_steppers.index(s)
+            self.sim.move(-dist if s.dir_inverted else dist, gear=True)
+        self.sim.selected_gate = selected
+        self.position[1] += dist
+        return dist, False
+
+    def move(self, newpos, speed):
+        dist = newpos[3] - self.position[3]
+        self.position[:3] = newpos[:3]
+        if dist:
+            gear = self.printer.mmu_toolhead.is_gear_synced_to_extruder()
+            moved, _ = self.sim.move(dist, gear=gear, extruder=True)
+            self.position[3] += moved
+            if gear:
+                self.printer.mmu_toolhead.position[1] += moved
+
+    def manual_move(self, coord, speed):
+        newpos = [c if c is not None else p for c, p in zip(coord, self.position)]
+        self.move(newpos + self.position[len(newpos):], speed)
+
+    def wait_moves(self):
+        pass
+
+    def dwell(self, delay):
+        self.printer.reactor.now += delay
+
+    def get_last_move_time(self):
+        return self.printer.reactor.now
+
+    def flush_step_generation(self):
+        pass
+
+    def register_step_generator(self, handler):
+        pass
+
+    def get_extruder(self):
+        return self.printer.objects['extruder']
+
+    def get_status(self, eventtime):
+        return {'homed_axes': 'xyz', 'position': self.get_position(), 'max_velocity': 300., 'max_accel': 3000.,
+                'axis_minimum': types.SimpleNamespace(x=0., y=0., z=0.), 'axis_maximum': types.SimpleNamespace(x=300., y=300., z=300.)}
+
+class FakeHeater:
+    def __init__(self, temp):
+        self.target_temp = temp
+        self.temperature = temp
+        self.min_extrude_temp = 170.
+        self.max_temp = 300.
+        self.can_extrude = True
+        self.control = None
+
+    def set_control(self, control):
+        old_control, self.control = self.control, control
+        return old_control
+
+    def get_temp(self, eventtime):
+        return self.temperature, self.target_temp
+
+class FakeHeaters:
+    def __init__(self):
+        self.heaters = {}
+
+    def get_all_heaters(self):
+        return list(self.heaters)
+
+    def lookup_heater(self, name):
+        return self.heaters[name]
+
+    def cmd_SET_HEATER_TEMPERATURE(self, gcmd):
+        heater = self.heaters.get(gcmd.get('HEATER'))
+        if heater is not None:
+            heater.target_temp = gcmd.get_float('TARGET', 0.)
+
+class FakeExtruder:
+    def __init__(self, temp=220.):
+        self.heater = FakeHeater(temp)
+        self.filament_area = math.pi * (1.75 * .5) ** 2
+        self.pressure_advance = 0.04
+        self.smooth_time = 0.04
+
+    def get_heater(self):
+        return self.heater
+
+    def get_status(self, eventtime):
+        return {'temperature': self.heater.temperature, 'target': self.heater.target_temp, 'can_extrude': True,
+                'pressure_advance': self.pressure_advance, 'smooth_time': self.smooth_time}
+
+    def cmd_SET_PRESSURE_ADVANCE(self, gcmd):
+        self.pressure_advance = gcmd.get_float('ADVANCE', self.pressure_advance)
+        self.smooth_time = gcmd.get_float('SMOOTH_TIME', self.smooth_time)
+
+class FakePrintStats:
+    def __init__(self):
+        self.state = "standby"
+        self.filename = ""
+
+    def get_status(self, eventtime):
+        return {'state': self.state, 'filename': self.filename}
+
+class FakeIdleTimeout:
+    def __init__(self):
+        self.idle_timeout = 600.
+        self.state = "Ready"
+
+    def get_status(self, eventtime):
+        return {'state': self.state}
+
+class FakePauseResume:
+    def __init__(self):
+        self.is_paused = False
+
+    def get_status(self, eventtime):
+        return {'is_paused': self.is_paused}
+
+class FakeGCodeMove:
+    def __init__(self):
+        self.transform = None
+
+    def get_status(self, eventtime):
+        return {'speed_factor': 1., 'extrude_factor': 1., 'absolute_coordinates': True, 'absolute_extrude': True,
+                'gcode_position': [0., 0., 0., 0.], 'position': [0., 0., 0., 0.], 'homing_origin': [0., 0., 0., 0.]}
+
+    def set_move_transform(self, transform, force=False):
+        prev, self.transform = self.transform, transform
+        return prev
+
+class FakePrinter:
+    command_error = CommandError
+
+    def __init__(self, sim):
+        self.sim = sim
+        self.reactor = FakeReactor()
+        self.objects = {}
+        self.event_handlers = {}
+        self.events = []
+        self.start_args = {'log_file': None}
+        self.gcode = FakeGCode(self)
+        self.objects['gcode'] = self.gcode
+        self.objects['gcode_move'] = FakeGCodeMove()
+        self.toolhead = FakeToolHead(self, sim)
+        self.objects['gcode_move'].transform = self.toolhead # Toolhead is always the last move transform
+        self.objects['toolhead'] = self.toolhead
+        self.objects['extruder'] = FakeExtruder()
+        self.gcode.register_command('SET_PRESSURE_ADVANCE', self.objects['extruder'].cmd_SET_PRESSURE_ADVANCE)
+        self.objects['print_stats'] = FakePrintStats()
+        self.objects['idle_timeout'] = FakeIdleTimeout()
+        self.objects['pause_resume'] = FakePauseResume()
+        self.objects['webhooks'] = MagicMock()
+        self.objects['stepper_enable'] = MagicMock()
+        self.objects['heaters'] = FakeHeaters()
+        self.gcode.register_command('SET_HEATER_TEMPERATURE', self.objects['heaters'].cmd_SET_HEATER_TEMPERATURE)
+        self.objects['pins'] = MagicMock()
+        self.objects['mcu'] = types.SimpleNamespace(estimated_print_time=lambda eventtime: eventtime)
+        self.mmu_toolhead = None
+
+    def get_reactor(self):
+        return self.reactor
+
+    def lookup_object(self, name, default=_REQUIRED):
+        if name in self.objects:
+            return self.objects[name]
+        if default is _REQUIRED:
+            raise CommandError("Unknown config object '%s'" % name)
+        return default
+
+    def lookup_objects(self, module=None):
+        return [(name, obj) for name, obj in self.objects.items() if module is None or name == module or name.startswith(module + " ")]
+
+    def load_object(self, config, name, default=_REQUIRED):
+        return self.lookup_object(name, default)
+
+    def register_event_handler(self, event, callback):
+        self.event_handlers.setdefault(event, []).append(callback)
+
+    def send_event(self, event, *params):
+        self.events.append((event, params))
+        return [cb(*params) for cb in self.event_handlers.get(event, [])]
+
+    def is_shutdown(self):
+        return False
+
+#########################
+# MMU CONSTRUCTION HELPER #
+#########################
+
+DEFAULT_CONFIG = {
+    'happy_hare_version': Mmu.VERSION,
+    'mmu_vendor': "ERCF",
+    'mmu_version': "2.0",
+    'mmu_num_gates': 4,
+    'servo_down_angle': 140.,
+    'servo_up_angle': 70.,
+    'gate_homing_endstop': "mmu_gate",
+    'extruder_homing_endstop': "extruder",
+    'toolhead_extruder_to_nozzle': 70.,
+    'toolhead_sensor_to_nozzle': 60.,
+    'toolhead_entry_to_extruder': 10.,
+    'log_level': 0,
+    'log_file_level': -1,
+    'selector_offsets': [4., 27., 50., 73.],
+}
+
+DEFAULT_VARIABLES = {
+    'mmu_gear_rotation_distance': 22.7,
+    'mmu_encoder_resolution': 1.,
+    'mmu_selector_offsets': [4., 27., 50., 73.],
+    'mmu_calibration_bowden_length': 400.,
+    'mmu_calibration_0': 1.,
+    'mmu_calibration_1': 1.,
+    'mmu_calibration_2': 1.,
+    'mmu_calibration_3': 1.,
+}
+
+# Build a real Mmu backed by the mock environment. 'config' and 'variables' override the defaults above and
+# 'sensors' lists which of "mmu_gate", "extruder" and "toolhead" filament sensors are fitted and 'gear_steppers'
+# is the number of gear motors (one per gate for multi-gear designs)
+def build_mmu(config=None, variables=None, sensors=("mmu_gate", "extruder", "toolhead"), encoder=True, selector_touch=False, sim=None,
+              gear_tmc=False, temperature_sensors=(), gear_steppers=1, heaters=()):
+    mmu_module.MmuToolHead = lambda config, homing_extruder: config.get_printer().mmu_toolhead
+    mmu_module.HomingMove = FakeHomingMove
+
+    values = dict(DEFAULT_CONFIG)
+    values.update(config or {})
+    sim = sim or FilamentSim(bowden_length=DEFAULT_VARIABLES['mmu_calibration_bowden_length'],
+                             extruder_entry_to_gears=values['toolhead_entry_to_extruder'],
+                             extruder_to_sensor=values['toolhead_extruder_to_nozzle'] - values['toolhead_sensor_to_nozzle'],
+                             extruder_to_nozzle=values['toolhead_extruder_to_nozzle'])
+    printer = FakePrinter(sim)
+    printer.mmu_toolhead = FakeMmuToolHead(printer, sim, gear_steppers)
+    gear_rail = printer.mmu_toolhead.kin.rails[1]
+    selector_rail = printer.mmu_toolhead.kin.rails[0]
+
+    saved = dict(DEFAULT_VARIABLES)
+    saved.update(variables or {})
+    printer.objects['save_variables'] = FakeSaveVariables(printer.gcode, saved)
+    printer.objects['mmu_servo mmu_servo'] = FakeServo(sim, values['servo_down_angle'])
+    if encoder:
+        printer.objects['mmu_encoder mmu_encoder'] = FakeEncoder(sim)
+    if gear_tmc:
+        printer.objects['tmc2209 stepper_mmu_gear'] = FakeTmc(printer.gcode)
+    for name in temperature_sensors:
+        printer.objects['temperature_sensor %s' % name] = FakeTemperatureSensor()
+    for name in heaters:
+        printer.objects['heaters'].heaters[name] = FakeHeater(0.)
+
+    sections = {}
+    for name in sensors:
+        section = "filament_switch_sensor %s_sensor" % name
+        sections[section] = {'switch_pin': "mmu:%s" % name}
+        printer.objects[section] = FakeSensor(sim, name)
+    # Pins resolve straight to endstops that query the simulated sensor of the same name
+    gear_rail.add_extra_endstop = lambda pin, name: FakeRail.add_extra_endstop(gear_rail, FakeEndstop(lambda: sim.sensor_present(name)), name)
+    if selector_touch:
+        FakeRail.add_extra_endstop(selector_rail, FakeEndstop(lambda: False), Mmu.ENDSTOP_SELECTOR_TOUCH, virtual=True)
+
+    # Standalone tip forming just retracts filament from the nozzle by a fixed amount
+    def form_tip(gcmd):
+        pos = printer.toolhead.get_position()
+        pos[3] -= 20.
+        printer.toolhead.move(pos, 50.)
+    printer.objects['gcode_macro _MMU_FORM_TIP_STANDALONE'] = FakeMacro(form_tip)
+
+    sections['mmu'] = values
+    sections['gcode_macro _MMU_FORM_TIP_STANDALONE'] = {}
+    printer.objects['configfile'] = FakeConfigFile(printer, sections)
+
+    mmu = Mmu(FakeConfig(printer, values, sections=sections))
+    sim.gate_offsets = mmu.selector_offsets
+    printer.objects['mmu'] = mmu
+    for event in ["klippy:connect", "klippy:ready"]:
+        for handler in printer.event_handlers.get(event, []):
+            handler()
+    return mmu, printer, sim
@@ -0,0 +1,138 @@
+# Happy Hare MMU Software
+# Offline replay of a toolchange journal (see 'enable_journal') against the mock klipper environment
+#
+# Usage: python3 -m test.extras.replay_journal <journal.jsonl> [option=value ...]
+#
+# The starting state of the journaled toolchange is recreated on the simulated MMU and the toolchange is run
+# again with every filament move, selector move and sensor read (_check_sensor() or _check_all_sensors())
+# answered from the journal in order. Because the state machine sees exactly the inputs it saw on the printer
+# it should take the same decisions and fail the same way. The first call that doesn't match the journal (a different move, or a sensor read in a
+# different place) is reported as a divergence after which the simulation takes over. Optional mmu config
+# options can be given to match the printer (e.g. toolhead_sensor_to_nozzle=62)
+#
+# Copyright (C) 2023  moggieuk#6538 (discord)
+#                     moggieuk@hotmail.com
+#
+# This file may be distributed under the terms of the GNU GPLv3 license.
+#
+import sys
+
+from extras.mmu_journal import load_journal, call_arguments
+from test.extras.mock_klipper import build_mmu
+
+class JournalReplay:
+    DIST_TOLERANCE = 0.1 # mm difference in requested move still considered the same move
+
+    def __init__(self, records, config=None):
+        self.records = records
+        self.start = next(r for r in records if r['kind'] == "start")
+        self.inputs = [r for r in records if r['kind'] in ("move", "selector", "sensor", "sensors")]
+        self.index = 0
+        self.divergence = None
+        self.mmu, self.printer, self.sim = build_mmu(config=config)
+        self._restore_state()
+        self._patch()
+
+    # Recreate starting state on the simulated MMU before journaled inputs take over
+    def _restore_state(self):
+        mmu, start = self.mmu, self.start
+        for gate, status in enumerate(start['gate_status'][:mmu.mmu_num_gates]):
+            if status != mmu.GATE_EMPTY:
+                self.sim.insert_filament(gate)
+        mmu.gate_material[:] = start['gate_material'][:mmu.mmu_num_gates]
+        mmu.tool_to_gate_map[:] = start['tool_to_gate_map'][:mmu.mmu_num_gates]
+        if start['is_homed']:
+            self._gcode("MMU_HOME")
+            if start['filament_pos'] == mmu.FILAMENT_POS_LOADED and start['tool_selected'] >= 0:
+                self._gcode("MMU_CHANGE_TOOL TOOL=%d" % start['tool_selected'])
+        if start['in_print']:
+            self._gcode("_MMU_PRINT_START")
+            self.printer.objects['idle_timeout'].state = "Printing"
+            self.printer.send_event("idle_timeout:printing", self.printer.reactor.monotonic())
+
+    def _patch(self):
+        mmu = self.mmu
+        trace_filament_move, trace_selector_move = mmu._trace_filament_move, mmu._trace_selector_move
+        check_sensor, check_all_sensors = mmu._check_sensor, mmu._check_all_sensors
+
+        def replay_filament_move(*args, **kwargs):
+            a = call_arguments(trace_filament_move, args, kwargs)
+            rec = self._next("move", dist=a['dist'], motor=a['motor'], homing_move=a['homing_move'], endstop=a['endstop_name'])
+            return tuple(rec['result']) if rec else trace_filament_move(*args, **kwargs)
+
+        def replay_selector_move(*args, **kwargs):
+            a = call_arguments(trace_selector_move, args, kwargs)
+            rec = self._next("selector", target=a['new_pos'], homing_move=a['homing_move'], endstop=a['endstop_name'])
+            return tuple(rec['result']) if rec else trace_selector_move(*args, **kwargs)
+
+        def replay_sensor(name):
+            rec = self._next("sensor", name=name)
+            return rec['result'] if rec else check_sensor(name)
+
+        def replay_all_sensors():
+            rec = self._next("sensors")
+            return dict(rec['result']) if rec else check_all_sensors()
+
+        mmu._trace_filament_move = replay_filament_move
+        mmu._trace_selector_move = replay_selector_move
+        mmu._check_sensor = replay_sensor
+        mmu._check_all_sensors = replay_all_sensors
+
+    # Next journaled input if it matches the call being made, otherwise record the divergence
+    def _next(self, kind, **call):
+        if self.divergence is not None:
+            return None
+        rec = self.inputs[self.index] if self.index < len(self.inputs) else None
+        if rec is None or rec['kind'] != kind or not self._matches(rec, call):
+            self.divergence = {'index': self.index, 'expected': rec, 'actual': dict(call, kind=kind)}
+            return None
+        self.index += 1
+        return rec
+
+    def _matches(self, rec, call):
+        for k, v in call.items():
+            if k in ('dist', 'target'):
+                if abs(rec.get(k, 0.) - v) > self.DIST_TOLERANCE:
+                    return False
+            elif rec.get(k) != v:
+                return False
+        return True
+
+    def _gcode(self, script):
+        self.printer.gcode.run_script_from_command(script)
+
+    # Replay the toolchange and return a report comparing outcome with the journal
+    def run(self):
+        self._gcode("MMU_CHANGE_TOOL TOOL=%d STANDALONE=%d" % (self.start['tool'], 0 if self.start['skip_tip'] else 1))
+        recorded = next((r for r in reversed(self.records) if r['kind'] == "error"), None)
+        replayed = self.mmu.last_error
+        return {
+            'inputs': len(self.inputs),
+            'replayed': self.index,
+            'divergence': self.divergence,
+            'recorded_error': recorded['code'] if recorded else None,
+            'replayed_error': replayed['code'] if replayed else None,
+            'reproduced': (recorded['code'] if recorded else None) == (replayed['code'] if replayed else None),
+        }
+
+def main(argv):
+    if len(argv) < 2:
+        print("Usage: python3 -m test.extras.replay_journal <journal.jsonl> [option=value ...]")
+        return 1
+    config = {}
+    for opt in argv[2:]:
+        k, v = opt.split('=', 1)
+        try:
+            config[k] = float(v) if '.' in v else int(v)
+        except ValueError:
+            config[k] = v
+    report = JournalReplay(load_journal(argv[1]), config).run()
+    print("Replayed %d of %d journaled moves and sensor reads" % (report['replayed'], report['inputs']))
+    if report['divergence']:
+        d = report['divergence']
+        print("Diverged at input %d: journal has %s but code now does %s" % (d['index'], d['expected'], d['actual']))
+    print("Journaled error: %s, replayed error: %s (%s)" % (report['recorded_error'], report['replayed_error'], "reproduced" if report['reproduced'] else "NOT reproduced"))
+    return 0 if report['reproduced'] else 2
+
+if __name__ == '__main__':
+    sys.exit(main(sys.argv))
@@ -0,0 +1,1299 @@
+import os, tempfile, unittest
+from unittest.mock import MagicMock
+
+from extras.mmu_journal import load_journal
+from test.extras.mock_klipper import build_mmu, FakeMacro
+from test.extras.replay_journal import JournalReplay
+
+class MmuScenarioTestCase(unittest.TestCase):
+    CONFIG = {}
+    ENCODER = True
+    SELECTOR_TOUCH = False
+    GEAR_TMC = False
+    TEMPERATURE_SENSORS = ()
+    GEAR_STEPPERS = 1
+    HEATERS = ()
+
+    def setUp(self):
+        self.mmu, self.printer, self.sim = build_mmu(config=self.CONFIG, encoder=self.ENCODER, selector_touch=self.SELECTOR_TOUCH,
+                                                     gear_tmc=self.GEAR_TMC, temperature_sensors=self.TEMPERATURE_SENSORS,
+                                                     gear_steppers=self.GEAR_STEPPERS, heaters=self.HEATERS)
+        for gate in range(self.mmu.mmu_num_gates):
+            self.sim.insert_filament(gate)
+        self.gcode("MMU_HOME")
+
+    def gcode(self, script):
+        self.printer.gcode.run_script_from_command(script)
+
+    def start_print(self):
+        self.gcode("_MMU_PRINT_START")
+        self.printer.objects['idle_timeout'].state = "Printing"
+        self.printer.send_event("idle_timeout:printing", self.printer.reactor.monotonic())
+
+    def assert_loaded(self, tool, gate):
+        self.assertIsNone(self.mmu.last_error)
+        self.assertEqual(self.mmu.tool_selected, tool)
+        self.assertEqual(self.mmu.gate_selected, gate)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+        self.assertEqual(self.sim.selected_gate, gate)
+        self.assertEqual(self.sim.tip(gate), self.sim.nozzle_pos)
+
+    def assert_error(self, code):
+        self.assertIsNotNone(self.mmu.last_error)
+        self.assertEqual(self.mmu.last_error['code'], code)
+
+class TestMmuLoadUnload(MmuScenarioTestCase):
+    def test_home_selects_first_gate(self):
+        self.assertTrue(self.mmu.is_homed)
+        self.assertEqual(self.mmu.gate_selected, 0)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+
+    def test_toolchange_loads_filament_to_nozzle(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_loaded(1, 1)
+        self.assertEqual(self.mmu.gate_status[1], self.mmu.GATE_AVAILABLE)
+        self.assertFalse(self.sim.gear_engaged)
+
+    def test_unload_parks_filament_behind_gate_sensor(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.gcode("MMU_UNLOAD")
+
+        self.assertIsNone(self.mmu.last_error)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+        self.assertFalse(self.sim.sensor_present('mmu_gate'))
+        self.assertAlmostEqual(self.sim.tip(2), -self.mmu.gate_parking_distance, delta=1.)
+
+    def test_toolchange_swaps_filament(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=3")
+
+        self.assert_loaded(3, 3)
+        self.assertLess(self.sim.tip(1), 0.)
+
+    def test_loaded_state_is_persisted(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        saved = self.printer.objects['save_variables'].allVariables
+        self.assertEqual(saved[self.mmu.VARS_MMU_TOOL_SELECTED], 1)
+        self.assertEqual(saved[self.mmu.VARS_MMU_GATE_SELECTED], 1)
+        self.assertEqual(saved[self.mmu.VARS_MMU_FILAMENT_POS], self.mmu.FILAMENT_POS_LOADED)
+
+class TestMmuLoadFailures(MmuScenarioTestCase):
+    def test_empty_gate_fails_gate_load(self):
+        self.sim.remove_filament(1)
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_error(self.mmu.ERR_GATE_LOAD_FAIL)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+
+    def test_jam_in_bowden_fails_bowden_load(self):
+        self.sim.jam_pos = 100.
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_error(self.mmu.ERR_BOWDEN_LOAD_FAIL)
+
+    def test_transitions_record_action_and_error_reason(self):
+        self.sim.jam_pos = 100.
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        transitions = self.mmu.state_transitions
+        self.assertIn({'kind': "filament_pos", 'new': "start_bowden", 'action': "Loading", 'cause': None},
+                      [{k: t[k] for k in ['kind', 'new', 'action', 'cause']} for t in transitions])
+        self.assertEqual(transitions[-1]['kind'], "error")
+        self.assertEqual(transitions[-1]['cause'], self.mmu.last_error['reason'])
+
+    def test_jam_at_extruder_fails_toolhead_homing(self):
+        self.sim.jam_pos = self.sim.toolhead_sensor_pos - 5.
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_error(self.mmu.ERR_TOOLHEAD_HOME_TIMEOUT)
+        self.assertNotEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+
+    def test_filament_stuck_in_extruder_fails_unload(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.sim.stuck = True
+
+        self.gcode("MMU_UNLOAD")
+
+        self.assert_error(self.mmu.ERR_EXTRUDER_STUCK)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+
+class TestMmuRunoutWithoutEncoder(MmuScenarioTestCase):
+    ENCODER = False
+
+    def test_runout_fragment_left_in_bowden_fails_unload(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.sim.gates[1][0] = 100. # Spool ran out and tail is well past the gear
+
+        self.gcode("MMU_UNLOAD")
+
+        self.assert_error(self.mmu.ERR_FILAMENT_STUCK)
+        self.assertNotEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+
+class TestMmuEndlessSpool(MmuScenarioTestCase):
+    CONFIG = {'enable_endless_spool': 1, 'endless_spool_groups': [0, 0, 1, 1]}
+
+    def test_runout_during_print_switches_to_next_gate(self):
+        self.gcode("MMU_GATE_MAP GATE=1 AVAILABLE=1")
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.sim.gates[0][0] = 1. # Tail of spool has just passed the gate sensor
+
+        self.gcode("__MMU_GATE_RUNOUT")
+
+        self.assert_loaded(0, 1)
+        self.assertEqual(self.mmu.tool_to_gate_map[0], 1)
+        self.assertEqual(self.mmu.gate_status[0], self.mmu.GATE_EMPTY)
+        self.assertEqual(self.mmu.print_state, "printing")
+
+    def test_runout_with_no_alternative_pauses(self):
+        self.gcode("MMU_GATE_MAP GATE=1 AVAILABLE=0")
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.sim.gates[0][0] = 1.
+
+        self.gcode("__MMU_GATE_RUNOUT")
+
+        self.assert_error(self.mmu.ERR_ENDLESS_SPOOL_EXHAUSTED)
+        self.assertTrue(self.mmu._is_mmu_paused())
+
+class TestMmuEndlessSpoolPriorities(MmuScenarioTestCase):
+    CONFIG = {'enable_endless_spool': 1, 'endless_spool_groups': [0, 0, 1, 1], 'endless_spool_priorities': [0, 0, 1, 0],
+              'endless_spool_match': ["exact", "color"], 'endless_spool_fallback': ["0:1"]}
+
+    def test_runout_prefers_matching_spool_from_fallback_group(self):
+        self.gcode("MMU_GATE_MAP GATE=0 MATERIAL=PLA COLOR=ff0000 AVAILABLE=1")
+        self.gcode("MMU_GATE_MAP GATE=1 MATERIAL=PETG COLOR=0000ff AVAILABLE=1") # Matches no rule
+        self.gcode("MMU_GATE_MAP GATE=2 MATERIAL=PLA COLOR=ff0000 AVAILABLE=1")
+        self.gcode("MMU_GATE_MAP GATE=3 MATERIAL=ABS COLOR=ff0000 AVAILABLE=1") # Preferred by priority but only same color
+        self.assertEqual(self.mmu._get_next_endless_spool_gate(0), (2, [1, 2, 3]))
+
+        self.gcode("MMU_ENDLESS_SPOOL PRIORITIES=0,0,0,1 QUIET=1")
+        self.gcode("MMU_GATE_MAP GATE=3 MATERIAL=PLA")
+        self.assertEqual(self.mmu._get_next_endless_spool_gate(0), (2, [1, 2, 3]))
+        self.gcode("MMU_ENDLESS_SPOOL PRIORITIES=0,0,1,0 QUIET=1")
+        self.assertEqual(self.mmu._get_next_endless_spool_gate(0), (3, [1, 2, 3]))
+
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.sim.gates[0][0] = 1.
+        self.gcode("__MMU_GATE_RUNOUT")
+
+        self.assert_loaded(0, 3)
+        self.assertEqual(self.mmu.tool_to_gate_map[0], 3)
+
+class TestMmuMultiGearVendor(MmuScenarioTestCase):
+    CONFIG = {'mmu_vendor': "3MS", 'mmu_version': "1.0"}
+    GEAR_STEPPERS = 4
+
+    def gear_rail_steppers(self):
+        return [s.get_name() for s in self.mmu.mmu_toolhead.get_kinematics().rails[1].get_steppers()]
+
+    def test_select_gate_places_its_gear_stepper_on_rail(self):
+        self.gcode("MMU_SELECT GATE=1")
+        self.assertEqual(self.mmu.gate_selected, 1)
+        self.assertEqual(self.gear_rail_steppers(), ["stepper_mmu_gear_1"])
+
+        self.gcode("MMU_SELECT GATE=0")
+        self.assertEqual(self.mmu.gate_selected, 0)
+        self.assertEqual(self.gear_rail_steppers(), ["stepper_mmu_gear"])
+
+class TestMmuPicoVendor(MmuScenarioTestCase):
+    CONFIG = {'mmu_vendor': "PicoMMU", 'mmu_version': "1.0"}
+
+    def test_selector_position_grips_and_releases_filament(self):
+        self.gcode("MMU_SELECT GATE=1")
+        gate_pos = self.mmu.selector_offsets[1]
+
+        self.mmu._servo_down()
+        self.assertEqual(self.mmu.mmu_toolhead.get_position()[0], gate_pos)
+        self.mmu._servo_up()
+        self.assertEqual(self.mmu.mmu_toolhead.get_position()[0], gate_pos + self.mmu.cad_gate_width / 2.)
+        self.assertEqual(self.mmu.servo_state, self.mmu.SERVO_UP_STATE)
+        self.mmu._servo_down()
+        self.assertEqual(self.mmu.mmu_toolhead.get_position()[0], gate_pos)
+
+class TestMmuChameleonVendor(MmuScenarioTestCase):
+    CONFIG = {'mmu_vendor': "3DChameleon", 'mmu_version': "1.0"}
+
+    def setUp(self):
+        self.selected = []
+        super().setUp()
+        self.printer.objects['gcode_macro _MMU_CHAMELEON_SELECT'] = FakeMacro(lambda gcmd: self.selected.append(gcmd.get_int('GATE')))
+
+    def test_gate_selection_is_delegated_to_macro(self):
+        self.gcode("MMU_SELECT GATE=2")
+        self.assertEqual(self.mmu.gate_selected, 2)
+        self.assertEqual(self.selected, [2])
+
+class TestMmuSelectorRecovery(MmuScenarioTestCase):
+    SELECTOR_TOUCH = True
+
+    def test_selector_stall_is_recovered_by_rehoming(self):
+        self.sim.selector_obstacle = 40.
+        self.mmu._publish_event = MagicMock(wraps=self.mmu._publish_event)
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        self.assert_loaded(2, 2)
+        self.assertIn('selector_recovered', [c.args[0] for c in self.mmu._publish_event.call_args_list])
+
+    def test_selector_that_cannot_home_fails(self):
+        self.sim.selector_obstacle = 40.
+        self.sim.selector_blocked = True
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        self.assert_error(self.mmu.ERR_SELECTOR)
+        self.assertFalse(self.mmu.is_homed)
+
+class TestMmuGateLock(MmuScenarioTestCase):
+    def test_locked_gate_is_refused(self):
+        self.gcode("MMU_GATE_MAP GATE=1 LOCK=1 OWNER=bob")
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_error(self.mmu.ERR_GATE_LOCKED)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+        self.assertLess(self.sim.tip(1), 0.)
+
+class TestMmuReloadConfig(MmuScenarioTestCase):
+    def edit_config(self, section, **options):
+        self.printer.objects['configfile'].sections[section].update(options)
+
+    def test_runtime_safe_change_is_applied(self):
+        self.edit_config('mmu', gear_from_buffer_speed=123., servo_down_angle=10)
+
+        self.gcode("MMU_RELOAD_CONFIG")
+
+        self.assertEqual(self.mmu.gear_from_buffer_speed, 123.)
+        self.assertIn("require a restart to take effect:\nservo_down_angle", self.printer.gcode.messages[-1])
+
+    def test_reloaded_value_becomes_base_of_override(self):
+        self.gcode("MMU_TEST_CONFIG GEAR_FROM_BUFFER_SPEED=50")
+        self.edit_config('mmu', gear_from_buffer_speed=123.)
+
+        self.gcode("MMU_RELOAD_CONFIG")
+        self.assertEqual(self.mmu.gear_from_buffer_speed, 50.)
+        self.gcode("MMU_TEST_CONFIG CLEAR=1")
+
+        self.assertEqual(self.mmu.gear_from_buffer_speed, 123.)
+
+    def test_macro_variable_change_is_applied(self):
+        self.edit_config('gcode_macro _MMU_FORM_TIP_STANDALONE', variable_final_eject="1")
+
+        self.gcode("MMU_RELOAD_CONFIG")
+
+        self.assertEqual(self.printer.objects['gcode_macro _MMU_FORM_TIP_STANDALONE'].variables['final_eject'], 1)
+
+class TestMmuGateSequenceOverride(MmuScenarioTestCase):
+    CONFIG = {'gate_load_sequence': ["1:_LOAD_PTFE"]}
+
+    def setUp(self):
+        super().setUp()
+        self.calls = []
+        self.printer.objects['gcode_macro _LOAD_PTFE'] = FakeMacro(lambda gcmd: self.calls.append(gcmd.get_command_parameters()))
+
+    def test_custom_load_sequence_used_only_for_its_gate(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.assert_loaded(2, 2)
+        self.assertEqual(self.calls, [])
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(len(self.calls), 1)
+        self.assertEqual(int(self.calls[0]['FILAMENT_POS']), self.mmu.FILAMENT_POS_UNLOADED)
+
+class TestMmuGearSlipDetection(MmuScenarioTestCase):
+    CONFIG = {'sync_to_extruder': 1, 'gear_slip_detection': 2, 'gear_slip_length': 15.}
+
+    def setUp(self):
+        super().setUp()
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        sensor_present = self.sim.sensor_present
+        self.sim.sensor_present = lambda name: False if name == 'extruder' else sensor_present(name) # Filament pulled taut past entry sensor
+
+    def check_after_extruding(self, length):
+        self.mmu._handle_gear_slip_check(self.printer.reactor.monotonic())
+        self.printer.toolhead.position[3] += length
+        self.mmu._handle_gear_slip_check(self.printer.reactor.monotonic())
+
+    def test_entry_sensor_lost_while_extruding_pauses_with_gear_slip(self):
+        self.assertTrue(self.printer.mmu_toolhead.is_gear_synced_to_extruder())
+
+        self.check_after_extruding(20.)
+
+        self.assert_error(self.mmu.ERR_GEAR_SLIP)
+
+    def test_entry_sensor_lost_without_extrusion_is_not_gear_slip(self):
+        self.check_after_extruding(0.)
+
+        self.assertIsNone(self.mmu.last_error)
+
+class TestMmuBowdenStallDetection(MmuScenarioTestCase):
+    CONFIG = {'bowden_stall_detection': 1, 'bowden_stall_samples': 2}
+    GEAR_TMC = True
+
+    def test_gear_disabled_when_jam_detected_during_bowden_move(self):
+        self.mmu.variables["%s1" % self.mmu.VARS_MMU_CALIB_STALL_PREFIX] = 250
+        fields = self.printer.objects['tmc2209 stepper_mmu_gear'].fields
+        reactor = self.printer.reactor
+        trace_filament_move = self.mmu._trace_filament_move
+        def jammed_bowden_move(*args, **kwargs):
+            if self.mmu.gear_stall_baseline:
+                fields.sg_result = 20
+                callback, waketime = self.mmu.gear_stall_timer
+                while waketime != reactor.NEVER and len(self.mmu.gear_stall_samples) < 10: # Timer fires while move is in progress
+                    reactor.now = max(reactor.now, waketime)
+                    waketime = callback(reactor.now)
+            return trace_filament_move(*args, **kwargs)
+        self.mmu._trace_filament_move = jammed_bowden_move
+        stepper_enable = self.printer.objects['stepper_enable']
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        stepper_enable.lookup_enable.return_value.motor_disable.assert_called_once()
+        self.assert_error(self.mmu.ERR_GEAR_STALL)
+
+class TestMmuReadyPark(MmuScenarioTestCase):
+    CONFIG = {'gate_ready_park': [5., 5., 0., 5.]}
+
+    def test_gate_is_ready_parked_in_print_and_deep_parked_after(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.gcode("MMU_CHANGE_TOOL TOOL=3")
+
+        self.assertAlmostEqual(self.sim.tip(1), -5., delta=1.)
+        self.assertAlmostEqual(self.sim.tip(2), -self.mmu.gate_parking_distance, delta=1.)
+
+        self.gcode("_MMU_PRINT_END")
+        self.printer.objects['idle_timeout'].state = "Ready"
+        self.mmu._handle_deep_park_timeout(self.printer.reactor.monotonic())
+
+        self.assertAlmostEqual(self.sim.tip(1), -self.mmu.gate_parking_distance, delta=1.)
+        self.assertEqual(self.mmu.ready_parked_gates, set())
+        self.assert_loaded(3, 3)
+
+    def test_gates_used_by_print_are_ready_parked_at_print_start(self):
+        for gate in range(self.mmu.mmu_num_gates):
+            self.sim.set_tip(-self.mmu.gate_parking_distance, gate)
+        self.gcode("_MMU_PRINT_START TOOLS=1,2,3")
+
+        self.assertEqual(self.mmu.ready_parked_gates, {1, 3})
+        self.assertAlmostEqual(self.sim.tip(1), -5., delta=1.)
+        self.assertAlmostEqual(self.sim.tip(0), -self.mmu.gate_parking_distance, delta=1.)
+        self.assertAlmostEqual(self.sim.tip(2), -self.mmu.gate_parking_distance, delta=1.)
+
+        self.printer.objects['idle_timeout'].state = "Printing"
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assert_loaded(1, 1)
+
+class TestMmuGatePressureAdvance(MmuScenarioTestCase):
+    CONFIG = {'gate_pressure_advance': [-1., .06, -1., -1.]}
+
+    def test_pressure_advance_before_override_is_restored(self):
+        extruder = self.printer.objects['extruder']
+        extruder.pressure_advance = .05 # Set by user/slicer, not the startup value
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.assertEqual(extruder.pressure_advance, .05)
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertEqual(extruder.pressure_advance, .06)
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.assertEqual(extruder.pressure_advance, .05)
+
+    def test_pressure_advance_untouched_without_override(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.printer.objects['extruder'].pressure_advance = .05
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        self.assertEqual(self.printer.objects['extruder'].pressure_advance, .05)
+        self.assertFalse([s for s in self.printer.gcode.scripts if s.startswith("SET_PRESSURE_ADVANCE EXTRUDER=")])
+
+class TestMmuLoadBalancing(MmuScenarioTestCase):
+    CONFIG = {'gate_load_balancing': 1}
+
+    def setUp(self):
+        super().setUp()
+        for gate in (1, 3):
+            self.gcode("MMU_GATE_MAP GATE=%d MATERIAL=PLA COLOR=ff0000 AVAILABLE=1" % gate)
+
+    def test_balanced_gate_used_for_load_without_changing_ttg_map(self):
+        ttg_map = list(self.mmu.tool_to_gate_map)
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_loaded(1, 3)
+        self.assertEqual(self.mmu.tool_to_gate_map, ttg_map)
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertIn("Tool T1 is already loaded", self.printer.gcode.messages)
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assert_loaded(1, 1)
+        self.assertEqual(self.mmu.tool_to_gate_map, ttg_map)
+
+    def test_fewest_loads_policy_uses_separate_counter(self):
+        self.mmu.gate_load_balancing = 2
+        self.mmu.gate_statistics[1]['full_loads'] = 3
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_loaded(1, 3)
+        self.assertEqual(self.mmu.gate_statistics[3]['full_loads'], 1)
+        self.assertEqual(self.mmu.gate_statistics[3]['loads'], 0)
+
+class TestMmuDryerZones(MmuScenarioTestCase):
+    HEATERS = ('mmu_dryer', 'mmu_dryer_2')
+    CONFIG = {'dryer_heater': ['mmu_dryer', 'mmu_dryer_2'], 'dryer_gate_zones': [0, 0, 1, 1], 'dryer_zone_pid': ['40:1:300', '-']}
+
+    def test_zone_pid_replaces_heater_control(self):
+        heaters = self.printer.objects['heaters']
+        self.assertEqual(heaters.lookup_heater('mmu_dryer').control.pid, (40., 1., 300.))
+        self.assertIsNone(heaters.lookup_heater('mmu_dryer_2').control)
+
+    def test_heater_alias_addresses_single_zone(self):
+        self.gcode("MMU_HEATER ZONE=1 TEMP=55")
+
+        self.assertIn("SET_HEATER_TEMPERATURE HEATER=mmu_dryer_2 TARGET=55.0", self.printer.gcode.scripts)
+        self.assertFalse([s for s in self.printer.gcode.scripts if "HEATER=mmu_dryer " in s])
+
+class TestMmuDryerJob(MmuScenarioTestCase):
+    HEATERS = ('mmu_dryer', 'mmu_dryer_2')
+    CONFIG = {'dryer_heater': ['mmu_dryer', 'mmu_dryer_2'], 'dryer_gate_zones': [0, 0, 1, 1], 'dryer_preheat_time': 600,
+              'dryer_material_temps': ['PLA:45', 'PETG:60']}
+
+    def setUp(self):
+        super().setUp()
+        for gate in range(self.mmu.mmu_num_gates):
+            self.gcode("MMU_GATE_MAP GATE=%d MATERIAL=%s AVAILABLE=1" % (gate, "PLA" if gate < 2 else "PETG"))
+        self.heaters = self.printer.objects['heaters']
+
+    def test_zone_turned_off_after_last_use_of_its_tools(self):
+        self.start_print()
+        self.gcode("MMU_DRYER TOOLS=0,1,2")
+        self.assertEqual(self.heaters.lookup_heater('mmu_dryer').target_temp, 45.)
+        self.assertEqual(self.heaters.lookup_heater('mmu_dryer_2').target_temp, 60.)
+
+        self.gcode("MMU_DRYER DONE=2")
+        self.assertEqual(self.heaters.lookup_heater('mmu_dryer').target_temp, 45.)
+        self.assertEqual(self.heaters.lookup_heater('mmu_dryer_2').target_temp, 0.)
+
+        self.gcode("MMU_DRYER DONE=0")
+        self.assertEqual(self.heaters.lookup_heater('mmu_dryer').target_temp, 45.) # T1 still needs zone 0
+
+    def test_preheat_does_not_block_and_first_load_waits_for_remainder(self):
+        self.gcode("MMU_DRYER TOOLS=0,2")
+        self.assertFalse([s for s in self.printer.gcode.scripts if s.startswith("G4")])
+
+        self.printer.reactor.now += 500.
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(self.printer.gcode.scripts.count("G4 P100000"), 1) # Only remainder of soak and only once
+
+class TestMmuCalibrationSets(MmuScenarioTestCase):
+    def test_restore_removes_calibration_added_after_backup(self):
+        self.gcode("MMU_CALIBRATION BACKUP=nozzle_a")
+        self.gcode("SAVE_VARIABLE VARIABLE=mmu_calibration_clog_length VALUE=20")
+
+        self.gcode("MMU_CALIBRATION RESTORE=nozzle_a")
+
+        self.assertIsNone(self.mmu.variables.get('mmu_calibration_clog_length'))
+        self.assertEqual(self.mmu._get_calibration_vars(), {k: v for k, v in self.mmu.variables['mmu_backup_calibration_nozzle_a'].items() if k != 'mmu_calibration_toolhead'})
+
+    def test_restore_applies_toolhead_settings_without_persisting(self):
+        self.gcode("MMU_CALIBRATION BACKUP=nozzle_a")
+        self.mmu.toolhead_extruder_to_nozzle = 80. # e.g. config edited for a different toolhead
+
+        self.gcode("MMU_CALIBRATION RESTORE=nozzle_a")
+
+        self.assertEqual(self.mmu.toolhead_extruder_to_nozzle, 70.)
+        self.assertNotIn('mmu_calibration_toolhead', self.mmu.variables)
+        self.assertEqual([(o['attr'], o['scope']) for o in self.mmu.config_overrides], [('toolhead_extruder_to_nozzle', self.mmu.CONFIG_SCOPE_SESSION)])
+
+    def test_restore_resets_calibration_missing_from_set(self):
+        for var in ['mmu_gear_rotation_distance'] + ['mmu_calibration_%d' % gate for gate in range(4)]:
+            self.mmu.variables.pop(var)
+        self.gcode("MMU_CALIBRATION BACKUP=nozzle_a")
+        self.gcode("SAVE_VARIABLE VARIABLE=mmu_calibration_1 VALUE=1.02")
+        self.gcode("SAVE_VARIABLE VARIABLE=mmu_gear_rotation_distance VALUE=23.5")
+        self.gcode("SAVE_VARIABLE VARIABLE=mmu_calibration_collision_current VALUE=30")
+        self.mmu._load_calibration()
+        self.assertEqual(self.mmu.gear_stepper.get_rotation_distance()[0], 23.5)
+
+        self.gcode("MMU_CALIBRATION RESTORE=nozzle_a")
+
+        self.assertFalse(self.mmu.calibration_status & self.mmu.CALIBRATED_GATES)
+        self.assertEqual(self.mmu.gear_stepper.get_rotation_distance()[0], self.mmu.gear_default_rotation_distance)
+        self.assertEqual(self.mmu.extruder_homing_current, self.mmu.default_extruder_homing_current)
+
+class TestMmuCutterStub(MmuScenarioTestCase):
+    CONFIG = {'cutter_location': "hub", 'cutter_tip_length': 10., 'cutter_stub_length': 10., 'purge_volume': 50.}
+
+    def setUp(self):
+        super().setUp()
+        self.printer.objects['gcode_macro _MMU_CUT_FILAMENT'] = FakeMacro(lambda gcmd: self.sim.set_tip(self.sim.tip() - 10.))
+
+    def test_hub_stub_is_tracked_then_consumed_and_purged_by_next_gate(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        self.assert_loaded(2, 2)
+        self.assertEqual(self.mmu.gate_stub_length, [0.] * self.mmu.mmu_num_gates)
+        stub_volume = 10. * self.printer.objects['extruder'].filament_area
+        self.assertAlmostEqual(self.mmu.toolchange_purge_volume, 50. + stub_volume, places=1)
+
+    def test_stub_recorded_against_cut_gate(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.gcode("MMU_UNLOAD")
+
+        self.assertEqual(self.mmu.gate_stub_length[1], 10.)
+
+class TestMmuCancelPrintUnload(MmuScenarioTestCase):
+    CONFIG = {'cancel_print_unload': 1}
+
+    def test_cancel_unloads_to_gate(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.gcode("CANCEL_PRINT")
+
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+        self.assertEqual(self.mmu.print_state, "cancelled")
+
+    def test_cancel_mid_toolchange_rolls_back_to_gate(self):
+        self.start_print()
+        self.sim.jam_pos = self.sim.toolhead_sensor_pos - 5.
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assert_error(self.mmu.ERR_TOOLHEAD_HOME_TIMEOUT)
+        self.sim.jam_pos = None
+
+        self.gcode("CANCEL_PRINT")
+
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+        self.assertLess(self.sim.tip(1), 0.)
+
+    def test_cancel_with_unknown_filament_position_moves_nothing(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.mmu._set_filament_pos_state(self.mmu.FILAMENT_POS_UNKNOWN)
+        gear_moved = self.sim.gear_moved
+
+        self.gcode("CANCEL_PRINT")
+
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNKNOWN)
+        self.assertEqual(self.sim.gear_moved, gear_moved)
+
+class TestMmuCancelPrintLeaveLoaded(MmuScenarioTestCase):
+    def test_cancel_mid_toolchange_leaves_filament(self):
+        self.start_print()
+        self.sim.jam_pos = self.sim.toolhead_sensor_pos - 5.
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assert_error(self.mmu.ERR_TOOLHEAD_HOME_TIMEOUT)
+        filament_pos, tip = self.mmu.filament_pos, self.sim.tip(1)
+
+        self.gcode("CANCEL_PRINT")
+
+        self.assertEqual(self.mmu.filament_pos, filament_pos)
+        self.assertEqual(self.sim.tip(1), tip)
+        self.assertEqual(self.mmu.print_state, "cancelled")
+
+class TestMmuGateMapTemplate(MmuScenarioTestCase):
+    def setUp(self):
+        super().setUp()
+        self.tmpdir = tempfile.TemporaryDirectory()
+        self.addCleanup(self.tmpdir.cleanup)
+        self.mmu.gate_map_template_dir = self.tmpdir.name
+        with open(os.path.join(self.tmpdir.name, "rainbow.cfg"), 'w') as f:
+            f.write("[gate 0]\nmaterial: PLA\ncolor: red\ntemp: 210\n\n[gate 1]\nmaterial: petg\ncolor: 00ff00\n")
+
+    def test_template_sets_lineup_and_empties_other_gates(self):
+        self.gcode("MMU_GATE_MAP TEMPLATE=rainbow")
+
+        self.assertEqual(self.mmu.gate_material[:2], ["PLA", "PETG"])
+        self.assertEqual(self.mmu.gate_temperature[0], 210)
+        self.assertEqual(self.mmu.gate_status[1], self.mmu.GATE_AVAILABLE)
+        self.assertEqual(self.mmu.gate_status[2], self.mmu.GATE_EMPTY)
+
+    def test_saved_template_can_be_reapplied(self):
+        self.gcode("MMU_GATE_MAP TEMPLATE=rainbow")
+        self.gcode("MMU_GATE_MAP SAVE_TEMPLATE=copy")
+        self.gcode("MMU_GATE_MAP GATE=0 MATERIAL=ABS")
+
+        self.gcode("MMU_GATE_MAP TEMPLATE=copy")
+
+        self.assertEqual(self.mmu.gate_material[0], "PLA")
+        self.assertEqual(self.mmu.gate_status[2], self.mmu.GATE_EMPTY)
+
+    def test_sensor_disagreement_requires_confirmation(self):
+        sensor = MagicMock()
+        sensor.runout_helper.sensor_enabled = True
+        sensor.runout_helper.filament_present = True
+        self.printer.objects['filament_switch_sensor mmu_pre_gate_2'] = sensor
+
+        self.gcode("MMU_GATE_MAP TEMPLATE=rainbow")
+        self.assertNotEqual(self.mmu.gate_status[2], self.mmu.GATE_EMPTY)
+
+        self.gcode("MMU_GATE_MAP TEMPLATE=rainbow CONFIRM=1")
+        self.assertEqual(self.mmu.gate_status[2], self.mmu.GATE_EMPTY)
+
+class TestMmuMapHistory(MmuScenarioTestCase):
+    def test_changes_recorded_with_source(self):
+        self.gcode("MMU_GATE_MAP GATE=1 MATERIAL=PETG QUIET=1")
+        self.gcode("__MMU_PRE_GATE_RUNOUT GATE=2")
+        self.gcode("MMU_GATE_MAP GATE=3 MATERIAL=TPU SOURCE=my_macro QUIET=1")
+
+        sources = [h['source'] for h in self.mmu.map_history[-3:]]
+        self.assertEqual(sources, ["command: MMU_GATE_MAP GATE=1 MATERIAL=PETG QUIET=1", "runout (pre-gate sensor)", "my_macro"])
+
+        self.gcode("MMU_GATE_MAP UNDO=1")
+        self.assertEqual(self.mmu.gate_material[3], "")
+
+class TestMmuPurgeRouter(MmuScenarioTestCase):
+    CONFIG = {'purge_volume': 100., 'purge_to_infill_percent': 50., 'purge_to_infill_flow': 10., 'purge_to_infill_caps': ["TPU:0"]}
+
+    def setUp(self):
+        super().setUp()
+        self.start_print()
+        self.transform = self.printer.objects['gcode_move'].transform
+
+    def infill_move(self, e_move):
+        pos = self.transform.get_position()
+        self.transform.move([pos[0] + 10., pos[1], pos[2], pos[3] + e_move], 100.)
+
+    def test_no_routing_without_infill_markers(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(self.mmu.toolchange_purge_volume, 100.)
+
+    def test_no_routing_when_toolchange_not_followed_by_infill(self):
+        self.gcode("MMU_PURGE_INFILL START=1")
+        self.gcode("MMU_PURGE_INFILL END=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(self.mmu.toolchange_purge_volume, 100.)
+        self.assertEqual(self.mmu.purge_router.budget, 0.)
+
+    def test_purge_routed_into_marked_infill_by_over_extrusion(self):
+        self.gcode("MMU_PURGE_INFILL START=1")
+        self.gcode("MMU_PURGE_INFILL END=1")
+        self.gcode("MMU_PURGE_INFILL NEXT=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertEqual(self.mmu.toolchange_purge_volume, 50.)
+        budget = self.mmu.purge_router.budget
+
+        self.gcode("MMU_PURGE_INFILL START=1")
+        e_start, e_gcode = self.printer.toolhead.position[3], self.transform.get_position()[3]
+        self.infill_move(10.)
+
+        self.assertAlmostEqual(self.printer.toolhead.position[3] - e_start, 11., places=3)
+        self.assertAlmostEqual(self.transform.get_position()[3] - e_gcode, 10., places=3) # Gcode position unaffected
+        self.assertAlmostEqual(self.mmu.purge_router.budget, budget - 1., places=3)
+
+    def test_material_cap_prevents_routing(self):
+        self.gcode("MMU_GATE_MAP GATE=1 MATERIAL=TPU QUIET=1")
+        self.gcode("MMU_PURGE_INFILL START=1")
+        self.gcode("MMU_PURGE_INFILL END=1")
+        self.gcode("MMU_PURGE_INFILL NEXT=1")
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(self.mmu.toolchange_purge_volume, 100.)
+
+class TestMmuResumeVerification(MmuScenarioTestCase):
+    def setUp(self):
+        super().setUp()
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.mmu._mmu_pause("Test pause")
+        self.mmu.pause_start_time -= 8 * 3600 # Paused overnight
+
+    def test_long_pause_rehomes_filament_before_resume(self):
+        self.sim.set_tip(self.sim.nozzle_pos - 20.) # Filament pulled back whilst paused
+
+        self.gcode("RESUME")
+
+        self.assertEqual(self.mmu.print_state, "printing")
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+        self.assertAlmostEqual(self.sim.tip(1), self.sim.nozzle_pos, delta=1.)
+
+    def test_temperature_mismatch_remains_paused_until_resumed_again(self):
+        self.mmu.gate_temperature[1] = 260
+
+        self.gcode("RESUME")
+        self.assertTrue(self.mmu._is_mmu_paused())
+
+        self.gcode("RESUME")
+        self.assertEqual(self.mmu.print_state, "printing")
+
+class TestMmuNotifications(MmuScenarioTestCase):
+    CONFIG = {'notify_routes': ["error:console:moonraker:macro"], 'notify_template_error': "Error {code} on gate {gate}"}
+
+    def setUp(self):
+        super().setUp()
+        self.calls = []
+        self.printer.objects['gcode_macro _MMU_NOTIFY'] = FakeMacro(lambda gcmd: self.calls.append(gcmd.get_command_parameters()))
+
+    def test_unrouted_toolchange_does_not_update_display(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_loaded(1, 1)
+        self.assertFalse([s for s in self.printer.gcode.scripts if s.startswith("M117")])
+        self.assertEqual(self.calls, [])
+
+    def test_error_routed_to_console_moonraker_and_macro(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.sim.gates[0][0] = 1.
+
+        self.gcode("__MMU_GATE_RUNOUT")
+
+        msg = "Error %s on gate 0" % self.mmu.ERR_RUNOUT
+        self.assertIn(msg, "\n".join(self.printer.gcode.messages))
+        self.printer.objects['webhooks'].call_remote_method.assert_any_call("notify", name="mmu", message=msg)
+        self.assertEqual([c['CLASS'] for c in self.calls], ["error"])
+
+class TestMmuVirtualEncoder(MmuScenarioTestCase):
+    ENCODER = False
+    CONFIG = {'enable_virtual_encoder': 1, 'virtual_encoder_samples': 1}
+
+    def test_extra_gear_movement_to_reach_sensor_reported_as_slip(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1") # Learns baseline
+        self.gcode("MMU_UNLOAD")
+        self.assertEqual(self.mmu.virtual_encoder.slip_events, 0)
+
+        self.sim.gear_slip = 0.03 # 12mm short over bowden
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+        self.assertAlmostEqual(self.sim.tip(1), self.sim.nozzle_pos, delta=1.)
+        self.assertEqual(self.mmu.virtual_encoder.slip_events, 1)
+
+    def test_no_slip_reported_for_repeatable_load(self):
+        for _ in range(3):
+            self.gcode("MMU_CHANGE_TOOL TOOL=1")
+            self.gcode("MMU_UNLOAD")
+
+        self.assertIsNone(self.mmu.last_error)
+        self.assertEqual(self.mmu.virtual_encoder.slip_events, 0)
+        self.assertIn("1:toolhead", self.mmu.virtual_encoder.get_status()['baselines'])
+
+class TestMmuJobTtgManifest(MmuScenarioTestCase):
+    def setUp(self):
+        super().setUp()
+        for gate, material in enumerate(["PLA", "PLA", "PETG", "ABS"]):
+            self.gcode("MMU_GATE_MAP GATE=%d MATERIAL=%s QUIET=1" % (gate, material))
+
+    def test_manifest_remaps_for_job_and_restores_after(self):
+        self.gcode("_MMU_PRINT_START TOOLS=0,1 MATERIALS=PLA,PETG")
+
+        self.assertEqual(self.mmu.tool_to_gate_map[:2], [0, 2])
+        self.assertIsNone(self.mmu.last_error)
+
+        self.gcode("_MMU_PRINT_END")
+        self.assertEqual(self.mmu.tool_to_gate_map[:2], [0, 1])
+
+    def test_strict_manifest_refuses_to_start(self):
+        self.gcode("_MMU_PRINT_START MATERIALS=PLA,NYLON TTG_MANIFEST=2")
+
+        self.assert_error(self.mmu.ERR_FILAMENT_MISMATCH)
+        self.assertTrue(self.mmu._is_mmu_paused())
+
+class TestMmuPrintStartGateCheck(MmuScenarioTestCase):
+    CONFIG = {'print_start_check_gates': 1}
+
+    def test_loaded_tool_restored_after_checking_gates(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.gcode("_MMU_PRINT_START TOOLS=1,2,3")
+
+        self.assert_loaded(1, 1)
+        for gate in [1, 2, 3]:
+            self.assertNotEqual(self.mmu.gate_status[gate], self.mmu.GATE_EMPTY)
+
+    def test_empty_gate_marked_and_print_paused(self):
+        self.sim.remove_filament(2)
+
+        self.gcode("_MMU_PRINT_START TOOLS=1,2")
+
+        self.assert_error(self.mmu.ERR_GATE_EMPTY)
+        self.assertEqual(self.mmu.gate_status[2], self.mmu.GATE_EMPTY)
+
+class TestMmuSafetyWatchdog(MmuScenarioTestCase):
+    SELECTOR_TOUCH = True
+    CONFIG = {'safety_watchdog': 1, 'safety_watchdog_failures': 1}
+
+    def test_blocked_selector_trips_watchdog_until_reset(self):
+        self.sim.selector_obstacle = 40.
+        self.sim.selector_blocked = True
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        self.assert_error(self.mmu.ERR_SAFETY_WATCHDOG)
+        self.assertIn('selector', self.mmu.safety_tripped)
+        self.assertEqual(self.mmu.safety_trips['selector'], 1)
+
+        self.sim.selector_blocked = False
+        self.mmu.last_error = None
+        self.gcode("MMU_HOME")
+        self.assert_error(self.mmu.ERR_SAFETY_WATCHDOG)
+
+        self.gcode("MMU_SAFETY RESET=1")
+        self.gcode("MMU_HOME")
+        self.assertTrue(self.mmu.is_homed)
+        self.assertEqual(self.mmu.safety_tripped, {})
+
+    def test_successful_moves_do_not_trip(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=3")
+
+        self.assert_loaded(3, 3)
+        self.assertEqual(self.mmu.safety_trips, {'selector': 0, 'servo': 0})
+
+    def test_timer_disables_selector_still_moving_after_timeout(self):
+        reactor = self.printer.reactor
+        position_selector = self.mmu._position_selector
+        def slow_position_selector(offset):
+            reactor.now += self.mmu.safety_watchdog_timeout + 1.
+            callback, waketime = reactor.timers[-1]
+            self.assertLessEqual(waketime, reactor.now)
+            callback(reactor.now) # Timer fires before move completes
+            position_selector(offset)
+        self.mmu._position_selector = slow_position_selector
+        stepper_enable = self.printer.objects['stepper_enable']
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        stepper_enable.lookup_enable.return_value.motor_disable.assert_called()
+        self.assert_error(self.mmu.ERR_SAFETY_WATCHDOG)
+        self.assertIn("not confirmed within 30.0s", self.mmu.safety_tripped['selector'])
+        self.assertFalse(self.mmu.is_homed)
+
+class TestMmuSafetyWatchdogTimeout(MmuScenarioTestCase):
+    SELECTOR_TOUCH = True
+    CONFIG = {'safety_watchdog': 1, 'safety_watchdog_failures': 2}
+
+    def test_timeout_disables_selector_immediately_before_trip(self):
+        reactor = self.printer.reactor
+        position_selector = self.mmu._position_selector
+        def slow_position_selector(offset):
+            reactor.now += self.mmu.safety_watchdog_timeout + 1.
+            callback, _ = reactor.timers[-1]
+            callback(reactor.now)
+            position_selector(offset)
+        self.mmu._position_selector = slow_position_selector
+        stepper_enable = self.printer.objects['stepper_enable']
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+
+        stepper_enable.lookup_enable.return_value.motor_disable.assert_called_with(reactor.now)
+        self.assert_error(self.mmu.ERR_SAFETY_WATCHDOG)
+        self.assertEqual(self.mmu.safety_tripped, {})
+        self.assertEqual(self.mmu.safety_failures['selector'], 1)
+
+class TestMmuToolheadCutter(MmuScenarioTestCase):
+    CONFIG = {'form_tip_macro': "_MMU_CUT_TIP", 'cutter_blade_life': 3}
+    BLADE_POS = 37.5
+
+    def setUp(self):
+        super().setUp()
+        # Cut only succeeds with at least 1.5mm of pin travel
+        def cut(gcmd):
+            if gcmd.get_float('CUT_TRAVEL', 5.) >= 1.5:
+                self.sim.set_tip(self.sim.nozzle_pos - self.BLADE_POS)
+            self.cutter.variables['output_park_pos'] = self.BLADE_POS
+        self.cutter = FakeMacro(cut, variables={'cut_travel': -1, 'output_park_pos': 0})
+        self.printer.objects['gcode_macro _MMU_CUT_TIP'] = self.cutter
+
+    def test_calibration_finds_minimal_reliable_travel(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.gcode("MMU_CALIBRATE_CUTTER MIN=0 MAX=3 STEP=0.5 REPEATS=2")
+
+        self.assertIsNone(self.mmu.last_error)
+        self.assertEqual(self.mmu.cutter_travel, 2.)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+        self.assertAlmostEqual(self.sim.tip(1), self.sim.nozzle_pos - self.BLADE_POS, delta=1.) # Reload allows for cut piece left in nozzle
+        self.gcode("MMU_UNLOAD")
+        self.assertIn("CUT_TRAVEL=2.0", [s for s in self.printer.gcode.scripts if s.startswith("_MMU_CUT_TIP")][-1])
+
+    def test_blade_cuts_counted_with_replacement_warning(self):
+        self.mmu._publish_event = MagicMock(wraps=self.mmu._publish_event)
+        for tool in [1, 2, 3]:
+            self.gcode("MMU_CHANGE_TOOL TOOL=%d" % tool)
+        self.gcode("MMU_UNLOAD")
+
+        self.assertEqual(self.mmu.cutter_blade['cuts'], 3)
+        self.assertEqual([c.args[0] for c in self.mmu._publish_event.call_args_list].count('cutter_blade_worn'), 1)
+
+        self.gcode("MMU_CALIBRATE_CUTTER NEW_BLADE=1")
+        self.assertEqual(self.mmu.cutter_blade, {'cuts': 0, 'total_cuts': 3, 'blades': 1})
+
+class TestMmuGateFrictionProfile(MmuScenarioTestCase):
+    def test_degraded_gate_is_reported(self):
+        self.mmu._publish_event = MagicMock(wraps=self.mmu._publish_event)
+        self.gcode("MMU_PROFILE_GATES GATES=1,2")
+
+        self.assertIsNone(self.mmu.last_error)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+        self.assertEqual(self.mmu.gate_health_baseline[1], 100.)
+        self.assertEqual(self.mmu._gate_health_to_human_string(), "")
+
+        self.sim.gear_slip = 0.02 # Drag in path
+        self.gcode("MMU_PROFILE_GATES GATES=1")
+
+        score = self.mmu.gate_health_history[1][-1][1]
+        self.assertAlmostEqual(score, 80., delta=2.)
+        self.assertIn('gate_health_degraded', [c.args[0] for c in self.mmu._publish_event.call_args_list])
+        self.assertIn("Gate #1", self.mmu._gate_health_to_human_string())
+        self.assertEqual(self.mmu.get_status(0)['gate_health'][:3], [-1, score, 100.])
+
+        self.gcode("MMU_PROFILE_GATES GATES=1 RESET=1")
+        self.assertEqual(self.mmu.gate_health_baseline[1], -1.)
+
+class PreGateRunoutHelper:
+    def __init__(self, sim, gate, pos):
+        self.sim, self.gate, self.pos = sim, gate, pos
+        self.sensor_enabled = True
+
+    @property
+    def filament_present(self):
+        tip = self.sim.tip(self.gate)
+        return tip is not None and tip >= self.pos
+
+class TestMmuRewindAssistRamp(MmuScenarioTestCase):
+    CONFIG = {'gate_final_eject': [2, 0, 0, 0], 'gate_final_eject_margin': 5., 'espooler_rewind_pwm': 0.8, 'espooler_ramp_time': 0.2,
+              'espooler_max_dpwm': 2., 'espooler_spool_inertia': [0.5, 1, 1, 1]}
+
+    def setUp(self):
+        super().setUp()
+        sensor = MagicMock()
+        sensor.runout_helper = PreGateRunoutHelper(self.sim, 0, -30.)
+        self.printer.objects['filament_switch_sensor mmu_pre_gate_0'] = sensor
+
+    def test_espooler_pwm_ramped_up_and_down(self):
+        calls = []
+        self.printer.objects['gcode_macro _MMU_REWIND_ASSIST'] = FakeMacro(lambda gcmd: calls.append((gcmd.get_int('ENABLE'), gcmd.get_float('PWM'))))
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        # Light spool: max(0.2s * 0.5, 0.8 * 0.5 / 2 per second) = 0.2s ramp in two 0.1s steps
+        self.assertEqual(calls, [(1, 0.4), (1, 0.8), (1, 0.4), (0, 0.)])
+class TestMmuBypassGuard(MmuScenarioTestCase):
+    def test_bypass_sensor_blocks_toolchange_until_bypass_selected(self):
+        bypass = MagicMock()
+        bypass.runout_helper.sensor_enabled = True
+        bypass.runout_helper.filament_present = True
+        self.printer.objects['filament_switch_sensor mmu_bypass_sensor'] = bypass
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_UNLOADED)
+        self.assertNotEqual(self.mmu.gate_selected, 1)
+        self.assertTrue(any("MMU_SELECT_BYPASS" in m for m in self.printer.gcode.messages))
+
+        self.mmu.bypass_offsets = [100.]
+        self.mmu.bypass_offset = 100.
+        self.gcode("MMU_SELECT_BYPASS")
+        self.assertEqual(self.mmu.tool_selected, self.mmu.TOOL_GATE_BYPASS)
+
+    def test_extruder_sensor_with_gates_unloaded_pauses_print(self):
+        self.start_print()
+        self.sim.set_tip(self.sim.extruder_sensor_pos + 5.) # Filament appears from elsewhere
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        self.assert_error(self.mmu.ERR_BYPASS_ACTIVE)
+        self.assertNotEqual(self.mmu.gate_selected, 1)
+
+class TestMmuHotendCooling(MmuScenarioTestCase):
+    CONFIG = {'hotend_type': "revo", 'form_tip_profile_materials': ["ABS:dragon_uhf"]}
+
+    def test_cooling_moves_by_material_with_profile_override(self):
+        self.mmu.gate_material[1] = "PETG"
+        self.mmu.gate_material[2] = "ABS"
+        for tool in [1, 2, 3]:
+            self.gcode("MMU_CHANGE_TOOL TOOL=%d" % tool)
+        form_tips = [s for s in self.printer.gcode.scripts if s.startswith("_MMU_FORM_TIP_STANDALONE")]
+
+        self.assertIsNone(self.mmu.last_error)
+        self.assertIn("COOLING_MOVES=3 COOLING_TUBE_LENGTH=10 COOLING_TUBE_POSITION=35 FINAL_COOLING_SPEED=40", form_tips[-2]) # PETG on revo
+        self.assertIn("COOLING_MOVES=5 COOLING_TUBE_LENGTH=10 COOLING_TUBE_POSITION=45", form_tips[-1]) # ABS profile overrides
+
+class TestMmuIdlePurgeScaling(MmuScenarioTestCase):
+    CONFIG = {'purge_volume': 100., 'purge_idle_curve': ["10:150", "30:200"], 'purge_idle_materials': ["PETG:2"]}
+
+    def setUp(self):
+        super().setUp()
+        self.start_print()
+        # Simulate toolchange stalling for 20 minutes after print last extruded
+        def stall(gcmd):
+            if self.mmu.last_extrusion_time:
+                self.mmu.last_extrusion_time -= 20 * 60
+        self.printer.objects['gcode_macro _MMU_POST_UNLOAD'] = FakeMacro(stall)
+
+    def test_purge_scaled_by_idle_time_and_material(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.assertEqual(self.mmu.toolchange_purge_volume, 100.) # Nothing extruded yet
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertAlmostEqual(self.mmu.toolchange_purge_volume, 175., places=0)
+
+        self.gcode("MMU_GATE_MAP GATE=2 MATERIAL=PETG QUIET=1")
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.assertAlmostEqual(self.mmu.toolchange_purge_volume, 250., places=0)
+
+class TestMmuJournal(MmuScenarioTestCase):
+    CONFIG = {'enable_journal': 1}
+
+    def setUp(self):
+        super().setUp()
+        self.tmpdir = tempfile.TemporaryDirectory()
+        self.mmu.journal.dirname = self.tmpdir.name
+
+    def tearDown(self):
+        self.tmpdir.cleanup()
+
+    def test_failed_toolchange_journal_replays_to_same_error(self):
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertEqual(self.mmu.journal.list_files(), []) # Successful toolchange not saved
+
+        self.sim.jam_pos = self.sim.toolhead_sensor_pos - 5.
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.assert_error(self.mmu.ERR_TOOLHEAD_HOME_TIMEOUT)
+        files = self.mmu.journal.list_files()
+        self.assertEqual(len(files), 1)
+
+        # Replay on a healthy simulated MMU still fails the same way because journaled inputs drive decisions
+        records = load_journal(os.path.join(self.tmpdir.name, files[0]))
+        self.assertEqual(records[0]['tool_selected'], 1)
+        report = JournalReplay(records).run()
+        self.assertIsNone(report['divergence'])
+        self.assertEqual(report['replayed'], report['inputs'])
+        self.assertTrue(report['reproduced'])
+
+class TestMmuGearThermalControl(MmuScenarioTestCase):
+    CONFIG = {'sync_to_extruder': 1, 'sync_gear_current': 50, 'gear_temp_sensor': "temperature_sensor gear_motor", 'gear_temp_max': 60.,
+              'gear_temp_hysteresis': 5., 'sync_gear_current_min': 30, 'sync_gear_current_step': 10}
+    GEAR_TMC = True
+    TEMPERATURE_SENSORS = ("gear_motor",)
+
+    def check_temperature(self, temperature):
+        self.printer.objects['temperature_sensor gear_motor'].temperature = temperature
+        self.mmu._handle_gear_thermal_check(self.printer.reactor.monotonic())
+        return round(self.printer.objects['tmc2209 stepper_mmu_gear'].run_current * 100)
+
+    def test_sync_current_stepped_down_when_hot_and_restored_when_cool(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertTrue(self.mmu.mmu_toolhead.is_gear_synced_to_extruder())
+        self.assertEqual(self.check_temperature(40.), 50)
+
+        self.assertEqual([self.check_temperature(65.) for _ in range(3)], [40, 30, 30]) # Limited by sync_gear_current_min
+        self.assertEqual(self.check_temperature(57.), 30) # Within hysteresis
+        self.assertEqual([self.check_temperature(50.) for _ in range(3)], [40, 50, 50])
+
+    def test_current_changed_through_internal_command(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        scripts = self.printer.gcode.scripts
+        self.check_temperature(65.)
+        self.assertEqual(scripts[-2], "__MMU_GEAR_THERMAL FROM=50")
+        self.assertTrue(scripts[-1].startswith("SET_TMC_CURRENT STEPPER=stepper_mmu_gear "))
+
+class TestMmuStatusSummary(MmuScenarioTestCase):
+    def summary(self):
+        self.gcode("MMU_STATUS SUMMARY=1")
+        return self.printer.gcode.messages[-1]
+
+    def test_summary_reports_findings_with_next_command(self):
+        self.assertIn("all good", self.summary())
+
+        self.mmu.gate_statistics[2].update(full_loads=8, load_failures=3)
+        self.gcode("MMU_MAINTENANCE GATE=1")
+        self.mmu.calibration_status &= ~self.mmu.CALIBRATED_BOWDEN
+
+        msg = self.summary()
+        self.assertIn("3 items need attention", msg)
+        self.assertIn("Calibration incomplete\n     -> MMU_CALIBRATE_BOWDEN", msg)
+        self.assertIn("Gate #2 load success rate is 73% (8 of 11)", msg)
+        self.assertIn("Gate #1 is in maintenance\n     -> MMU_MAINTENANCE GATE=1 ENABLE=1", msg)
+
+class TestMmuMaterialSpeedCaps(MmuScenarioTestCase):
+    CONFIG = {'gear_material_max_speed': ["TPU:40"], 'gear_material_max_accel': ["TPU:150"]}
+
+    def gear_speeds(self, tool):
+        speeds = []
+        toolhead = self.mmu.mmu_toolhead
+        move = toolhead.move
+        def record_move(newpos, speed):
+            if newpos[1] != toolhead.position[1]:
+                speeds.append(speed)
+            return move(newpos, speed)
+        toolhead.move = record_move
+        try:
+            self.gcode("MMU_CHANGE_TOOL TOOL=%d" % tool)
+        finally:
+            toolhead.move = move
+        return speeds
+
+    def test_gear_speed_clamped_for_material_in_any_gate(self):
+        self.gcode("MMU_GATE_MAP GATE=1 MATERIAL=PLA")
+        self.gcode("MMU_GATE_MAP GATE=2 MATERIAL=TPU")
+
+        self.assertGreater(max(self.gear_speeds(1)), 40)
+        self.gcode("MMU_UNLOAD")
+        self.assertLessEqual(max(self.gear_speeds(2)), 40)
+        self.assert_loaded(2, 2)
+        self.assertEqual(self.mmu._clamp_material_gear_speed(160., 400.), (40., 150.))
+
+class TestMmuHotendStandby(MmuScenarioTestCase):
+    CONFIG = {'hotend_standby_time': 60, 'hotend_standby_temp': 150.}
+
+    def test_standby_applied_at_next_action_not_from_timer(self):
+        scripts = self.printer.gcode.scripts
+        self.mmu._set_action(self.mmu.ACTION_CHECKING)
+        self.mmu._handle_hotend_standby_timeout(self.printer.reactor.monotonic())
+        self.assertFalse(any(s.startswith("SET_HEATER_TEMPERATURE") for s in scripts))
+
+        self.mmu._set_action(self.mmu.ACTION_SELECTING)
+        self.assertIn("SET_HEATER_TEMPERATURE HEATER=extruder TARGET=150.0", scripts)
+        self.assertEqual(self.mmu._get_extruder_target_temp(), 220.)
+
+        self.mmu._set_action(self.mmu.ACTION_LOADING_EXTRUDER)
+        self.assertIn("SET_HEATER_TEMPERATURE HEATER=extruder TARGET=220.0", scripts)
+        self.assertIn("TEMPERATURE_WAIT SENSOR=extruder MINIMUM=219.0 MAXIMUM=221.0", scripts)
+        self.assertIsNone(self.mmu.hotend_standby_saved_temp)
+
+class TestMmuFinalEject(MmuScenarioTestCase):
+    CONFIG = {'gate_final_eject': [2, 0, 0, 0], 'gate_final_eject_margin': 5.}
+
+    def setUp(self):
+        super().setUp()
+        sensor = MagicMock()
+        sensor.runout_helper = PreGateRunoutHelper(self.sim, 0, -30.)
+        self.printer.objects['filament_switch_sensor mmu_pre_gate_0'] = sensor
+
+    def test_gate_swap_rewinds_past_pre_gate_sensor_and_gate_stays_available(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=0")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assert_loaded(1, 1)
+        self.assertLess(self.sim.tip(0), -30.)
+        status = self.mmu.gate_status[0]
+
+        # Sensor runout event arrives after the eject completes
+        self.gcode("__MMU_PRE_GATE_RUNOUT GATE=0")
+        self.assertEqual(self.mmu.gate_status[0], status)
+        self.assertNotEqual(status, self.mmu.GATE_EMPTY)
+        self.assertEqual(self.mmu.final_eject_gates, set())
+
+class TestMmuMaintenanceWhileLoaded(MmuScenarioTestCase):
+    CONFIG = {'sync_to_extruder': 1}
+
+    def test_selector_design_refuses_to_cross_loaded_filament(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        with self.assertRaises(self.printer.gcode.error):
+            self.gcode("MMU_MAINTENANCE GATE=2")
+        self.assert_loaded(1, 1)
+        self.assertNotIn(2, self.mmu.gate_maintenance)
+        self.assertTrue(self.mmu.mmu_toolhead.is_gear_synced_to_extruder())
+
+class TestMmuMultiGearMaintenanceWhileLoaded(MmuScenarioTestCase):
+    CONFIG = {'mmu_vendor': "3MS", 'mmu_version': "1.0", 'sync_to_extruder': 1}
+    GEAR_STEPPERS = 4
+
+    def test_loaded_gate_restored_after_servicing_another(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+        self.assertTrue(self.mmu.mmu_toolhead.is_gear_synced_to_extruder())
+
+        self.gcode("MMU_MAINTENANCE GATE=2")
+        self.assertIn(2, self.mmu.gate_maintenance)
+        self.assertEqual(self.mmu.gate_selected, 1)
+        self.assertEqual(self.mmu.filament_pos, self.mmu.FILAMENT_POS_LOADED)
+        self.assertTrue(self.mmu.mmu_toolhead.is_gear_synced_to_extruder())
+        rail = self.mmu.mmu_toolhead.get_kinematics().rails[1]
+        self.assertEqual([s.get_name() for s in rail.get_steppers()], ["stepper_mmu_gear_1"])
+
+class TestMmuToolchangeOverlap(MmuScenarioTestCase):
+    CONFIG = {'mmu_vendor': "3MS", 'mmu_version': "1.0", 'toolchange_overlap_length': 5.}
+    GEAR_STEPPERS = 4
+
+    def test_next_gate_feeds_whilst_previous_retracts_through_bowden(self):
+        self.start_print()
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.gcode("MMU_CHANGE_TOOL TOOL=1")
+
+        moves = []
+        overlap_feed = self.mmu._overlap_next_gate_feed
+        def spy(next_gate, overlap):
+            tips = self.sim.tip(1), self.sim.tip(2)
+            overlap_feed(next_gate, overlap)
+            moves.append((next_gate, overlap, self.sim.tip(1) - tips[0], self.sim.tip(2) - tips[1]))
+        self.mmu._overlap_next_gate_feed = spy
+
+        self.gcode("MMU_CHANGE_TOOL TOOL=2")
+        self.assert_loaded(2, 2)
+        self.assertEqual(moves, [(2, 5., -5., 5.)])
+        rail = self.mmu.mmu_toolhead.get_kinematics().rails[1]
+        self.assertEqual([s.get_name() for s in rail.get_steppers()], ["stepper_mmu_gear_2"])
+        self.assertFalse(any(s.dir_inverted for s in self.mmu.mmu_toolhead.all_gear_rail_steppers))
+
+class TestMmuPhaseDevicePulse(MmuScenarioTestCase):
+    CONFIG = {'phase_device_control': ["toolchange:fan_generic brush:1:5"]}
+
+    def test_pulse_restored_at_first_action_change_after_duration(self):
+        self.printer.objects['fan_generic brush'] = MagicMock(get_status=lambda eventtime: {'speed': 0.})
+        scripts = self.printer.gcode.scripts
+        with self.mmu._wrap_device_phase(self.mmu.PHASE_TOOLCHANGE):
+            self.assertEqual(scripts[-1], "SET_FAN_SPEED FAN=brush SPEED=1.000")
+            self.mmu._set_action(self.mmu.ACTION_CHECKING)
+            self.assertEqual(scripts[-1], "SET_FAN_SPEED FAN=brush SPEED=1.000")
+
+            self.printer.reactor.now += 6.
+            self.mmu._set_action(self.mmu.ACTION_SELECTING)
+            self.assertEqual(scripts[-1], "SET_FAN_SPEED FAN=brush SPEED=0.000")
+            self.assertEqual(self.mmu.phase_device_restore, [])
+        self.assertEqual(scripts.count("SET_FAN_SPEED FAN=brush SPEED=0.000"), 1)
+
+class TestMmuApiEvents(MmuScenarioTestCase):
+    PLUGIN = "from extras.mmu_plugin import MmuPlugin\n" \
+             "class Recorder(MmuPlugin):\n" \
+             "    events = []\n" \
+             "    def on_event(self, event, **kwargs):\n" \
+             "        self.events.append(event)\n" \
+             "def load_plugin(mmu):\n" \
+             "    return Recorder(mmu)\n"
+
+    def setUp(self):
+        self.tmpdir = tempfile.TemporaryDirectory()
+        with open(os.path.join(self.tmpdir.name, "recorder.py"), "w") as f:
+            f.write(self.PLUGIN)
+        self.CONFIG = {'plugin_dir': self.tmpdir.name, 'plugins': "recorder"}
+        super().setUp()
+
+    def tearDown(self):
+        self.tmpdir.cleanup()
+
+    def test_plugins_and_listeners_receive_each_event_once(self):
+        received = []
+        self.mmu.api.register_listener(lambda event, **kwargs: received.append(event))
+        plugin = self.mmu.plugin_manager.plugins['recorder']
+        del plugin.events[:]
+
+        self.mmu._publish_event("test_event", gate=1)
+
+        self.assertEqual(received, ["test_event"])
+        self.assertEqual(plugin.events, ["test_event"])
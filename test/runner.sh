@@ -8,5 +8,8 @@
 #   1. Create a file in the test directory with the name test_*.py
 #   2. Create a class in that file that inherits from unittest.TestCase
 #   3. Ensure that each test directory has a blank file named `__init__.py`
+#
+# Tests of the MMU load/unload/toolchange state machine (test/extras) run against the mock klipper environment
+# and simulated filament path in test/extras/mock_klipper.py so no printer is required
 
 python3 -m unittest
@@ -0,0 +1,14 @@
+G90
+T0
+G1 X0 Y0 F6000
+G1 X100 Y0 E5
+G1 X100 Y100 E5
+G1 X0 Y100 E5
+T1
+G1 X0 Y0 F6000
+G1 X100 Y0 E5
+T1
+G1 X100 Y100 E5
+T0
+G1 X0 Y100 E5
+; temperature = 215,250
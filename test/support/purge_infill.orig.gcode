@@ -0,0 +1,12 @@
+G90
+T0
+;TYPE:Perimeter
+G1 X0 Y0 E5
+;TYPE:Internal infill
+G1 X100 Y0 E5
+G1 X100 Y100 E5
+T1
+;TYPE:Perimeter
+G1 X0 Y100 E5
+; FEATURE: Sparse infill
+G1 X0 Y0 E5
@@ -0,0 +1,20 @@
+MMU_SLICER_TIP_PARAMETERS PROFILES="!slicer_tip_parameters!"
+MMU_CHECK_FILAMENT_SUFFICIENCY GRAMS=!filament_grams!
+_MMU_PRINT_START MATERIALS=!filament_materials! COLORS=!filament_colors!
+T0
+G1 X167.759 Y180.16 E.00802
+T1
+G1 X166.433 Y179.911 E.02797
+; filament used [mm] = 120.34, 30.12
+; filament used [g] = 12.5, 3.25
+; cooling_tube_length = 5
+; cooling_tube_retraction = 91.5
+; filament_colour = #FF0000;#00ff00
+; filament_cooling_final_speed = 3.4,2
+; filament_cooling_initial_speed = 2.2,1.5
+; filament_cooling_moves = 4,2
+; filament_diameter = 1.75,2.85
+; filament_ramming_parameters = "120 100 6.6 6.8 7.2| 0.05 6.6 0.45 6.8 0.95 7.2";"120 100 0 0 0| 0.05 0 0.45 0"
+; filament_type = PLA;PETG
+; filament_unloading_speed = 90,60
+; filament_unloading_speed_start = 100,80